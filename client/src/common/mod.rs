@@ -205,6 +205,11 @@ pub fn initialize_common_modules(
     let vm = VmFactory::new(1024 * 32);
     let machine = Arc::new(new_machine_with_builtin(conf.common_params(), vm));
 
+    let genesis_state = match conf.raw_conf.genesis_state {
+        Some(ref file) => Some(genesis::load_state_file(file)?),
+        None => None,
+    };
+
     let genesis_block = genesis_block(
         &storage_manager,
         genesis_accounts.clone(),
@@ -213,6 +218,7 @@ pub fn initialize_common_modules(
         machine.clone(),
         conf.raw_conf.execute_genesis, /* need_to_execute */
         conf.raw_conf.chain_id,
+        genesis_state,
     );
     debug!("Initialize genesis_block={:?}", genesis_block);
 
@@ -229,16 +235,18 @@ pub fn initialize_common_modules(
         pow.clone(),
     ));
 
+    let notifications = Notifications::init();
+
     let verification_config = conf.verification_config(machine.clone());
     let txpool = Arc::new(TransactionPool::new(
         conf.txpool_config(),
         verification_config.clone(),
         data_man.clone(),
         machine.clone(),
+        notifications.clone(),
     ));
 
     let statistics = Arc::new(Statistics::new());
-    let notifications = Notifications::init();
 
     let consensus = Arc::new(ConsensusGraph::new(
         consensus_conf,
@@ -333,6 +341,7 @@ pub fn initialize_not_light_node_modules(
         Option<TcpServer>,
         Option<WSServer>,
         Option<WSServer>,
+        Option<IpcServer>,
         Runtime,
     ),
     String,
@@ -350,7 +359,7 @@ pub fn initialize_not_light_node_modules(
         network,
         common_impl,
         accounts,
-        _notifications,
+        notifications,
         pubsub,
         runtime,
     ) = initialize_common_modules(&conf, exit.clone(), node_type)?;
@@ -373,6 +382,7 @@ pub fn initialize_not_light_node_modules(
         conf.state_sync_config(),
         SyncPhaseType::CatchUpRecoverBlockHeaderFromDB,
         light_provider,
+        notifications,
     ));
     sync.register().unwrap();
 
@@ -482,6 +492,8 @@ pub fn initialize_not_light_node_modules(
         accounts,
     ));
 
+    let rpc_auth = conf.rpc_auth()?;
+
     let debug_rpc_http_server = super::rpc::start_http(
         conf.local_http_config(),
         setup_debug_rpc_apis(
@@ -490,6 +502,7 @@ pub fn initialize_not_light_node_modules(
             pubsub.clone(),
             &conf,
         ),
+        RpcExtractor,
     )?;
 
     let debug_rpc_tcp_server = super::rpc::start_tcp(
@@ -503,6 +516,17 @@ pub fn initialize_not_light_node_modules(
         RpcExtractor,
     )?;
 
+    let rpc_ipc_server = super::rpc::start_ipc(
+        conf.ipc_config(),
+        setup_debug_rpc_apis(
+            common_impl.clone(),
+            rpc_impl.clone(),
+            pubsub.clone(),
+            &conf,
+        ),
+        RpcExtractor,
+    )?;
+
     let rpc_tcp_server = super::rpc::start_tcp(
         conf.tcp_config(),
         setup_public_rpc_apis(
@@ -510,6 +534,7 @@ pub fn initialize_not_light_node_modules(
             rpc_impl.clone(),
             pubsub.clone(),
             &conf,
+            None,
         ),
         RpcExtractor,
     )?;
@@ -521,6 +546,7 @@ pub fn initialize_not_light_node_modules(
             rpc_impl.clone(),
             pubsub.clone(),
             &conf,
+            None,
         ),
         RpcExtractor,
     )?;
@@ -532,13 +558,15 @@ pub fn initialize_not_light_node_modules(
             rpc_impl.clone(),
             pubsub.clone(),
             &conf,
+            rpc_auth.clone(),
         ),
         RpcExtractor,
     )?;
 
     let rpc_http_server = super::rpc::start_http(
         conf.http_config(),
-        setup_public_rpc_apis(common_impl, rpc_impl, pubsub, &conf),
+        setup_public_rpc_apis(common_impl, rpc_impl, pubsub, &conf, rpc_auth),
+        RpcExtractor,
     )?;
 
     Ok((
@@ -554,6 +582,7 @@ pub fn initialize_not_light_node_modules(
         rpc_tcp_server,
         debug_rpc_ws_server,
         rpc_ws_server,
+        rpc_ipc_server,
         runtime,
     ))
 }
@@ -756,6 +785,7 @@ use cfxcore::{
 use cfxcore_accounts::AccountProvider;
 use cfxkey::public_to_address;
 use jsonrpc_http_server::Server as HttpServer;
+use jsonrpc_ipc_server::Server as IpcServer;
 use jsonrpc_tcp_server::Server as TcpServer;
 use jsonrpc_ws_server::Server as WSServer;
 use keylib::KeyPair;
@@ -4,11 +4,17 @@
 
 use crate::rpc::{
     impls::RpcImplConfiguration, rpc_apis::ApiSet, HttpConfiguration,
-    TcpConfiguration, WsConfiguration,
+    IpcConfiguration, RpcAuth, TcpConfiguration, WsConfiguration,
 };
 use cfx_addr::{cfx_addr_decode, Network};
 use cfx_internal_common::{ChainIdParams, ChainIdParamsInner};
-use cfx_parameters::block::DEFAULT_TARGET_BLOCK_GAS_LIMIT;
+use cfx_parameters::{
+    block::DEFAULT_TARGET_BLOCK_GAS_LIMIT,
+    rpc::{
+        GAS_PRICE_BLOCK_SAMPLE_SIZE, GAS_PRICE_DEFAULT_PERCENTILE,
+        GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
+    },
+};
 use cfx_storage::{
     defaults::DEFAULT_DEBUG_SNAPSHOT_CHECKER_THREADS, storage_dir,
     ConsensusParam, ProvideExtraSnapshotSyncConfig, StorageConfiguration,
@@ -24,7 +30,8 @@ use cfxcore::{
     },
     consensus::{
         consensus_inner::consensus_executor::ConsensusExecutionConfiguration,
-        ConsensusConfig, ConsensusInnerConfig,
+        ConsensusConfig, ConsensusInnerConfig, GasPriceOracleConfig,
+        HistoricalStateConfig,
     },
     consensus_internal_parameters::*,
     consensus_parameters::*,
@@ -38,7 +45,9 @@ use cfxcore::{
 };
 use lazy_static::*;
 use metrics::MetricsConfiguration;
-use network::DiscoveryConfiguration;
+use network::{
+    DiscoveryConfiguration, IpFilter, IpNetwork, ReputationConfig, TreeLink,
+};
 use parking_lot::RwLock;
 use rand::Rng;
 use std::{collections::BTreeMap, convert::TryInto, path::PathBuf, sync::Arc};
@@ -49,6 +58,7 @@ lazy_static! {
 }
 const BLOCK_DB_DIR_NAME: &str = "blockchain_db";
 const NET_CONFIG_DB_DIR_NAME: &str = "net_config";
+const LOCAL_TX_JOURNAL_FILE_NAME: &str = "local_transactions.rlp";
 
 // usage:
 // ```
@@ -132,6 +142,7 @@ build_config! {
         (heavy_block_difficulty_ratio, (u64), HEAVY_BLOCK_DEFAULT_DIFFICULTY_RATIO)
         (genesis_accounts, (Option<String>), None)
         (genesis_secrets, (Option<String>), None)
+        (genesis_state, (Option<String>), None)
         (initial_difficulty, (Option<u64>), None)
         (tanzanite_transition_height, (u64), TANZANITE_HEIGHT)
         (unnamed_21autumn_transition_number, (Option<u64>), None)
@@ -163,6 +174,8 @@ build_config! {
         (jsonrpc_cors, (Option<String>), None)
         (jsonrpc_http_keep_alive, (bool), false)
         (jsonrpc_ws_max_payload_bytes, (usize), 30 * 1024 * 1024)
+        (rpc_auth_conf, (Option<String>), None)
+        (jsonrpc_ipc_path, (Option<String>), None)
         // The network_id, if unset, defaults to the chain_id.
         // Only override the network_id for local experiments,
         // when user would like to keep the existing blockchain data
@@ -223,11 +236,24 @@ build_config! {
         (discovery_throttling_interval_ms, (u64), 1_000)
         (discovery_throttling_limit_ping, (usize), 20)
         (discovery_throttling_limit_find_nodes, (usize), 10)
+        (dns_bootnodes, (Option<String>), None)
+        (dns_discovery_interval_s, (u64), 1800)
         (enable_discovery, (bool), true)
         (netconf_dir, (Option<String>), None)
+        (net_allow_ips, (Option<String>), None)
+        (net_deny_ips, (Option<String>), None)
         (net_key, (Option<String>), None)
+        (nat, (String), "any".into())
+        (nat_lease_s, (u64), 7200)
         (node_table_timeout_s, (u64), 300)
         (node_table_promotion_timeout_s, (u64), 3 * 24 * 3600)
+        (peer_reputation_ban_duration_s, (u64), 3600)
+        (peer_reputation_ban_threshold, (i32), -500)
+        (peer_reputation_disconnect_threshold, (i32), -200)
+        (peer_reputation_half_life_s, (u64), 600)
+        (session_compression, (bool), false)
+        (session_compression_threshold_bytes, (usize), 1024)
+        (session_encryption, (String), "disabled".into())
         (session_ip_limits, (String), "1,8,4,2".into())
         (subnet_quota, (usize), 128)
 
@@ -235,8 +261,17 @@ build_config! {
         (tx_cache_index_maintain_timeout_ms, (u64), 300_000)
         (tx_pool_size, (usize), 200_000)
         (tx_pool_min_tx_gas_price, (u64), 1)
+        (tx_pool_replace_price_bump, (u8), 0)
+        (tx_pool_journal_local_txs, (bool), true)
+        (tx_pool_local_txs, (bool), false)
+        (tx_pool_max_local_tx_bytes, (usize), 10 * 1024 * 1024)
+        (tx_pool_max_local_txs, (usize), 1000)
         (tx_weight_scaling, (u64), 1)
         (tx_weight_exp, (u8), 1)
+        (gas_price_oracle_sample_blocks, (usize), GAS_PRICE_BLOCK_SAMPLE_SIZE)
+        (gas_price_oracle_sample_transactions, (usize), GAS_PRICE_TRANSACTION_SAMPLE_SIZE)
+        (gas_price_oracle_percentile, (u8), GAS_PRICE_DEFAULT_PERCENTILE)
+        (gas_price_oracle_ignore_price, (u64), 0)
 
         // Storage Section.
         (additional_maintained_snapshot_count, (u32), 1)
@@ -252,6 +287,11 @@ build_config! {
         (checkpoint_gc_time_in_era_count, (f64), 0.5)
         // The conflux data dir, if unspecified, is the workdir where conflux is started.
         (conflux_data_dir, (String), "./blockchain_data".to_string())
+        (historical_state_cache_size, (usize), 16)
+        (historical_state_max_cached_entries, (usize), 100_000)
+        (historical_state_max_concurrent, (usize), 2)
+        (historical_state_max_epochs, (u64), 2000)
+        (historical_state_reconstruction, (bool), false)
         (ledger_cache_size, (usize), DEFAULT_LEDGER_CACHE_SIZE)
         (invalid_block_hash_cache_size_in_count, (usize), DEFAULT_INVALID_BLOCK_HASH_CACHE_SIZE_IN_COUNT)
         (rocksdb_cache_size, (Option<usize>), Some(128))
@@ -282,6 +322,7 @@ build_config! {
         (print_memory_usage_period_s, (Option<u64>), None)
         (target_block_gas_limit, (u64), DEFAULT_TARGET_BLOCK_GAS_LIMIT)
         (executive_trace, (bool), false)
+        (persist_replayed_trace, (bool), false)
 
         // TreeGraph Section.
         (candidate_pivot_waiting_timeout_ms, (u64), 10_000)
@@ -327,6 +368,7 @@ build_config! {
             ProvideExtraSnapshotSyncConfig::parse_config_list)
         (node_type, (Option<NodeType>), None, NodeType::from_str)
         (public_rpc_apis, (ApiSet), ApiSet::Safe, ApiSet::from_str)
+        (jsonrpc_ipc_permissions, (u32), 0o600, parse_file_mode)
     }
 }
 
@@ -392,6 +434,11 @@ impl Configuration {
         network_config.discovery_enabled = self.raw_conf.enable_discovery;
         network_config.boot_nodes = to_bootnodes(&self.raw_conf.bootnodes)
             .map_err(|e| format!("failed to parse bootnodes: {}", e))?;
+        network_config.dns_bootnodes =
+            to_dns_bootnodes(&self.raw_conf.dns_bootnodes)
+                .map_err(|e| format!("failed to parse dns_bootnodes: {}", e))?;
+        network_config.dns_discovery_interval =
+            Duration::from_secs(self.raw_conf.dns_discovery_interval_s);
         network_config.config_path = Some(match &self.raw_conf.netconf_dir {
             Some(dir) => dir.clone(),
             None => Path::new(&self.raw_conf.conflux_data_dir)
@@ -400,6 +447,12 @@ impl Configuration {
                 .into_string()
                 .unwrap(),
         });
+        network_config.ip_filter = IpFilter::new(
+            to_ip_networks(&self.raw_conf.net_allow_ips)
+                .map_err(|e| format!("failed to parse net_allow_ips: {}", e))?,
+            to_ip_networks(&self.raw_conf.net_deny_ips)
+                .map_err(|e| format!("failed to parse net_deny_ips: {}", e))?,
+        );
         network_config.use_secret =
             self.raw_conf.net_key.as_ref().map(|sec_str| {
                 parse_hex_string(sec_str)
@@ -425,12 +478,41 @@ impl Configuration {
                     }
                 };
         }
+        network_config.nat_type = self
+            .raw_conf
+            .nat
+            .parse()
+            .map_err(|e| format!("failed to parse nat: {}", e))?;
+        if self.raw_conf.nat_lease_s == 0 {
+            return Err("nat_lease_s must be positive".into());
+        }
+        network_config.nat_lease =
+            Duration::from_secs(self.raw_conf.nat_lease_s);
         network_config.node_table_timeout =
             Duration::from_secs(self.raw_conf.node_table_timeout_s);
         network_config.connection_lifetime_for_promotion =
             Duration::from_secs(self.raw_conf.node_table_promotion_timeout_s);
         network_config.test_mode = self.is_test_mode();
         network_config.subnet_quota = self.raw_conf.subnet_quota;
+        network_config.session_encryption =
+            self.raw_conf.session_encryption.parse().map_err(|e| {
+                format!("failed to parse session_encryption: {}", e)
+            })?;
+        network_config.session_compression = self.raw_conf.session_compression;
+        network_config.session_compression_threshold =
+            self.raw_conf.session_compression_threshold_bytes;
+        network_config.reputation_config = ReputationConfig {
+            disconnect_threshold: self
+                .raw_conf
+                .peer_reputation_disconnect_threshold,
+            ban_threshold: self.raw_conf.peer_reputation_ban_threshold,
+            ban_duration: Duration::from_secs(
+                self.raw_conf.peer_reputation_ban_duration_s,
+            ),
+            decay_half_life: Duration::from_secs(
+                self.raw_conf.peer_reputation_half_life_s,
+            ),
+        };
         network_config.session_ip_limit_config =
             self.raw_conf.session_ip_limits.clone().try_into().map_err(
                 |e| format!("failed to parse session ip limit config: {}", e),
@@ -461,12 +543,16 @@ impl Configuration {
         cache_config
     }
 
-    pub fn db_config(&self) -> (PathBuf, DatabaseConfig) {
-        let db_dir: PathBuf = match &self.raw_conf.block_db_dir {
+    pub fn block_db_dir(&self) -> PathBuf {
+        match &self.raw_conf.block_db_dir {
             Some(dir) => dir.into(),
             None => Path::new(&self.raw_conf.conflux_data_dir)
                 .join(BLOCK_DB_DIR_NAME),
-        };
+        }
+    }
+
+    pub fn db_config(&self) -> (PathBuf, DatabaseConfig) {
+        let db_dir = self.block_db_dir();
         if let Err(e) = fs::create_dir_all(&db_dir) {
             panic!("Error creating database directory: {:?}", e);
         }
@@ -555,6 +641,23 @@ impl Configuration {
             get_logs_filter_max_epoch_range: self.raw_conf.get_logs_filter_max_epoch_range,
             sync_state_starting_epoch: self.raw_conf.sync_state_starting_epoch,
             sync_state_epoch_gap: self.raw_conf.sync_state_epoch_gap,
+            gas_price_oracle: GasPriceOracleConfig {
+                sample_blocks: self.raw_conf.gas_price_oracle_sample_blocks,
+                sample_transactions: self
+                    .raw_conf
+                    .gas_price_oracle_sample_transactions,
+                percentile: self.raw_conf.gas_price_oracle_percentile,
+                ignore_price: self.raw_conf.gas_price_oracle_ignore_price.into(),
+            },
+            historical_state: HistoricalStateConfig {
+                enabled: self.raw_conf.historical_state_reconstruction,
+                max_epochs: self.raw_conf.historical_state_max_epochs,
+                cache_size: self.raw_conf.historical_state_cache_size,
+                max_cached_entries: self
+                    .raw_conf
+                    .historical_state_max_cached_entries,
+                max_concurrent: self.raw_conf.historical_state_max_concurrent,
+            },
         };
         match self.raw_conf.node_type {
             Some(NodeType::Archive) => {
@@ -892,6 +995,19 @@ impl Configuration {
             tx_weight_scaling: self.raw_conf.tx_weight_scaling,
             tx_weight_exp: self.raw_conf.tx_weight_exp,
             target_block_gas_limit: self.raw_conf.target_block_gas_limit,
+            replace_price_bump: self.raw_conf.tx_pool_replace_price_bump,
+            local_tx_journal: if self.raw_conf.tx_pool_journal_local_txs {
+                Some(
+                    Path::new(&self.raw_conf.conflux_data_dir)
+                        .join(LOCAL_TX_JOURNAL_FILE_NAME),
+                )
+            } else {
+                None
+            },
+            max_local_transactions: self.raw_conf.tx_pool_max_local_txs,
+            max_local_transaction_bytes: self
+                .raw_conf
+                .tx_pool_max_local_tx_bytes,
         }
     }
 
@@ -901,6 +1017,14 @@ impl Configuration {
             dev_pack_tx_immediately: self.is_dev_mode()
                 && self.raw_conf.dev_block_interval_ms.is_none(),
             max_payload_bytes: self.raw_conf.jsonrpc_ws_max_payload_bytes,
+            local_transactions: self.raw_conf.tx_pool_local_txs,
+        }
+    }
+
+    pub fn rpc_auth(&self) -> Result<Option<Arc<RpcAuth>>, String> {
+        match self.raw_conf.rpc_auth_conf {
+            Some(ref file) => Ok(Some(Arc::new(RpcAuth::load(file)?))),
+            None => Ok(None),
         }
     }
 
@@ -951,9 +1075,17 @@ impl Configuration {
         )
     }
 
+    pub fn ipc_config(&self) -> IpcConfiguration {
+        IpcConfiguration::new(
+            self.raw_conf.jsonrpc_ipc_path.clone(),
+            self.raw_conf.jsonrpc_ipc_permissions,
+        )
+    }
+
     pub fn execution_config(&self) -> ConsensusExecutionConfiguration {
         ConsensusExecutionConfiguration {
             executive_trace: self.raw_conf.executive_trace,
+            persist_replayed_trace: self.raw_conf.persist_replayed_trace,
         }
     }
 
@@ -1126,10 +1258,56 @@ pub fn to_bootnodes(bootnodes: &Option<String>) -> Result<Vec<String>, String> {
     }
 }
 
+/// Parses a comma-separated list of links to the node trees published in
+/// DNS, e.g. "enrtree://<public key>@nodes.example.org".
+pub fn to_dns_bootnodes(
+    dns_bootnodes: &Option<String>,
+) -> Result<Vec<String>, String> {
+    match dns_bootnodes {
+        Some(links) => links
+            .split(',')
+            .map(str::trim)
+            // ignore empty strings
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                TreeLink::from_str(s)
+                    .map(|_| s.to_owned())
+                    .map_err(|e| format!("{}: {}", s, e))
+            })
+            .collect(),
+        None => Ok(vec![]),
+    }
+}
+
+/// Parses a comma-separated list of CIDR ranges, e.g. "10.0.0.0/8,::1/128".
+pub fn to_ip_networks(
+    networks: &Option<String>,
+) -> Result<Vec<IpNetwork>, String> {
+    match *networks {
+        Some(ref x) => x
+            .split(',')
+            .map(str::trim)
+            // ignore empty strings
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                IpNetwork::from_str(s)
+                    .map_err(|e| format!("invalid CIDR {}: {:?}", s, e))
+            })
+            .collect(),
+        None => Ok(vec![]),
+    }
+}
+
 pub fn parse_hex_string<F: FromStr>(hex_str: &str) -> Result<F, F::Err> {
     hex_str.strip_prefix("0x").unwrap_or(hex_str).parse()
 }
 
+/// Parses Unix file permissions in octal, e.g. "600".
+pub fn parse_file_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode.strip_prefix("0o").unwrap_or(mode), 8)
+        .map_err(|e| format!("invalid file mode {:?}: {:?}", mode, e))
+}
+
 pub fn parse_config_address_string(
     addr: &str, network: &Network,
 ) -> Result<Address, String> {
@@ -1163,8 +1341,11 @@ pub fn parse_config_address_string(
 
 #[cfg(test)]
 mod tests {
-    use crate::configuration::parse_config_address_string;
+    use crate::configuration::{
+        parse_config_address_string, parse_file_mode, to_ip_networks,
+    };
     use cfx_addr::Network;
+    use network::IpFilter;
 
     #[test]
     fn test_config_address_string() {
@@ -1201,4 +1382,34 @@ mod tests {
             .unwrap()
         );
     }
+
+    #[test]
+    fn test_ip_filter() {
+        let allow = to_ip_networks(&Some("10.0.0.0/8, 192.168.1.0/24,".into()))
+            .unwrap();
+        let deny = to_ip_networks(&Some("10.0.0.0/16".into())).unwrap();
+        assert_eq!(allow.len(), 2);
+        assert!(to_ip_networks(&Some("10.0.0.0/33".into())).is_err());
+
+        let filter = IpFilter::new(allow, deny.clone());
+        assert!(filter.is_allowed(&"10.1.0.1".parse().unwrap()));
+        assert!(filter.is_allowed(&"192.168.1.1".parse().unwrap()));
+        assert!(!filter.is_allowed(&"10.0.0.1".parse().unwrap()));
+        assert!(!filter.is_allowed(&"8.8.8.8".parse().unwrap()));
+
+        // Only the denied addresses are refused without an allow list.
+        let filter = IpFilter::new(vec![], deny);
+        assert!(filter.is_allowed(&"8.8.8.8".parse().unwrap()));
+        assert!(!filter.is_allowed(&"10.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_file_mode() {
+        assert_eq!(parse_file_mode("600"), Ok(0o600));
+        assert_eq!(parse_file_mode("0o660"), Ok(0o660));
+        assert_eq!(parse_file_mode("0755"), Ok(0o755));
+        assert!(parse_file_mode("800").is_err());
+        assert!(parse_file_mode("rw").is_err());
+        assert!(parse_file_mode("").is_err());
+    }
 }
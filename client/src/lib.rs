@@ -22,7 +22,10 @@ pub mod common;
 pub mod configuration;
 pub mod full;
 pub mod light;
+pub mod offline_storage;
 pub mod rpc;
+pub mod snapshot_inspection;
+pub mod state_dump;
 #[cfg(test)]
 mod tests;
 
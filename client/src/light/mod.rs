@@ -8,6 +8,7 @@ use parking_lot::{Condvar, Mutex};
 use secret_store::SecretStore;
 
 use jsonrpc_http_server::Server as HttpServer;
+use jsonrpc_ipc_server::Server as IpcServer;
 use jsonrpc_tcp_server::Server as TcpServer;
 use jsonrpc_ws_server::Server as WsServer;
 
@@ -34,6 +35,7 @@ pub struct LightClientExtraComponents {
     pub debug_rpc_ws_server: Option<WsServer>,
     pub light: Arc<LightQueryService>,
     pub rpc_http_server: Option<HttpServer>,
+    pub rpc_ipc_server: Option<IpcServer>,
     pub rpc_tcp_server: Option<TcpServer>,
     pub rpc_ws_server: Option<WsServer>,
     pub runtime: Runtime,
@@ -91,6 +93,8 @@ impl LightClient {
             data_man.clone(),
         ));
 
+        let rpc_auth = conf.rpc_auth()?;
+
         let debug_rpc_http_server = super::rpc::start_http(
             conf.local_http_config(),
             setup_debug_rpc_apis_light(
@@ -99,6 +103,7 @@ impl LightClient {
                 pubsub.clone(),
                 &conf,
             ),
+            RpcExtractor,
         )?;
 
         let debug_rpc_tcp_server = super::rpc::start_tcp(
@@ -112,6 +117,17 @@ impl LightClient {
             RpcExtractor,
         )?;
 
+        let rpc_ipc_server = super::rpc::start_ipc(
+            conf.ipc_config(),
+            setup_debug_rpc_apis_light(
+                common_impl.clone(),
+                rpc_impl.clone(),
+                pubsub.clone(),
+                &conf,
+            ),
+            RpcExtractor,
+        )?;
+
         let rpc_tcp_server = super::rpc::start_tcp(
             conf.tcp_config(),
             setup_public_rpc_apis_light(
@@ -119,6 +135,7 @@ impl LightClient {
                 rpc_impl.clone(),
                 pubsub.clone(),
                 &conf,
+                None,
             ),
             RpcExtractor,
         )?;
@@ -130,6 +147,7 @@ impl LightClient {
                 rpc_impl.clone(),
                 pubsub.clone(),
                 &conf,
+                None,
             ),
             RpcExtractor,
         )?;
@@ -141,6 +159,7 @@ impl LightClient {
                 rpc_impl.clone(),
                 pubsub.clone(),
                 &conf,
+                rpc_auth.clone(),
             ),
             RpcExtractor,
         )?;
@@ -152,7 +171,9 @@ impl LightClient {
                 rpc_impl,
                 pubsub.clone(),
                 &conf,
+                rpc_auth,
             ),
+            RpcExtractor,
         )?;
 
         Ok(Box::new(ClientComponents {
@@ -165,6 +186,7 @@ impl LightClient {
                 debug_rpc_ws_server,
                 light,
                 rpc_http_server,
+                rpc_ipc_server,
                 rpc_tcp_server,
                 rpc_ws_server,
                 runtime,
@@ -0,0 +1,63 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Access to the storage of a stopped node, shared by the offline subcommands
+//! such as `dump` and `snapshot`.
+
+use crate::configuration::Configuration;
+use cfx_storage::StorageManager;
+
+/// Open the storage without modifying it, see
+/// `StorageManager::new_readonly`. The ledger database is not opened.
+pub fn open_storage_readonly(
+    conf: &Configuration, open_delta_mpts: bool,
+) -> Result<StorageManager, String> {
+    check_node_stopped(conf)?;
+    StorageManager::new_readonly(conf.storage_config(), open_delta_mpts)
+        .map_err(|e| format!("Failed to open storage: {}", e))
+}
+
+/// Fails if the ledger database is locked by a running node. The lock is
+/// only tested, so the node may still start afterwards.
+#[cfg(unix)]
+fn check_node_stopped(conf: &Configuration) -> Result<(), String> {
+    use std::{fs::OpenOptions, io, os::unix::io::AsRawFd};
+
+    let lock_path = conf.block_db_dir().join("LOCK");
+    let lock_file = match OpenOptions::new().write(true).open(&lock_path) {
+        Ok(lock_file) => lock_file,
+        // The node has never started.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(format!(
+                "Failed to open {}: {}",
+                lock_path.display(),
+                e
+            ))
+        }
+    };
+    // RocksDB holds a POSIX record lock on the whole file.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    if unsafe { libc::fcntl(lock_file.as_raw_fd(), libc::F_GETLK, &mut lock) }
+        == -1
+    {
+        return Err(format!(
+            "Failed to test the lock of {}: {}",
+            lock_path.display(),
+            io::Error::last_os_error()
+        ));
+    }
+    if lock.l_type != libc::F_UNLCK as libc::c_short {
+        return Err(format!(
+            "The database is locked by process {}, stop the node first",
+            lock.l_pid
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_node_stopped(_conf: &Configuration) -> Result<(), String> { Ok(()) }
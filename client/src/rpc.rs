@@ -2,11 +2,15 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use jsonrpc_core::{MetaIoHandler, Result as JsonRpcResult};
+use jsonrpc_core::{MetaIoHandler, RemoteProcedure};
 use jsonrpc_http_server::{
     AccessControlAllowOrigin, DomainsValidation, Server as HttpServer,
     ServerBuilder as HttpServerBuilder,
 };
+use jsonrpc_ipc_server::{
+    MetaExtractor as IpcMetaExtractor, Server as IpcServer,
+    ServerBuilder as IpcServerBuilder,
+};
 use jsonrpc_tcp_server::{
     MetaExtractor as TpcMetaExtractor, Server as TcpServer,
     ServerBuilder as TcpServerBuilder,
@@ -20,6 +24,7 @@ use std::{
     sync::Arc,
 };
 
+mod auth;
 mod authcodes;
 pub mod error_codes;
 pub mod extractor;
@@ -30,6 +35,7 @@ pub mod informant;
 mod interceptor;
 pub mod metadata;
 pub mod rpc_apis;
+mod throttle;
 mod traits;
 pub mod types;
 
@@ -40,6 +46,7 @@ pub use cfxcore::rpc_errors::{
 
 use self::{
     impls::{
+        admin::AdminHandler,
         cfx::{CfxHandler, LocalRpcImpl, RpcImpl, TestRpcImpl},
         common::RpcImpl as CommonImpl,
         light::{
@@ -50,22 +57,27 @@ use self::{
         trace::TraceHandler,
     },
     traits::{
-        cfx::Cfx, debug::LocalRpc, pubsub::PubSub, test::TestRpc, trace::Trace,
+        admin::Admin, cfx::Cfx, debug::LocalRpc, pubsub::PubSub, test::TestRpc,
+        trace::Trace,
     },
 };
 
-pub use self::types::{Block as RpcBlock, Origin};
+pub use self::{
+    auth::RpcAuth,
+    types::{Block as RpcBlock, Origin},
+};
 use crate::{
     configuration::Configuration,
     rpc::{
-        error_codes::request_rejected_too_many_request_error,
-        interceptor::{RpcInterceptor, RpcProxy},
+        auth::AuthInterceptor,
+        http_common::{HttpMetaExtractor, MetaExtractor},
+        interceptor::RpcProxy,
         rpc_apis::{Api, ApiSet},
+        throttle::ThrottleInterceptor,
     },
 };
 pub use metadata::Metadata;
 use std::collections::HashSet;
-use throttling::token_bucket::{ThrottleResult, TokenBucketManager};
 
 #[derive(Debug, PartialEq)]
 pub struct TcpConfiguration {
@@ -153,9 +165,27 @@ impl WsConfiguration {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct IpcConfiguration {
+    pub enabled: bool,
+    pub socket_path: String,
+    /// Permissions of the socket file, only used on Unix.
+    pub permissions: u32,
+}
+
+impl IpcConfiguration {
+    pub fn new(socket_path: Option<String>, permissions: u32) -> Self {
+        IpcConfiguration {
+            enabled: socket_path.is_some(),
+            socket_path: socket_path.unwrap_or_default(),
+            permissions,
+        }
+    }
+}
+
 pub fn setup_public_rpc_apis(
     common: Arc<CommonImpl>, rpc: Arc<RpcImpl>, pubsub: PubSubClient,
-    conf: &Configuration,
+    conf: &Configuration, auth: Option<Arc<RpcAuth>>,
 ) -> MetaIoHandler<Metadata>
 {
     setup_rpc_apis(
@@ -165,6 +195,7 @@ pub fn setup_public_rpc_apis(
         &conf.raw_conf.throttling_conf,
         "rpc",
         conf.raw_conf.public_rpc_apis.list_apis(),
+        auth,
     )
 }
 
@@ -180,18 +211,28 @@ pub fn setup_debug_rpc_apis(
         &conf.raw_conf.throttling_conf,
         "rpc_local",
         ApiSet::All.list_apis(),
+        None,
     )
 }
 
 fn setup_rpc_apis(
     common: Arc<CommonImpl>, rpc: Arc<RpcImpl>, pubsub: PubSubClient,
     throttling_conf: &Option<String>, throttling_section: &str,
-    apis: HashSet<Api>,
+    mut apis: HashSet<Api>, auth: Option<Arc<RpcAuth>>,
 ) -> MetaIoHandler<Metadata>
 {
+    if let Some(ref auth) = auth {
+        apis.extend(auth.apis());
+    }
     let mut handler = MetaIoHandler::default();
     for api in apis {
         match api {
+            Api::Admin => extend_with_auth(
+                &mut handler,
+                AdminHandler::new(common.network().clone()).to_delegate(),
+                &api,
+                &auth,
+            ),
             Api::Cfx => {
                 let cfx =
                     CfxHandler::new(common.clone(), rpc.clone()).to_delegate();
@@ -199,18 +240,34 @@ fn setup_rpc_apis(
                     throttling_conf,
                     throttling_section,
                 );
-                handler.extend_with(RpcProxy::new(cfx, interceptor));
+                extend_with_auth(
+                    &mut handler,
+                    RpcProxy::new(cfx, interceptor),
+                    &api,
+                    &auth,
+                );
             }
             Api::Debug => {
-                handler.extend_with(
+                extend_with_auth(
+                    &mut handler,
                     LocalRpcImpl::new(common.clone(), rpc.clone())
                         .to_delegate(),
+                    &api,
+                    &auth,
                 );
             }
-            Api::Pubsub => handler.extend_with(pubsub.clone().to_delegate()),
+            Api::Pubsub => extend_with_auth(
+                &mut handler,
+                pubsub.clone().to_delegate(),
+                &api,
+                &auth,
+            ),
             Api::Test => {
-                handler.extend_with(
+                extend_with_auth(
+                    &mut handler,
                     TestRpcImpl::new(common.clone(), rpc.clone()).to_delegate(),
+                    &api,
+                    &auth,
                 );
             }
             Api::Trace => {
@@ -224,7 +281,12 @@ fn setup_rpc_apis(
                     throttling_conf,
                     throttling_section,
                 );
-                handler.extend_with(RpcProxy::new(trace, interceptor));
+                extend_with_auth(
+                    &mut handler,
+                    RpcProxy::new(trace, interceptor),
+                    &api,
+                    &auth,
+                );
             }
         }
     }
@@ -233,7 +295,7 @@ fn setup_rpc_apis(
 
 pub fn setup_public_rpc_apis_light(
     common: Arc<CommonImpl>, rpc: Arc<LightImpl>, pubsub: PubSubClient,
-    conf: &Configuration,
+    conf: &Configuration, auth: Option<Arc<RpcAuth>>,
 ) -> MetaIoHandler<Metadata>
 {
     setup_rpc_apis_light(
@@ -243,6 +305,7 @@ pub fn setup_public_rpc_apis_light(
         &conf.raw_conf.throttling_conf,
         "rpc",
         conf.raw_conf.public_rpc_apis.list_apis(),
+        auth,
     )
 }
 
@@ -260,18 +323,28 @@ pub fn setup_debug_rpc_apis_light(
         &conf.raw_conf.throttling_conf,
         "rpc_local",
         light_debug_apis,
+        None,
     )
 }
 
 fn setup_rpc_apis_light(
     common: Arc<CommonImpl>, rpc: Arc<LightImpl>, pubsub: PubSubClient,
     throttling_conf: &Option<String>, throttling_section: &str,
-    apis: HashSet<Api>,
+    mut apis: HashSet<Api>, auth: Option<Arc<RpcAuth>>,
 ) -> MetaIoHandler<Metadata>
 {
+    if let Some(ref auth) = auth {
+        apis.extend(auth.apis());
+    }
     let mut handler = MetaIoHandler::default();
     for api in apis {
         match api {
+            Api::Admin => extend_with_auth(
+                &mut handler,
+                AdminHandler::new(common.network().clone()).to_delegate(),
+                &api,
+                &auth,
+            ),
             Api::Cfx => {
                 let cfx = LightCfxHandler::new(common.clone(), rpc.clone())
                     .to_delegate();
@@ -279,19 +352,35 @@ fn setup_rpc_apis_light(
                     throttling_conf,
                     throttling_section,
                 );
-                handler.extend_with(RpcProxy::new(cfx, interceptor));
+                extend_with_auth(
+                    &mut handler,
+                    RpcProxy::new(cfx, interceptor),
+                    &api,
+                    &auth,
+                );
             }
             Api::Debug => {
-                handler.extend_with(
+                extend_with_auth(
+                    &mut handler,
                     LightDebugRpcImpl::new(common.clone(), rpc.clone())
                         .to_delegate(),
+                    &api,
+                    &auth,
                 );
             }
-            Api::Pubsub => handler.extend_with(pubsub.clone().to_delegate()),
+            Api::Pubsub => extend_with_auth(
+                &mut handler,
+                pubsub.clone().to_delegate(),
+                &api,
+                &auth,
+            ),
             Api::Test => {
-                handler.extend_with(
+                extend_with_auth(
+                    &mut handler,
                     LightTestRpcImpl::new(common.clone(), rpc.clone())
                         .to_delegate(),
+                    &api,
+                    &auth,
                 );
             }
             Api::Trace => {
@@ -302,6 +391,23 @@ fn setup_rpc_apis_light(
     handler
 }
 
+/// Adds the methods of `api` to `handler`, checking the auth tokens of the
+/// requests if `auth` is set.
+fn extend_with_auth<T>(
+    handler: &mut MetaIoHandler<Metadata>, methods: T, api: &Api,
+    auth: &Option<Arc<RpcAuth>>,
+) where
+    T: IntoIterator<Item = (String, RemoteProcedure<Metadata>)>,
+{
+    match auth {
+        Some(auth) => handler.extend_with(RpcProxy::new(
+            methods,
+            AuthInterceptor::new(api.clone(), auth.clone()),
+        )),
+        None => handler.extend_with(methods),
+    }
+}
+
 pub fn start_tcp<H, T>(
     conf: TcpConfiguration, handler: H, extractor: T,
 ) -> Result<Option<TcpServer>, String>
@@ -323,13 +429,17 @@ where
     }
 }
 
-pub fn start_http(
-    conf: HttpConfiguration, handler: MetaIoHandler<Metadata>,
-) -> Result<Option<HttpServer>, String> {
+pub fn start_http<T>(
+    conf: HttpConfiguration, handler: MetaIoHandler<Metadata>, extractor: T,
+) -> Result<Option<HttpServer>, String>
+where T: HttpMetaExtractor<Metadata = Metadata> {
     if !conf.enabled {
         return Ok(None);
     }
-    let mut builder = HttpServerBuilder::new(handler);
+    let mut builder = HttpServerBuilder::with_meta_extractor(
+        handler,
+        MetaExtractor::new(extractor),
+    );
     if let Some(threads) = conf.threads {
         builder = builder.threads(threads);
     }
@@ -347,6 +457,46 @@ pub fn start_http(
     }
 }
 
+pub fn start_ipc<H, T>(
+    conf: IpcConfiguration, handler: H, extractor: T,
+) -> Result<Option<IpcServer>, String>
+where
+    H: Into<MetaIoHandler<Metadata>>,
+    T: IpcMetaExtractor<Metadata> + 'static,
+{
+    if !conf.enabled {
+        return Ok(None);
+    }
+
+    // Remove the socket left by a previous run that was not shut down
+    // cleanly, otherwise binding fails.
+    #[cfg(unix)]
+    {
+        use std::{fs, os::unix::fs::FileTypeExt};
+        if let Ok(metadata) = fs::symlink_metadata(&conf.socket_path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(&conf.socket_path).map_err(|e| {
+                    format!("IPC error: {} (path = {})", e, conf.socket_path)
+                })?;
+            }
+        }
+    }
+
+    let builder = IpcServerBuilder::with_meta_extractor(handler, extractor);
+    // The permissions are set right after the socket is bound.
+    #[cfg(unix)]
+    let builder = builder.set_security_attributes(
+        jsonrpc_ipc_server::SecurityAttributes::empty()
+            .set_mode((conf.permissions & 0o7777) as u16)
+            .map_err(|e| format!("IPC error: {}", e))?,
+    );
+    let server = builder.start(&conf.socket_path).map_err(|io_error| {
+        format!("IPC error: {} (path = {})", io_error, conf.socket_path)
+    })?;
+
+    Ok(Some(server))
+}
+
 pub fn start_ws<H, T>(
     conf: WsConfiguration, handler: H, extractor: T,
 ) -> Result<Option<WsServer>, String>
@@ -369,46 +519,29 @@ where
     }
 }
 
-struct ThrottleInterceptor {
-    manager: TokenBucketManager,
-}
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{
+        extractor::RpcExtractor, start_ipc, IpcConfiguration, Metadata,
+    };
+    use jsonrpc_core::MetaIoHandler;
+    use std::{fs, os::unix::fs::PermissionsExt};
+    use tempdir::TempDir;
 
-impl ThrottleInterceptor {
-    fn new(file: &Option<String>, section: &str) -> Self {
-        let manager = match file {
-            Some(file) => TokenBucketManager::load(file, Some(section))
-                .expect("invalid throttling configuration file"),
-            None => TokenBucketManager::default(),
-        };
+    #[test]
+    fn test_ipc_socket_permissions() {
+        let dir = TempDir::new("ipc").unwrap();
+        let socket_path = dir.path().join("conflux.ipc");
+        let conf = IpcConfiguration::new(
+            Some(socket_path.to_str().unwrap().into()),
+            0o640,
+        );
+        let handler = MetaIoHandler::<Metadata>::default();
+        let server = start_ipc(conf, handler, RpcExtractor).unwrap().unwrap();
 
-        ThrottleInterceptor { manager }
-    }
-}
+        let mode = fs::metadata(&socket_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
 
-impl RpcInterceptor for ThrottleInterceptor {
-    fn before(&self, name: &String) -> JsonRpcResult<()> {
-        let bucket = match self.manager.get(name) {
-            Some(bucket) => bucket,
-            None => return Ok(()),
-        };
-
-        let result = bucket.lock().throttle_default();
-
-        match result {
-            ThrottleResult::Success => Ok(()),
-            ThrottleResult::Throttled(wait_time) => {
-                debug!("RPC {} throttled in {:?}", name, wait_time);
-                bail!(request_rejected_too_many_request_error(Some(format!(
-                    "throttled in {:?}",
-                    wait_time
-                ))))
-            }
-            ThrottleResult::AlreadyThrottled => {
-                debug!("RPC {} already throttled", name);
-                bail!(request_rejected_too_many_request_error(Some(
-                    "already throttled, please try again later".into()
-                )))
-            }
-        }
+        server.close();
     }
 }
@@ -0,0 +1,394 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::{
+    authcodes::{
+        constant_time_eq, AuthCodes, DefaultTimeProvider, TimeProvider,
+        TOKEN_LENGTH,
+    },
+    error_codes::unauthorized,
+    interceptor::RpcInterceptor,
+    rpc_apis::{Api, ApiSet},
+    Metadata,
+};
+use cfx_types::H256;
+use jsonrpc_core::Result as JsonRpcResult;
+use parking_lot::RwLock;
+use std::{
+    collections::HashSet,
+    fs::{self, read_to_string},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+/// Access control of the public HTTP and WebSocket RPC servers, loaded from
+/// the file given by `rpc_auth_conf`. Every request must carry a token
+/// granting access to the API of the requested method, either a static
+/// secret or a rotating token derived from a code in the authcodes file.
+pub struct RpcAuth {
+    /// The authcodes file. A client holding a code presents the token
+    /// `<keccak("<code>:<time>") in hex>_<time>`, where `time` is the current
+    /// unix time in seconds.
+    authcodes: Option<AuthCodesFile>,
+    /// APIs accessible with the rotating tokens.
+    authcodes_apis: HashSet<Api>,
+    /// Static secrets and the APIs accessible with each of them.
+    secrets: Vec<(String, HashSet<Api>)>,
+}
+
+impl RpcAuth {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let content = read_to_string(file)
+            .map_err(|e| format!("failed to read toml file: {:?}", e))?;
+        let toml_val = content
+            .parse::<toml::Value>()
+            .map_err(|e| format!("failed to parse toml file: {:?}", e))?;
+
+        Self::from_toml(&toml_val)
+    }
+
+    fn from_toml(val: &toml::Value) -> Result<Self, String> {
+        let authcodes = match val.get("authcodes_path") {
+            Some(path) => Some(AuthCodesFile::new(PathBuf::from(
+                path.as_str().ok_or("authcodes_path must be a string")?,
+            ))),
+            None => None,
+        };
+        let authcodes_apis = match val.get("authcodes_apis") {
+            Some(apis) => parse_apis(apis)?,
+            None => ApiSet::Safe.list_apis(),
+        };
+
+        let mut secrets = Vec::new();
+        if let Some(table) = val.get("secrets") {
+            let table = table.as_table().ok_or("secrets must be a table")?;
+            for (secret, apis) in table {
+                if secret.len() < TOKEN_LENGTH {
+                    return Err(format!(
+                        "secrets must have at least {} characters",
+                        TOKEN_LENGTH
+                    ));
+                }
+                secrets.push((secret.clone(), parse_apis(apis)?));
+            }
+        }
+
+        Ok(RpcAuth {
+            authcodes,
+            authcodes_apis,
+            secrets,
+        })
+    }
+
+    /// All the APIs accessible with some token.
+    pub fn apis(&self) -> HashSet<Api> {
+        let mut apis = HashSet::new();
+        if self.authcodes.is_some() {
+            apis.extend(self.authcodes_apis.iter().cloned());
+        }
+        for (_, secret_apis) in &self.secrets {
+            apis.extend(secret_apis.iter().cloned());
+        }
+        apis
+    }
+
+    /// Returns the APIs accessible with `token`, or `None` if the token is
+    /// invalid.
+    fn allowed_apis(&self, token: &str) -> Option<&HashSet<Api>> {
+        // Compare with all the secrets so that the time taken does not
+        // depend on which secret matches the token.
+        let mut allowed = None;
+        for (secret, apis) in &self.secrets {
+            if constant_time_eq(secret.as_bytes(), token.as_bytes()) {
+                allowed = Some(apis);
+            }
+        }
+        if allowed.is_some() {
+            return allowed;
+        }
+
+        let authcodes = self.authcodes.as_ref()?;
+        let mut fields = token.splitn(2, '_');
+        let hash =
+            H256::from_str(fields.next()?.trim_start_matches("0x")).ok()?;
+        let time = u64::from_str(fields.next()?).ok()?;
+
+        if authcodes.is_valid(&hash, time) {
+            Some(&self.authcodes_apis)
+        } else {
+            None
+        }
+    }
+}
+
+/// The codes of the authcodes file, kept in memory. The file is checked for
+/// modifications at most once per second, and only written back when a code
+/// is removed or used for the first time.
+struct AuthCodesFile {
+    path: PathBuf,
+    state: RwLock<LoadedAuthCodes>,
+    /// The unix time in seconds of the last check of the file.
+    last_checked: AtomicU64,
+}
+
+struct LoadedAuthCodes {
+    codes: AuthCodes,
+    /// The modification time of the file when it was last loaded or saved.
+    modified: Option<SystemTime>,
+}
+
+impl AuthCodesFile {
+    fn new(path: PathBuf) -> Self {
+        AuthCodesFile {
+            path,
+            state: RwLock::new(LoadedAuthCodes {
+                codes: AuthCodes::new(vec![], DefaultTimeProvider),
+                modified: None,
+            }),
+            last_checked: AtomicU64::new(0),
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn is_valid(&self, hash: &H256, time: u64) -> bool {
+        self.refresh();
+
+        let found = self.state.read().codes.find(hash, time);
+        match found {
+            Some((_, true)) => true,
+            Some((code, false)) => {
+                // Save the first use, which keeps the code from being
+                // removed as garbage.
+                let mut state = self.state.write();
+                if state.codes.mark_used(&code) {
+                    self.save(&mut state);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reloads the codes if the file was modified since they were loaded and
+    /// removes the old unused ones. Does nothing if already done within the
+    /// current second.
+    fn refresh(&self) {
+        let now = DefaultTimeProvider.now();
+        let last_checked = self.last_checked.load(Ordering::Relaxed);
+        if last_checked == now
+            || self
+                .last_checked
+                .compare_exchange(
+                    last_checked,
+                    now,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return;
+        }
+
+        let modified = self.modified();
+        let mut state = self.state.write();
+        if modified != state.modified {
+            match AuthCodes::from_file(&self.path) {
+                Ok(codes) => {
+                    state.codes = codes;
+                    state.modified = modified;
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to read authcodes file {:?}: {:?}",
+                        self.path, e
+                    );
+                }
+            }
+        }
+        // remove old tokens
+        if state.codes.clear_garbage() {
+            self.save(&mut state);
+        }
+    }
+
+    fn save(&self, state: &mut LoadedAuthCodes) {
+        match state.codes.to_file(&self.path) {
+            // Do not reload our own changes.
+            Ok(()) => state.modified = self.modified(),
+            Err(e) => {
+                warn!("Failed to save authcodes file {:?}: {:?}", self.path, e)
+            }
+        }
+    }
+}
+
+fn parse_apis(val: &toml::Value) -> Result<HashSet<Api>, String> {
+    let apis = val.as_str().ok_or("apis must be a string")?;
+    Ok(ApiSet::from_str(apis)?.list_apis())
+}
+
+/// Rejects the requests of the methods of `api` not carrying a token granting
+/// access to it.
+pub struct AuthInterceptor {
+    api: Api,
+    auth: Arc<RpcAuth>,
+}
+
+impl AuthInterceptor {
+    pub fn new(api: Api, auth: Arc<RpcAuth>) -> Self {
+        AuthInterceptor { api, auth }
+    }
+}
+
+impl RpcInterceptor<Metadata> for AuthInterceptor {
+    fn before(&self, name: &String, meta: &Metadata) -> JsonRpcResult<()> {
+        let token = match meta.auth_token {
+            Some(ref token) => token,
+            None => bail!(unauthorized(Some("auth token required".into()))),
+        };
+
+        match self.auth.allowed_apis(token) {
+            Some(apis) if apis.contains(&self.api) => Ok(()),
+            Some(_) => {
+                debug!("RPC {} rejected: {:?} not granted", name, self.api);
+                bail!(unauthorized(Some(format!(
+                    "auth token does not grant access to {:?} api",
+                    self.api
+                ))))
+            }
+            None => {
+                debug!("RPC {} rejected: invalid auth token", name);
+                bail!(unauthorized(Some("invalid auth token".into())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthInterceptor, RpcAuth};
+    use crate::rpc::{
+        authcodes::generate_hash, interceptor::RpcInterceptor, rpc_apis::Api,
+        Metadata,
+    };
+    use std::{
+        fs,
+        sync::Arc,
+        thread,
+        time::{Duration, UNIX_EPOCH},
+    };
+    use tempdir::TempDir;
+
+    const SECRET: &str = "0123456789abcdef";
+    const CODE: &str = "fedcba9876543210";
+    const OTHER_CODE: &str = "0011223344556677";
+
+    fn new_auth(authcodes_path: &str) -> Arc<RpcAuth> {
+        let config = format!(
+            r#"
+            authcodes_path = "{}"
+            authcodes_apis = "cfx,debug"
+
+            [secrets]
+            "{}" = "cfx,trace"
+            "#,
+            authcodes_path, SECRET
+        );
+        Arc::new(RpcAuth::from_toml(&config.parse().unwrap()).unwrap())
+    }
+
+    fn check(auth: &Arc<RpcAuth>, api: Api, token: Option<String>) -> bool {
+        let meta = Metadata {
+            auth_token: token,
+            ..Default::default()
+        };
+        AuthInterceptor::new(api, auth.clone())
+            .before(&"test_method".into(), &meta)
+            .is_ok()
+    }
+
+    #[test]
+    fn test_secret() {
+        let auth = new_auth("authcodes");
+        let secret = Some(SECRET.to_owned());
+
+        assert!(check(&auth, Api::Cfx, secret.clone()));
+        assert!(check(&auth, Api::Trace, secret.clone()));
+        assert!(!check(&auth, Api::Debug, secret));
+        assert!(!check(&auth, Api::Cfx, None));
+        assert!(!check(&auth, Api::Cfx, Some("wrong".into())));
+        assert_eq!(
+            auth.apis(),
+            vec![Api::Cfx, Api::Debug, Api::Trace].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_authcodes() {
+        let dir = TempDir::new("rpc_auth").unwrap();
+        let path = dir.path().join("authcodes");
+        fs::write(&path, CODE).unwrap();
+        let auth = new_auth(path.to_str().unwrap());
+
+        let time = UNIX_EPOCH.elapsed().unwrap().as_secs();
+        let token = Some(format!("{:x}_{}", generate_hash(CODE, time), time));
+        assert!(check(&auth, Api::Debug, token.clone()));
+        assert!(!check(&auth, Api::Trace, token));
+
+        // Tokens expire after a few seconds.
+        let time = time - 60;
+        let token = Some(format!("{:x}_{}", generate_hash(CODE, time), time));
+        assert!(!check(&auth, Api::Debug, token));
+    }
+
+    #[test]
+    fn test_authcodes_reload() {
+        let dir = TempDir::new("rpc_auth").unwrap();
+        let path = dir.path().join("authcodes");
+        fs::write(&path, CODE).unwrap();
+        let auth = new_auth(path.to_str().unwrap());
+        let token = |code: &str| {
+            let time = UNIX_EPOCH.elapsed().unwrap().as_secs();
+            Some(format!("{:x}_{}", generate_hash(code, time), time))
+        };
+        // Wait for the next check of the file.
+        let wait = || thread::sleep(Duration::from_millis(1100));
+
+        // The first use of a code is saved.
+        assert!(check(&auth, Api::Cfx, token(CODE)));
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.split(';').count(), 3);
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+        // Later uses are not.
+        wait();
+        assert!(check(&auth, Api::Cfx, token(CODE)));
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        // The codes are reloaded when the file is modified.
+        fs::write(&path, OTHER_CODE).unwrap();
+        wait();
+        assert!(!check(&auth, Api::Cfx, token(CODE)));
+        assert!(check(&auth, Api::Cfx, token(OTHER_CODE)));
+    }
+
+    #[test]
+    fn test_short_secret() {
+        let config = r#"
+            [secrets]
+            "short" = "all"
+        "#;
+        assert!(RpcAuth::from_toml(&config.parse().unwrap()).is_err());
+    }
+}
@@ -24,7 +24,7 @@ use std::{
     fs,
     io::{self, Read, Write},
     mem,
-    path::Path,
+    path::{Path, PathBuf},
     time,
 };
 
@@ -54,7 +54,7 @@ impl TimeProvider for DefaultTimeProvider {
 /// No of seconds the hash is valid
 const TIME_THRESHOLD: u64 = 7;
 /// minimal length of hash
-const TOKEN_LENGTH: usize = 16;
+pub const TOKEN_LENGTH: usize = 16;
 /// Separator between fields in serialized tokens file.
 const SEPARATOR: &str = ";";
 /// Number of seconds to keep unused tokens.
@@ -75,6 +75,19 @@ fn decode_time(val: &str) -> Option<time::Duration> {
 
 fn encode_time(time: time::Duration) -> String { format!("{}", time.as_secs()) }
 
+/// Returns the token presented at `time` by a client holding `code`.
+pub fn generate_hash(code: &str, time: u64) -> H256 {
+    keccak(format!("{}:{}", code, time))
+}
+
+/// Compares `a` and `b` in a time only depending on their lengths.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 /// Manages authorization codes for `SignerUIs`
 pub struct AuthCodes<T: TimeProvider = DefaultTimeProvider> {
     codes: Vec<Code>,
@@ -127,9 +140,13 @@ impl AuthCodes<DefaultTimeProvider> {
 }
 
 impl<T: TimeProvider> AuthCodes<T> {
-    /// Writes all `AuthCodes` to a disk.
+    /// Writes all `AuthCodes` to a disk. The codes are written to a temporary
+    /// file which then replaces `file`, so that a concurrent reader never
+    /// sees a partially written file.
     pub fn to_file(&self, file: &Path) -> io::Result<()> {
-        let mut file = fs::File::create(file)?;
+        let mut tmp_path = file.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
         let content = self
             .codes
             .iter()
@@ -142,7 +159,12 @@ impl<T: TimeProvider> AuthCodes<T> {
                 data.join(SEPARATOR)
             })
             .join("\n");
-        file.write_all(content.as_bytes())
+        {
+            let mut tmp_file = fs::File::create(&tmp_path)?;
+            tmp_file.write_all(content.as_bytes())?;
+            tmp_file.sync_all()?;
+        }
+        fs::rename(&tmp_path, file)
     }
 
     /// Creates a new `AuthCodes` store with given `TimeProvider`.
@@ -163,24 +185,45 @@ impl<T: TimeProvider> AuthCodes<T> {
     /// Checks if given hash is correct authcode of `SignerUI`
     /// Updates this hash last used field in case it's valid.
     pub fn is_valid(&mut self, hash: &H256, time: u64) -> bool {
+        match self.find(hash, time) {
+            Some((code, _)) => {
+                self.mark_used(&code);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the code of which `hash` is the token at `time`, and whether
+    /// the code was used before. The hash is compared with the tokens of all
+    /// the codes in constant time.
+    pub fn find(&self, hash: &H256, time: u64) -> Option<(String, bool)> {
         let now = self.now.now();
         // check time
         if time >= now + TIME_THRESHOLD || time <= now - TIME_THRESHOLD {
             warn!(target: "signer", "Received old authentication request. ({} vs {})", now, time);
-            return false;
+            return None;
         }
 
-        let as_token = |code| keccak(format!("{}:{}", code, time));
-
         // look for code
-        for code in &mut self.codes {
-            if &as_token(&code.code) == hash {
-                code.last_used_at = Some(time::Duration::from_secs(now));
-                return true;
+        let mut found = None;
+        for code in &self.codes {
+            let token = generate_hash(&code.code, time);
+            if constant_time_eq(token.as_bytes(), hash.as_bytes()) {
+                found = Some((code.code.clone(), code.last_used_at.is_some()));
             }
         }
+        found
+    }
 
-        false
+    /// Updates the last used time of `code`. Returns true if the code was
+    /// never used before.
+    pub fn mark_used(&mut self, code: &str) -> bool {
+        let now = time::Duration::from_secs(self.now.now());
+        match self.codes.iter_mut().find(|c| c.code == code) {
+            Some(code) => code.last_used_at.replace(now).is_none(),
+            None => false,
+        }
     }
 
     /// Generates and returns a new code that can be used by `SignerUIs`
@@ -207,13 +250,15 @@ impl<T: TimeProvider> AuthCodes<T> {
     /// Returns true if there are no tokens in this store
     pub fn is_empty(&self) -> bool { self.codes.is_empty() }
 
-    /// Removes old tokens that have not been used since creation.
-    pub fn clear_garbage(&mut self) {
+    /// Removes old tokens that have not been used since creation. Returns
+    /// true if any token was removed.
+    pub fn clear_garbage(&mut self) -> bool {
         let now = self.now.now();
         let threshold =
             time::Duration::from_secs(now.saturating_sub(UNUSED_TOKEN_TIMEOUT));
 
         let codes = mem::replace(&mut self.codes, Vec::new());
+        let count = codes.len();
         for code in codes {
             // Skip codes that are old and were never used.
             if code.last_used_at.is_none() && code.created_at <= threshold {
@@ -221,14 +266,13 @@ impl<T: TimeProvider> AuthCodes<T> {
             }
             self.codes.push(code);
         }
+        self.codes.len() != count
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cfx_types::H256;
-    use keccak_hash::keccak;
     use std::{
         cell::Cell,
         fs,
@@ -237,10 +281,6 @@ mod tests {
     };
     use tempdir::TempDir;
 
-    fn generate_hash(val: &str, time: u64) -> H256 {
-        keccak(format!("{}:{}", val, time))
-    }
-
     #[test]
     fn should_return_false_even_if_code_is_initial_and_store_is_empty() {
         // given
@@ -41,7 +41,7 @@ pub mod codes {
     /// by 1.
     ///
     /// Do not recycle deprecated error codes.
-    const NEXT_SERVER_ERROR_CODE: i64 = -32078;
+    const NEXT_SERVER_ERROR_CODE: i64 = -32080;
     /// When the above number is equal to -32100, take the number below on the
     /// right for new error code, then increase it by 1.
     const CFX_EXTRA_SERVER_ERROR_CODE: i64 = -31999;
@@ -73,6 +73,9 @@ pub mod codes {
     /// This is mostly an application error but it's generic enough to define it
     /// here.
     pub const REQUEST_REJECTED_LIMIT_DATA: i64 = -32041;
+    /// When the request does not carry a valid auth token granting access to
+    /// the requested API, and the server requires one.
+    pub const UNAUTHORIZED: i64 = -32079;
 
     /* Conflux node status related error codes
      *
@@ -126,6 +129,12 @@ pub mod codes {
     /// Encoding error happened in signing structured data. Related to EIP712.
     pub const ENCODING_ERROR: i64 = -32058;
 
+    /* Transaction pool related error codes */
+    /// The transaction can not replace the transaction with the same sender
+    /// and nonce in the transaction pool, e.g. its gas price is not high
+    /// enough.
+    pub const TX_REPLACEMENT_REJECTED: i64 = -32078;
+
     /* Other application error codes */
     /// Call() execution error. This is clearly an application level error code,
     /// but we keep the error code to be ethereum rpc client compatible.
@@ -166,6 +175,14 @@ pub fn request_rejected_too_many_request_error(
     }
 }
 
+pub fn unauthorized(details: Option<String>) -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::UNAUTHORIZED),
+        message: "Unauthorized.".into(),
+        data: details.map(Value::String),
+    }
+}
+
 pub fn request_rejected_in_catch_up_mode(details: Option<String>) -> Error {
     Error {
         code: ErrorCode::ServerError(codes::REQUEST_REJECTED_IN_CATCH_UP),
@@ -174,6 +191,14 @@ pub fn request_rejected_in_catch_up_mode(details: Option<String>) -> Error {
     }
 }
 
+pub fn tx_replacement_rejected(message: String, details: Value) -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::TX_REPLACEMENT_REJECTED),
+        message,
+        data: Some(details),
+    }
+}
+
 pub fn pivot_assumption_failed(expected: H256, got: H256) -> Error {
     Error {
         code: ErrorCode::ServerError(codes::CONFLUX_PIVOT_CHAIN_UNSTABLE),
@@ -18,6 +18,7 @@
 
 use crate::rpc::{http_common::HttpMetaExtractor, Metadata, Origin};
 use cfx_types::H256;
+use jsonrpc_ipc_server as ipc;
 use jsonrpc_pubsub::Session;
 use jsonrpc_tcp_server as tcp;
 use jsonrpc_ws_server as ws;
@@ -32,6 +33,8 @@ impl HttpMetaExtractor for RpcExtractor {
 
     fn read_metadata(
         &self, origin: Option<String>, user_agent: Option<String>,
+        api_key: Option<String>, forwarded_for: Option<String>,
+        auth_token: Option<String>,
     ) -> Metadata {
         Metadata {
             origin: Origin::Rpc(format!(
@@ -40,6 +43,12 @@ impl HttpMetaExtractor for RpcExtractor {
                 user_agent.unwrap_or_else(|| "unknown agent".to_string())
             )),
             session: None,
+            api_key,
+            // The first address is the client, followed by the proxies.
+            forwarded_for: forwarded_for.and_then(|addresses| {
+                addresses.split(',').next()?.trim().parse().ok()
+            }),
+            auth_token,
         }
     }
 }
@@ -49,6 +58,17 @@ impl tcp::MetaExtractor<Metadata> for RpcExtractor {
         Metadata {
             origin: Origin::Tcp(req.peer_addr),
             session: Some(Arc::new(Session::new(req.sender.clone()))),
+            ..Default::default()
+        }
+    }
+}
+
+impl ipc::MetaExtractor<Metadata> for RpcExtractor {
+    fn extract(&self, req: &ipc::RequestContext) -> Metadata {
+        Metadata {
+            origin: Origin::Ipc(H256::from_low_u64_be(req.session_id)),
+            session: Some(Arc::new(Session::new(req.sender.clone()))),
+            ..Default::default()
         }
     }
 }
@@ -60,6 +80,10 @@ impl ws::MetaExtractor<Metadata> for RpcExtractor {
                 session: H256::from_low_u64_be(req.session_id),
             },
             session: Some(Arc::new(Session::new(req.sender()))),
+            // Browsers cannot set headers on WebSocket connections, so the
+            // token is passed as the subprotocol.
+            auth_token: req.protocols.get(0).cloned(),
+            ..Default::default()
         }
     }
 }
@@ -263,11 +287,21 @@ mod tests {
         let extractor = RpcExtractor;
 
         // when
-        let meta1 = extractor.read_metadata(None, None);
-        let meta2 = extractor
-            .read_metadata(None, Some("https://conflux-chain.org".to_owned()));
-        let meta3 = extractor
-            .read_metadata(None, Some("https://conflux-chain.org".to_owned()));
+        let meta1 = extractor.read_metadata(None, None, None, None, None);
+        let meta2 = extractor.read_metadata(
+            None,
+            Some("https://conflux-chain.org".to_owned()),
+            None,
+            None,
+            None,
+        );
+        let meta3 = extractor.read_metadata(
+            None,
+            Some("https://conflux-chain.org".to_owned()),
+            None,
+            None,
+            None,
+        );
 
         // then
         assert_eq!(
@@ -283,4 +317,28 @@ mod tests {
             Origin::Rpc("unknown origin / https://conflux-chain.org".into())
         );
     }
+
+    #[test]
+    fn should_extract_api_key_and_forwarded_for() {
+        let extractor = RpcExtractor;
+
+        let meta = extractor.read_metadata(
+            None,
+            None,
+            Some("key".to_owned()),
+            Some("10.0.0.1, 192.168.0.1".to_owned()),
+            None,
+        );
+        assert_eq!(meta.api_key, Some("key".to_owned()));
+        assert_eq!(meta.forwarded_for, Some("10.0.0.1".parse().unwrap()));
+
+        let meta = extractor.read_metadata(
+            None,
+            None,
+            None,
+            Some("unknown".to_owned()),
+            None,
+        );
+        assert_eq!(meta.forwarded_for, None);
+    }
 }
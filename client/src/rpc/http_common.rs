@@ -20,6 +20,9 @@
 use jsonrpc_core;
 use jsonrpc_http_server::{self as http, hyper};
 
+/// Header carrying the API key identifying a client for RPC throttling.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
 /// HTTP RPC server impl-independent metadata extractor
 pub trait HttpMetaExtractor: Send + Sync + 'static {
     /// Type of Metadata
@@ -27,6 +30,8 @@ pub trait HttpMetaExtractor: Send + Sync + 'static {
     /// Extracts metadata from given params.
     fn read_metadata(
         &self, origin: Option<String>, user_agent: Option<String>,
+        api_key: Option<String>, forwarded_for: Option<String>,
+        auth_token: Option<String>,
     ) -> Self::Metadata;
 }
 
@@ -50,6 +55,16 @@ where
 
         let origin = as_string(req.headers().get("origin"));
         let user_agent = as_string(req.headers().get("user-agent"));
-        self.extractor.read_metadata(origin, user_agent)
+        let api_key = as_string(req.headers().get(API_KEY_HEADER));
+        let forwarded_for = as_string(req.headers().get("x-forwarded-for"));
+        let auth_token = as_string(req.headers().get("authorization"))
+            .map(|val| val.trim_start_matches("Bearer ").to_owned());
+        self.extractor.read_metadata(
+            origin,
+            user_agent,
+            api_key,
+            forwarded_for,
+            auth_token,
+        )
     }
 }
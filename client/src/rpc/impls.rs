@@ -14,8 +14,13 @@ pub struct RpcImplConfiguration {
     // note: currently we only handle this for `cfx_getEpochReceipts`,
     // other APIs will disconnect on oversized response
     pub max_payload_bytes: usize,
+
+    /// If it's `true`, the transactions sent through `cfx_sendTransaction`
+    /// and `cfx_sendRawTransaction` are local transactions of the tx pool.
+    pub local_transactions: bool,
 }
 
+pub mod admin;
 pub mod cfx;
 pub mod common;
 pub mod light;
@@ -0,0 +1,186 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::{
+    error_codes::invalid_params,
+    traits::admin::Admin,
+    types::{BlacklistedNode, BlacklistedSubnet, IpFilter as RpcIpFilter},
+};
+use cfx_types::U64;
+use jsonrpc_core::{Error as RpcError, Result as JsonRpcResult};
+use network::{
+    node_table::{Node, NodeEndpoint, NodeEntry, NodeId},
+    Error as NetworkError, IpFilter, IpNetwork, NetworkService,
+};
+use std::{
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub struct AdminHandler {
+    network: Arc<NetworkService>,
+}
+
+impl AdminHandler {
+    pub fn new(network: Arc<NetworkService>) -> Self {
+        AdminHandler { network }
+    }
+}
+
+fn network_error(e: NetworkError) -> RpcError {
+    let mut error = RpcError::internal_error();
+    error.data = Some(format!("{}", e).into());
+    error
+}
+
+fn parse_subnet(subnet: &str) -> JsonRpcResult<IpNetwork> {
+    IpNetwork::from_str(subnet).map_err(|e| invalid_params("subnet", e))
+}
+
+fn parse_subnets(subnets: &[String]) -> JsonRpcResult<Vec<IpNetwork>> {
+    subnets.iter().map(|s| parse_subnet(s)).collect()
+}
+
+fn expiry(duration: Option<U64>) -> JsonRpcResult<Option<SystemTime>> {
+    match duration {
+        Some(d) => SystemTime::now()
+            .checked_add(Duration::from_secs(d.as_u64()))
+            .map(Some)
+            .ok_or_else(|| invalid_params("duration", "duration is too long")),
+        None => Ok(None),
+    }
+}
+
+fn unix_time(time: Option<SystemTime>) -> Option<U64> {
+    time.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs().into())
+}
+
+impl Admin for AdminHandler {
+    fn trusted_nodes(&self) -> JsonRpcResult<Vec<Node>> {
+        self.network.trusted_nodes().map_err(network_error)
+    }
+
+    fn add_trusted_node(
+        &self, id: NodeId, address: SocketAddr,
+    ) -> JsonRpcResult<()> {
+        let node = NodeEntry {
+            id,
+            endpoint: NodeEndpoint {
+                address,
+                udp_port: address.port(),
+            },
+        };
+        info!("RPC Request: admin_addTrustedNode({:?})", node);
+        self.network.add_trusted_node(node).map_err(network_error)
+    }
+
+    fn remove_trusted_node(&self, id: NodeId) -> JsonRpcResult<()> {
+        info!("RPC Request: admin_removeTrustedNode({:?})", id);
+        self.network.remove_trusted_node(&id).map_err(network_error)
+    }
+
+    fn blacklisted_nodes(&self) -> JsonRpcResult<Vec<BlacklistedNode>> {
+        let nodes = self.network.blacklisted_nodes().map_err(network_error)?;
+        Ok(nodes
+            .into_iter()
+            .map(|(node, until)| BlacklistedNode {
+                id: node.id,
+                until: unix_time(until),
+            })
+            .collect())
+    }
+
+    fn blacklist_node(
+        &self, id: NodeId, duration: Option<U64>,
+    ) -> JsonRpcResult<()> {
+        info!("RPC Request: admin_blacklistNode({:?}, {:?})", id, duration);
+        self.network
+            .blacklist_node(&id, expiry(duration)?)
+            .map_err(network_error)
+    }
+
+    fn unblacklist_node(&self, id: NodeId) -> JsonRpcResult<bool> {
+        info!("RPC Request: admin_unblacklistNode({:?})", id);
+        self.network.unblacklist_node(&id).map_err(network_error)
+    }
+
+    fn blacklisted_subnets(&self) -> JsonRpcResult<Vec<BlacklistedSubnet>> {
+        let subnets =
+            self.network.blacklisted_subnets().map_err(network_error)?;
+        Ok(subnets
+            .into_iter()
+            .map(|(subnet, until)| BlacklistedSubnet {
+                subnet: subnet.to_string(),
+                until: unix_time(until),
+            })
+            .collect())
+    }
+
+    fn blacklist_subnet(
+        &self, subnet: String, duration: Option<U64>,
+    ) -> JsonRpcResult<()> {
+        info!(
+            "RPC Request: admin_blacklistSubnet({:?}, {:?})",
+            subnet, duration
+        );
+        self.network
+            .blacklist_subnet(parse_subnet(&subnet)?, expiry(duration)?)
+            .map_err(network_error)
+    }
+
+    fn unblacklist_subnet(&self, subnet: String) -> JsonRpcResult<bool> {
+        info!("RPC Request: admin_unblacklistSubnet({:?})", subnet);
+        self.network
+            .unblacklist_subnet(&parse_subnet(&subnet)?)
+            .map_err(network_error)
+    }
+
+    fn ip_filter(&self) -> JsonRpcResult<RpcIpFilter> {
+        let filter = self.network.ip_filter().map_err(network_error)?;
+        Ok(RpcIpFilter {
+            allow: filter.custom_allow.iter().map(|s| s.to_string()).collect(),
+            deny: filter.custom_block.iter().map(|s| s.to_string()).collect(),
+        })
+    }
+
+    fn set_ip_filter(&self, filter: RpcIpFilter) -> JsonRpcResult<()> {
+        info!("RPC Request: admin_setIpFilter({:?})", filter);
+        let filter = IpFilter::new(
+            parse_subnets(&filter.allow)?,
+            parse_subnets(&filter.deny)?,
+        );
+        self.network.set_ip_filter(filter).map_err(network_error)
+    }
+
+    fn set_node_tag(
+        &self, id: NodeId, key: String, value: Option<String>,
+    ) -> JsonRpcResult<bool> {
+        info!(
+            "RPC Request: admin_setNodeTag({:?}, {:?}, {:?})",
+            id, key, value
+        );
+        self.network
+            .set_node_tag(id, &key, value.as_deref())
+            .map_err(network_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expiry;
+    use cfx_types::U64;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_expiry() {
+        assert_eq!(expiry(None).unwrap(), None);
+        assert!(
+            expiry(Some(U64::from(60))).unwrap().unwrap() > SystemTime::now()
+        );
+        assert!(expiry(Some(U64::from(u64::MAX))).is_err());
+    }
+}
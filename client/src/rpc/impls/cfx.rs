@@ -4,7 +4,7 @@
 
 use crate::rpc::types::{
     call_request::rpc_call_request_network, errors::check_rpc_address_network,
-    RpcAddress, SponsorInfo, TokenSupplyInfo, MAX_GAS_CALL_REQUEST,
+    RpcAddress, SponsorInfo, TokenSupplyInfo,
 };
 use blockgen::BlockGenerator;
 use cfx_state::state_trait::StateOpsTrait;
@@ -13,10 +13,12 @@ use cfx_types::{
     address_util::AddressUtil, BigEndianHash, H256, H520, U128, U256, U64,
 };
 use cfxcore::{
-    executive::{ExecutionError, ExecutionOutcome, TxDropError},
+    executive::ExecutionOutcome,
     rpc_errors::{account_result_to_rpc_result, invalid_params_check},
+    state::StateOverride as PrimitiveStateOverride,
     state_exposer::STATE_EXPOSER,
-    vm, ConsensusGraph, ConsensusGraphTrait, PeerInfo, SharedConsensusGraph,
+    transaction_pool::InsertError,
+    ConsensusGraph, ConsensusGraphTrait, PeerInfo, SharedConsensusGraph,
     SharedSynchronizationService, SharedTransactionPool,
 };
 use cfxcore_accounts::AccountProvider;
@@ -43,8 +45,8 @@ use crate::{
     common::delegate_convert,
     rpc::{
         error_codes::{
-            call_execution_error, invalid_params, pivot_assumption_failed,
-            request_rejected_in_catch_up_mode,
+            invalid_params, pivot_assumption_failed,
+            request_rejected_in_catch_up_mode, tx_replacement_rejected,
         },
         impls::{
             common::{self, RpcImpl as CommonImpl},
@@ -54,27 +56,30 @@ use crate::{
         types::{
             sign_call, Account as RpcAccount, AccountPendingInfo,
             AccountPendingTransactions, BlameInfo, Block as RpcBlock,
-            BlockHashOrEpochNumber, Bytes, CallRequest,
+            BlockHashOrEpochNumber, Bytes, CallRequest, CallResult,
             CheckBalanceAgainstTransactionResponse, ConsensusGraphStates,
-            EpochNumber, EstimateGasAndCollateralResponse, Log as RpcLog,
-            LogFilter as RpcFilter, PackedOrExecuted, Receipt as RpcReceipt,
-            RewardInfo as RpcRewardInfo, SendTxRequest, Status as RpcStatus,
-            SyncGraphStates, Transaction as RpcTransaction, TxPoolPendingInfo,
+            EpochNumber, EstimateGasAndCollateralResponse, FeeHistory,
+            Log as RpcLog, LogFilter as RpcFilter, PackedOrExecuted,
+            Receipt as RpcReceipt, RewardInfo as RpcRewardInfo, SendTxRequest,
+            StateOverride, Status as RpcStatus, StorageRange,
+            StorageRangeEntry, SyncGraphStates, Transaction as RpcTransaction,
+            TransactionStructLogs, TxPoolPendingInfo, TxReplacement,
             TxWithPoolInfo,
         },
         RpcResult,
     },
 };
 use cfx_addr::Network;
-use cfx_parameters::consensus_internal::REWARD_EPOCH_COUNT;
+use cfx_parameters::{
+    consensus_internal::REWARD_EPOCH_COUNT,
+    rpc::{MAX_FEE_HISTORY_EPOCH_COUNT, MAX_STORAGE_RANGE_LIMIT},
+};
 use cfxcore::{
     consensus::{MaybeExecutedTxExtraInfo, TransactionInfo},
     consensus_parameters::DEFERRED_STATE_EPOCH_COUNT,
-    executive::revert_reason_decode,
     spec::genesis::{
         genesis_contract_address_four_year, genesis_contract_address_two_year,
     },
-    trace::ErrorUnwind,
 };
 use lazy_static::lazy_static;
 use metrics::{register_timer_with_group, ScopeTimer, Timer};
@@ -448,6 +453,54 @@ impl RpcImpl {
         })
     }
 
+    fn storage_range(
+        &self, address: RpcAddress, start_key: Bytes, limit: U64,
+        epoch_num: Option<EpochNumber>,
+    ) -> RpcResult<StorageRange>
+    {
+        self.check_address_network(address.network)?;
+        let epoch_num = epoch_num.unwrap_or(EpochNumber::LatestState).into();
+
+        info!(
+            "RPC Request: cfx_getStorageRange address={:?}, start_key={:?}, limit={:?}, epoch_num={:?}",
+            address, start_key, limit, epoch_num
+        );
+
+        let limit = limit.as_u64();
+        if limit == 0 || limit > MAX_STORAGE_RANGE_LIMIT {
+            bail!(invalid_params(
+                "limit",
+                format!(
+                    "limit should be between 1 and {}",
+                    MAX_STORAGE_RANGE_LIMIT
+                )
+            ));
+        }
+
+        let mut state_db = self
+            .consensus
+            .get_kept_state_db_by_epoch_number(epoch_num, "epoch_num")?;
+
+        let (entries, next_key) = state_db.get_original_storage_range(
+            &address.hex_address,
+            &start_key.0,
+            limit as usize,
+        )?;
+
+        let storage = entries
+            .into_iter()
+            .map(|(key, entry)| StorageRangeEntry {
+                key: Bytes::new(key),
+                value: H256::from_uint(&entry.value),
+            })
+            .collect();
+
+        Ok(StorageRange {
+            storage,
+            next_key: next_key.map(Bytes::new),
+        })
+    }
+
     fn send_transaction_with_signature(
         &self, tx: TransactionWithSignature,
     ) -> RpcResult<H256> {
@@ -460,8 +513,11 @@ impl RpcImpl {
             warn!("Ignore send_transaction request {}. Cannot send transaction when the node is still in catch-up mode.", tx.hash());
             bail!(request_rejected_in_catch_up_mode(None));
         }
-        let (signed_trans, failed_trans) =
-            self.tx_pool.insert_new_transactions(vec![tx]);
+        let (signed_trans, failed_trans) = if self.config.local_transactions {
+            self.tx_pool.insert_new_local_transactions(vec![tx])
+        } else {
+            self.tx_pool.insert_new_transactions(vec![tx])
+        };
         // FIXME: how is it possible?
         if signed_trans.len() + failed_trans.len() > 1 {
             // This should never happen
@@ -473,9 +529,17 @@ impl RpcImpl {
             // FIXME: this is not invalid params
             bail!(invalid_params("tx", String::from("tx already exist")))
         } else if signed_trans.is_empty() {
-            let tx_err = failed_trans.iter().next().expect("Not empty").1;
-            // FIXME: this is not invalid params
-            bail!(invalid_params("tx", tx_err))
+            match failed_trans.into_iter().next().expect("Not empty").1 {
+                InsertError::Replacement(e) => {
+                    bail!(tx_replacement_rejected(
+                        e.to_string(),
+                        serde_json::to_value(e)
+                            .expect("serialization should not fail")
+                    ))
+                }
+                // FIXME: this is not invalid params
+                tx_err => bail!(invalid_params("tx", tx_err.to_string())),
+            }
         } else {
             let tx_hash = signed_trans[0].hash();
             self.sync.append_received_transactions(signed_trans);
@@ -546,7 +610,7 @@ impl RpcImpl {
 
         let root = self
             .consensus
-            .get_state_db_by_epoch_number(epoch_num, "epoch_num")?
+            .get_kept_state_db_by_epoch_number(epoch_num, "epoch_num")?
             .get_original_storage_root(&address.hex_address)?;
 
         Ok(Some(root))
@@ -1105,146 +1169,113 @@ impl RpcImpl {
     fn call(
         &self, request: CallRequest, epoch: Option<EpochNumber>,
     ) -> RpcResult<Bytes> {
-        match self.exec_transaction(request, epoch)? {
-            ExecutionOutcome::NotExecutedDrop(TxDropError::OldNonce(expected, got)) => {
-                bail!(call_execution_error(
-                    "Transaction can not be executed".into(),
-                    format! {"nonce is too old expected {:?} got {:?}", expected, got}.into_bytes()
-                ))
-            }
-            ExecutionOutcome::NotExecutedDrop(TxDropError::InvalidRecipientAddress(recipient)) => {
-                bail!(call_execution_error(
-                    "Transaction can not be executed".into(),
-                    format! {"invalid recipient address {:?}", recipient}.into_bytes()
-                ))
-            }
-            ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
-                bail!(call_execution_error(
-                    "Transaction can not be executed".into(),
-                    format! {"{:?}", e}.into_bytes()
-                ))
-            }
-            ExecutionOutcome::ExecutionErrorBumpNonce(
-                ExecutionError::VmError(vm::Error::Reverted),
-                executed,
-            ) => bail!(call_execution_error(
-                "Transaction reverted".into(),
-                executed.output
-            )),
-            ExecutionOutcome::ExecutionErrorBumpNonce(e, _) => {
-                bail!(call_execution_error(
-                    "Transaction execution failed".into(),
-                    format! {"{:?}", e}.into_bytes()
-                ))
-            }
-            ExecutionOutcome::Finished(executed) => Ok(executed.output.into()),
-        }
+        common::call_result(self.exec_transaction(request, epoch)?)
     }
 
-    fn estimate_gas_and_collateral(
-        &self, request: CallRequest, epoch: Option<EpochNumber>,
-    ) -> RpcResult<EstimateGasAndCollateralResponse> {
-        let executed = match self.exec_transaction(request, epoch)? {
-            ExecutionOutcome::NotExecutedDrop(TxDropError::OldNonce(expected, got)) => {
-                bail!(call_execution_error(
-                    "Can not estimate: transaction can not be executed".into(),
-                    format! {"nonce is too old expected {:?} got {:?}", expected, got}.into_bytes()
-                ))
-            }
-            ExecutionOutcome::NotExecutedDrop(TxDropError::InvalidRecipientAddress(recipient)) => {
-                bail!(call_execution_error(
-                    "Can not estimate: transaction can not be executed".into(),
-                    format! {"invalid recipient address {:?}", recipient}.into_bytes()
-                ))
-            }
-            ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
-                bail!(call_execution_error(
-                    "Can not estimate: transaction can not be executed".into(),
-                    format! {"{:?}", e}.into_bytes()
-                ))
+    fn fee_history(
+        &self, epoch_count: U64, newest_epoch: EpochNumber,
+        reward_percentiles: Vec<f64>,
+    ) -> RpcResult<FeeHistory>
+    {
+        info!(
+            "RPC Request: cfx_feeHistory(epoch_count={:?}, newest_epoch={:?}, reward_percentiles={:?})",
+            epoch_count, newest_epoch, reward_percentiles
+        );
+
+        let epoch_count = epoch_count.as_u64();
+        if epoch_count == 0 || epoch_count > MAX_FEE_HISTORY_EPOCH_COUNT {
+            bail!(invalid_params(
+                "epoch_count",
+                format!(
+                    "epoch_count should be between 1 and {}",
+                    MAX_FEE_HISTORY_EPOCH_COUNT
+                )
+            ));
+        }
+        let mut last_percentile = 0.0;
+        for percentile in &reward_percentiles {
+            if !(last_percentile..=100.0).contains(percentile) {
+                bail!(invalid_params(
+                    "reward_percentiles",
+                    "reward_percentiles should be ascending values between 0 and 100"
+                ));
             }
-            ExecutionOutcome::ExecutionErrorBumpNonce(
-                ExecutionError::VmError(vm::Error::Reverted),
-                executed,
-            ) => {
-                let network_type = *self.sync.network.get_network_type();
-
-                // When a revert exception happens, there is usually an error in the sub-calls.
-                // So we return the trace information for debugging contract.
-                let errors = ErrorUnwind::from_traces(executed.trace).errors.iter()
-                    .map(|(addr,error)| {
-                        let cip37_addr = RpcAddress::try_from_h160(addr.clone(),network_type).unwrap().base32_address;
-                        format!("{}: {}", cip37_addr, error)
-                    })
-                    .collect::<Vec<String>>();
-
-                // Decode revert error
-                let revert_error = revert_reason_decode(&executed.output);
-                let revert_error = if !revert_error.is_empty() {
-                    format!(": {}.",revert_error)
-                }else{
-                    format!(".")
-                };
+            last_percentile = *percentile;
+        }
 
-                // Try to fetch the innermost error.
-                let innermost_error = if errors.len()>0{
-                    format!(" Innermost error is at {}.", errors[0])
-                }else{
-                    String::default()
-                };
+        Ok(self
+            .consensus_graph()
+            .fee_history(newest_epoch.into(), epoch_count, &reward_percentiles)?
+            .into())
+    }
 
-                bail!(call_execution_error(
-                    format!("Estimation isn't accurate: transaction is reverted{}{}",
-                        revert_error, innermost_error),
-                    errors.join("\n").into_bytes(),
-                ))
+    fn call_many(
+        &self, requests: Vec<CallRequest>, epoch: Option<EpochNumber>,
+        state_override: Option<StateOverride>,
+    ) -> RpcResult<Vec<CallResult>>
+    {
+        info!(
+            "RPC Request: cfx_callMany({} requests, epoch={:?})",
+            requests.len(),
+            epoch
+        );
+
+        let mut primitive_override = PrimitiveStateOverride::new();
+        for (address, account_override) in state_override.unwrap_or_default()
+        {
+            self.check_address_network(address.network)?;
+            let is_contract = address.hex_address.is_contract_address();
+            if !is_contract
+                && (account_override.code.is_some()
+                    || account_override.sponsor_info.is_some())
+            {
+                bail!(invalid_params(
+                    "state_override",
+                    format!(
+                        "code and sponsor info can only be set for contracts, \
+                        {} is not a contract address",
+                        address.base32_address
+                    )
+                ));
             }
-            ExecutionOutcome::ExecutionErrorBumpNonce(e, _) => {
-                bail!(call_execution_error(
-                    format! {"Can not estimate: transaction execution failed, \
-                    all gas will be charged (execution error: {:?})", e}
-                    .into(),
-                    format! {"{:?}", e}.into_bytes()
-                ))
+            if let Some(sponsor_info) = &account_override.sponsor_info {
+                self.check_address_network(
+                    sponsor_info.sponsor_for_gas.network,
+                )?;
+                self.check_address_network(
+                    sponsor_info.sponsor_for_collateral.network,
+                )?;
             }
-            ExecutionOutcome::Finished(executed) => executed,
-        };
-        let mut storage_collateralized = U64::from(0);
-        for storage_change in &executed.storage_collateralized {
-            storage_collateralized += storage_change.collaterals;
+            primitive_override
+                .insert(address.hex_address, account_override.into_primitive());
         }
-        // In case of unlimited full gas charge at some VM call, or if there are
-        // infinite loops, the total estimated gas used is very close to
-        // MAX_GAS_CALL_REQUEST, 0.8 is chosen to check if it's close.
-        const TOO_MUCH_GAS_USED: u64 =
-            (0.8 * (MAX_GAS_CALL_REQUEST as f32)) as u64;
-        if executed.gas_used >= U256::from(TOO_MUCH_GAS_USED) {
-            bail!(call_execution_error(
-                format!(
-                    "Gas too high. Most likely there are problems within the contract code. \
-                    gas {}, storage_limit {}",
-                    executed.gas_used, storage_collateralized
-                ),
-                format!(
-                    "gas {}, storage_limit {}", executed.gas_used, storage_collateralized
-                )
-                .into_bytes(),
-            ));
+
+        let consensus_graph = self.consensus_graph();
+        let epoch = epoch.unwrap_or(EpochNumber::LatestState);
+        let best_epoch_height = consensus_graph.best_epoch_number();
+        let chain_id = consensus_graph.best_chain_id();
+
+        let mut txs = Vec::with_capacity(requests.len());
+        for request in requests {
+            self.check_call_request_network(&request)?;
+            txs.push(sign_call(best_epoch_height, chain_id, request)?);
         }
-        let response = EstimateGasAndCollateralResponse {
-            // We multiply the gas_used for 2 reasons:
-            // 1. In each EVM call, the gas passed is at most 63/64 of the
-            // remaining gas, so the gas_limit should be multiplied a factor so
-            // that the gas passed into the sub-call is sufficient. The 4 / 3
-            // factor is sufficient for 18 level of calls.
-            // 2. In Conflux, we recommend setting the gas_limit to (gas_used *
-            // 4) / 3, because the extra gas will be refunded up to
-            // 1/4 of the gas limit.
-            gas_limit: executed.gas_used * 4 / 3,
-            gas_used: executed.gas_used,
-            storage_collateralized,
-        };
-        Ok(response)
+
+        let network = *self.sync.network.get_network_type();
+        consensus_graph
+            .call_virtual_many(&txs, epoch.into(), &primitive_override)?
+            .into_iter()
+            .map(|outcome| common::call_many_result(outcome, network))
+            .collect()
+    }
+
+    fn estimate_gas_and_collateral(
+        &self, request: CallRequest, epoch: Option<EpochNumber>,
+    ) -> RpcResult<EstimateGasAndCollateralResponse> {
+        common::estimate_gas_and_collateral_result(
+            self.exec_transaction(request, epoch)?,
+            *self.sync.network.get_network_type(),
+        )
     }
 
     fn check_balance_against_transaction(
@@ -1293,9 +1324,9 @@ impl RpcImpl {
         ))
     }
 
-    fn exec_transaction(
-        &self, request: CallRequest, epoch: Option<EpochNumber>,
-    ) -> RpcResult<ExecutionOutcome> {
+    fn check_call_request_network(
+        &self, request: &CallRequest,
+    ) -> RpcResult<()> {
         let rpc_request_network = invalid_params_check(
             "request",
             rpc_call_request_network(
@@ -1309,7 +1340,13 @@ impl RpcImpl {
                 rpc_request_network,
                 self.sync.network.get_network_type(),
             ),
-        )?;
+        )
+    }
+
+    fn exec_transaction(
+        &self, request: CallRequest, epoch: Option<EpochNumber>,
+    ) -> RpcResult<ExecutionOutcome> {
+        self.check_call_request_network(&request)?;
 
         let consensus_graph = self.consensus_graph();
         let epoch = epoch.unwrap_or(EpochNumber::LatestState);
@@ -1525,6 +1562,38 @@ impl RpcImpl {
 
         Ok(Some(epoch_receipts))
     }
+
+    fn trace_transaction(
+        &self, tx_hash: H256,
+    ) -> RpcResult<Option<TransactionStructLogs>> {
+        info!("RPC Request: debug_traceTransaction({:?})", tx_hash);
+
+        let (outcome, struct_logs) =
+            match self.consensus_graph().trace_transaction_steps(&tx_hash)? {
+                None => return Ok(None),
+                Some(result) => result,
+            };
+
+        let (failed, executed) = match outcome {
+            ExecutionOutcome::Finished(executed) => (false, executed),
+            ExecutionOutcome::ExecutionErrorBumpNonce(_, executed) => {
+                (true, executed)
+            }
+            ExecutionOutcome::NotExecutedDrop(e) => {
+                bail!(format!("Transaction not executed: {:?}", e))
+            }
+            ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
+                bail!(format!("Transaction not executed: {:?}", e))
+            }
+        };
+
+        Ok(Some(TransactionStructLogs {
+            gas: executed.gas_used,
+            failed,
+            return_value: executed.output.into(),
+            struct_logs: struct_logs.into_iter().map(Into::into).collect(),
+        }))
+    }
 }
 
 #[allow(dead_code)]
@@ -1579,24 +1648,35 @@ impl Cfx for CfxHandler {
             fn collateral_for_storage(&self, address: RpcAddress, num: Option<EpochNumber>)
                 -> BoxFuture<U256>;
             fn call(&self, request: CallRequest, epoch: Option<EpochNumber>)
-                -> JsonRpcResult<Bytes>;
+                -> BoxFuture<Bytes>;
+            fn call_many(
+                &self, requests: Vec<CallRequest>, epoch: Option<EpochNumber>,
+                state_override: Option<StateOverride>)
+                -> BoxFuture<Vec<CallResult>>;
+            fn fee_history(
+                &self, epoch_count: U64, newest_epoch: EpochNumber,
+                reward_percentiles: Vec<f64>) -> BoxFuture<FeeHistory>;
             fn estimate_gas_and_collateral(
                 &self, request: CallRequest, epoch_number: Option<EpochNumber>)
-                -> JsonRpcResult<EstimateGasAndCollateralResponse>;
+                -> BoxFuture<EstimateGasAndCollateralResponse>;
             fn check_balance_against_transaction(
                 &self, account_addr: RpcAddress, contract_addr: RpcAddress, gas_limit: U256, gas_price: U256, storage_limit: U256, epoch: Option<EpochNumber>,
             ) -> BoxFuture<CheckBalanceAgainstTransactionResponse>;
             fn get_logs(&self, filter: RpcFilter) -> BoxFuture<Vec<RpcLog>>;
-            fn get_block_reward_info(&self, num: EpochNumber) -> JsonRpcResult<Vec<RpcRewardInfo>>;
+            fn get_block_reward_info(&self, num: EpochNumber) -> BoxFuture<Vec<RpcRewardInfo>>;
             fn send_raw_transaction(&self, raw: Bytes) -> JsonRpcResult<H256>;
             fn storage_at(&self, addr: RpcAddress, pos: H256, epoch_number: Option<EpochNumber>)
                 -> BoxFuture<Option<H256>>;
+            fn storage_range(
+                &self, addr: RpcAddress, start_key: Bytes, limit: U64,
+                epoch_number: Option<EpochNumber>)
+                -> BoxFuture<StorageRange>;
             fn transaction_by_hash(&self, hash: H256) -> BoxFuture<Option<RpcTransaction>>;
             fn account_pending_info(&self, addr: RpcAddress) -> BoxFuture<Option<AccountPendingInfo>>;
             fn account_pending_transactions(&self, address: RpcAddress, maybe_start_nonce: Option<U256>, maybe_limit: Option<U64>) -> BoxFuture<AccountPendingTransactions>;
             fn transaction_receipt(&self, tx_hash: H256) -> BoxFuture<Option<RpcReceipt>>;
             fn storage_root(&self, address: RpcAddress, epoch_num: Option<EpochNumber>) -> BoxFuture<Option<StorageRoot>>;
-            fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<TokenSupplyInfo>;
+            fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> BoxFuture<TokenSupplyInfo>;
         }
     }
 }
@@ -1686,6 +1766,8 @@ impl LocalRpc for LocalRpcImpl {
             fn txpool_inspect(&self, address: Option<RpcAddress>) -> JsonRpcResult<
                 BTreeMap<String, BTreeMap<String, BTreeMap<usize, Vec<String>>>>>;
             fn txpool_status(&self) -> JsonRpcResult<BTreeMap<String, usize>>;
+            fn txpool_replacement_history(&self, address: Option<RpcAddress>)
+                -> JsonRpcResult<Vec<TxReplacement>>;
             fn accounts(&self) -> JsonRpcResult<Vec<RpcAddress>>;
             fn new_account(&self, password: String) -> JsonRpcResult<RpcAddress>;
             fn unlock_account(
@@ -1706,6 +1788,7 @@ impl LocalRpc for LocalRpcImpl {
             fn send_transaction(
                 &self, tx: SendTxRequest, password: Option<String>) -> BoxFuture<H256>;
             fn sign_transaction(&self, tx: SendTxRequest, password: Option<String>) -> JsonRpcResult<String>;
+            fn trace_transaction(&self, tx_hash: H256) -> JsonRpcResult<Option<TransactionStructLogs>>;
         }
     }
 }
@@ -3,11 +3,14 @@
 // See http://www.gnu.org/licenses/
 
 use crate::rpc::{
+    error_codes::call_execution_error,
     types::{
         errors::check_rpc_address_network, Block as RpcBlock,
-        BlockHashOrEpochNumber, Bytes, CheckBalanceAgainstTransactionResponse,
-        EpochNumber, RpcAddress, Status as RpcStatus,
-        Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+        BlockHashOrEpochNumber, Bytes, CallResult,
+        CheckBalanceAgainstTransactionResponse, EpochNumber,
+        EstimateGasAndCollateralResponse, Log as RpcLog, RpcAddress,
+        Status as RpcStatus, Transaction as RpcTransaction, TxPoolPendingInfo,
+        TxReplacement, TxWithPoolInfo, MAX_GAS_CALL_REQUEST,
     },
     RpcResult,
 };
@@ -16,8 +19,13 @@ use cfx_addr::Network;
 use cfx_parameters::staking::DRIPS_PER_STORAGE_COLLATERAL_UNIT;
 use cfx_types::{Address, H160, H256, H520, U128, U256, U512, U64};
 use cfxcore::{
-    rpc_errors::invalid_params_check, BlockDataManager, ConsensusGraph,
-    ConsensusGraphTrait, PeerInfo, SharedConsensusGraph, SharedTransactionPool,
+    executive::{
+        revert_reason_decode, ExecutionError, ExecutionOutcome, TxDropError,
+    },
+    rpc_errors::invalid_params_check,
+    trace::ErrorUnwind,
+    vm, BlockDataManager, ConsensusGraph, ConsensusGraphTrait, PeerInfo,
+    SharedConsensusGraph, SharedTransactionPool,
 };
 use cfxcore_accounts::AccountProvider;
 use cfxkey::Password;
@@ -131,6 +139,233 @@ pub fn check_balance_against_transaction(
     }
 }
 
+/// Convert the outcome of a virtual call into the result of `cfx_call`.
+pub fn call_result(outcome: ExecutionOutcome) -> RpcResult<Bytes> {
+    match outcome {
+        ExecutionOutcome::NotExecutedDrop(TxDropError::OldNonce(
+            expected,
+            got,
+        )) => {
+            bail!(call_execution_error(
+                "Transaction can not be executed".into(),
+                format! {"nonce is too old expected {:?} got {:?}", expected, got}.into_bytes()
+            ))
+        }
+        ExecutionOutcome::NotExecutedDrop(
+            TxDropError::InvalidRecipientAddress(recipient),
+        ) => {
+            bail!(call_execution_error(
+                "Transaction can not be executed".into(),
+                format! {"invalid recipient address {:?}", recipient}
+                    .into_bytes()
+            ))
+        }
+        ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
+            bail!(call_execution_error(
+                "Transaction can not be executed".into(),
+                format! {"{:?}", e}.into_bytes()
+            ))
+        }
+        ExecutionOutcome::ExecutionErrorBumpNonce(
+            ExecutionError::VmError(vm::Error::Reverted),
+            executed,
+        ) => bail!(call_execution_error(
+            "Transaction reverted".into(),
+            executed.output
+        )),
+        ExecutionOutcome::ExecutionErrorBumpNonce(e, _) => {
+            bail!(call_execution_error(
+                "Transaction execution failed".into(),
+                format! {"{:?}", e}.into_bytes()
+            ))
+        }
+        ExecutionOutcome::Finished(executed) => Ok(executed.output.into()),
+    }
+}
+
+/// Convert the outcome of a virtual call into the result of
+/// `cfx_estimateGasAndCollateral`.
+pub fn estimate_gas_and_collateral_result(
+    outcome: ExecutionOutcome, network_type: Network,
+) -> RpcResult<EstimateGasAndCollateralResponse> {
+    let executed = match outcome {
+        ExecutionOutcome::NotExecutedDrop(TxDropError::OldNonce(
+            expected,
+            got,
+        )) => {
+            bail!(call_execution_error(
+                "Can not estimate: transaction can not be executed".into(),
+                format! {"nonce is too old expected {:?} got {:?}", expected, got}.into_bytes()
+            ))
+        }
+        ExecutionOutcome::NotExecutedDrop(
+            TxDropError::InvalidRecipientAddress(recipient),
+        ) => {
+            bail!(call_execution_error(
+                "Can not estimate: transaction can not be executed".into(),
+                format! {"invalid recipient address {:?}", recipient}
+                    .into_bytes()
+            ))
+        }
+        ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
+            bail!(call_execution_error(
+                "Can not estimate: transaction can not be executed".into(),
+                format! {"{:?}", e}.into_bytes()
+            ))
+        }
+        ExecutionOutcome::ExecutionErrorBumpNonce(
+            ExecutionError::VmError(vm::Error::Reverted),
+            executed,
+        ) => {
+            // When a revert exception happens, there is usually an error in the
+            // sub-calls. So we return the trace information for debugging
+            // contract.
+            let errors = ErrorUnwind::from_traces(executed.trace)
+                .errors
+                .iter()
+                .map(|(addr, error)| {
+                    let cip37_addr =
+                        RpcAddress::try_from_h160(addr.clone(), network_type)
+                            .unwrap()
+                            .base32_address;
+                    format!("{}: {}", cip37_addr, error)
+                })
+                .collect::<Vec<String>>();
+
+            // Decode revert error
+            let revert_error = revert_reason_decode(&executed.output);
+            let revert_error = if !revert_error.is_empty() {
+                format!(": {}.", revert_error)
+            } else {
+                format!(".")
+            };
+
+            // Try to fetch the innermost error.
+            let innermost_error = if errors.len() > 0 {
+                format!(" Innermost error is at {}.", errors[0])
+            } else {
+                String::default()
+            };
+
+            bail!(call_execution_error(
+                format!(
+                    "Estimation isn't accurate: transaction is reverted{}{}",
+                    revert_error, innermost_error
+                ),
+                errors.join("\n").into_bytes(),
+            ))
+        }
+        ExecutionOutcome::ExecutionErrorBumpNonce(e, _) => {
+            bail!(call_execution_error(
+                format! {"Can not estimate: transaction execution failed, \
+                all gas will be charged (execution error: {:?})", e}
+                .into(),
+                format! {"{:?}", e}.into_bytes()
+            ))
+        }
+        ExecutionOutcome::Finished(executed) => executed,
+    };
+    let mut storage_collateralized = U64::from(0);
+    for storage_change in &executed.storage_collateralized {
+        storage_collateralized += storage_change.collaterals;
+    }
+    // In case of unlimited full gas charge at some VM call, or if there are
+    // infinite loops, the total estimated gas used is very close to
+    // MAX_GAS_CALL_REQUEST, 0.8 is chosen to check if it's close.
+    const TOO_MUCH_GAS_USED: u64 = (0.8 * (MAX_GAS_CALL_REQUEST as f32)) as u64;
+    if executed.gas_used >= U256::from(TOO_MUCH_GAS_USED) {
+        bail!(call_execution_error(
+            format!(
+                "Gas too high. Most likely there are problems within the contract code. \
+                gas {}, storage_limit {}",
+                executed.gas_used, storage_collateralized
+            ),
+            format!(
+                "gas {}, storage_limit {}", executed.gas_used, storage_collateralized
+            )
+            .into_bytes(),
+        ));
+    }
+    let response = EstimateGasAndCollateralResponse {
+        // We multiply the gas_used for 2 reasons:
+        // 1. In each EVM call, the gas passed is at most 63/64 of the
+        // remaining gas, so the gas_limit should be multiplied a factor so
+        // that the gas passed into the sub-call is sufficient. The 4 / 3
+        // factor is sufficient for 18 level of calls.
+        // 2. In Conflux, we recommend setting the gas_limit to (gas_used *
+        // 4) / 3, because the extra gas will be refunded up to
+        // 1/4 of the gas limit.
+        gas_limit: executed.gas_used * 4 / 3,
+        gas_used: executed.gas_used,
+        storage_collateralized,
+    };
+    Ok(response)
+}
+
+/// Convert the outcome of a virtual call into the result of `cfx_callMany`.
+/// Unlike `call_result`, a failed call is reported in the result so that the
+/// following calls are still returned.
+pub fn call_many_result(
+    outcome: ExecutionOutcome, network_type: Network,
+) -> RpcResult<CallResult> {
+    let not_executed = |error: String| CallResult {
+        output: Bytes::default(),
+        gas_used: U256::zero(),
+        storage_collateralized: U64::from(0),
+        storage_released: U64::from(0),
+        logs: vec![],
+        error: Some(format!("Transaction can not be executed: {}", error)),
+    };
+    let (executed, error) = match outcome {
+        ExecutionOutcome::NotExecutedDrop(e) => {
+            return Ok(not_executed(format!("{:?}", e)));
+        }
+        ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
+            return Ok(not_executed(format!("{:?}", e)));
+        }
+        ExecutionOutcome::ExecutionErrorBumpNonce(
+            ExecutionError::VmError(vm::Error::Reverted),
+            executed,
+        ) => {
+            let revert_error = revert_reason_decode(&executed.output);
+            let error = if revert_error.is_empty() {
+                "Transaction reverted".into()
+            } else {
+                format!("Transaction reverted: {}", revert_error)
+            };
+            (executed, Some(error))
+        }
+        ExecutionOutcome::ExecutionErrorBumpNonce(e, executed) => (
+            executed,
+            Some(format!("Transaction execution failed: {:?}", e)),
+        ),
+        ExecutionOutcome::Finished(executed) => (executed, None),
+    };
+
+    let mut storage_collateralized = U64::from(0);
+    for storage_change in &executed.storage_collateralized {
+        storage_collateralized += storage_change.collaterals;
+    }
+    let mut storage_released = U64::from(0);
+    for storage_change in &executed.storage_released {
+        storage_released += storage_change.collaterals;
+    }
+    let logs = executed
+        .logs
+        .into_iter()
+        .map(|log| RpcLog::try_from(log, network_type))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CallResult {
+        output: executed.output.into(),
+        gas_used: executed.gas_used,
+        storage_collateralized,
+        storage_released,
+        logs,
+        error,
+    })
+}
+
 pub struct RpcImpl {
     exit: Arc<(Mutex<bool>, Condvar)>,
     consensus: SharedConsensusGraph,
@@ -159,6 +394,8 @@ impl RpcImpl {
         }
     }
 
+    pub fn network(&self) -> &Arc<NetworkService> { &self.network }
+
     fn consensus_graph(&self) -> &ConsensusGraph {
         self.consensus
             .as_any()
@@ -835,6 +1072,27 @@ impl RpcImpl {
         Ok(ret)
     }
 
+    pub fn txpool_replacement_history(
+        &self, address: Option<RpcAddress>,
+    ) -> RpcResult<Vec<TxReplacement>> {
+        let address: Option<H160> = match address {
+            None => None,
+            Some(addr) => {
+                self.check_address_network(addr.network)?;
+                Some(addr.into())
+            }
+        };
+
+        let network = *self.network.get_network_type();
+        let result = self
+            .tx_pool
+            .get_replacement_history(address.as_ref())
+            .into_iter()
+            .map(|replacement| TxReplacement::try_from(replacement, network))
+            .collect::<Result<_, _>>()?;
+        Ok(result)
+    }
+
     pub fn accounts(&self) -> RpcResult<Vec<RpcAddress>> {
         let accounts: Vec<Address> = self.accounts.accounts().map_err(|e| {
             format!("Could not fetch accounts. With error {:?}", e)
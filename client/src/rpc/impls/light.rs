@@ -2,15 +2,21 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::rpc::types::errors::check_rpc_address_network;
+use crate::rpc::types::{
+    call_request::rpc_call_request_network, errors::check_rpc_address_network,
+};
 use cfx_types::{H160, H256, H520, U128, U256, U64};
 use cfxcore::{
     block_data_manager::BlockDataManager,
     consensus_parameters::ONE_GDRIP_IN_DRIP,
+    executive::ExecutionOutcome,
     light_protocol::{
         self, query_service::TxInfo, Error as LightError, ErrorKind,
     },
     rpc_errors::{account_result_to_rpc_result, invalid_params_check},
+    spec::genesis::{
+        genesis_contract_address_four_year, genesis_contract_address_two_year,
+    },
     ConsensusGraph, LightQueryService, PeerInfo, SharedConsensusGraph,
 };
 use cfxcore_accounts::AccountProvider;
@@ -37,15 +43,17 @@ use crate::{
         },
         traits::{cfx::Cfx, debug::LocalRpc, test::TestRpc},
         types::{
-            Account as RpcAccount, AccountPendingInfo,
+            sign_call, Account as RpcAccount, AccountPendingInfo,
             AccountPendingTransactions, BlameInfo, Block as RpcBlock,
-            BlockHashOrEpochNumber, Bytes, CallRequest,
+            BlockHashOrEpochNumber, Bytes, CallRequest, CallResult,
             CheckBalanceAgainstTransactionResponse, ConsensusGraphStates,
-            EpochNumber, EstimateGasAndCollateralResponse, Log as RpcLog,
-            LogFilter as RpcFilter, Receipt as RpcReceipt,
+            EpochNumber, EstimateGasAndCollateralResponse, FeeHistory,
+            Log as RpcLog, LogFilter as RpcFilter, Receipt as RpcReceipt,
             RewardInfo as RpcRewardInfo, RpcAddress, SendTxRequest,
-            SponsorInfo, Status as RpcStatus, SyncGraphStates, TokenSupplyInfo,
-            Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+            SponsorInfo, StateOverride, Status as RpcStatus, StorageRange,
+            SyncGraphStates, TokenSupplyInfo, Transaction as RpcTransaction,
+            TransactionStructLogs, TxPoolPendingInfo, TxReplacement,
+            TxWithPoolInfo,
         },
         RpcBoxFuture, RpcResult,
     },
@@ -1018,6 +1026,171 @@ impl RpcImpl {
 
         Box::new(fut.boxed().compat())
     }
+
+    async fn exec_transaction(
+        light: Arc<LightQueryService>, request: CallRequest,
+        epoch: primitives::EpochNumber,
+    ) -> RpcResult<ExecutionOutcome>
+    {
+        let rpc_request_network = invalid_params_check(
+            "request",
+            rpc_call_request_network(
+                request.from.as_ref(),
+                request.to.as_ref(),
+            ),
+        )?;
+        invalid_params_check(
+            "request",
+            check_rpc_address_network(
+                rpc_request_network,
+                light.get_network_type(),
+            ),
+        )?;
+
+        let epoch_height = light.get_latest_verifiable_epoch_number().map_err(|_| {
+            format!("the light client cannot retrieve/verify the latest mined pivot block.")
+        })?;
+        let chain_id = light.get_latest_verifiable_chain_id().map_err(|_| {
+            format!("the light client cannot retrieve/verify the latest chain_id.")
+        })?;
+        let tx = sign_call(epoch_height, chain_id, request)?;
+        trace!("call tx {:?}", tx);
+
+        Ok(light.call_virtual(tx, epoch).await?)
+    }
+
+    fn call(
+        &self, request: CallRequest, epoch: Option<EpochNumber>,
+    ) -> RpcBoxFuture<Bytes> {
+        let epoch = epoch.unwrap_or(EpochNumber::LatestState).into();
+
+        info!(
+            "RPC Request: cfx_call request={:?} epoch={:?}",
+            request, epoch
+        );
+
+        // clone `self.light` to avoid lifetime issues due to capturing `self`
+        let light = self.light.clone();
+
+        let fut = async move {
+            common::call_result(
+                Self::exec_transaction(light, request, epoch).await?,
+            )
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn estimate_gas_and_collateral(
+        &self, request: CallRequest, epoch: Option<EpochNumber>,
+    ) -> RpcBoxFuture<EstimateGasAndCollateralResponse> {
+        let epoch = epoch.unwrap_or(EpochNumber::LatestState).into();
+
+        info!(
+            "RPC Request: cfx_estimateGasAndCollateral request={:?} epoch={:?}",
+            request, epoch
+        );
+
+        // clone `self.light` to avoid lifetime issues due to capturing `self`
+        let light = self.light.clone();
+
+        let fut = async move {
+            let network_type = *light.get_network_type();
+
+            common::estimate_gas_and_collateral_result(
+                Self::exec_transaction(light, request, epoch).await?,
+                network_type,
+            )
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_block_reward_info(
+        &self, epoch: EpochNumber,
+    ) -> RpcBoxFuture<Vec<RpcRewardInfo>> {
+        info!(
+            "RPC Request: cfx_getBlockRewardInfo epoch_number={:?}",
+            epoch
+        );
+
+        // clone to avoid lifetime issues due to capturing `self`
+        let data_man = self.data_man.clone();
+        let light = self.light.clone();
+
+        let fut = async move {
+            let network = *light.get_network_type();
+            let rewards = light.get_block_rewards(epoch.into()).await?;
+
+            let mut ret = Vec::new();
+
+            for (hash, reward_result) in rewards {
+                if let Some(header) = data_man.block_header_by_hash(&hash) {
+                    let author =
+                        RpcAddress::try_from_h160(*header.author(), network)?;
+
+                    ret.push(RpcRewardInfo::new(hash, author, reward_result));
+                }
+            }
+
+            Ok(ret)
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_supply_info(
+        &self, epoch: Option<EpochNumber>,
+    ) -> RpcBoxFuture<TokenSupplyInfo> {
+        let epoch: primitives::EpochNumber =
+            epoch.unwrap_or(EpochNumber::LatestState).into();
+
+        info!("RPC Request: cfx_getSupplyInfo epoch={:?}", epoch);
+
+        // clone `self.light` to avoid lifetime issues due to capturing `self`
+        let light = self.light.clone();
+
+        let fut = async move {
+            // retrieve the totals and the locked accounts in parallel
+            let (
+                (total_issued, total_staking, total_collateral),
+                (two_year_locked, four_year_locked),
+            ) = future::try_join(
+                future::try_join3(
+                    light.get_total_issued_tokens(epoch.clone()),
+                    light.get_total_staking_tokens(epoch.clone()),
+                    light.get_total_storage_tokens(epoch.clone()),
+                ),
+                future::try_join(
+                    light.get_account(
+                        epoch.clone(),
+                        genesis_contract_address_two_year(),
+                    ),
+                    light.get_account(
+                        epoch,
+                        genesis_contract_address_four_year(),
+                    ),
+                ),
+            )
+            .await?;
+
+            let two_year_locked =
+                two_year_locked.map(|a| a.balance).unwrap_or_default();
+            let four_year_locked =
+                four_year_locked.map(|a| a.balance).unwrap_or_default();
+
+            Ok(TokenSupplyInfo {
+                total_circulating: total_issued
+                    - two_year_locked
+                    - four_year_locked,
+                total_issued,
+                total_staking,
+                total_collateral,
+            })
+        };
+
+        Box::new(fut.boxed().compat())
+    }
 }
 
 pub struct CfxHandler {
@@ -1050,13 +1223,17 @@ impl Cfx for CfxHandler {
             fn block_by_hash_with_pivot_assumption(&self, block_hash: H256, pivot_hash: H256, epoch_number: U64) -> BoxFuture<RpcBlock>;
             fn block_by_hash(&self, hash: H256, include_txs: bool) -> BoxFuture<Option<RpcBlock>>;
             fn blocks_by_epoch(&self, num: EpochNumber) -> JsonRpcResult<Vec<H256>>;
+            fn call(&self, request: CallRequest, epoch: Option<EpochNumber>) -> BoxFuture<Bytes>;
             fn check_balance_against_transaction(&self, account_addr: RpcAddress, contract_addr: RpcAddress, gas_limit: U256, gas_price: U256, storage_limit: U256, epoch: Option<EpochNumber>) -> BoxFuture<CheckBalanceAgainstTransactionResponse>;
             fn code(&self, address: RpcAddress, epoch_num: Option<EpochNumber>) -> BoxFuture<Bytes>;
             fn collateral_for_storage(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<U256>;
             fn deposit_list(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<Vec<DepositInfo>>;
             fn epoch_number(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<U256>;
+            fn estimate_gas_and_collateral(&self, request: CallRequest, epoch_num: Option<EpochNumber>) -> BoxFuture<EstimateGasAndCollateralResponse>;
             fn gas_price(&self) -> BoxFuture<U256>;
+            fn get_block_reward_info(&self, num: EpochNumber) -> BoxFuture<Vec<RpcRewardInfo>>;
             fn get_logs(&self, filter: RpcFilter) -> BoxFuture<Vec<RpcLog>>;
+            fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> BoxFuture<TokenSupplyInfo>;
             fn interest_rate(&self, num: Option<EpochNumber>) -> BoxFuture<U256>;
             fn next_nonce(&self, address: RpcAddress, num: Option<BlockHashOrEpochNumber>) -> BoxFuture<U256>;
             fn send_raw_transaction(&self, raw: Bytes) -> JsonRpcResult<H256>;
@@ -1075,10 +1252,9 @@ impl Cfx for CfxHandler {
     not_supported! {
         fn account_pending_transactions(&self, address: RpcAddress, maybe_start_nonce: Option<U256>, maybe_limit: Option<U64>) -> BoxFuture<AccountPendingTransactions>;
         fn block_by_block_number(&self, block_number: U64, include_txs: bool) -> BoxFuture<Option<RpcBlock>>;
-        fn call(&self, request: CallRequest, epoch: Option<EpochNumber>) -> JsonRpcResult<Bytes>;
-        fn estimate_gas_and_collateral(&self, request: CallRequest, epoch_num: Option<EpochNumber>) -> JsonRpcResult<EstimateGasAndCollateralResponse>;
-        fn get_block_reward_info(&self, num: EpochNumber) -> JsonRpcResult<Vec<RpcRewardInfo>>;
-        fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<TokenSupplyInfo>;
+        fn call_many(&self, requests: Vec<CallRequest>, epoch: Option<EpochNumber>, state_override: Option<StateOverride>) -> BoxFuture<Vec<CallResult>>;
+        fn fee_history(&self, epoch_count: U64, newest_epoch: EpochNumber, reward_percentiles: Vec<f64>) -> BoxFuture<FeeHistory>;
+        fn storage_range(&self, addr: RpcAddress, start_key: Bytes, limit: U64, epoch_number: Option<EpochNumber>) -> BoxFuture<StorageRange>;
     }
 }
 
@@ -1157,6 +1333,7 @@ impl LocalRpc for DebugRpcImpl {
             fn tx_inspect(&self, hash: H256) -> JsonRpcResult<TxWithPoolInfo>;
             fn txpool_content(&self, address: Option<RpcAddress>) -> JsonRpcResult<BTreeMap<String, BTreeMap<String, BTreeMap<usize, Vec<RpcTransaction>>>>>;
             fn txpool_inspect(&self, address: Option<RpcAddress>) -> JsonRpcResult<BTreeMap<String, BTreeMap<String, BTreeMap<usize, Vec<String>>>>>;
+            fn txpool_replacement_history(&self, address: Option<RpcAddress>) -> JsonRpcResult<Vec<TxReplacement>>;
             fn txpool_status(&self) -> JsonRpcResult<BTreeMap<String, usize>>;
             fn txs_from_pool(&self, address: Option<RpcAddress>) -> JsonRpcResult<Vec<RpcTransaction>>;
             fn unlock_account(&self, address: RpcAddress, password: String, duration: Option<U128>) -> JsonRpcResult<bool>;
@@ -1173,5 +1350,6 @@ impl LocalRpc for DebugRpcImpl {
         fn epoch_receipts(&self, epoch: BlockHashOrEpochNumber) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
         fn sign_transaction(&self, tx: SendTxRequest, password: Option<String>) -> JsonRpcResult<String>;
         fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
+        fn trace_transaction(&self, tx_hash: H256) -> JsonRpcResult<Option<TransactionStructLogs>>;
     }
 }
@@ -48,6 +48,8 @@ pub struct PubSubClient {
     heads_subscribers: Arc<RwLock<Subscribers<Client>>>,
     epochs_subscribers: Arc<RwLock<Subscribers<Client>>>,
     logs_subscribers: Arc<RwLock<Subscribers<(Client, LogFilter)>>>,
+    pending_transactions_subscribers: Arc<RwLock<Subscribers<Client>>>,
+    syncing_subscribers: Arc<RwLock<Subscribers<Client>>>,
     epochs_ordered: Arc<Channel<(u64, Vec<H256>)>>,
 }
 
@@ -61,12 +63,18 @@ impl PubSubClient {
         let heads_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let epochs_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let logs_subscribers = Arc::new(RwLock::new(Subscribers::default()));
+        let pending_transactions_subscribers =
+            Arc::new(RwLock::new(Subscribers::default()));
+        let syncing_subscribers = Arc::new(RwLock::new(Subscribers::default()));
 
         let handler = Arc::new(ChainNotificationHandler {
             executor,
             consensus: consensus.clone(),
             data_man: consensus.get_data_manager().clone(),
             heads_subscribers: heads_subscribers.clone(),
+            pending_transactions_subscribers: pending_transactions_subscribers
+                .clone(),
+            syncing_subscribers: syncing_subscribers.clone(),
             network,
         });
 
@@ -84,11 +92,41 @@ impl PubSubClient {
         // run futures@0.3 future on tokio@0.1 executor
         handler.executor.spawn(fut.unit_error().boxed().compat());
 
+        // --------- newPendingTransactions ---------
+        // subscribe to the `new_pending_transactions` channel
+        let receiver = notifications.new_pending_transactions.subscribe();
+
+        // loop asynchronously
+        let handler_clone = handler.clone();
+
+        let fut = receiver.for_each(move |hash| {
+            handler_clone.notify_pending_transaction(&hash);
+        });
+
+        // run futures@0.3 future on tokio@0.1 executor
+        handler.executor.spawn(fut.unit_error().boxed().compat());
+
+        // --------- syncing ---------
+        // subscribe to the `sync_phase_changes` channel
+        let receiver = notifications.sync_phase_changes.subscribe();
+
+        // loop asynchronously
+        let handler_clone = handler.clone();
+
+        let fut = receiver.for_each(move |phase| {
+            handler_clone.notify_sync_phase(phase);
+        });
+
+        // run futures@0.3 future on tokio@0.1 executor
+        handler.executor.spawn(fut.unit_error().boxed().compat());
+
         PubSubClient {
             handler,
             heads_subscribers,
             epochs_subscribers,
             logs_subscribers,
+            pending_transactions_subscribers,
+            syncing_subscribers,
             epochs_ordered: notifications.epochs_ordered.clone(),
         }
     }
@@ -226,6 +264,8 @@ pub struct ChainNotificationHandler {
     consensus: SharedConsensusGraph,
     data_man: Arc<BlockDataManager>,
     heads_subscribers: Arc<RwLock<Subscribers<Client>>>,
+    pending_transactions_subscribers: Arc<RwLock<Subscribers<Client>>>,
+    syncing_subscribers: Arc<RwLock<Subscribers<Client>>>,
     network: Network,
 }
 
@@ -289,6 +329,35 @@ impl ChainNotificationHandler {
         }
     }
 
+    // notify each subscriber about pending transaction `hash` concurrently
+    fn notify_pending_transaction(&self, hash: &H256) {
+        trace!("notify_pending_transaction({:?})", hash);
+
+        for subscriber in self.pending_transactions_subscribers.read().values()
+        {
+            Self::notify(
+                &self.executor,
+                subscriber,
+                pubsub::Result::TransactionHash(*hash),
+            );
+        }
+    }
+
+    // notify each subscriber about the new sync phase concurrently
+    fn notify_sync_phase(&self, phase: &'static str) {
+        trace!("notify_sync_phase({:?})", phase);
+
+        for subscriber in self.syncing_subscribers.read().values() {
+            Self::notify(
+                &self.executor,
+                subscriber,
+                pubsub::Result::SyncState {
+                    sync_phase: phase.into(),
+                },
+            );
+        }
+    }
+
     async fn notify_epoch(&self, subscriber: Client, epoch: (u64, Vec<H256>)) {
         trace!("notify_epoch({:?})", epoch);
 
@@ -519,7 +588,28 @@ impl PubSub for PubSubClient {
                 "logs",
                 "Expected filter parameter.",
             ),
-            _ => error_codes::unimplemented(None),
+            // --------- newPendingTransactions ---------
+            (pubsub::Kind::NewPendingTransactions, None) => {
+                self.pending_transactions_subscribers
+                    .write()
+                    .push(subscriber);
+                return;
+            }
+            (pubsub::Kind::NewPendingTransactions, _) => {
+                error_codes::invalid_params(
+                    "newPendingTransactions",
+                    "Expected no parameters.",
+                )
+            }
+            // --------- syncing ---------
+            (pubsub::Kind::Syncing, None) => {
+                self.syncing_subscribers.write().push(subscriber);
+                return;
+            }
+            (pubsub::Kind::Syncing, _) => error_codes::invalid_params(
+                "syncing",
+                "Expected no parameters.",
+            ),
         };
 
         let _ = subscriber.reject(error);
@@ -531,7 +621,13 @@ impl PubSub for PubSubClient {
         let res0 = self.heads_subscribers.write().remove(&id).is_some();
         let res1 = self.epochs_subscribers.write().remove(&id).is_some();
         let res2 = self.logs_subscribers.write().remove(&id).is_some();
-
-        Ok(res0 || res1 || res2)
+        let res3 = self
+            .pending_transactions_subscribers
+            .write()
+            .remove(&id)
+            .is_some();
+        let res4 = self.syncing_subscribers.write().remove(&id).is_some();
+
+        Ok(res0 || res1 || res2 || res3 || res4)
     }
 }
@@ -6,10 +6,12 @@ use super::super::types::LocalizedBlockTrace;
 use crate::{
     common::delegate_convert::into_jsonrpc_result,
     rpc::{
+        error_codes::invalid_params,
         traits::trace::Trace,
         types::{
             Action as RpcAction, LocalizedTrace as RpcLocalizedTrace,
-            LocalizedTrace, TraceFilter as RpcTraceFilter, TraceFilter,
+            LocalizedTrace, StateDiff as RpcStateDiff,
+            TraceFilter as RpcTraceFilter, TraceFilter, TraceResults,
         },
         RpcResult,
     },
@@ -17,7 +19,9 @@ use crate::{
 use cfx_addr::Network;
 use cfx_types::H256;
 use cfxcore::{
-    block_data_manager::DataVersionTuple, trace::trace::ExecTrace,
+    block_data_manager::DataVersionTuple,
+    executive::ExecutionOutcome,
+    trace::trace::{ExecTrace, TransactionExecTraces},
     BlockDataManager, ConsensusGraph, SharedConsensusGraph,
 };
 use jsonrpc_core::Result as JsonRpcResult;
@@ -63,7 +67,7 @@ impl TraceHandler {
             }
         };
 
-        match self.data_man.block_traces_by_hash(&block_hash) {
+        match self.consensus_graph().block_traces_or_replay(&block_hash)? {
             None => Ok(None),
             Some(DataVersionTuple(pivot_hash, traces)) => {
                 let epoch_number = self
@@ -111,46 +115,107 @@ impl TraceHandler {
     fn transaction_trace_impl(
         &self, tx_hash: &H256,
     ) -> RpcResult<Option<Vec<RpcLocalizedTrace>>> {
-        Ok(self
+        let tx_index = match self
             .data_man
             .transaction_index_by_hash(tx_hash, true /* update_cache */)
-            .and_then(|tx_index| {
-                self.data_man
-                    .transactions_traces_by_block_hash(&tx_index.block_hash)
-                    .and_then(|(pivot_hash, traces)| {
-                        traces
-                            .into_iter()
-                            .nth(tx_index.index)
-                            .map(Into::<Vec<ExecTrace>>::into)
-                            .map(|traces| {
-                                traces
-                                    .into_iter()
-                                    .map(|trace| RpcLocalizedTrace {
-                                        action: RpcAction::try_from(
-                                            trace.action,
-                                            self.network,
-                                        )
-                                        .expect("local address convert error"),
-                                        epoch_hash: Some(pivot_hash),
-                                        epoch_number: Some(
-                                            self.data_man
-                                                .block_height_by_hash(
-                                                    &pivot_hash,
-                                                )
-                                                .expect("pivot block missing")
-                                                .into(),
-                                        ),
-                                        block_hash: Some(tx_index.block_hash),
-                                        transaction_position: Some(
-                                            tx_index.index.into(),
-                                        ),
-                                        transaction_hash: Some(*tx_hash),
-                                    })
-                                    .collect()
-                            })
+        {
+            None => return Ok(None),
+            Some(tx_index) => tx_index,
+        };
+
+        let (pivot_hash, traces) = match self
+            .consensus_graph()
+            .block_traces_or_replay(&tx_index.block_hash)?
+        {
+            None => return Ok(None),
+            Some(DataVersionTuple(pivot_hash, traces)) => (pivot_hash, traces),
+        };
+
+        let epoch_number = self
+            .data_man
+            .block_height_by_hash(&pivot_hash)
+            .ok_or("pivot block missing")?;
+
+        Ok(Into::<Vec<TransactionExecTraces>>::into(traces)
+            .into_iter()
+            .nth(tx_index.index)
+            .map(Into::<Vec<ExecTrace>>::into)
+            .map(|traces| {
+                traces
+                    .into_iter()
+                    .map(|trace| RpcLocalizedTrace {
+                        action: RpcAction::try_from(trace.action, self.network)
+                            .expect("local address convert error"),
+                        epoch_hash: Some(pivot_hash),
+                        epoch_number: Some(epoch_number.into()),
+                        block_hash: Some(tx_index.block_hash),
+                        transaction_position: Some(tx_index.index.into()),
+                        transaction_hash: Some(*tx_hash),
                     })
+                    .collect()
             }))
     }
+
+    fn replay_transaction_impl(
+        &self, tx_hash: &H256, trace_types: Vec<String>,
+    ) -> RpcResult<Option<TraceResults>> {
+        let mut with_trace = false;
+        let mut with_state_diff = false;
+        for trace_type in &trace_types {
+            match trace_type.as_str() {
+                "trace" => with_trace = true,
+                "stateDiff" => with_state_diff = true,
+                _ => bail!(invalid_params(
+                    "trace_types",
+                    format!("unsupported trace type {:?}", trace_type)
+                )),
+            }
+        }
+
+        let (outcome, state_diff) =
+            match self.consensus_graph().replay_transaction(tx_hash)? {
+                None => return Ok(None),
+                Some(result) => result,
+            };
+
+        let executed = match outcome {
+            ExecutionOutcome::Finished(executed)
+            | ExecutionOutcome::ExecutionErrorBumpNonce(_, executed) => {
+                executed
+            }
+            ExecutionOutcome::NotExecutedDrop(e) => {
+                bail!(format!("Transaction not executed: {:?}", e))
+            }
+            ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
+                bail!(format!("Transaction not executed: {:?}", e))
+            }
+        };
+
+        let trace = if with_trace {
+            Some(
+                executed
+                    .trace
+                    .into_iter()
+                    .map(|trace| {
+                        RpcAction::try_from(trace.action, self.network)
+                    })
+                    .collect::<Result<Vec<_>, String>>()?,
+            )
+        } else {
+            None
+        };
+        let state_diff = if with_state_diff {
+            Some(RpcStateDiff::try_from(state_diff, self.network)?)
+        } else {
+            None
+        };
+
+        Ok(Some(TraceResults {
+            output: executed.output.into(),
+            trace,
+            state_diff,
+        }))
+    }
 }
 
 impl Trace for TraceHandler {
@@ -171,4 +236,10 @@ impl Trace for TraceHandler {
     ) -> JsonRpcResult<Option<Vec<LocalizedTrace>>> {
         into_jsonrpc_result(self.transaction_trace_impl(&tx_hash))
     }
+
+    fn replay_transaction(
+        &self, tx_hash: H256, trace_types: Vec<String>,
+    ) -> JsonRpcResult<Option<TraceResults>> {
+        into_jsonrpc_result(self.replay_transaction_impl(&tx_hash, trace_types))
+    }
 }
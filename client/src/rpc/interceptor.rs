@@ -10,15 +10,15 @@ use jsonrpc_core::{
 use serde_json::Value;
 use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 
-pub trait RpcInterceptor: Send + Sync + 'static {
-    fn before(&self, _name: &String) -> RpcResult<()>;
+pub trait RpcInterceptor<M: Metadata>: Send + Sync + 'static {
+    fn before(&self, _name: &String, _meta: &M) -> RpcResult<()>;
 }
 
 pub struct RpcProxy<M, T, I>
 where
     M: Metadata,
     T: IntoIterator<Item = (String, RemoteProcedure<M>)>,
-    I: RpcInterceptor,
+    I: RpcInterceptor<M>,
 {
     underlying: T,
     interceptor: Arc<I>,
@@ -29,7 +29,7 @@ impl<M, T, I> RpcProxy<M, T, I>
 where
     M: Metadata,
     T: IntoIterator<Item = (String, RemoteProcedure<M>)>,
-    I: RpcInterceptor,
+    I: RpcInterceptor<M>,
 {
     pub fn new(underlying: T, interceptor: I) -> Self {
         RpcProxy {
@@ -44,7 +44,7 @@ impl<M, T, I> IntoIterator for RpcProxy<M, T, I>
 where
     M: Metadata,
     T: IntoIterator<Item = (String, RemoteProcedure<M>)>,
-    I: RpcInterceptor,
+    I: RpcInterceptor<M>,
 {
     type IntoIter =
         std::collections::hash_map::IntoIter<String, RemoteProcedure<M>>;
@@ -73,7 +73,7 @@ where
 struct RpcMethodWithInterceptor<M, I>
 where
     M: Metadata,
-    I: RpcInterceptor,
+    I: RpcInterceptor<M>,
 {
     name: String,
     method: Arc<dyn RpcMethod<M>>,
@@ -83,7 +83,7 @@ where
 impl<M, I> RpcMethodWithInterceptor<M, I>
 where
     M: Metadata,
-    I: RpcInterceptor,
+    I: RpcInterceptor<M>,
 {
     pub fn new(
         name: String, method: Arc<dyn RpcMethod<M>>, interceptor: Arc<I>,
@@ -99,13 +99,16 @@ where
 impl<M, I> RpcMethod<M> for RpcMethodWithInterceptor<M, I>
 where
     M: Metadata,
-    I: RpcInterceptor,
+    I: RpcInterceptor<M>,
 {
     fn call(&self, params: Params, meta: M) -> BoxFuture<Value> {
         let name = self.name.clone();
         let interceptor = self.interceptor.clone();
+        let before_meta = meta.clone();
         let before_future = poll_fn(move || {
-            interceptor.before(&name).map(|_| Async::Ready(()))
+            interceptor
+                .before(&name, &before_meta)
+                .map(|_| Async::Ready(()))
         });
 
         let method = self.method.clone();
@@ -144,8 +147,8 @@ mod tests {
         error: Option<RpcError>,
     }
 
-    impl RpcInterceptor for Bar {
-        fn before(&self, _name: &String) -> RpcResult<()> {
+    impl RpcInterceptor<()> for Bar {
+        fn before(&self, _name: &String, _meta: &()) -> RpcResult<()> {
             self.handled.store(true, Ordering::SeqCst);
             match self.error {
                 Some(ref err) => Err(err.clone()),
@@ -18,7 +18,7 @@
 use super::types::Origin;
 use jsonrpc_core;
 use jsonrpc_pubsub::{PubSubMetadata, Session};
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 /// RPC methods metadata.
 #[derive(Clone, Default, Debug)]
@@ -27,6 +27,23 @@ pub struct Metadata {
     pub origin: Origin,
     /// Request PubSub Session
     pub session: Option<Arc<Session>>,
+    /// API key presented by the client (HTTP only)
+    pub api_key: Option<String>,
+    /// Auth token presented by the client (HTTP and WebSocket)
+    pub auth_token: Option<String>,
+    /// Client address reported by a reverse proxy in `X-Forwarded-For`
+    /// (HTTP only)
+    pub forwarded_for: Option<IpAddr>,
+}
+
+impl Metadata {
+    /// Address of the remote peer, if known by the transport.
+    pub fn remote_ip(&self) -> Option<IpAddr> {
+        match self.origin {
+            Origin::Tcp(ref address) => Some(address.ip()),
+            _ => None,
+        }
+    }
 }
 
 impl jsonrpc_core::Metadata for Metadata {}
@@ -6,6 +6,7 @@ use std::{collections::HashSet, str::FromStr};
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub enum Api {
+    Admin,
     Cfx,
     Debug,
     Pubsub,
@@ -19,6 +20,7 @@ impl FromStr for Api {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use self::Api::*;
         match s {
+            "admin" => Ok(Admin),
             "cfx" => Ok(Cfx),
             "debug" => Ok(Debug),
             "pubsub" => Ok(Pubsub),
@@ -40,12 +42,17 @@ impl ApiSet {
     pub fn list_apis(&self) -> HashSet<Api> {
         match *self {
             ApiSet::List(ref apis) => apis.clone(),
-            ApiSet::All => {
-                [Api::Cfx, Api::Debug, Api::Pubsub, Api::Test, Api::Trace]
-                    .iter()
-                    .cloned()
-                    .collect()
-            }
+            ApiSet::All => [
+                Api::Admin,
+                Api::Cfx,
+                Api::Debug,
+                Api::Pubsub,
+                Api::Test,
+                Api::Trace,
+            ]
+            .iter()
+            .cloned()
+            .collect(),
             ApiSet::Safe => [Api::Cfx, Api::Pubsub].iter().cloned().collect(),
         }
     }
@@ -0,0 +1,331 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::{
+    error_codes::request_rejected_too_many_request_error,
+    interceptor::RpcInterceptor, Metadata,
+};
+use jsonrpc_core::Result as JsonRpcResult;
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    net::IpAddr,
+};
+use throttling::token_bucket::{
+    ThrottleResult, TokenBucket, TokenBucketManager,
+};
+use transient_hashmap::TransientHashMap;
+
+/// Buckets of a client are dropped if it has not called any throttled RPC
+/// for this long, so that they do not pile up with new addresses.
+const CLIENT_BUCKETS_LIFETIME_SECS: u32 = 600;
+
+/// The identity of a client owning its own token buckets.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum ClientId {
+    ApiKey(String),
+    Ip(IpAddr),
+    /// Clients that cannot be identified share the same buckets. The HTTP and
+    /// WebSocket servers do not expose the address of the peer, so these are
+    /// the clients over them without a known API key or a trusted
+    /// `X-Forwarded-For`.
+    Anonymous,
+}
+
+/// Settings of per-client throttling, loaded from the `[<section>_clients]`
+/// table of the throttling configuration file.
+#[derive(Default)]
+struct ClientsConfig {
+    /// Addresses that are never throttled.
+    trusted_ips: HashSet<IpAddr>,
+    /// Whether to identify HTTP clients by the `X-Forwarded-For` header,
+    /// which is only reliable behind a reverse proxy setting it.
+    trust_forwarded_for: bool,
+    /// Known API keys and the names of their tiers.
+    api_keys: HashMap<String, String>,
+    /// Bucket templates of each tier, overriding those of the default tier
+    /// for the listed methods.
+    tiers: HashMap<String, TokenBucketManager>,
+}
+
+impl ClientsConfig {
+    fn from_toml(val: &toml::Value) -> Result<Self, String> {
+        let mut config = ClientsConfig::default();
+
+        if let Some(ips) = val.get("trusted_ips") {
+            let ips = ips.as_array().ok_or("trusted_ips must be an array")?;
+            for ip in ips {
+                let ip: IpAddr = ip
+                    .as_str()
+                    .ok_or("trusted_ips must be strings")?
+                    .parse()
+                    .map_err(|e| format!("invalid trusted ip: {:?}", e))?;
+                config.trusted_ips.insert(ip);
+            }
+        }
+
+        if let Some(trust) = val.get("trust_forwarded_for") {
+            config.trust_forwarded_for = trust
+                .as_bool()
+                .ok_or("trust_forwarded_for must be a boolean")?;
+        }
+
+        if let Some(tiers) = val.get("tiers") {
+            let tiers = tiers.as_table().ok_or("tiers must be a table")?;
+            for (name, tier) in tiers {
+                config.tiers.insert(
+                    name.clone(),
+                    TokenBucketManager::from_toml(tier, None)?,
+                );
+            }
+        }
+
+        if let Some(api_keys) = val.get("api_keys") {
+            let api_keys =
+                api_keys.as_table().ok_or("api_keys must be a table")?;
+            for (key, tier) in api_keys {
+                let tier =
+                    tier.as_str().ok_or("tier of api key must be a string")?;
+                if !config.tiers.contains_key(tier) {
+                    return Err(format!("tier {:?} not found", tier));
+                }
+                config.api_keys.insert(key.clone(), tier.into());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Throttles RPC requests with token buckets kept per client and method.
+/// The buckets of a client are created on demand from the templates of its
+/// tier, so every client gets its own quota.
+pub struct ThrottleInterceptor {
+    /// Bucket templates of the clients without a tier.
+    default_tier: TokenBucketManager,
+    clients: ClientsConfig,
+    buckets: Mutex<TransientHashMap<(ClientId, String), TokenBucket>>,
+}
+
+impl ThrottleInterceptor {
+    pub fn new(file: &Option<String>, section: &str) -> Self {
+        let interceptor = match file {
+            Some(file) => read_to_string(file)
+                .map_err(|e| format!("failed to read toml file: {:?}", e))
+                .and_then(|content| {
+                    content.parse::<toml::Value>().map_err(|e| {
+                        format!("failed to parse toml file: {:?}", e)
+                    })
+                })
+                .and_then(|toml_val| Self::from_toml(&toml_val, section)),
+            None => Ok(Self::with_config(
+                TokenBucketManager::default(),
+                ClientsConfig::default(),
+            )),
+        };
+
+        interceptor.expect("invalid throttling configuration file")
+    }
+
+    fn from_toml(
+        toml_val: &toml::Value, section: &str,
+    ) -> Result<Self, String> {
+        let default_tier =
+            TokenBucketManager::from_toml(toml_val, Some(section))?;
+        let clients = match toml_val.get(&format!("{}_clients", section)) {
+            Some(val) => ClientsConfig::from_toml(val)?,
+            None => ClientsConfig::default(),
+        };
+
+        Ok(Self::with_config(default_tier, clients))
+    }
+
+    fn with_config(
+        default_tier: TokenBucketManager, clients: ClientsConfig,
+    ) -> Self {
+        ThrottleInterceptor {
+            default_tier,
+            clients,
+            buckets: Mutex::new(TransientHashMap::new(
+                CLIENT_BUCKETS_LIFETIME_SECS,
+            )),
+        }
+    }
+
+    /// Returns the identity of the client and its tier, or `None` if the
+    /// client is trusted.
+    fn identify(
+        &self, meta: &Metadata,
+    ) -> Option<(ClientId, Option<&TokenBucketManager>)> {
+        // Unknown keys are ignored, otherwise a client could get a new quota
+        // by making up a new key.
+        if let Some(tier) = meta
+            .api_key
+            .as_ref()
+            .and_then(|key| self.clients.api_keys.get(key))
+        {
+            let key = meta.api_key.clone().expect("checked above");
+            return Some((ClientId::ApiKey(key), self.clients.tiers.get(tier)));
+        }
+
+        let ip = match meta.remote_ip() {
+            Some(ip) => Some(ip),
+            None if self.clients.trust_forwarded_for => meta.forwarded_for,
+            None => None,
+        };
+        match ip {
+            Some(ip) if self.clients.trusted_ips.contains(&ip) => None,
+            Some(ip) => Some((ClientId::Ip(ip), None)),
+            None => Some((ClientId::Anonymous, None)),
+        }
+    }
+}
+
+impl RpcInterceptor<Metadata> for ThrottleInterceptor {
+    fn before(&self, name: &String, meta: &Metadata) -> JsonRpcResult<()> {
+        let (client, tier) = match self.identify(meta) {
+            Some(identity) => identity,
+            None => return Ok(()),
+        };
+
+        let template = match tier
+            .and_then(|tier| tier.get(name))
+            .or_else(|| self.default_tier.get(name))
+        {
+            Some(template) => template,
+            None => return Ok(()),
+        };
+
+        let key = (client, name.clone());
+        let result = {
+            let mut buckets = self.buckets.lock();
+            if buckets.get_mut(&key).is_none() {
+                buckets.prune();
+                buckets.insert(key.clone(), template.lock().fresh_copy());
+            }
+            buckets
+                .get_mut(&key)
+                .expect("inserted above")
+                .throttle_default()
+        };
+
+        match result {
+            ThrottleResult::Success => Ok(()),
+            ThrottleResult::Throttled(wait_time) => {
+                debug!(
+                    "RPC {} of {:?} throttled in {:?}",
+                    name, key.0, wait_time
+                );
+                bail!(request_rejected_too_many_request_error(Some(format!(
+                    "throttled in {:?}",
+                    wait_time
+                ))))
+            }
+            ThrottleResult::AlreadyThrottled => {
+                debug!("RPC {} of {:?} already throttled", name, key.0);
+                bail!(request_rejected_too_many_request_error(Some(
+                    "already throttled, please try again later".into()
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThrottleInterceptor;
+    use crate::rpc::{interceptor::RpcInterceptor, Metadata, Origin};
+    use std::net::SocketAddr;
+
+    const CONFIG: &str = r#"
+        [rpc]
+        cfx_getLogs = "1,1,1,1,0"
+
+        [rpc_clients]
+        trusted_ips = ["10.0.0.1"]
+
+        [rpc_clients.api_keys]
+        "premium-key" = "premium"
+
+        [rpc_clients.tiers.premium]
+        cfx_getLogs = "2,2,1,1,0"
+    "#;
+
+    fn new_interceptor() -> ThrottleInterceptor {
+        ThrottleInterceptor::from_toml(&CONFIG.parse().unwrap(), "rpc").unwrap()
+    }
+
+    fn tcp_meta(address: &str) -> Metadata {
+        let address: SocketAddr = address.parse().unwrap();
+        Metadata {
+            origin: Origin::Tcp(address),
+            ..Default::default()
+        }
+    }
+
+    fn call(
+        interceptor: &ThrottleInterceptor, meta: &Metadata, times: usize,
+    ) -> Vec<bool> {
+        (0..times)
+            .map(|_| interceptor.before(&"cfx_getLogs".into(), meta).is_ok())
+            .collect()
+    }
+
+    #[test]
+    fn test_throttle_per_ip() {
+        let interceptor = new_interceptor();
+        let client_1 = tcp_meta("1.1.1.1:1000");
+        let client_1_port_2 = tcp_meta("1.1.1.1:2000");
+        let client_2 = tcp_meta("2.2.2.2:1000");
+
+        assert_eq!(call(&interceptor, &client_1, 2), vec![true, false]);
+        assert_eq!(call(&interceptor, &client_1_port_2, 1), vec![false]);
+        assert_eq!(call(&interceptor, &client_2, 2), vec![true, false]);
+
+        // Methods not configured are not throttled.
+        assert!(interceptor
+            .before(&"cfx_epochNumber".into(), &client_1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_trusted_ip() {
+        let interceptor = new_interceptor();
+        let trusted = tcp_meta("10.0.0.1:1000");
+        assert_eq!(call(&interceptor, &trusted, 3), vec![true; 3]);
+    }
+
+    #[test]
+    fn test_api_key_tier() {
+        let interceptor = new_interceptor();
+        let mut premium = tcp_meta("1.1.1.1:1000");
+        premium.api_key = Some("premium-key".into());
+        assert_eq!(call(&interceptor, &premium, 3), vec![true, true, false]);
+
+        // An unknown key is identified by the address instead.
+        let mut unknown = tcp_meta("1.1.1.1:1000");
+        unknown.api_key = Some("unknown-key".into());
+        assert_eq!(call(&interceptor, &unknown, 2), vec![true, false]);
+    }
+
+    #[test]
+    fn test_forwarded_for() {
+        let interceptor = new_interceptor();
+        let http_meta = |ip: &str| Metadata {
+            origin: Origin::Rpc("test".into()),
+            forwarded_for: Some(ip.parse().unwrap()),
+            ..Default::default()
+        };
+
+        // Without `trust_forwarded_for`, HTTP clients share the buckets.
+        assert_eq!(call(&interceptor, &http_meta("1.1.1.1"), 1), vec![true]);
+        assert_eq!(call(&interceptor, &http_meta("2.2.2.2"), 1), vec![false]);
+
+        let mut interceptor = new_interceptor();
+        interceptor.clients.trust_forwarded_for = true;
+        assert_eq!(call(&interceptor, &http_meta("1.1.1.1"), 1), vec![true]);
+        assert_eq!(call(&interceptor, &http_meta("2.2.2.2"), 1), vec![true]);
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::super::types::{BlacklistedNode, BlacklistedSubnet, IpFilter};
+use cfx_types::U64;
+use jsonrpc_core::Result as JsonRpcResult;
+use jsonrpc_derive::rpc;
+use network::node_table::{Node, NodeId};
+use std::net::SocketAddr;
+
+/// Management of the trusted and blacklisted peers. All the changes are
+/// persisted in the node database immediately.
+#[rpc(server)]
+pub trait Admin {
+    #[rpc(name = "admin_trustedNodes")]
+    fn trusted_nodes(&self) -> JsonRpcResult<Vec<Node>>;
+
+    #[rpc(name = "admin_addTrustedNode")]
+    fn add_trusted_node(
+        &self, id: NodeId, address: SocketAddr,
+    ) -> JsonRpcResult<()>;
+
+    /// Removes the node from the node database and disconnects it.
+    #[rpc(name = "admin_removeTrustedNode")]
+    fn remove_trusted_node(&self, id: NodeId) -> JsonRpcResult<()>;
+
+    #[rpc(name = "admin_blacklistedNodes")]
+    fn blacklisted_nodes(&self) -> JsonRpcResult<Vec<BlacklistedNode>>;
+
+    /// Blacklists the node for `duration` seconds, or forever if not given,
+    /// and disconnects it.
+    #[rpc(name = "admin_blacklistNode")]
+    fn blacklist_node(
+        &self, id: NodeId, duration: Option<U64>,
+    ) -> JsonRpcResult<()>;
+
+    /// Returns `false` if the node is not blacklisted.
+    #[rpc(name = "admin_unblacklistNode")]
+    fn unblacklist_node(&self, id: NodeId) -> JsonRpcResult<bool>;
+
+    #[rpc(name = "admin_blacklistedSubnets")]
+    fn blacklisted_subnets(&self) -> JsonRpcResult<Vec<BlacklistedSubnet>>;
+
+    /// Blacklists the subnet in CIDR notation, e.g. `10.0.0.0/8`, for
+    /// `duration` seconds, or forever if not given, and disconnects the nodes
+    /// in it.
+    #[rpc(name = "admin_blacklistSubnet")]
+    fn blacklist_subnet(
+        &self, subnet: String, duration: Option<U64>,
+    ) -> JsonRpcResult<()>;
+
+    /// Returns `false` if the subnet is not blacklisted.
+    #[rpc(name = "admin_unblacklistSubnet")]
+    fn unblacklist_subnet(&self, subnet: String) -> JsonRpcResult<bool>;
+
+    #[rpc(name = "admin_ipFilter")]
+    fn ip_filter(&self) -> JsonRpcResult<IpFilter>;
+
+    /// Replaces the allowed and denied CIDR ranges of the peers, and
+    /// disconnects the peers not allowed. The change is lost on restart.
+    #[rpc(name = "admin_setIpFilter")]
+    fn set_ip_filter(&self, filter: IpFilter) -> JsonRpcResult<()>;
+
+    /// Sets the tag of the node, or removes it if `value` is not given.
+    /// Returns `false` if the node is not found.
+    #[rpc(name = "admin_setNodeTag")]
+    fn set_node_tag(
+        &self, id: NodeId, key: String, value: Option<String>,
+    ) -> JsonRpcResult<bool>;
+}
@@ -4,10 +4,11 @@
 
 use super::super::types::{
     Account as RpcAccount, AccountPendingInfo, Block, Bytes, CallRequest,
-    CheckBalanceAgainstTransactionResponse, EpochNumber,
-    EstimateGasAndCollateralResponse, Log as RpcLog, LogFilter as RpcFilter,
-    Receipt as RpcReceipt, RewardInfo as RpcRewardInfo, SponsorInfo,
-    Status as RpcStatus, TokenSupplyInfo, Transaction,
+    CallResult, CheckBalanceAgainstTransactionResponse, EpochNumber,
+    EstimateGasAndCollateralResponse, FeeHistory, Log as RpcLog,
+    LogFilter as RpcFilter, Receipt as RpcReceipt, RewardInfo as RpcRewardInfo,
+    SponsorInfo, StateOverride, Status as RpcStatus, StorageRange,
+    TokenSupplyInfo, Transaction,
 };
 use crate::rpc::types::{
     AccountPendingTransactions, BlockHashOrEpochNumber, RpcAddress,
@@ -40,6 +41,15 @@ pub trait Cfx {
     #[rpc(name = "cfx_gasPrice")]
     fn gas_price(&self) -> BoxFuture<U256>;
 
+    /// Returns the gas used ratio of `epoch_count` epochs up to
+    /// `newest_epoch`, and the gas prices at `reward_percentiles` in each of
+    /// them.
+    #[rpc(name = "cfx_feeHistory")]
+    fn fee_history(
+        &self, epoch_count: U64, newest_epoch: EpochNumber,
+        reward_percentiles: Vec<f64>,
+    ) -> BoxFuture<FeeHistory>;
+
     /// Returns highest epoch number.
     #[rpc(name = "cfx_epochNumber")]
     fn epoch_number(
@@ -100,6 +110,17 @@ pub trait Cfx {
         &self, addr: RpcAddress, pos: H256, epoch_number: Option<EpochNumber>,
     ) -> BoxFuture<Option<H256>>;
 
+    /// Returns at most `limit` storage entries of a contract in key order,
+    /// starting from `start_key`, and the key to continue from. A page may
+    /// hold fewer than `limit` entries while there are more to read. The
+    /// entries come without proofs, since proving that no key of the range
+    /// was omitted is not supported.
+    #[rpc(name = "cfx_getStorageRange")]
+    fn storage_range(
+        &self, addr: RpcAddress, start_key: Bytes, limit: U64,
+        epoch_number: Option<EpochNumber>,
+    ) -> BoxFuture<StorageRange>;
+
     #[rpc(name = "cfx_getStorageRoot")]
     fn storage_root(
         &self, address: RpcAddress, epoch_num: Option<EpochNumber>,
@@ -162,7 +183,15 @@ pub trait Cfx {
     #[rpc(name = "cfx_call")]
     fn call(
         &self, tx: CallRequest, epoch_number: Option<EpochNumber>,
-    ) -> JsonRpcResult<Bytes>;
+    ) -> BoxFuture<Bytes>;
+
+    /// Simulates the calls one after another on the state of the given epoch
+    /// with the state overrides applied, returning the result of each call.
+    #[rpc(name = "cfx_callMany")]
+    fn call_many(
+        &self, requests: Vec<CallRequest>, epoch_number: Option<EpochNumber>,
+        state_override: Option<StateOverride>,
+    ) -> BoxFuture<Vec<CallResult>>;
 
     /// Returns logs matching the filter provided.
     #[rpc(name = "cfx_getLogs")]
@@ -191,7 +220,7 @@ pub trait Cfx {
     #[rpc(name = "cfx_estimateGasAndCollateral")]
     fn estimate_gas_and_collateral(
         &self, request: CallRequest, epoch_number: Option<EpochNumber>,
-    ) -> JsonRpcResult<EstimateGasAndCollateralResponse>;
+    ) -> BoxFuture<EstimateGasAndCollateralResponse>;
 
     /// Check if user balance is enough for the transaction.
     #[rpc(name = "cfx_checkBalanceAgainstTransaction")]
@@ -246,7 +275,7 @@ pub trait Cfx {
     #[rpc(name = "cfx_getBlockRewardInfo")]
     fn get_block_reward_info(
         &self, num: EpochNumber,
-    ) -> JsonRpcResult<Vec<RpcRewardInfo>>;
+    ) -> BoxFuture<Vec<RpcRewardInfo>>;
 
     /// Return the client version as a string
     #[rpc(name = "cfx_clientVersion")]
@@ -256,7 +285,7 @@ pub trait Cfx {
     #[rpc(name = "cfx_getSupplyInfo")]
     fn get_supply_info(
         &self, epoch_number: Option<EpochNumber>,
-    ) -> JsonRpcResult<TokenSupplyInfo>;
+    ) -> BoxFuture<TokenSupplyInfo>;
 
     //        /// Returns transaction at given block hash and index.
     //        #[rpc(name = "cfx_getTransactionByBlockHashAndIndex")]
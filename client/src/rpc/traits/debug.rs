@@ -5,7 +5,8 @@
 use super::super::types::{
     BlockHashOrEpochNumber, Bytes as RpcBytes, ConsensusGraphStates,
     Receipt as RpcReceipt, RpcAddress, SyncGraphStates,
-    Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+    Transaction as RpcTransaction, TransactionStructLogs, TxPoolPendingInfo,
+    TxReplacement, TxWithPoolInfo,
 };
 use crate::rpc::types::SendTxRequest;
 use cfx_types::{H256, H520, U128};
@@ -47,6 +48,14 @@ pub trait LocalRpc {
         >,
     >;
 
+    /// Returns the recent replacements of transactions in the pool sent by
+    /// `address`, or by all the senders if it is not given, including the
+    /// rejected ones.
+    #[rpc(name = "txpool_replacementHistory")]
+    fn txpool_replacement_history(
+        &self, address: Option<RpcAddress>,
+    ) -> JsonRpcResult<Vec<TxReplacement>>;
+
     #[rpc(name = "getTransactionsFromPool")]
     fn txs_from_pool(
         &self, address: Option<RpcAddress>,
@@ -119,4 +128,10 @@ pub trait LocalRpc {
     fn epoch_receipts(
         &self, epoch: BlockHashOrEpochNumber,
     ) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
+
+    /// Re-executes a packed transaction and returns its opcode-level trace.
+    #[rpc(name = "debug_traceTransaction")]
+    fn trace_transaction(
+        &self, tx_hash: H256,
+    ) -> JsonRpcResult<Option<TransactionStructLogs>>;
 }
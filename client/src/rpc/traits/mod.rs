@@ -2,12 +2,14 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+pub use admin::Admin;
 pub use cfx::Cfx;
 pub use debug::LocalRpc;
 pub use pubsub::PubSub;
 pub use test::TestRpc;
 pub use trace::Trace;
 
+pub mod admin;
 pub mod cfx;
 pub mod debug;
 pub mod pubsub;
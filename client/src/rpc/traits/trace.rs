@@ -2,7 +2,7 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use super::super::types::{LocalizedBlockTrace, LocalizedTrace};
+use super::super::types::{LocalizedBlockTrace, LocalizedTrace, TraceResults};
 use crate::rpc::types::TraceFilter;
 use cfx_types::H256;
 use jsonrpc_core::Result as JsonRpcResult;
@@ -28,4 +28,11 @@ pub trait Trace {
     fn transaction_traces(
         &self, tx_hash: H256,
     ) -> JsonRpcResult<Option<Vec<LocalizedTrace>>>;
+
+    /// Re-executes the given transaction and returns the requested traces.
+    /// The supported trace types are `trace` and `stateDiff`.
+    #[rpc(name = "trace_replayTransaction")]
+    fn replay_transaction(
+        &self, tx_hash: H256, trace_types: Vec<String>,
+    ) -> JsonRpcResult<Option<TraceResults>>;
 }
@@ -4,22 +4,29 @@
 
 mod account;
 pub mod address;
+mod blacklist;
 mod blame_info;
 mod block;
 mod bytes;
+mod call_many;
 pub mod call_request;
 mod consensus_graph_states;
 mod epoch_number;
 pub mod errors;
+mod fee_history;
 mod filter;
 mod index;
+mod ip_filter;
 mod log;
 mod provenance;
 pub mod pubsub;
 mod receipt;
 mod reward_info;
 mod sponsor_info;
+mod state_diff;
 mod status;
+mod storage_range;
+mod struct_log;
 mod sync_graph_states;
 mod token_supply_info;
 mod trace;
@@ -29,23 +36,30 @@ mod transaction;
 pub use self::{
     account::Account,
     address::RpcAddress,
+    blacklist::{BlacklistedNode, BlacklistedSubnet},
     blame_info::BlameInfo,
     block::{Block, BlockTransactions, Header},
     bytes::Bytes,
+    call_many::{AccountOverride, CallResult, StateOverride},
     call_request::{
         sign_call, CallRequest, CheckBalanceAgainstTransactionResponse,
         EstimateGasAndCollateralResponse, SendTxRequest, MAX_GAS_CALL_REQUEST,
     },
     consensus_graph_states::ConsensusGraphStates,
     epoch_number::{BlockHashOrEpochNumber, EpochNumber},
+    fee_history::FeeHistory,
     filter::LogFilter,
     index::Index,
+    ip_filter::IpFilter,
     log::Log,
     provenance::Origin,
     receipt::Receipt,
     reward_info::RewardInfo,
     sponsor_info::SponsorInfo,
+    state_diff::{StateDiff, TraceResults},
     status::Status,
+    storage_range::{StorageRange, StorageRangeEntry},
+    struct_log::TransactionStructLogs,
     sync_graph_states::SyncGraphStates,
     token_supply_info::TokenSupplyInfo,
     trace::{
@@ -54,6 +68,6 @@ pub use self::{
     trace_filter::TraceFilter,
     transaction::{
         AccountPendingInfo, AccountPendingTransactions, PackedOrExecuted,
-        Transaction, TxPoolPendingInfo, TxWithPoolInfo,
+        Transaction, TxPoolPendingInfo, TxReplacement, TxWithPoolInfo,
     },
 };
@@ -0,0 +1,24 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::U64;
+use network::node_table::NodeId;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BlacklistedNode {
+    pub id: NodeId,
+    /// Unix time in seconds when the node is removed from the blacklist, or
+    /// `None` if blacklisted forever.
+    pub until: Option<U64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BlacklistedSubnet {
+    pub subnet: String,
+    /// Unix time in seconds when the subnet is removed from the blacklist, or
+    /// `None` if blacklisted forever.
+    pub until: Option<U64>,
+}
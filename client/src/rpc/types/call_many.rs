@@ -0,0 +1,57 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{Bytes, Log, RpcAddress, SponsorInfo};
+use cfx_types::{H256, U256, U64};
+use cfxcore::state::AccountOverride as PrimitiveAccountOverride;
+use primitives::SponsorInfo as PrimitiveSponsorInfo;
+use std::collections::HashMap;
+
+/// Values replacing those of an account before the calls are simulated.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<U256>,
+    pub code: Option<Bytes>,
+    /// Storage slots to set. Slots not listed keep their values.
+    pub storage: Option<HashMap<H256, U256>>,
+    pub sponsor_info: Option<SponsorInfo>,
+}
+
+pub type StateOverride = HashMap<RpcAddress, AccountOverride>;
+
+impl AccountOverride {
+    pub fn into_primitive(self) -> PrimitiveAccountOverride {
+        PrimitiveAccountOverride {
+            balance: self.balance,
+            nonce: self.nonce,
+            code: self.code.map(Bytes::into_vec),
+            storage: self.storage.unwrap_or_default(),
+            sponsor_info: self.sponsor_info.map(|info| PrimitiveSponsorInfo {
+                sponsor_for_gas: info.sponsor_for_gas.hex_address,
+                sponsor_for_collateral: info.sponsor_for_collateral.hex_address,
+                sponsor_gas_bound: info.sponsor_gas_bound,
+                sponsor_balance_for_gas: info.sponsor_balance_for_gas,
+                sponsor_balance_for_collateral: info
+                    .sponsor_balance_for_collateral,
+            }),
+        }
+    }
+}
+
+/// Result of a single call simulated by `cfx_callMany`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallResult {
+    pub output: Bytes,
+    pub gas_used: U256,
+    pub storage_collateralized: U64,
+    pub storage_released: U64,
+    pub logs: Vec<Log>,
+    /// The reason why the call failed, if any. The state changes of a failed
+    /// call are discarded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
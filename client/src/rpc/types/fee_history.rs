@@ -0,0 +1,29 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{U256, U64};
+use cfxcore::consensus::FeeHistory as PrimitiveFeeHistory;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    /// The first epoch of the returned range.
+    pub oldest_epoch: U64,
+    /// The gas used by each epoch divided by the sum of its blocks' gas
+    /// limits.
+    pub gas_used_ratio: Vec<f64>,
+    /// The requested percentiles of the gas prices in each epoch, weighted
+    /// by gas used.
+    pub reward: Vec<Vec<U256>>,
+}
+
+impl From<PrimitiveFeeHistory> for FeeHistory {
+    fn from(fee_history: PrimitiveFeeHistory) -> Self {
+        FeeHistory {
+            oldest_epoch: fee_history.oldest_epoch.into(),
+            gas_used_ratio: fee_history.gas_used_ratio,
+            reward: fee_history.reward,
+        }
+    }
+}
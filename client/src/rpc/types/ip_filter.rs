@@ -0,0 +1,14 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// Allowed and denied CIDR ranges of the P2P peers.
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpFilter {
+    /// Only the peers in these ranges are allowed, or all the peers if
+    /// empty.
+    pub allow: Vec<String>,
+    /// The peers in these ranges are refused.
+    pub deny: Vec<String>,
+}
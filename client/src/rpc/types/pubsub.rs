@@ -51,6 +51,10 @@ pub enum Result {
     /// Chain reorg
     #[serde(rename_all = "camelCase")]
     ChainReorg { revert_to: U256 },
+
+    /// Sync phase change
+    #[serde(rename_all = "camelCase")]
+    SyncState { sync_phase: String },
 }
 
 /// Subscription kind.
@@ -0,0 +1,123 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::{Action, Bytes, RpcAddress, SponsorInfo};
+use cfx_addr::Network;
+use cfx_types::U256;
+use cfxcore::trace::state_diff::{
+    AccountDiff as PrimitiveAccountDiff, Diff as PrimitiveDiff,
+    StateDiff as PrimitiveStateDiff,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Results of `trace_replayTransaction`. Only the requested trace types are
+/// present.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceResults {
+    pub output: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_diff: Option<StateDiff>,
+}
+
+/// Change of a single value, serialized as `"="`, `{"+": new}`, `{"-": old}`
+/// or `{"*": {"from": old, "to": new}}`.
+#[derive(Debug, Serialize)]
+pub enum Diff<T> {
+    #[serde(rename = "=")]
+    Same,
+    #[serde(rename = "+")]
+    Born(T),
+    #[serde(rename = "-")]
+    Died(T),
+    #[serde(rename = "*")]
+    Changed(ChangedType<T>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedType<T> {
+    pub from: T,
+    pub to: T,
+}
+
+impl<T> Diff<T> {
+    fn try_from<U>(
+        diff: PrimitiveDiff<U>, f: impl Fn(U) -> Result<T, String>,
+    ) -> Result<Self, String> {
+        Ok(match diff {
+            PrimitiveDiff::Same => Diff::Same,
+            PrimitiveDiff::Born(after) => Diff::Born(f(after)?),
+            PrimitiveDiff::Died(before) => Diff::Died(f(before)?),
+            PrimitiveDiff::Changed(before, after) => {
+                Diff::Changed(ChangedType {
+                    from: f(before)?,
+                    to: f(after)?,
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDiff {
+    pub balance: Diff<U256>,
+    pub nonce: Diff<U256>,
+    pub code: Diff<Bytes>,
+    pub staking_balance: Diff<U256>,
+    pub collateral_for_storage: Diff<U256>,
+    pub sponsor_info: Diff<SponsorInfo>,
+    pub storage: HashMap<Bytes, Diff<U256>>,
+}
+
+impl AccountDiff {
+    pub fn try_from(
+        diff: PrimitiveAccountDiff, network: Network,
+    ) -> Result<Self, String> {
+        Ok(AccountDiff {
+            balance: Diff::try_from(diff.balance, Ok)?,
+            nonce: Diff::try_from(diff.nonce, Ok)?,
+            code: Diff::try_from(diff.code, |code| Ok(code.into()))?,
+            staking_balance: Diff::try_from(diff.staking_balance, Ok)?,
+            collateral_for_storage: Diff::try_from(
+                diff.collateral_for_storage,
+                Ok,
+            )?,
+            sponsor_info: Diff::try_from(diff.sponsor_info, |info| {
+                SponsorInfo::try_from(info, network)
+            })?,
+            storage: diff
+                .storage
+                .into_iter()
+                .map(|(key, value)| {
+                    Ok((key.into(), Diff::try_from(value, Ok)?))
+                })
+                .collect::<Result<_, String>>()?,
+        })
+    }
+}
+
+/// Changes made by a transaction, keyed by the modified accounts.
+#[derive(Debug, Serialize)]
+pub struct StateDiff(pub HashMap<RpcAddress, AccountDiff>);
+
+impl StateDiff {
+    pub fn try_from(
+        diff: PrimitiveStateDiff, network: Network,
+    ) -> Result<Self, String> {
+        Ok(StateDiff(
+            diff.into_iter()
+                .map(|(address, account_diff)| {
+                    Ok((
+                        RpcAddress::try_from_h160(address, network)?,
+                        AccountDiff::try_from(account_diff, network)?,
+                    ))
+                })
+                .collect::<Result<_, String>>()?,
+        ))
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::Bytes;
+use cfx_types::H256;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageRange {
+    /// The storage entries in key order, possibly fewer than the limit even
+    /// if there are more entries.
+    pub storage: Vec<StorageRangeEntry>,
+    /// The key to continue from, or `null` if there are no more entries.
+    pub next_key: Option<Bytes>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageRangeEntry {
+    pub key: Bytes,
+    pub value: H256,
+}
@@ -0,0 +1,83 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::Bytes;
+use cfx_types::{U256, U64};
+use cfxcore::trace::struct_logger::{
+    MemoryDiff as PrimitiveMemoryDiff, StorageDiff as PrimitiveStorageDiff,
+    StructLog as PrimitiveStructLog,
+};
+use serde::Serialize;
+
+/// Opcode-level execution trace of a transaction.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionStructLogs {
+    pub gas: U256,
+    pub failed: bool,
+    pub return_value: Bytes,
+    pub struct_logs: Vec<StructLog>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    pub pc: U64,
+    pub op: String,
+    pub gas: U256,
+    pub gas_cost: U256,
+    pub depth: U64,
+    pub stack: Vec<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_diff: Option<MemoryDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_diff: Option<StorageDiff>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryDiff {
+    pub offset: U64,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDiff {
+    pub key: U256,
+    pub value: U256,
+}
+
+impl From<PrimitiveStructLog> for StructLog {
+    fn from(log: PrimitiveStructLog) -> Self {
+        StructLog {
+            pc: (log.pc as u64).into(),
+            op: log.op.into(),
+            gas: log.gas,
+            gas_cost: log.gas_cost,
+            depth: (log.depth as u64).into(),
+            stack: log.stack,
+            memory_diff: log.memory_diff.map(Into::into),
+            storage_diff: log.storage_diff.map(Into::into),
+        }
+    }
+}
+
+impl From<PrimitiveMemoryDiff> for MemoryDiff {
+    fn from(diff: PrimitiveMemoryDiff) -> Self {
+        MemoryDiff {
+            offset: (diff.offset as u64).into(),
+            data: diff.data.into(),
+        }
+    }
+}
+
+impl From<PrimitiveStorageDiff> for StorageDiff {
+    fn from(diff: PrimitiveStorageDiff) -> Self {
+        StorageDiff {
+            key: diff.key,
+            value: diff.value,
+        }
+    }
+}
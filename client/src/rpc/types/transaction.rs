@@ -5,7 +5,10 @@
 use crate::rpc::types::{receipt::Receipt, Bytes, RpcAddress};
 use cfx_addr::Network;
 use cfx_types::{H256, U256, U64};
-use cfxcore::transaction_pool::TransactionStatus;
+use cfxcore::transaction_pool::{
+    ReplacementError, TransactionStatus,
+    TxReplacement as PrimitiveTxReplacement,
+};
 use cfxkey::Error;
 use primitives::{
     transaction::Action, SignedTransaction,
@@ -167,6 +170,39 @@ pub struct TxPoolPendingInfo {
     pub max_nonce: U256,
 }
 
+/// A transaction which replaced, or failed to replace, the transaction with
+/// the same sender and nonce in the transaction pool.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxReplacement {
+    pub sender: RpcAddress,
+    pub nonce: U256,
+    pub replaced_hash: H256,
+    pub replaced_gas_price: U256,
+    pub hash: H256,
+    pub gas_price: U256,
+    pub timestamp: U64,
+    /// The reason why the replacement is rejected, `null` if it succeeded.
+    pub rejected: Option<ReplacementError>,
+}
+
+impl TxReplacement {
+    pub fn try_from(
+        replacement: PrimitiveTxReplacement, network: Network,
+    ) -> Result<Self, String> {
+        Ok(TxReplacement {
+            sender: RpcAddress::try_from_h160(replacement.sender, network)?,
+            nonce: replacement.nonce,
+            replaced_hash: replacement.replaced_hash,
+            replaced_gas_price: replacement.replaced_gas_price,
+            hash: replacement.hash,
+            gas_price: replacement.gas_price,
+            timestamp: replacement.timestamp.into(),
+            rejected: replacement.rejected,
+        })
+    }
+}
+
 #[derive(Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountPendingInfo {
@@ -0,0 +1,213 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Offline inspection of the snapshots of the state. It opens the storage of
+//! the node read-only, and refuses to run while the node is running.
+
+use crate::{
+    configuration::Configuration, offline_storage::open_storage_readonly,
+};
+use cfx_storage::{
+    snapshot_inspection::{
+        self, check_snapshot_merkle_root, key_type_name,
+        snapshot_key_value_stats, KeyValueStats, SnapshotKvDiff,
+    },
+    KVInserter,
+};
+use cfx_types::H256;
+use rustc_hex::ToHex;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// List the snapshots ordered by height, and whether the node drops them
+/// when it starts because their files are missing.
+pub fn list_snapshots(conf: &Configuration) -> Result<String, String> {
+    let storage =
+        open_storage_readonly(conf, /* open_delta_mpts = */ false)?;
+    let storage_manager = storage.get_storage_manager();
+    let snapshot_infos = storage_manager.get_snapshot_infos();
+
+    let mut lines = vec![format!("{} snapshots", snapshot_infos.len())];
+    for (epoch_id, snapshot_info) in snapshot_infos {
+        lines.push(format!(
+            "height {} epoch {:?} merkle_root {:?} parent {:?} \
+             kept_to_provide_sync {:?} status {:?}",
+            snapshot_info.height,
+            epoch_id,
+            snapshot_info.merkle_root,
+            snapshot_info.parent_snapshot_epoch_id,
+            snapshot_info.snapshot_info_kept_to_provide_sync,
+            storage_manager.get_snapshot_info_status(&epoch_id, &snapshot_info),
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Recompute the merkle root of the snapshot at `epoch_id` and check it
+/// against the snapshot info. A corrupt snapshot is reported as an error.
+pub fn verify_snapshot(
+    conf: &Configuration, epoch_id: &str,
+) -> Result<String, String> {
+    let epoch_id = parse_epoch_id(epoch_id)?;
+    let storage =
+        open_storage_readonly(conf, /* open_delta_mpts = */ false)?;
+    let check =
+        check_snapshot_merkle_root(storage.get_storage_manager(), &epoch_id)
+            .map_err(|e| {
+            format!("Failed to verify snapshot {:?}: {}", epoch_id, e)
+        })?;
+
+    if !check.is_valid() {
+        return Err(format!(
+            "Snapshot {:?} is corrupt: expected merkle root {:?}, \
+             snapshot MPT merkle root {:?}, recomputed merkle root {:?}",
+            epoch_id,
+            check.expected,
+            check.mpt_merkle_root,
+            check.recomputed_merkle_root,
+        ));
+    }
+    Ok(format!(
+        "Snapshot {:?} is valid, {} key-values with merkle root {:?}",
+        epoch_id, check.key_value_count, check.expected
+    ))
+}
+
+/// Print the number and size of the key-values of the snapshot at
+/// `epoch_id` by key type.
+pub fn snapshot_stats(
+    conf: &Configuration, epoch_id: &str,
+) -> Result<String, String> {
+    let epoch_id = parse_epoch_id(epoch_id)?;
+    let storage =
+        open_storage_readonly(conf, /* open_delta_mpts = */ false)?;
+    let stats =
+        snapshot_key_value_stats(storage.get_storage_manager(), &epoch_id)
+            .map_err(|e| {
+                format!("Failed to read snapshot {:?}: {}", epoch_id, e)
+            })?;
+
+    let mut lines = vec![format!(
+        "{:<14}{:>14}{:>18}{:>18}",
+        "key type", "count", "key bytes", "value bytes"
+    )];
+    let mut total = KeyValueStats::default();
+    for (key_type, stats) in stats {
+        lines.push(format!(
+            "{:<14}{:>14}{:>18}{:>18}",
+            key_type, stats.count, stats.key_bytes, stats.value_bytes
+        ));
+        total.count += stats.count;
+        total.key_bytes += stats.key_bytes;
+        total.value_bytes += stats.value_bytes;
+    }
+    lines.push(format!(
+        "{:<14}{:>14}{:>18}{:>18}",
+        "total", total.count, total.key_bytes, total.value_bytes
+    ));
+    Ok(lines.join("\n"))
+}
+
+/// Print the key-value differences from the snapshot at `from` to the
+/// snapshot at `to`, counted by key type, and the first `limit` of them.
+pub fn diff_snapshots(
+    conf: &Configuration, from: &str, to: &str, limit: usize,
+) -> Result<String, String> {
+    let from = parse_epoch_id(from)?;
+    let to = parse_epoch_id(to)?;
+    let storage =
+        open_storage_readonly(conf, /* open_delta_mpts = */ false)?;
+    let mut collector = DiffCollector {
+        limit,
+        ..Default::default()
+    };
+    snapshot_inspection::diff_snapshots(
+        storage.get_storage_manager(),
+        &from,
+        &to,
+        &mut collector,
+    )
+    .map_err(|e| {
+        format!("Failed to diff snapshots {:?} and {:?}: {}", from, to, e)
+    })?;
+
+    let mut lines = vec![format!(
+        "{} differences from snapshot {:?} to {:?}",
+        collector.total, from, to
+    )];
+    for (key_type, counts) in &collector.counts {
+        lines.push(format!(
+            "{}: {} added, {} removed, {} changed",
+            key_type, counts.added, counts.removed, counts.changed
+        ));
+    }
+    lines.extend(collector.diffs);
+    if collector.total > limit as u64 {
+        lines.push(format!("... {} more", collector.total - limit as u64));
+    }
+    Ok(lines.join("\n"))
+}
+
+#[derive(Default)]
+struct DiffCounts {
+    added: u64,
+    removed: u64,
+    changed: u64,
+}
+
+/// Counts the differences by key type, and keeps the first `limit` of them
+/// for printing.
+#[derive(Default)]
+struct DiffCollector {
+    limit: usize,
+    total: u64,
+    counts: BTreeMap<&'static str, DiffCounts>,
+    diffs: Vec<String>,
+}
+
+impl KVInserter<SnapshotKvDiff> for DiffCollector {
+    fn push(&mut self, diff: SnapshotKvDiff) -> cfx_storage::Result<()> {
+        self.total += 1;
+        let key = match &diff {
+            SnapshotKvDiff::Added((key, _))
+            | SnapshotKvDiff::Removed((key, _))
+            | SnapshotKvDiff::Changed { key, .. } => key,
+        };
+        let counts = self.counts.entry(key_type_name(key)).or_default();
+        match &diff {
+            SnapshotKvDiff::Added(_) => counts.added += 1,
+            SnapshotKvDiff::Removed(_) => counts.removed += 1,
+            SnapshotKvDiff::Changed { .. } => counts.changed += 1,
+        }
+        if self.diffs.len() < self.limit {
+            self.diffs.push(format_diff(&diff));
+        }
+        Ok(())
+    }
+}
+
+fn format_diff(diff: &SnapshotKvDiff) -> String {
+    match diff {
+        SnapshotKvDiff::Added((key, value)) => {
+            format!("+ {} {}", key.to_hex::<String>(), value.to_hex::<String>())
+        }
+        SnapshotKvDiff::Removed((key, value)) => {
+            format!("- {} {}", key.to_hex::<String>(), value.to_hex::<String>())
+        }
+        SnapshotKvDiff::Changed {
+            key,
+            old_value,
+            new_value,
+        } => format!(
+            "~ {} {} -> {}",
+            key.to_hex::<String>(),
+            old_value.to_hex::<String>(),
+            new_value.to_hex::<String>()
+        ),
+    }
+}
+
+fn parse_epoch_id(epoch_id: &str) -> Result<H256, String> {
+    H256::from_str(epoch_id.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid epoch id {}: {}", epoch_id, e))
+}
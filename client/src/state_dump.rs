@@ -0,0 +1,86 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    configuration::Configuration, offline_storage::open_storage_readonly,
+};
+use cfx_storage::{StateIndex, StorageManagerTrait};
+use cfxcore::{
+    block_data_manager::{db_manager::DBManager, DbType},
+    pow::PowComputer,
+    state_dump::{dump_state, StateDumpHeader, STATE_DUMP_VERSION},
+};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Dump the state of the pivot block of `epoch_number` into `output`. It
+/// opens the databases of the node directly, so the node must be stopped.
+/// The storage is opened read-only.
+pub fn dump_state_at_epoch(
+    conf: &Configuration, epoch_number: u64, output: &Path,
+) -> Result<String, String> {
+    // The storage tests the lock of the ledger database held by a running
+    // node, so it's opened before this process holds the lock itself.
+    let storage_manager = Arc::new(open_storage_readonly(
+        conf, /* open_delta_mpts = */ true,
+    )?);
+
+    let (db_path, db_config) = conf.db_config();
+    let ledger_db = db::open_database(db_path.to_str().unwrap(), &db_config)
+        .map_err(|e| {
+            format!("Failed to open database {:?}, is the node stopped?", e)
+        })?;
+    let pow = Arc::new(PowComputer::new(conf.pow_config().use_octopus()));
+    let db_manager = match conf.data_mananger_config().db_type {
+        DbType::Rocksdb => DBManager::new_from_rocksdb(ledger_db, pow),
+        DbType::Sqlite => {
+            DBManager::new_from_sqlite(&PathBuf::from("./sqlite_db"), pow)
+        }
+    };
+
+    // The pivot block is the last block of the epoch.
+    let epoch_hash = db_manager
+        .executed_epoch_set_hashes_from_db(epoch_number)
+        .and_then(|hashes| hashes.last().cloned())
+        .ok_or(format!("Epoch {} is not executed", epoch_number))?;
+    let commitment = db_manager
+        .epoch_execution_commitment_from_db(&epoch_hash)
+        .ok_or(format!(
+            "Execution commitment of epoch {} is not found",
+            epoch_number
+        ))?;
+    let state_root = &commitment.state_root_with_aux_info;
+
+    let mut state = storage_manager
+        .get_state_no_commit(
+            StateIndex::new_for_readonly(&epoch_hash, state_root),
+            /* try_open = */ false,
+        )
+        .map_err(|e| format!("Failed to open state: {}", e))?
+        .ok_or(format!(
+            "State of epoch {} is not available, it may have been removed",
+            epoch_number
+        ))?;
+
+    let header = StateDumpHeader {
+        version: STATE_DUMP_VERSION,
+        epoch_number,
+        epoch_hash,
+        state_root: state_root.state_root.compute_state_root_hash(),
+    };
+    let file = File::create(output)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let entries = dump_state(&mut state, &header, BufWriter::new(file))?;
+
+    Ok(format!(
+        "Dumped {} entries of the state at epoch {} to {}",
+        entries,
+        epoch_number,
+        output.display()
+    ))
+}
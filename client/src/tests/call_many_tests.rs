@@ -0,0 +1,150 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::test_node::TestNode;
+use cfx_parameters::staking::COLLATERAL_UNITS_PER_STORAGE_KEY;
+use cfx_types::{address_util::AddressUtil, Address, U256};
+use cfxcore::{
+    executive::ExecutionOutcome,
+    state::{AccountOverride, StateOverride},
+    ConsensusGraphTrait,
+};
+use primitives::{Action, EpochNumber, SignedTransaction, Transaction};
+
+// Increments storage slot 0 and returns the new value.
+const COUNTER_CODE: &[u8] = &[
+    0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x80, 0x60, 0x00, 0x55, 0x60, 0x00,
+    0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+];
+// Clears storage slot 0.
+const CLEAR_CODE: &[u8] = &[0x60, 0x00, 0x60, 0x00, 0x55, 0x00];
+// Returns the balance of the contract itself.
+const BALANCE_CODE: &[u8] =
+    &[0x30, 0x31, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+
+fn contract_address(low: u64) -> Address {
+    let mut address = Address::from_low_u64_be(low);
+    address.set_contract_type_bits();
+    address
+}
+
+fn call(node: &TestNode, to: Address) -> SignedTransaction {
+    let consensus = node.consensus();
+    let mut from = Address::from_low_u64_be(1);
+    from.set_user_account_type_bits();
+    Transaction {
+        nonce: 0.into(),
+        gas_price: 1.into(),
+        gas: 1_000_000.into(),
+        action: Action::Call(to),
+        value: 0.into(),
+        storage_limit: std::u64::MAX,
+        epoch_height: consensus.best_epoch_number(),
+        chain_id: consensus.best_chain_id(),
+        data: vec![],
+    }
+    .fake_sign(from)
+}
+
+fn output(outcome: &ExecutionOutcome) -> U256 {
+    match outcome {
+        ExecutionOutcome::Finished(executed) => {
+            U256::from_big_endian(&executed.output)
+        }
+        outcome => panic!("call failed: {:?}", outcome),
+    }
+}
+
+#[test]
+fn test_call_virtual_many() {
+    let node = TestNode::start(13005, |_| {});
+    node.generate_deferred_blocks();
+    let consensus = node.consensus().clone();
+
+    let counter = contract_address(1);
+    let with_balance = contract_address(2);
+    let mut state_override = StateOverride::new();
+    state_override.insert(
+        counter,
+        AccountOverride {
+            code: Some(COUNTER_CODE.to_vec()),
+            storage: vec![(Default::default(), 5.into())].into_iter().collect(),
+            ..Default::default()
+        },
+    );
+    state_override.insert(
+        with_balance,
+        AccountOverride {
+            balance: Some(12_345.into()),
+            code: Some(BALANCE_CODE.to_vec()),
+            ..Default::default()
+        },
+    );
+
+    // Each call sees the storage written by the previous ones, starting
+    // from the overridden value.
+    let txs = vec![
+        call(&node, counter),
+        call(&node, counter),
+        call(&node, with_balance),
+        call(&node, counter),
+    ];
+    let outcomes = consensus
+        .call_virtual_many(&txs, EpochNumber::LatestState, &state_override)
+        .unwrap();
+    let outputs: Vec<U256> = outcomes.iter().map(output).collect();
+    assert_eq!(
+        outputs,
+        vec![6.into(), 7.into(), U256::from(12_345), 8.into()]
+    );
+
+    // Nothing is committed, so a new simulation starts over.
+    let outcomes = consensus
+        .call_virtual_many(&txs[..1], EpochNumber::LatestState, &state_override)
+        .unwrap();
+    assert_eq!(output(&outcomes[0]), U256::from(6));
+
+    node.shutdown();
+}
+
+/// An overridden storage entry is collateralized, so clearing it in a call
+/// releases the collateral of the contract.
+#[test]
+fn test_call_virtual_clears_overridden_storage() {
+    let node = TestNode::start(13008, |_| {});
+    node.generate_deferred_blocks();
+    let consensus = node.consensus().clone();
+
+    let clear = contract_address(3);
+    let mut state_override = StateOverride::new();
+    state_override.insert(
+        clear,
+        AccountOverride {
+            code: Some(CLEAR_CODE.to_vec()),
+            storage: vec![(Default::default(), 5.into())].into_iter().collect(),
+            ..Default::default()
+        },
+    );
+
+    let outcomes = consensus
+        .call_virtual_many(
+            &[call(&node, clear)],
+            EpochNumber::LatestState,
+            &state_override,
+        )
+        .unwrap();
+    match &outcomes[0] {
+        ExecutionOutcome::Finished(executed) => {
+            assert_eq!(executed.storage_released.len(), 1);
+            assert_eq!(executed.storage_released[0].address, clear);
+            assert_eq!(
+                executed.storage_released[0].collaterals,
+                COLLATERAL_UNITS_PER_STORAGE_KEY.into()
+            );
+        }
+        outcome => panic!("call failed: {:?}", outcome),
+    }
+
+    node.shutdown();
+}
@@ -0,0 +1,123 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::test_node::TestNode;
+use cfx_state::state_trait::StateOpsTrait;
+use cfx_types::{address_util::AddressUtil, Address, U256};
+use cfxcore::{
+    executive::contract_address, state::State, vm::CreateContractAddress,
+    ConsensusGraphTrait,
+};
+use primitives::{Action, EpochNumber, SkipInputCheck, StorageKey};
+
+// Deploys a contract which increments storage slot 0 when it's called.
+const COUNTER_INIT_CODE: &[u8] = &[
+    0x60, 0x12, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, 0x12, 0x60, 0x00, 0xf3,
+    // The runtime code.
+    0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x80, 0x60, 0x00, 0x55, 0x60, 0x00,
+    0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+];
+const SNAPSHOT_EPOCH_COUNT: u32 = 10;
+const EPOCH_COUNT: u64 = 4 * SNAPSHOT_EPOCH_COUNT as u64;
+
+fn user_address(low: u64) -> Address {
+    let mut address = Address::from_low_u64_be(low);
+    address.set_user_account_type_bits();
+    address
+}
+
+/// Reconstructs the state of an epoch after a snapshot while the state is
+/// still kept, and compares them.
+#[test]
+fn test_reconstruct_state() {
+    let node = TestNode::start(13007, |conf| {
+        conf.raw_conf.dev_snapshot_epoch_count = SNAPSHOT_EPOCH_COUNT;
+        conf.raw_conf.historical_state_reconstruction = true;
+    });
+    let consensus = node.consensus().clone();
+
+    let create = node.genesis_transaction(
+        0,
+        Action::Create,
+        0.into(),
+        COUNTER_INIT_CODE.to_vec(),
+    );
+    let (counter, _) = contract_address(
+        CreateContractAddress::FromSenderNonceAndCodeHash,
+        0.into(),
+        &create.sender,
+        &create.nonce,
+        &create.data,
+    );
+    let genesis_address = create.sender;
+    node.generate_block(vec![create]);
+
+    // Every epoch changes balances and the storage of the counter.
+    let receivers: Vec<Address> = (1..=4).map(user_address).collect();
+    for epoch in 0..EPOCH_COUNT {
+        let nonce = 2 * epoch + 1;
+        let transfer = node.genesis_transfer(
+            nonce,
+            receivers[epoch as usize % receivers.len()],
+            (1_000 * (epoch + 1)).into(),
+        );
+        let increment = node.genesis_transaction(
+            nonce + 1,
+            Action::Call(counter),
+            0.into(),
+            vec![],
+        );
+        node.generate_block(vec![transfer, increment]);
+    }
+    node.generate_deferred_blocks();
+    let best_executed_epoch = consensus.best_executed_state_epoch_number();
+
+    let snapshot_height = consensus
+        .data_man
+        .storage_manager
+        .get_storage_manager()
+        .get_snapshot_infos()
+        .iter()
+        .map(|(_, snapshot_info)| snapshot_info.height)
+        .filter(|height| *height > 0)
+        .max()
+        .expect("a snapshot is created");
+    let height = snapshot_height + SNAPSHOT_EPOCH_COUNT as u64 / 2;
+    assert!(height <= best_executed_epoch);
+    let hash = consensus
+        .get_hash_from_epoch_number(EpochNumber::Number(height))
+        .unwrap();
+
+    let reconstructed_db =
+        consensus.reconstruct_state_db(height, &hash).unwrap();
+    let kept_db = consensus
+        .get_kept_state_db_by_epoch_number(EpochNumber::Number(height), "epoch")
+        .unwrap();
+    // Every entry read or changed by the re-executed epochs has the value in
+    // the kept state.
+    let entries = reconstructed_db.accessed_entries();
+    assert!(!entries.is_empty());
+    for (key, value) in entries {
+        let storage_key = StorageKey::from_key_bytes::<SkipInputCheck>(&key);
+        assert_eq!(kept_db.get_raw(storage_key).unwrap(), value);
+    }
+
+    let reconstructed = State::new(reconstructed_db).unwrap();
+    let kept = State::new(kept_db).unwrap();
+    for address in receivers.iter().chain(&[genesis_address, counter]) {
+        assert_eq!(
+            reconstructed.balance(address).unwrap(),
+            kept.balance(address).unwrap()
+        );
+        assert_eq!(
+            reconstructed.nonce(address).unwrap(),
+            kept.nonce(address).unwrap()
+        );
+    }
+    let count = kept.storage_at(&counter, &[0; 32]).unwrap();
+    assert!(count > U256::zero());
+    assert_eq!(reconstructed.storage_at(&counter, &[0; 32]).unwrap(), count);
+
+    node.shutdown();
+}
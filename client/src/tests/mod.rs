@@ -5,4 +5,14 @@
 #[cfg(test)]
 mod blockgen_tests;
 #[cfg(test)]
+mod call_many_tests;
+#[cfg(test)]
+mod historical_state_tests;
+#[cfg(test)]
 mod load_chain_tests;
+#[cfg(test)]
+mod test_node;
+#[cfg(test)]
+mod trace_replay_tests;
+#[cfg(test)]
+mod txpool_tests;
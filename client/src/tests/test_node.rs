@@ -0,0 +1,122 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+extern crate tempdir;
+
+use self::tempdir::TempDir;
+use crate::{
+    archive::{ArchiveClient, ArchiveClientExtraComponents},
+    common::{client_methods, ClientComponents},
+    configuration::Configuration,
+};
+use blockgen::BlockGenerator;
+use cfx_parameters::consensus::DEFERRED_STATE_EPOCH_COUNT;
+use cfx_types::{Address, H256, U256};
+use cfxcore::{
+    spec::genesis::DEV_GENESIS_KEY_PAIR, ConsensusGraph, ConsensusGraphTrait,
+    TransactionPool,
+};
+use parking_lot::{Condvar, Mutex};
+use primitives::{Action, SignedTransaction, Transaction};
+use std::{sync::Arc, thread, time::Duration};
+
+/// An archive node in test mode whose blocks are only generated by the test
+/// itself.
+pub struct TestNode {
+    handle: Box<ClientComponents<BlockGenerator, ArchiveClientExtraComponents>>,
+    // The data directory is removed when the node is dropped.
+    _data_dir: TempDir,
+}
+
+impl TestNode {
+    /// Starts a node listening on `tcp_port`, and on `tcp_port + 5000` for
+    /// http rpc. `configure` is applied after the common test settings.
+    pub fn start<F>(tcp_port: u16, configure: F) -> TestNode
+    where F: FnOnce(&mut Configuration) {
+        let mut conf = Configuration::default();
+        conf.raw_conf.mode = Some("test".to_owned());
+        conf.raw_conf.dev_allow_phase_change_without_peer = true;
+
+        let data_dir = TempDir::new("conflux-test").unwrap();
+        conf.raw_conf.conflux_data_dir =
+            data_dir.path().to_str().unwrap().to_string() + "/";
+        conf.raw_conf.tcp_port = tcp_port;
+        conf.raw_conf.jsonrpc_http_port = Some(tcp_port + 5000);
+        conf.raw_conf.mining_author =
+            Some("1aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".into());
+        // Avoid starting the mining thread in `ArchiveClient::start`.
+        conf.raw_conf.mining_type = Some("disable".into());
+        configure(&mut conf);
+
+        let exit = Arc::new((Mutex::new(false), Condvar::new()));
+        let handle = ArchiveClient::start(conf, exit).unwrap();
+        // BlockGenerator does not wait for catching up in test mode, so we
+        // wait here.
+        while handle.other_components.sync.catch_up_mode() {
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        TestNode {
+            handle,
+            _data_dir: data_dir,
+        }
+    }
+
+    pub fn consensus(&self) -> &Arc<ConsensusGraph> {
+        &self.handle.other_components.consensus
+    }
+
+    pub fn txpool(&self) -> &Arc<TransactionPool> {
+        &self.handle.other_components.txpool
+    }
+
+    /// Generates a block with `transactions` on the current pivot chain and
+    /// returns its hash.
+    pub fn generate_block(
+        &self, transactions: Vec<Arc<SignedTransaction>>,
+    ) -> H256 {
+        self.handle
+            .blockgen
+            .as_ref()
+            .unwrap()
+            .generate_custom_block(transactions, None)
+    }
+
+    /// Generates enough empty blocks for all the blocks generated so far to
+    /// be executed.
+    pub fn generate_deferred_blocks(&self) {
+        for _ in 0..DEFERRED_STATE_EPOCH_COUNT {
+            self.generate_block(vec![]);
+        }
+    }
+
+    /// Signs a transaction from the genesis account with `nonce`, valid at
+    /// the current best epoch.
+    pub fn genesis_transaction(
+        &self, nonce: u64, action: Action, value: U256, data: Vec<u8>,
+    ) -> Arc<SignedTransaction> {
+        let consensus = self.consensus();
+        let tx = Transaction {
+            nonce: nonce.into(),
+            gas_price: 1.into(),
+            gas: 1_000_000.into(),
+            action,
+            value,
+            storage_limit: 10_000,
+            epoch_height: consensus.best_epoch_number(),
+            chain_id: consensus.best_chain_id(),
+            data,
+        };
+        Arc::new(tx.sign(DEV_GENESIS_KEY_PAIR.secret()))
+    }
+
+    /// Signs a transfer of `value` from the genesis account to `to`.
+    pub fn genesis_transfer(
+        &self, nonce: u64, to: Address, value: U256,
+    ) -> Arc<SignedTransaction> {
+        self.genesis_transaction(nonce, Action::Call(to), value, vec![])
+    }
+
+    pub fn shutdown(self) { client_methods::shutdown(self.handle); }
+}
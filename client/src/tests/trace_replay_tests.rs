@@ -0,0 +1,67 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::test_node::TestNode;
+use cfx_types::{Address, H256};
+use cfxcore::trace::trace::TransactionExecTraces;
+
+/// Generates a block with a transfer and executes it, with `executive_trace`
+/// off so that no traces are recorded.
+fn generate_untraced_block(node: &TestNode) -> H256 {
+    let to: Address =
+        "1000000000000000000000000000000000000001".parse().unwrap();
+    let tx = node.genesis_transfer(0, to, 1_000.into());
+    let block_hash = node.generate_block(vec![tx]);
+    node.generate_deferred_blocks();
+    block_hash
+}
+
+fn test_trace_replay_inner(tcp_port: u16, persist_replayed_trace: bool) {
+    let node = TestNode::start(tcp_port, |conf| {
+        conf.raw_conf.executive_trace = false;
+        conf.raw_conf.persist_replayed_trace = persist_replayed_trace;
+    });
+    let consensus = node.consensus().clone();
+    let data_man = consensus.data_man.clone();
+
+    let block_hash = generate_untraced_block(&node);
+    assert!(data_man.block_traces_by_hash(&block_hash).is_none());
+
+    let replayed = consensus
+        .block_traces_or_replay(&block_hash)
+        .unwrap()
+        .expect("traces are replayed");
+    let tx_traces: Vec<TransactionExecTraces> = replayed.1.clone().into();
+    assert_eq!(tx_traces.len(), 1);
+
+    // The replayed traces are cached in memory in any case.
+    let cached = data_man.block_traces_by_hash(&block_hash).unwrap();
+    assert_eq!(cached.0, replayed.0);
+    assert_eq!(cached.1, replayed.1);
+
+    // Only persisted traces survive the eviction from the memory cache.
+    data_man.remove_block_traces(&block_hash, false /* remove_db */);
+    let stored = data_man.block_traces_by_hash(&block_hash);
+    if persist_replayed_trace {
+        let stored = stored.expect("replayed traces are persisted");
+        assert_eq!(stored.0, replayed.0);
+        assert_eq!(stored.1, replayed.1);
+    } else {
+        assert!(stored.is_none());
+        // The traces can still be replayed again.
+        let replayed_again = consensus
+            .block_traces_or_replay(&block_hash)
+            .unwrap()
+            .expect("traces are replayed");
+        assert_eq!(replayed_again.1, replayed.1);
+    }
+
+    node.shutdown();
+}
+
+#[test]
+fn test_trace_replay_persisted() { test_trace_replay_inner(13003, true); }
+
+#[test]
+fn test_trace_replay_not_persisted() { test_trace_replay_inner(13004, false); }
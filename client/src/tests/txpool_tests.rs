@@ -0,0 +1,58 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::test_node::TestNode;
+use cfx_types::Address;
+
+#[test]
+fn test_local_transactions_skip_min_gas_price() {
+    let node = TestNode::start(13006, |conf| {
+        conf.raw_conf.tx_pool_min_tx_gas_price = 2;
+    });
+    let txpool = node.txpool();
+    let to: Address =
+        "1000000000000000000000000000000000000001".parse().unwrap();
+    // The genesis transactions have a gas price of 1.
+    let tx = node.genesis_transfer(0, to, 1_000.into());
+
+    let (passed, failure) =
+        txpool.insert_new_transactions(vec![tx.transaction.clone()]);
+    assert!(passed.is_empty());
+    assert!(failure.contains_key(&tx.hash()));
+
+    let (passed, failure) =
+        txpool.insert_new_local_transactions(vec![tx.transaction.clone()]);
+    assert!(failure.is_empty());
+    assert_eq!(passed.len(), 1);
+    assert!(txpool.get_transaction(&tx.hash()).is_some());
+
+    node.shutdown();
+}
+
+#[test]
+fn test_local_transactions_limit() {
+    let node = TestNode::start(13009, |conf| {
+        conf.raw_conf.tx_pool_max_local_txs = 1;
+    });
+    let txpool = node.txpool();
+    let to: Address =
+        "1000000000000000000000000000000000000001".parse().unwrap();
+    let first = node.genesis_transfer(0, to, 1_000.into());
+    let second = node.genesis_transfer(1, to, 1_000.into());
+
+    let (passed, _) =
+        txpool.insert_new_local_transactions(vec![first.transaction.clone()]);
+    assert_eq!(passed.len(), 1);
+    let (passed, failure) =
+        txpool.insert_new_local_transactions(vec![second.transaction.clone()]);
+    assert!(passed.is_empty());
+    assert!(failure.contains_key(&second.hash()));
+
+    // Remote transactions are still accepted.
+    let (passed, _) =
+        txpool.insert_new_transactions(vec![second.transaction.clone()]);
+    assert_eq!(passed.len(), 1);
+
+    node.shutdown();
+}
@@ -102,8 +102,14 @@ pub mod consensus_internal {
 pub mod rpc {
     pub const GAS_PRICE_BLOCK_SAMPLE_SIZE: usize = 100;
     pub const GAS_PRICE_TRANSACTION_SAMPLE_SIZE: usize = 10000;
+    pub const GAS_PRICE_DEFAULT_PERCENTILE: u8 = 50;
     pub const TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW: usize = 100;
     pub const TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM: usize = 600;
+    /// The maximum number of epochs returned by `cfx_feeHistory`.
+    pub const MAX_FEE_HISTORY_EPOCH_COUNT: u64 = 1024;
+    /// The maximum number of storage entries returned by
+    /// `cfx_getStorageRange`.
+    pub const MAX_STORAGE_RANGE_LIMIT: u64 = 1024;
 }
 
 pub mod sync {
@@ -280,6 +286,7 @@ pub mod light {
         pub static ref TX_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
         pub static ref TX_INFO_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
         pub static ref STORAGE_ROOT_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+        pub static ref BLOCK_REWARD_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
 
         /// Maximum time period we wait for a response for an on-demand query.
         /// After this timeout has been reached, we try another peer or give up.
@@ -307,6 +314,7 @@ pub mod light {
     pub const TX_REQUEST_BATCH_SIZE: usize = 30;
     pub const TX_INFO_REQUEST_BATCH_SIZE: usize = 30;
     pub const STORAGE_ROOT_REQUEST_BATCH_SIZE: usize = 30;
+    pub const BLOCK_REWARD_REQUEST_BATCH_SIZE: usize = 30;
 
     /// Maximum number of in-flight items at any given time.
     /// If we reach this limit, we will not request any more.
@@ -320,6 +328,7 @@ pub mod light {
     pub const MAX_TXS_IN_FLIGHT: usize = 100;
     pub const MAX_TX_INFOS_IN_FLIGHT: usize = 100;
     pub const MAX_STORAGE_ROOTS_IN_FLIGHT: usize = 100;
+    pub const MAX_BLOCK_REWARDS_IN_FLIGHT: usize = 100;
 
     /// Maximum number of in-flight epoch requests at any given time.
     /// Similar to `MAX_HEADERS_IN_FLIGHT`. However, it is hard to match
@@ -363,6 +372,11 @@ pub mod light {
 
     // Number of blocks we retrieve in parallel for the gas price sample.
     pub const GAS_PRICE_BATCH_SIZE: usize = 30;
+
+    /// During virtual calls, we re-execute the transaction each time it
+    /// accesses state entries we have not retrieved yet. This is the maximum
+    /// number of such executions before we give up.
+    pub const MAX_VIRTUAL_CALL_ROUNDS: usize = 64;
 }
 
 pub const WORKER_COMPUTATION_PARALLELISM: usize = 8;
@@ -113,6 +113,8 @@ pub struct Notifications {
     pub new_block_hashes: Arc<Channel<H256>>,
     pub epochs_ordered: Arc<Channel<(u64, Vec<H256>)>>,
     pub blame_verification_results: Arc<Channel<(u64, Option<u64>)>>, /* <height, witness> */
+    pub new_pending_transactions: Arc<Channel<H256>>,
+    pub sync_phase_changes: Arc<Channel<&'static str>>, /* phase name */
 }
 
 impl Notifications {
@@ -123,6 +125,10 @@ impl Notifications {
             blame_verification_results: Arc::new(Channel::new(
                 "blame-verification-results",
             )),
+            new_pending_transactions: Arc::new(Channel::new(
+                "new-pending-transactions",
+            )),
+            sync_phase_changes: Arc::new(Channel::new("sync-phase-changes")),
         })
     }
 }
@@ -19,12 +19,18 @@ use crate::{
     rpc_errors::{invalid_params_check, Result as RpcResult},
     spec::genesis::initialize_internal_contract_accounts,
     state::{
+        apply_state_override,
         prefetcher::{
             prefetch_accounts, ExecutionStatePrefetcher, PrefetchTaskHandle,
         },
-        State,
+        State, StateOverride,
+    },
+    trace::{
+        state_diff::StateDiff,
+        struct_logger::StructLog,
+        trace::{BlockExecTraces, ExecTrace, TransactionExecTraces},
+        StructLogger,
     },
-    trace::trace::{ExecTrace, TransactionExecTraces},
     verification::{
         compute_receipts_root, VerificationConfig, VerifyTxLocalMode,
         VerifyTxMode,
@@ -393,91 +399,162 @@ impl ConsensusExecutor {
                         inner,
                     )
                     .unwrap();
+                    // Cache `vote_valid` of the epoch blocks in the arena.
+                    let ordered_epoch_blocks = inner
+                        .get_ordered_executable_epoch_blocks(pivot_arena_index)
+                        .clone();
+                    for index in ordered_epoch_blocks {
+                        if index != pivot_arena_index
+                            && !inner.arena[index].data.partial_invalid
+                        {
+                            inner.compute_vote_valid_for_pivot_block(
+                                index,
+                                pivot_arena_index,
+                            );
+                        }
+                    }
                 }
 
-                let epoch_blocks =
-                    inner.get_executable_epoch_blocks(pivot_arena_index);
-
-                let mut epoch_block_no_reward =
-                    Vec::with_capacity(epoch_blocks.len());
-                let mut epoch_block_anticone_difficulties =
-                    Vec::with_capacity(epoch_blocks.len());
-
-                let epoch_difficulty =
-                    inner.arena[pivot_arena_index].difficulty;
-                let anticone_cutoff_epoch_anticone_set_ref_opt = inner
-                    .anticone_cache
-                    .get(anticone_penalty_cutoff_epoch_arena_index);
-                let anticone_cutoff_epoch_anticone_set;
-                if let Some(r) = anticone_cutoff_epoch_anticone_set_ref_opt {
-                    anticone_cutoff_epoch_anticone_set = r.clone();
+                let inner = &*inner;
+                self.build_reward_execution_info(
+                    inner,
+                    pivot_arena_index,
+                    anticone_penalty_cutoff_epoch_arena_index,
+                    |index| Some(inner.arena[index].data.vote_valid),
+                )
+                .expect("computed in wait_and_compute_state_valid_locked")
+            },
+        )
+    }
+
+    /// The same as `get_reward_execution_info`, but it only reads `inner`, so
+    /// that it can be called with the read lock. Returns `Err` if the
+    /// validity of the blocks which the rewards depend on is not computed
+    /// yet, which needs `get_reward_execution_info`.
+    pub fn get_reward_execution_info_readonly(
+        &self, inner: &ConsensusGraphInner, epoch_arena_index: usize,
+    ) -> Result<Option<RewardExecutionInfo>, String> {
+        match inner.get_pivot_reward_index(epoch_arena_index) {
+            Some((
+                pivot_arena_index,
+                anticone_penalty_cutoff_epoch_arena_index,
+            )) => self
+                .build_reward_execution_info(
+                    inner,
+                    pivot_arena_index,
+                    anticone_penalty_cutoff_epoch_arena_index,
+                    |index| {
+                        inner.compute_vote_valid_for_pivot_block_readonly(
+                            index,
+                            pivot_arena_index,
+                        )
+                    },
+                )
+                .map(Some)
+                .ok_or_else(|| {
+                    format!(
+                        "Validity of the blocks rewarded in epoch {:?} is \
+                         not computed",
+                        inner.arena[pivot_arena_index].hash
+                    )
+                }),
+            None => Ok(None),
+        }
+    }
+
+    /// Build the reward information of the epoch of `pivot_arena_index` from
+    /// the block validity computed in `inner`, where `vote_valid` returns the
+    /// `vote_valid` of a block for the pivot block. Returns `None` if any
+    /// validity is not computed.
+    fn build_reward_execution_info<F>(
+        &self, inner: &ConsensusGraphInner, pivot_arena_index: usize,
+        anticone_penalty_cutoff_epoch_arena_index: usize, vote_valid: F,
+    ) -> Option<RewardExecutionInfo>
+    where
+        F: Fn(usize) -> Option<bool>,
+    {
+        let epoch_blocks = inner.get_executable_epoch_blocks(pivot_arena_index);
+
+        let mut epoch_block_no_reward = Vec::with_capacity(epoch_blocks.len());
+        let mut epoch_block_anticone_difficulties =
+            Vec::with_capacity(epoch_blocks.len());
+
+        let epoch_difficulty = inner.arena[pivot_arena_index].difficulty;
+        let anticone_cutoff_epoch_anticone_set_ref_opt = inner
+            .anticone_cache
+            .get(anticone_penalty_cutoff_epoch_arena_index);
+        let anticone_cutoff_epoch_anticone_set;
+        if let Some(r) = anticone_cutoff_epoch_anticone_set_ref_opt {
+            anticone_cutoff_epoch_anticone_set = r.clone();
+        } else {
+            anticone_cutoff_epoch_anticone_set =
+                ConsensusNewBlockHandler::compute_anticone_hashset_bruteforce(
+                    inner,
+                    anticone_penalty_cutoff_epoch_arena_index,
+                );
+        }
+        let ordered_epoch_blocks = inner
+            .get_ordered_executable_epoch_blocks(pivot_arena_index)
+            .clone();
+        for index in ordered_epoch_blocks.iter() {
+            let block_consensus_node = &inner.arena[*index];
+
+            let mut no_reward = block_consensus_node.data.partial_invalid;
+            if !self.consensus_graph_bench_mode && !no_reward {
+                if *index == pivot_arena_index {
+                    no_reward =
+                        !inner.arena[pivot_arena_index].data.state_valid?;
                 } else {
-                    anticone_cutoff_epoch_anticone_set = ConsensusNewBlockHandler::compute_anticone_hashset_bruteforce(inner, anticone_penalty_cutoff_epoch_arena_index);
+                    no_reward = !vote_valid(*index)?;
                 }
-                let ordered_epoch_blocks = inner.get_ordered_executable_epoch_blocks(pivot_arena_index).clone();
-                for index in ordered_epoch_blocks.iter() {
-                    let block_consensus_node = &inner.arena[*index];
-
-                    let mut no_reward =
-                        block_consensus_node.data.partial_invalid;
-                    if !self.consensus_graph_bench_mode && !no_reward {
-                        if *index == pivot_arena_index {
-                            no_reward = !inner.arena[pivot_arena_index]
-                                .data
-                                .state_valid.expect("computed in wait_and_compute_state_valid_locked");
-                        } else {
-                            no_reward = !inner
-                                .compute_vote_valid_for_pivot_block(
-                                    *index,
-                                    pivot_arena_index,
-                                );
-                        }
-                    }
-                    // If a block is partial_invalid, it won't have reward and
-                    // anticone_difficulty will not be used, so it's okay to set
-                    // it to 0.
-                    let mut anticone_difficulty: U512 = 0.into();
-                    if !no_reward {
-                        let block_consensus_node_anticone_opt =
-                            inner.anticone_cache.get(*index);
-                        let block_consensus_node_anticone = if let Some(r) = block_consensus_node_anticone_opt {
-                            r.clone()
-                        } else {
-                            ConsensusNewBlockHandler::compute_anticone_hashset_bruteforce(inner, *index)
-                        };
-
-                        for idx in block_consensus_node_anticone {
-                            if inner.is_same_era(idx, pivot_arena_index) && !anticone_cutoff_epoch_anticone_set.contains(&idx) {
-                                anticone_difficulty +=
-                                    U512::from(U256::from(inner.block_weight(
-                                        idx
-                                    )));
-                            }
-                        }
+            }
+            // If a block is partial_invalid, it won't have reward and
+            // anticone_difficulty will not be used, so it's okay to set
+            // it to 0.
+            let mut anticone_difficulty: U512 = 0.into();
+            if !no_reward {
+                let block_consensus_node_anticone_opt =
+                    inner.anticone_cache.get(*index);
+                let block_consensus_node_anticone = if let Some(r) =
+                    block_consensus_node_anticone_opt
+                {
+                    r.clone()
+                } else {
+                    ConsensusNewBlockHandler::compute_anticone_hashset_bruteforce(inner, *index)
+                };
 
-                        // TODO: check the clear definition of anticone penalty,
-                        // normally and around the time of difficulty
-                        // adjustment.
-                        // LINT.IfChange(ANTICONE_PENALTY_1)
-                        if anticone_difficulty / U512::from(epoch_difficulty)
-                            >= U512::from(self.handler.machine.params().anticone_penalty_ratio)
-                        {
-                            no_reward = true;
-                        }
-                        // LINT.ThenChange(consensus/consensus_executor.
-                        // rs#ANTICONE_PENALTY_2)
+                for idx in block_consensus_node_anticone {
+                    if inner.is_same_era(idx, pivot_arena_index)
+                        && !anticone_cutoff_epoch_anticone_set.contains(&idx)
+                    {
+                        anticone_difficulty +=
+                            U512::from(U256::from(inner.block_weight(idx)));
                     }
-                    epoch_block_no_reward.push(no_reward);
-                    epoch_block_anticone_difficulties.push(anticone_difficulty);
                 }
-                RewardExecutionInfo {
-                    past_block_count: inner.arena[pivot_arena_index].past_num_blocks,
-                    epoch_blocks,
-                    epoch_block_no_reward,
-                    epoch_block_anticone_difficulties,
+
+                // TODO: check the clear definition of anticone penalty,
+                // normally and around the time of difficulty
+                // adjustment.
+                // LINT.IfChange(ANTICONE_PENALTY_1)
+                if anticone_difficulty / U512::from(epoch_difficulty)
+                    >= U512::from(
+                        self.handler.machine.params().anticone_penalty_ratio,
+                    )
+                {
+                    no_reward = true;
                 }
-            },
-        )
+                // LINT.ThenChange(consensus/consensus_executor.
+                // rs#ANTICONE_PENALTY_2)
+            }
+            epoch_block_no_reward.push(no_reward);
+            epoch_block_anticone_difficulties.push(anticone_difficulty);
+        }
+        Some(RewardExecutionInfo {
+            past_block_count: inner.arena[pivot_arena_index].past_num_blocks,
+            epoch_blocks,
+            epoch_block_no_reward,
+            epoch_block_anticone_difficulties,
+        })
     }
 
     pub fn get_reward_execution_info(
@@ -624,6 +701,56 @@ impl ConsensusExecutor {
         self.handler.call_virtual(tx, epoch_id, epoch_size)
     }
 
+    pub fn call_virtual_many(
+        &self, txs: &[SignedTransaction], epoch_id: &H256, epoch_size: usize,
+        state_override: &StateOverride,
+    ) -> RpcResult<Vec<ExecutionOutcome>>
+    {
+        self.handler.call_virtual_many(
+            txs,
+            epoch_id,
+            epoch_size,
+            state_override,
+        )
+    }
+
+    pub fn trace_transaction_steps(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+        tx_index: &TransactionIndex,
+    ) -> RpcResult<(ExecutionOutcome, Vec<StructLog>)>
+    {
+        self.handler.trace_transaction_steps(
+            pivot_hash,
+            epoch_block_hashes,
+            tx_index,
+        )
+    }
+
+    pub fn trace_transaction_state_diff(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+        tx_index: &TransactionIndex,
+    ) -> RpcResult<(ExecutionOutcome, StateDiff)>
+    {
+        self.handler.trace_transaction_state_diff(
+            pivot_hash,
+            epoch_block_hashes,
+            tx_index,
+        )
+    }
+
+    pub fn compute_epoch_traces(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+    ) -> RpcResult<Vec<BlockExecTraces>> {
+        self.handler
+            .compute_epoch_traces(pivot_hash, epoch_block_hashes)
+    }
+
+    pub fn execute_epoch_in_memory(
+        &self, task: &EpochExecutionTask, state: &mut State,
+    ) -> RpcResult<()> {
+        self.handler.execute_epoch_in_memory(task, state)
+    }
+
     pub fn stop(&self) {
         // `stopped` is used to allow the execution thread to stopped even the
         // queue is not empty and `ExecutionTask::Stop` has not been
@@ -1689,6 +1816,23 @@ impl ConsensusExecutionHandler {
     pub fn call_virtual(
         &self, tx: &SignedTransaction, epoch_id: &H256, epoch_size: usize,
     ) -> RpcResult<ExecutionOutcome> {
+        let mut outcomes = self.call_virtual_many(
+            std::slice::from_ref(tx),
+            epoch_id,
+            epoch_size,
+            &StateOverride::new(),
+        )?;
+        Ok(outcomes.pop().expect("one outcome for each call"))
+    }
+
+    /// Executes `txs` one after another on the state of `epoch_id` with
+    /// `state_override` applied. Each transaction sees the changes made by
+    /// the previous ones. The state is never committed.
+    pub fn call_virtual_many(
+        &self, txs: &[SignedTransaction], epoch_id: &H256, epoch_size: usize,
+        state_override: &StateOverride,
+    ) -> RpcResult<Vec<ExecutionOutcome>>
+    {
         let best_block_header = self.data_man.block_header_by_hash(epoch_id);
         if best_block_header.is_none() {
             bail!("invalid epoch id");
@@ -1702,16 +1846,18 @@ impl ConsensusExecutionHandler {
         let spec = self.machine.spec(start_block_number);
         let transitions = &self.machine.params().transition_heights;
 
-        invalid_params_check(
-            "tx",
-            self.verification_config.verify_transaction_common(
-                tx,
-                tx.chain_id,
-                block_height,
-                transitions,
-                VerifyTxMode::Local(VerifyTxLocalMode::Full, &spec),
-            ),
-        )?;
+        for tx in txs {
+            invalid_params_check(
+                "tx",
+                self.verification_config.verify_transaction_common(
+                    tx,
+                    tx.chain_id,
+                    block_height,
+                    transitions,
+                    VerifyTxMode::Local(VerifyTxLocalMode::Full, &spec),
+                ),
+            )?;
+        }
 
         // Keep the lock until we get the desired State, otherwise the State may
         // expire.
@@ -1725,7 +1871,7 @@ impl ConsensusExecutionHandler {
         let state_index = self.data_man.get_state_readonly_index(epoch_id);
         trace!("best_block_header: {:?}", best_block_header);
         let time_stamp = best_block_header.timestamp();
-        let mut state = State::new(StateDb::new(
+        let mut state_db = StateDb::new(
             self.data_man
                 .storage_manager
                 .get_state_no_commit(
@@ -1733,8 +1879,14 @@ impl ConsensusExecutionHandler {
                     /* try_open = */ true,
                 )?
                 .ok_or("state deleted")?,
-        ))?;
+        );
         drop(state_availability_boundary);
+        apply_state_override(
+            &mut state_db,
+            state_override,
+            &spec.account_start_nonce,
+        )?;
+        let mut state = State::new(state_db)?;
 
         let author = {
             let mut address = H160::random();
@@ -1742,28 +1894,298 @@ impl ConsensusExecutionHandler {
             address
         };
 
-        let env = Env {
-            number: start_block_number,
-            author,
-            timestamp: time_stamp,
-            difficulty: Default::default(),
-            accumulated_gas_used: U256::zero(),
-            last_hash: epoch_id.clone(),
-            gas_limit: tx.gas.clone(),
-            epoch_height: block_height,
-            transaction_epoch_bound: self
-                .verification_config
-                .transaction_epoch_bound,
+        let mut outcomes = Vec::with_capacity(txs.len());
+        let mut accumulated_gas_used = U256::zero();
+        for tx in txs {
+            let env = Env {
+                number: start_block_number,
+                author,
+                timestamp: time_stamp,
+                difficulty: Default::default(),
+                accumulated_gas_used,
+                last_hash: epoch_id.clone(),
+                gas_limit: tx.gas.clone(),
+                epoch_height: block_height,
+                transaction_epoch_bound: self
+                    .verification_config
+                    .transaction_epoch_bound,
+            };
+            let spec = self.machine.spec(env.number);
+            let mut ex =
+                Executive::new(&mut state, &env, self.machine.as_ref(), &spec);
+            let r = ex.transact_virtual(tx);
+            trace!("Execution result {:?}", r);
+            let r = r?;
+            if let ExecutionOutcome::Finished(executed)
+            | ExecutionOutcome::ExecutionErrorBumpNonce(_, executed) = &r
+            {
+                accumulated_gas_used += executed.gas_used;
+            }
+            outcomes.push(r);
+        }
+        Ok(outcomes)
+    }
+
+    /// Re-executes the epoch of `pivot_hash` up to the transaction at
+    /// `tx_index`, which is executed with a `StructLogger`.
+    pub fn trace_transaction_steps(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+        tx_index: &TransactionIndex,
+    ) -> RpcResult<(ExecutionOutcome, Vec<StructLog>)>
+    {
+        let mut result = None;
+        self.replay_epoch_transactions(
+            pivot_hash,
+            epoch_block_hashes,
+            |block, idx, mut executive| {
+                let transaction = &block.transactions[idx];
+                if block.hash() != tx_index.block_hash || idx != tx_index.index
+                {
+                    let options = TransactOptions::with_no_tracing();
+                    return Ok(Some(executive.transact(transaction, options)?));
+                }
+
+                let mut logger = StructLogger::default();
+                let options = TransactOptions::new(&mut logger);
+                let r = executive.transact(transaction, options)?;
+                result = Some((r, logger.into_logs()));
+                Ok(None)
+            },
+        )?;
+
+        match result {
+            Some(result) => Ok(result),
+            None => bail!("transaction not found in epoch {:?}", pivot_hash),
+        }
+    }
+
+    /// Re-executes the epoch of `pivot_hash` up to the transaction at
+    /// `tx_index`, which is executed with tracing and returns the changes it
+    /// makes to the state.
+    pub fn trace_transaction_state_diff(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+        tx_index: &TransactionIndex,
+    ) -> RpcResult<(ExecutionOutcome, StateDiff)>
+    {
+        let mut result = None;
+        self.replay_epoch_transactions(
+            pivot_hash,
+            epoch_block_hashes,
+            |block, idx, mut executive| {
+                let transaction = &block.transactions[idx];
+                if block.hash() != tx_index.block_hash || idx != tx_index.index
+                {
+                    let options = TransactOptions::with_no_tracing();
+                    return Ok(Some(executive.transact(transaction, options)?));
+                }
+
+                let options = TransactOptions::with_tracing();
+                result = Some(
+                    executive.transact_with_state_diff(transaction, options)?,
+                );
+                Ok(None)
+            },
+        )?;
+
+        match result {
+            Some(result) => Ok(result),
+            None => bail!("transaction not found in epoch {:?}", pivot_hash),
+        }
+    }
+
+    /// Re-executes the epoch of `pivot_hash` with tracing and returns the
+    /// traces of each block in the epoch, in the same order as
+    /// `epoch_block_hashes`.
+    pub fn compute_epoch_traces(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+    ) -> RpcResult<Vec<BlockExecTraces>> {
+        let mut epoch_traces: Vec<Vec<TransactionExecTraces>> =
+            vec![Vec::new(); epoch_block_hashes.len()];
+        self.replay_epoch_transactions(
+            pivot_hash,
+            epoch_block_hashes,
+            |block, idx, mut executive| {
+                let options = TransactOptions::with_tracing();
+                let r =
+                    executive.transact(&block.transactions[idx], options)?;
+                let traces = match &r {
+                    ExecutionOutcome::ExecutionErrorBumpNonce(_, executed)
+                    | ExecutionOutcome::Finished(executed) => {
+                        executed.trace.clone()
+                    }
+                    _ => Vec::<ExecTrace>::new(),
+                };
+                let block_index = epoch_block_hashes
+                    .iter()
+                    .position(|h| *h == block.hash())
+                    .expect("block is in epoch");
+                epoch_traces[block_index].push(traces.into());
+                Ok(Some(r))
+            },
+        )?;
+
+        let epoch_traces: Vec<BlockExecTraces> =
+            epoch_traces.into_iter().map(Into::into).collect();
+        for (hash, traces) in epoch_block_hashes.iter().zip(&epoch_traces) {
+            self.data_man.insert_block_traces(
+                *hash,
+                traces.clone(),
+                *pivot_hash,
+                self.config.persist_replayed_trace,
+            );
+        }
+        Ok(epoch_traces)
+    }
+
+    /// Executes the epoch of `task` on `state` like `compute_epoch`, but
+    /// keeps the changes in the state db of `state` instead of committing
+    /// them, so that the following epochs can be executed on top of it. The
+    /// receipts and rewards are not persisted.
+    pub fn execute_epoch_in_memory(
+        &self, task: &EpochExecutionTask, state: &mut State,
+    ) -> RpcResult<()> {
+        let epoch_blocks = self
+            .data_man
+            .blocks_by_hash_list(
+                &task.epoch_block_hashes,
+                false, /* update_cache */
+            )
+            .ok_or("blocks in epoch missing")?;
+        self.process_epoch_transactions(
+            task.epoch_hash,
+            state,
+            &epoch_blocks,
+            task.start_block_number,
+            false, /* on_local_pivot */
+        )?;
+        if let Some(reward_execution_info) = &task.reward_info {
+            self.process_rewards_and_fees(
+                state,
+                reward_execution_info,
+                &task.epoch_hash,
+                false, /* on_local_pivot */
+                None,  /* debug_record */
+                self.machine
+                    .spec(task.start_block_number)
+                    .account_start_nonce,
+            );
+        }
+        state.commit_cache_to_db(/* debug_record = */ None)?;
+        Ok(())
+    }
+
+    /// Re-executes the transactions of the epoch of `pivot_hash` on top of
+    /// its parent state. Each transaction is executed by `transact`, which
+    /// returns the outcome, or `None` to stop the replay. The state is never
+    /// committed.
+    fn replay_epoch_transactions<F>(
+        &self, pivot_hash: &H256, epoch_block_hashes: &Vec<H256>,
+        mut transact: F,
+    ) -> RpcResult<()>
+    where
+        F: FnMut(
+            &Block,
+            usize,
+            Executive<'_>,
+        ) -> RpcResult<Option<ExecutionOutcome>>,
+    {
+        let start_block_number = match self.data_man.get_epoch_execution_context(pivot_hash) {
+            Some(v) => v.start_block_number,
+            None => bail!("cannot obtain the execution context. Database is potentially corrupted!"),
         };
-        let spec = self.machine.spec(env.number);
-        let mut ex =
-            Executive::new(&mut state, &env, self.machine.as_ref(), &spec);
-        let r = ex.transact_virtual(tx);
-        trace!("Execution result {:?}", r);
-        Ok(r?)
+        let epoch_blocks = self
+            .data_man
+            .blocks_by_hash_list(
+                epoch_block_hashes,
+                false, /* update_cache */
+            )
+            .ok_or("blocks in epoch missing")?;
+        let pivot_block = epoch_blocks.last().expect("Not empty");
+        if pivot_block.block_header.height() == 0 {
+            bail!("cannot re-execute the genesis epoch");
+        }
+        let parent_hash = pivot_block.block_header.parent_hash();
+        let parent_height = pivot_block.block_header.height() - 1;
+
+        // Keep the lock until we get the desired State, otherwise the State may
+        // expire.
+        let state_availability_boundary =
+            self.data_man.state_availability_boundary.read();
+        if !state_availability_boundary
+            .check_availability(parent_height, parent_hash)
+        {
+            bail!("state is not ready");
+        }
+        let mut state = State::new(StateDb::new(
+            self.data_man
+                .storage_manager
+                .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                    parent_hash,
+                    &self
+                        .data_man
+                        .get_epoch_execution_commitment_with_db(parent_hash)
+                        .ok_or("state deleted")?
+                        .state_root_with_aux_info,
+                    parent_height,
+                    self.data_man.get_snapshot_epoch_count(),
+                ))?
+                .ok_or("state deleted")?,
+        ))?;
+        drop(state_availability_boundary);
+
+        let mut block_number = start_block_number;
+        let mut last_block_hash = parent_hash.clone();
+        for block in epoch_blocks.iter() {
+            let mut env = Env {
+                number: block_number,
+                author: block.block_header.author().clone(),
+                timestamp: pivot_block.block_header.timestamp(),
+                difficulty: block.block_header.difficulty().clone(),
+                accumulated_gas_used: U256::zero(),
+                last_hash: last_block_hash,
+                gas_limit: U256::from(block.block_header.gas_limit()),
+                epoch_height: pivot_block.block_header.height(),
+                transaction_epoch_bound: self
+                    .verification_config
+                    .transaction_epoch_bound,
+            };
+            let spec = self.machine.spec(env.number);
+            state.bump_block_number_accumulate_interest();
+            initialize_internal_contract_accounts(
+                &mut state,
+                self.machine.internal_contracts().initialized_at(env.number),
+                spec.contract_start_nonce,
+            );
+            block_number += 1;
+            last_block_hash = block.hash();
+
+            for idx in 0..block.transactions.len() {
+                let executive = Executive::new(
+                    &mut state,
+                    &env,
+                    self.machine.as_ref(),
+                    &spec,
+                );
+                match transact(block, idx, executive)? {
+                    None => return Ok(()),
+                    Some(ExecutionOutcome::ExecutionErrorBumpNonce(
+                        _,
+                        executed,
+                    ))
+                    | Some(ExecutionOutcome::Finished(executed)) => {
+                        env.accumulated_gas_used += executed.gas_used;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
 pub struct ConsensusExecutionConfiguration {
     pub executive_trace: bool,
+    /// Persist the traces of epochs re-executed for trace RPCs.
+    pub persist_replayed_trace: bool,
 }
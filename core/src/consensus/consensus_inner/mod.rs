@@ -2863,6 +2863,68 @@ impl ConsensusGraphInner {
         self.arena[me].data.vote_valid
     }
 
+    /// The same as `compute_vote_valid_for_pivot_block`, but the results are
+    /// not cached in the arena, so that it can be called with the read lock.
+    /// Returns `None` if the `state_valid` it depends on is not computed yet.
+    fn compute_vote_valid_for_pivot_block_readonly(
+        &self, me: usize, pivot_arena_index: usize,
+    ) -> Option<bool> {
+        let lca = self.lca(me, pivot_arena_index);
+        let lca_height = self.arena[lca].height;
+        let mut computed = HashMap::new();
+        let vote_valid = |index: usize, computed: &HashMap<usize, bool>| {
+            if self.arena[index].data.vote_valid_lca_height == lca_height {
+                Some(self.arena[index].data.vote_valid)
+            } else {
+                computed.get(&index).cloned()
+            }
+        };
+        let mut stack = Vec::new();
+        stack.push((0, me, 0));
+        while let Some((stage, index, a)) = stack.pop() {
+            if stage == 0 {
+                if vote_valid(index, &computed).is_some() {
+                    continue;
+                }
+                let header = self
+                    .data_man
+                    .block_header_by_hash(&self.arena[index].hash)
+                    .unwrap();
+                let blame = header.blame();
+                if self.arena[index].height > lca_height + 1 + blame as u64 {
+                    let ancestor = self.ancestor_at(
+                        index,
+                        self.arena[index].height - blame as u64 - 1,
+                    );
+                    stack.push((1, index, ancestor));
+                    stack.push((0, ancestor, 0));
+                } else {
+                    let start_height =
+                        self.arena[index].height - blame as u64 - 1;
+                    let mut cur_height = lca_height;
+                    let mut cur = lca;
+                    let mut valid = true;
+                    while cur_height > start_height {
+                        if self.arena[cur].data.state_valid? {
+                            valid = false;
+                            break;
+                        }
+                        cur_height -= 1;
+                        cur = self.arena[cur].parent;
+                    }
+                    if valid && !self.arena[cur].data.state_valid? {
+                        valid = false;
+                    }
+                    computed.insert(index, valid);
+                }
+            } else {
+                let valid = vote_valid(a, &computed)?;
+                computed.insert(index, valid);
+            }
+        }
+        vote_valid(me, &computed)
+    }
+
     /// Compute the total weight in the epoch represented by the block of
     /// my_hash.
     fn total_weight_in_own_epoch(
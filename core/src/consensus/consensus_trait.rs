@@ -93,6 +93,13 @@ pub trait ConsensusGraphTrait: Send + Sync {
         &self, epoch_number: EpochNumber, rpc_param_name: &str,
     ) -> RpcResult<StateDb>;
 
+    /// Like `get_state_db_by_epoch_number`, but the state is never
+    /// reconstructed, for the queries of merkle roots and proofs which only
+    /// a kept state can answer.
+    fn get_kept_state_db_by_epoch_number(
+        &self, epoch_number: EpochNumber, rpc_param_name: &str,
+    ) -> RpcResult<StateDb>;
+
     fn get_blocks_needing_bodies(&self) -> HashSet<H256>;
 
     fn catch_up_completed(&self, peer_median_epoch: u64) -> bool;
@@ -0,0 +1,170 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_parameters::rpc::{
+    GAS_PRICE_BLOCK_SAMPLE_SIZE, GAS_PRICE_DEFAULT_PERCENTILE,
+    GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
+};
+use cfx_types::U256;
+
+/// Configuration of the gas price oracle behind `cfx_gasPrice`.
+#[derive(Clone, Debug)]
+pub struct GasPriceOracleConfig {
+    /// The number of most recent blocks to sample gas prices from.
+    pub sample_blocks: usize,
+    /// The maximum number of transactions to sample.
+    pub sample_transactions: usize,
+    /// The percentile of the sampled prices to suggest when the transaction
+    /// pool holds about one block of unpacked transactions.
+    pub percentile: u8,
+    /// Transactions with a lower gas price are not sampled.
+    pub ignore_price: U256,
+}
+
+impl Default for GasPriceOracleConfig {
+    fn default() -> Self {
+        GasPriceOracleConfig {
+            sample_blocks: GAS_PRICE_BLOCK_SAMPLE_SIZE,
+            sample_transactions: GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
+            percentile: GAS_PRICE_DEFAULT_PERCENTILE,
+            ignore_price: U256::zero(),
+        }
+    }
+}
+
+/// Gas used ratios and gas price percentiles of a range of epochs.
+#[derive(Debug, Default)]
+pub struct FeeHistory {
+    pub oldest_epoch: u64,
+    /// The gas used by each epoch divided by the sum of its blocks' gas
+    /// limits.
+    pub gas_used_ratio: Vec<f64>,
+    /// The requested percentiles of the gas prices in each epoch, weighted
+    /// by gas used.
+    pub reward: Vec<Vec<U256>>,
+}
+
+/// Adjusts the configured `percentile` by the backlog of the transaction
+/// pool, measured in blocks of `average_block_tx_count` transactions. With
+/// less than one block of backlog the next block can pack everything, so the
+/// percentile goes down towards 0; with more it goes up towards 100.
+pub fn backlog_weighted_percentile(
+    percentile: u8, backlog: usize, average_block_tx_count: f64,
+) -> f64 {
+    let percentile = percentile.min(100) as f64;
+    if average_block_tx_count <= 0.0 {
+        return percentile;
+    }
+    let backlog_blocks = backlog as f64 / average_block_tx_count;
+    if backlog_blocks < 1.0 {
+        percentile * backlog_blocks
+    } else {
+        percentile + (100.0 - percentile) * (1.0 - 1.0 / backlog_blocks)
+    }
+}
+
+/// Returns the `percentile`-th value of the sorted `prices`, which must not
+/// be empty.
+pub fn price_at_percentile(sorted_prices: &[U256], percentile: f64) -> U256 {
+    let last = sorted_prices.len() - 1;
+    let index = (last as f64 * percentile / 100.0).round() as usize;
+    sorted_prices[index.min(last)]
+}
+
+/// Returns `gas_used / gas_limit`, or 0 if `gas_limit` is zero. Both are
+/// converted as a whole, so values beyond `u64` are not truncated.
+pub fn gas_used_ratio(gas_used: U256, gas_limit: U256) -> f64 {
+    if gas_limit.is_zero() {
+        return 0.0;
+    }
+    u256_to_f64(&gas_used) / u256_to_f64(&gas_limit)
+}
+
+fn u256_to_f64(value: &U256) -> f64 {
+    value
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |result, limb| result * 2f64.powi(64) + *limb as f64)
+}
+
+/// Returns the gas price at each of the ascending `percentiles` of the total
+/// gas used by `prices_and_gas`.
+pub fn gas_weighted_percentiles(
+    mut prices_and_gas: Vec<(U256, U256)>, percentiles: &[f64],
+) -> Vec<U256> {
+    if prices_and_gas.is_empty() {
+        return vec![U256::zero(); percentiles.len()];
+    }
+    prices_and_gas.sort();
+    let total_gas = prices_and_gas
+        .iter()
+        .fold(U256::zero(), |total, (_, gas)| total + *gas);
+
+    let mut result = Vec::with_capacity(percentiles.len());
+    let mut index = 0;
+    let mut accumulated_gas = prices_and_gas[0].1;
+    for percentile in percentiles {
+        // Percentiles are scaled by 100 to keep two decimals in integers.
+        let threshold =
+            total_gas * U256::from((percentile * 100.0) as u64) / 10000;
+        while accumulated_gas < threshold && index + 1 < prices_and_gas.len()
+        {
+            index += 1;
+            accumulated_gas += prices_and_gas[index].1;
+        }
+        result.push(prices_and_gas[index].0);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backlog_weighted_percentile() {
+        assert_eq!(backlog_weighted_percentile(50, 0, 100.0), 0.0);
+        assert_eq!(backlog_weighted_percentile(50, 50, 100.0), 25.0);
+        assert_eq!(backlog_weighted_percentile(50, 100, 100.0), 50.0);
+        assert_eq!(backlog_weighted_percentile(50, 200, 100.0), 75.0);
+        assert_eq!(backlog_weighted_percentile(50, 10, 0.0), 50.0);
+    }
+
+    #[test]
+    fn test_price_at_percentile() {
+        let prices: Vec<U256> = (1u64..=5).map(U256::from).collect();
+        assert_eq!(price_at_percentile(&prices, 0.0), U256::from(1));
+        assert_eq!(price_at_percentile(&prices, 50.0), U256::from(3));
+        assert_eq!(price_at_percentile(&prices, 100.0), U256::from(5));
+    }
+
+    #[test]
+    fn test_gas_used_ratio() {
+        assert_eq!(gas_used_ratio(U256::from(1), U256::zero()), 0.0);
+        assert_eq!(gas_used_ratio(U256::from(1), U256::from(4)), 0.25);
+        let gas_limit = U256::from(u64::MAX) * 4;
+        assert_eq!(gas_used_ratio(gas_limit / 2, gas_limit), 0.5);
+    }
+
+    #[test]
+    fn test_gas_weighted_percentiles() {
+        let prices_and_gas = vec![
+            (U256::from(3), U256::from(10)),
+            (U256::from(1), U256::from(80)),
+            (U256::from(2), U256::from(10)),
+        ];
+        assert_eq!(
+            gas_weighted_percentiles(prices_and_gas, &[0.0, 50.0, 85.0, 100.0]),
+            vec![1u64, 1, 2, 3]
+                .into_iter()
+                .map(U256::from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            gas_weighted_percentiles(vec![], &[10.0, 90.0]),
+            vec![U256::zero(); 2]
+        );
+    }
+}
@@ -0,0 +1,436 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Reconstruction of the states which are no longer kept by a full node. The
+//! state of an old epoch is rebuilt in memory from the nearest snapshot before
+//! it, by re-executing the epochs in between without committing them.
+
+/// Configuration of the reconstruction of the states which are no longer
+/// kept.
+#[derive(Clone, Debug)]
+pub struct HistoricalStateConfig {
+    /// Whether to reconstruct the state of an epoch which is no longer kept.
+    pub enabled: bool,
+    /// The maximum number of epochs to re-execute to reconstruct a state.
+    pub max_epochs: u64,
+    /// The number of reconstructed states to cache.
+    pub cache_size: usize,
+    /// A reconstructed state with more entries than this is not cached.
+    pub max_cached_entries: usize,
+    /// The maximum number of states reconstructed at the same time. Queries
+    /// beyond it are rejected.
+    pub max_concurrent: usize,
+}
+
+impl Default for HistoricalStateConfig {
+    fn default() -> Self {
+        HistoricalStateConfig {
+            enabled: false,
+            max_epochs: 2000,
+            cache_size: 16,
+            max_cached_entries: 100_000,
+            max_concurrent: 2,
+        }
+    }
+}
+
+/// A state reconstructed in memory. It consists of the snapshot it's
+/// reconstructed from, and the entries read or changed by the re-executed
+/// epochs.
+pub struct ReconstructedState {
+    snapshot_epoch_id: EpochId,
+    entries: Vec<(Vec<u8>, Option<Arc<[u8]>>)>,
+}
+
+/// The number of epochs whose execution tasks are built while holding the
+/// consensus inner lock once.
+const RECONSTRUCTION_TASK_BATCH_SIZE: u64 = 50;
+
+/// The reconstructed states and the threads reconstructing them.
+pub struct HistoricalStates {
+    cache: Mutex<LruCache<H256, Arc<ReconstructedState>>>,
+    /// The epochs are re-executed in this pool, so that the rpc threads only
+    /// wait for them. It's `None` if the reconstruction is disabled.
+    thread_pool: Option<ThreadPool>,
+    /// The number of reconstructions in progress.
+    in_progress: AtomicUsize,
+}
+
+impl HistoricalStates {
+    pub fn new(config: &HistoricalStateConfig) -> Self {
+        let thread_pool = if config.enabled {
+            Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(cmp::max(config.max_concurrent, 1))
+                    .thread_name(|index| {
+                        format!("Historical State Reconstruction {}", index)
+                    })
+                    .build()
+                    .expect("Failed to create the reconstruction thread pool"),
+            )
+        } else {
+            None
+        };
+        HistoricalStates {
+            cache: Mutex::new(LruCache::with_capacity(config.cache_size)),
+            thread_pool,
+            in_progress: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Counts a reconstruction in progress until it's dropped.
+struct InProgressGuard<'a> {
+    in_progress: &'a AtomicUsize,
+    /// The number of reconstructions in progress, including this one.
+    count: usize,
+}
+
+impl<'a> InProgressGuard<'a> {
+    fn new(in_progress: &'a AtomicUsize) -> Self {
+        let count = in_progress.fetch_add(1, Ordering::SeqCst) + 1;
+        InProgressGuard { in_progress, count }
+    }
+}
+
+impl<'a> Drop for InProgressGuard<'a> {
+    fn drop(&mut self) { self.in_progress.fetch_sub(1, Ordering::SeqCst); }
+}
+
+impl ConsensusGraph {
+    /// Get the state db of the epoch. When the state is no longer kept, it's
+    /// reconstructed if it's enabled in `HistoricalStateConfig`.
+    pub(super) fn get_state_db_or_reconstruct(
+        &self, height: u64, hash: &H256,
+    ) -> RpcResult<StateDb> {
+        let lower_bound =
+            self.data_man.state_availability_boundary.read().lower_bound;
+        if !self.config.historical_state.enabled || height >= lower_bound {
+            return self.get_state_db_by_height_and_hash(height, hash);
+        }
+
+        let maybe_reconstructed =
+            self.historical_states.cache.lock().get(hash).cloned();
+        if let Some(reconstructed) = maybe_reconstructed {
+            if let Some(state_db) =
+                self.load_reconstructed_state(&reconstructed)?
+            {
+                return Ok(state_db);
+            }
+        }
+        self.reconstruct_state_db(height, hash)
+    }
+
+    /// Reconstruct the state db of the pivot chain epoch `hash` at `height`
+    /// from the nearest snapshot before it, even if its state is still kept.
+    /// The epochs are re-executed in the reconstruction thread pool.
+    pub fn reconstruct_state_db(
+        &self, height: u64, hash: &H256,
+    ) -> RpcResult<StateDb> {
+        let thread_pool = match &self.historical_states.thread_pool {
+            Some(thread_pool) => thread_pool,
+            None => bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 state reconstruction is disabled",
+                height, hash
+            )),
+        };
+        let in_progress =
+            InProgressGuard::new(&self.historical_states.in_progress);
+        let max_concurrent = self.config.historical_state.max_concurrent;
+        if in_progress.count > max_concurrent {
+            bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 {} states are being reconstructed, try again later",
+                height, hash, max_concurrent
+            ));
+        }
+        let reconstructed =
+            thread_pool.install(|| self.reconstruct_state(height, hash))?;
+        drop(in_progress);
+
+        match self.load_reconstructed_state(&reconstructed)? {
+            Some(state_db) => Ok(state_db),
+            None => bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 snapshot {:?} is removed",
+                height, hash, reconstructed.snapshot_epoch_id
+            )),
+        }
+    }
+
+    /// Re-execute the epochs from the reconstruction base to the epoch at
+    /// `height`, and cache the result if it's not too large.
+    fn reconstruct_state(
+        &self, height: u64, hash: &H256,
+    ) -> RpcResult<Arc<ReconstructedState>> {
+        let (base_height, snapshot_epoch_id, state_db) =
+            self.get_reconstruction_base(height, hash)?;
+        let max_epochs = self.config.historical_state.max_epochs;
+        if height - base_height > max_epochs {
+            bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 reconstructing it from epoch {} exceeds the limit of {} \
+                 epochs",
+                height, hash, base_height, max_epochs
+            ));
+        }
+        let mut state = State::new(state_db)?;
+        for batch_start in (base_height + 1..=height)
+            .step_by(RECONSTRUCTION_TASK_BATCH_SIZE as usize)
+        {
+            let batch_end = cmp::min(
+                batch_start + RECONSTRUCTION_TASK_BATCH_SIZE - 1,
+                height,
+            );
+            let tasks = self.get_reconstruction_tasks(
+                batch_start,
+                batch_end,
+                height,
+                hash,
+            )?;
+            for task in &tasks {
+                self.executor.execute_epoch_in_memory(task, &mut state)?;
+            }
+        }
+        debug!(
+            "Reconstructed state for epoch (number={:?} hash={:?}) from \
+             epoch {} on snapshot {:?}",
+            height, hash, base_height, snapshot_epoch_id
+        );
+
+        let reconstructed = Arc::new(ReconstructedState {
+            snapshot_epoch_id,
+            entries: state.accessed_storage_entries(),
+        });
+        let config = &self.config.historical_state;
+        if config.cache_size > 0
+            && reconstructed.entries.len() <= config.max_cached_entries
+        {
+            self.historical_states
+                .cache
+                .lock()
+                .insert(*hash, reconstructed.clone());
+        }
+        Ok(reconstructed)
+    }
+
+    /// Find the state to reconstruct the epoch at `height` from, which is the
+    /// latest available snapshot on the pivot chain before the epoch, or a
+    /// cached state after the snapshot. Returns its height, the snapshot and
+    /// the state db.
+    fn get_reconstruction_base(
+        &self, height: u64, hash: &H256,
+    ) -> RpcResult<(u64, EpochId, StateDb)> {
+        let snapshot_infos = self
+            .data_man
+            .storage_manager
+            .get_storage_manager()
+            .get_snapshot_infos();
+        let mut base = None;
+        for (snapshot_epoch_id, snapshot_info) in snapshot_infos.iter().rev() {
+            if snapshot_info.height > height
+                || self
+                    .inner
+                    .read()
+                    .get_pivot_hash_from_epoch_number(snapshot_info.height)
+                    .ok()
+                    != Some(*snapshot_epoch_id)
+            {
+                continue;
+            }
+            if let Some(state) = self.open_snapshot_state(snapshot_epoch_id)? {
+                base = Some((
+                    snapshot_info.height,
+                    *snapshot_epoch_id,
+                    StateDb::new(state),
+                ));
+                break;
+            }
+        }
+        let (snapshot_height, snapshot_epoch_id, state_db) = match base {
+            Some(base) => base,
+            None => bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 no snapshot before it is available",
+                height, hash
+            )),
+        };
+
+        for base_height in (snapshot_height + 1..height).rev() {
+            let base_hash = match self
+                .inner
+                .read()
+                .get_pivot_hash_from_epoch_number(base_height)
+            {
+                Ok(base_hash) => base_hash,
+                Err(_) => continue,
+            };
+            let maybe_reconstructed =
+                self.historical_states.cache.lock().get(&base_hash).cloned();
+            if let Some(reconstructed) = maybe_reconstructed {
+                if let Some(state_db) =
+                    self.load_reconstructed_state(&reconstructed)?
+                {
+                    return Ok((
+                        base_height,
+                        reconstructed.snapshot_epoch_id,
+                        state_db,
+                    ));
+                }
+            }
+        }
+        Ok((snapshot_height, snapshot_epoch_id, state_db))
+    }
+
+    /// The execution tasks of the pivot chain epochs from `start_height` to
+    /// `end_height`, which must be before the epoch `hash` at
+    /// `target_height`. The inner lock is only held for these epochs, and it's
+    /// checked again that the epoch `hash` is still on the pivot chain.
+    ///
+    /// The tasks are built with the read lock. The write lock is only taken
+    /// if the validity of the blocks which the rewards depend on is not
+    /// computed yet.
+    fn get_reconstruction_tasks(
+        &self, start_height: u64, end_height: u64, target_height: u64,
+        hash: &H256,
+    ) -> RpcResult<Vec<EpochExecutionTask>>
+    {
+        {
+            let inner = &*self.inner.read();
+            self.check_reconstruction_epochs(
+                inner,
+                start_height,
+                target_height,
+                hash,
+            )?;
+            let tasks = (start_height..=end_height)
+                .map(|height| {
+                    let epoch_arena_index =
+                        inner.get_pivot_block_arena_index(height);
+                    let reward_execution_info =
+                        self.executor.get_reward_execution_info_readonly(
+                            inner,
+                            epoch_arena_index,
+                        )?;
+                    Ok(EpochExecutionTask::new(
+                        epoch_arena_index,
+                        inner,
+                        reward_execution_info,
+                        false, /* on_local_pivot */
+                        false, /* force_recompute */
+                    ))
+                })
+                .collect::<Result<Vec<_>, String>>();
+            match tasks {
+                Ok(tasks) => return Ok(tasks),
+                Err(e) => debug!("Build reconstruction tasks: {}", e),
+            }
+        }
+
+        let inner = &mut *self.inner.write();
+        self.check_reconstruction_epochs(
+            inner,
+            start_height,
+            target_height,
+            hash,
+        )?;
+        let mut tasks = Vec::new();
+        for height in start_height..=end_height {
+            let epoch_arena_index = inner.get_pivot_block_arena_index(height);
+            let reward_execution_info = self
+                .executor
+                .get_reward_execution_info(inner, epoch_arena_index);
+            tasks.push(EpochExecutionTask::new(
+                epoch_arena_index,
+                inner,
+                reward_execution_info,
+                false, /* on_local_pivot */
+                false, /* force_recompute */
+            ));
+        }
+        Ok(tasks)
+    }
+
+    /// Check that the epochs from `start_height` can be re-executed, and that
+    /// the epoch `hash` at `target_height` is on the pivot chain.
+    fn check_reconstruction_epochs(
+        &self, inner: &ConsensusGraphInner, start_height: u64,
+        target_height: u64, hash: &H256,
+    ) -> RpcResult<()>
+    {
+        // The rewards of an epoch are computed from the blocks
+        // `REWARD_EPOCH_COUNT` epochs before it, which must be in the
+        // consensus graph.
+        let era_genesis_height = inner.get_cur_era_genesis_height();
+        if era_genesis_height > 0
+            && start_height < era_genesis_height + REWARD_EPOCH_COUNT
+        {
+            bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 epoch {} is too old to re-execute",
+                target_height, hash, start_height
+            ));
+        }
+        if inner.get_pivot_hash_from_epoch_number(target_height)? != *hash {
+            bail!(format!(
+                "State for epoch (number={:?} hash={:?}) does not exist: \
+                 the epoch is no longer on the pivot chain",
+                target_height, hash
+            ));
+        }
+        Ok(())
+    }
+
+    /// Load the reconstructed state on top of its snapshot. Returns None if
+    /// the snapshot is no longer available.
+    fn load_reconstructed_state(
+        &self, reconstructed: &ReconstructedState,
+    ) -> RpcResult<Option<StateDb>> {
+        let maybe_state =
+            self.open_snapshot_state(&reconstructed.snapshot_epoch_id)?;
+        Ok(maybe_state.map(|state| {
+            StateDb::new_with_loaded_entries(
+                state,
+                reconstructed.entries.iter().cloned(),
+            )
+        }))
+    }
+
+    fn open_snapshot_state(
+        &self, snapshot_epoch_id: &EpochId,
+    ) -> RpcResult<Option<StorageState>> {
+        Ok(self
+            .data_man
+            .storage_manager
+            .get_state_readonly_at_snapshot(
+                snapshot_epoch_id,
+                /* try_open = */ true,
+            )
+            .map_err(|e| format!("Error to get state, err={:?}", e))?)
+    }
+}
+
+use super::{
+    consensus_inner::{
+        consensus_executor::EpochExecutionTask, ConsensusGraphInner,
+    },
+    ConsensusGraph,
+};
+use crate::{rpc_errors::Result as RpcResult, state::State};
+use cfx_parameters::consensus_internal::REWARD_EPOCH_COUNT;
+use cfx_statedb::StateDb;
+use cfx_storage::{state_manager::StateManagerTrait, StorageState};
+use cfx_types::H256;
+use lru_time_cache::LruCache;
+use parking_lot::Mutex;
+use primitives::EpochId;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::{
+    cmp,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
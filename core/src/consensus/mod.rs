@@ -6,17 +6,28 @@ mod anticone_cache;
 pub mod consensus_inner;
 pub mod consensus_trait;
 pub mod debug_recompute;
+mod gas_price_oracle;
+mod historical_state;
 mod pastset_cache;
 
 pub use crate::consensus::{
     consensus_inner::{ConsensusGraphInner, ConsensusInnerConfig},
     consensus_trait::{ConsensusGraphTrait, SharedConsensusGraph},
+    gas_price_oracle::{FeeHistory, GasPriceOracleConfig},
+    historical_state::HistoricalStateConfig,
 };
 
-use super::consensus::consensus_inner::{
-    confirmation_meter::ConfirmationMeter,
-    consensus_executor::ConsensusExecutor,
-    consensus_new_block_handler::ConsensusNewBlockHandler,
+use super::consensus::{
+    consensus_inner::{
+        confirmation_meter::ConfirmationMeter,
+        consensus_executor::ConsensusExecutor,
+        consensus_new_block_handler::ConsensusNewBlockHandler,
+    },
+    gas_price_oracle::{
+        backlog_weighted_percentile, gas_used_ratio, gas_weighted_percentiles,
+        price_at_percentile,
+    },
+    historical_state::HistoricalStates,
 };
 use crate::{
     block_data_manager::{
@@ -28,9 +39,11 @@ use crate::{
     executive::ExecutionOutcome,
     pow::{PowComputer, ProofOfWorkConfig},
     rpc_errors::{invalid_params_check, Result as RpcResult},
-    state::State,
+    state::{State, StateOverride},
     statistics::SharedStatistics,
     trace::{
+        state_diff::StateDiff,
+        struct_logger::StructLog,
         trace::{ActionType, BlockExecTraces, LocalizedTrace},
         trace_filter::TraceFilter,
     },
@@ -39,15 +52,7 @@ use crate::{
     NodeType, Notifications,
 };
 use cfx_internal_common::ChainIdParams;
-use cfx_parameters::{
-    consensus::*,
-    consensus_internal::REWARD_EPOCH_COUNT,
-    rpc::{
-        GAS_PRICE_BLOCK_SAMPLE_SIZE, GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
-        TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
-        TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM,
-    },
-};
+use cfx_parameters::{consensus::*, consensus_internal::REWARD_EPOCH_COUNT};
 use cfx_state::state_trait::StateOpsTrait;
 use cfx_statedb::StateDb;
 use cfx_storage::state_manager::StateManagerTrait;
@@ -133,6 +138,11 @@ pub struct ConsensusConfig {
     /// The number of extra epochs that we want to keep
     /// states/receipts/transactions.
     pub sync_state_epoch_gap: Option<u64>,
+    /// The configuration of the gas price oracle.
+    pub gas_price_oracle: GasPriceOracleConfig,
+    /// The configuration of the reconstruction of the states which are no
+    /// longer kept.
+    pub historical_state: HistoricalStateConfig,
 }
 
 #[derive(Debug)]
@@ -205,6 +215,8 @@ pub struct ConsensusGraph {
     /// The epoch id of the remotely synchronized state.
     /// This is always `None` for archive nodes.
     pub synced_epoch_id: Mutex<Option<EpochId>>,
+    /// The states reconstructed for old epochs, see `HistoricalStateConfig`.
+    historical_states: HistoricalStates,
     pub config: ConsensusConfig,
 }
 
@@ -270,6 +282,7 @@ impl ConsensusGraph {
             best_info: RwLock::new(Arc::new(Default::default())),
             ready_for_mining: AtomicBool::new(false),
             synced_epoch_id: Default::default(),
+            historical_states: HistoricalStates::new(&conf.historical_state),
             config: conf,
         };
         graph.update_best_info(false /* ready_for_mining */);
@@ -382,12 +395,14 @@ impl ConsensusGraph {
         })
     }
 
-    /// Get the average gas price of the last GAS_PRICE_TRANSACTION_SAMPLE_SIZE
-    /// blocks
+    /// Suggest a gas price from the prices of the transactions in the most
+    /// recent blocks. The percentile picked is weighted by the backlog of the
+    /// transaction pool, see `GasPriceOracleConfig`.
     pub fn gas_price(&self) -> Option<U256> {
+        let oracle = &self.config.gas_price_oracle;
         let inner = self.inner.read();
         let mut last_epoch_number = inner.best_epoch_number();
-        let mut number_of_blocks_to_sample = GAS_PRICE_BLOCK_SAMPLE_SIZE;
+        let mut number_of_blocks_to_sample = oracle.sample_blocks;
         let mut tx_hashes = HashSet::new();
         let mut prices = Vec::new();
         let mut total_transaction_count_in_processed_blocks = 0;
@@ -396,7 +411,7 @@ impl ConsensusGraph {
             if number_of_blocks_to_sample == 0 || last_epoch_number == 0 {
                 break;
             }
-            if prices.len() == GAS_PRICE_TRANSACTION_SAMPLE_SIZE {
+            if prices.len() == oracle.sample_transactions {
                 break;
             }
             let mut hashes = inner
@@ -413,9 +428,12 @@ impl ConsensusGraph {
                 total_transaction_count_in_processed_blocks +=
                     block.transactions.len();
                 for tx in block.transactions.iter() {
+                    if *tx.gas_price() < oracle.ignore_price {
+                        continue;
+                    }
                     if tx_hashes.insert(tx.hash()) {
                         prices.push(tx.gas_price().clone());
-                        if prices.len() == GAS_PRICE_TRANSACTION_SAMPLE_SIZE {
+                        if prices.len() == oracle.sample_transactions {
                             break;
                         }
                     }
@@ -426,32 +444,91 @@ impl ConsensusGraph {
                 }
             }
         }
+        drop(inner);
 
         let processed_block_count =
-            GAS_PRICE_BLOCK_SAMPLE_SIZE - number_of_blocks_to_sample;
+            oracle.sample_blocks - number_of_blocks_to_sample;
         let average_transaction_count_per_block = if processed_block_count != 0
         {
-            total_transaction_count_in_processed_blocks / processed_block_count
+            total_transaction_count_in_processed_blocks as f64
+                / processed_block_count as f64
         } else {
-            0
+            0.0
         };
 
         prices.sort();
         if prices.is_empty() {
-            Some(U256::from(1))
+            // Without samples, suggest the lowest price that is sampled.
+            Some(oracle.ignore_price.max(U256::from(1)))
         } else {
-            if average_transaction_count_per_block
-                < TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW
-            {
-                Some(U256::from(1))
-            } else if average_transaction_count_per_block
-                < TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM
-            {
-                Some(prices[prices.len() / 8])
-            } else {
-                Some(prices[prices.len() / 2])
+            let (_, _, _, backlog) = self.txpool.stats();
+            let percentile = backlog_weighted_percentile(
+                oracle.percentile,
+                backlog,
+                average_transaction_count_per_block,
+            );
+            Some(price_at_percentile(&prices, percentile))
+        }
+    }
+
+    /// Returns the gas used ratio of `epoch_count` epochs up to
+    /// `newest_epoch`, and the gas prices at `percentiles` in each of them.
+    pub fn fee_history(
+        &self, newest_epoch: EpochNumber, epoch_count: u64, percentiles: &[f64],
+    ) -> RpcResult<FeeHistory> {
+        self.validate_stated_epoch(&newest_epoch)?;
+        let newest_epoch = self.get_height_from_epoch_number(newest_epoch)?;
+        let oldest_epoch = (newest_epoch + 1).saturating_sub(epoch_count);
+
+        let mut fee_history = FeeHistory {
+            oldest_epoch,
+            ..Default::default()
+        };
+        for epoch in oldest_epoch..=newest_epoch {
+            let hashes =
+                self.get_block_hashes_by_epoch(EpochNumber::Number(epoch))?;
+            let pivot_hash = hashes.last().expect("pivot block always exist");
+
+            let mut gas_limit = U256::zero();
+            let mut gas_used = U256::zero();
+            let mut tx_hashes = HashSet::new();
+            let mut prices_and_gas = Vec::new();
+            for hash in &hashes {
+                let block = self
+                    .data_man
+                    .block_by_hash(hash, false /* update_cache */)
+                    .ok_or("block not found")?;
+                gas_limit += *block.block_header.gas_limit();
+                let execution_result = self
+                    .data_man
+                    .block_execution_result_by_hash_with_epoch(
+                        hash, pivot_hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .ok_or("block execution result not found")?;
+
+                let mut prior_gas_used = U256::zero();
+                let receipts = &execution_result.block_receipts.receipts;
+                for (tx, receipt) in block.transactions.iter().zip(receipts) {
+                    let tx_gas_used =
+                        receipt.accumulated_gas_used - prior_gas_used;
+                    prior_gas_used = receipt.accumulated_gas_used;
+                    if !tx_gas_used.is_zero() && tx_hashes.insert(tx.hash()) {
+                        prices_and_gas.push((*tx.gas_price(), tx_gas_used));
+                    }
+                }
+                gas_used += prior_gas_used;
             }
+
+            fee_history
+                .gas_used_ratio
+                .push(gas_used_ratio(gas_used, gas_limit));
+            fee_history
+                .reward
+                .push(gas_weighted_percentiles(prices_and_gas, percentiles));
         }
+        Ok(fee_history)
     }
 
     fn validate_stated_epoch(
@@ -1028,27 +1105,168 @@ impl ConsensusGraph {
             .collect())
     }
 
-    pub fn call_virtual(
-        &self, tx: &SignedTransaction, epoch: EpochNumber,
-    ) -> RpcResult<ExecutionOutcome> {
+    /// Returns the pivot hash and the number of blocks of a stated epoch to
+    /// execute virtual calls on.
+    fn call_epoch(&self, epoch: EpochNumber) -> RpcResult<(H256, usize)> {
         // only allow to call against stated epoch
         self.validate_stated_epoch(&epoch)?;
-        let (epoch_id, epoch_size) = if let Ok(v) =
-            self.get_block_hashes_by_epoch(epoch)
-        {
-            (v.last().expect("pivot block always exist").clone(), v.len())
+        if let Ok(v) = self.get_block_hashes_by_epoch(epoch) {
+            Ok((v.last().expect("pivot block always exist").clone(), v.len()))
         } else {
             bail!("cannot get block hashes in the specified epoch, maybe it does not exist?");
-        };
+        }
+    }
+
+    pub fn call_virtual(
+        &self, tx: &SignedTransaction, epoch: EpochNumber,
+    ) -> RpcResult<ExecutionOutcome> {
+        let (epoch_id, epoch_size) = self.call_epoch(epoch)?;
         self.executor.call_virtual(tx, &epoch_id, epoch_size)
     }
 
+    /// Executes `txs` one after another on the state of `epoch` with
+    /// `state_override` applied, without committing any change.
+    pub fn call_virtual_many(
+        &self, txs: &[SignedTransaction], epoch: EpochNumber,
+        state_override: &StateOverride,
+    ) -> RpcResult<Vec<ExecutionOutcome>>
+    {
+        let (epoch_id, epoch_size) = self.call_epoch(epoch)?;
+        self.executor.call_virtual_many(
+            txs,
+            &epoch_id,
+            epoch_size,
+            state_override,
+        )
+    }
+
+    /// Returns the index of a packed transaction and the block hashes of the
+    /// epoch it is executed in, or `None` if it is not executed.
+    fn transaction_epoch(
+        &self, tx_hash: &H256,
+    ) -> RpcResult<Option<(TransactionIndex, Vec<H256>)>> {
+        let tx_index = match self
+            .data_man
+            .transaction_index_by_hash(tx_hash, false /* update_cache */)
+        {
+            None => return Ok(None),
+            Some(tx_index) => tx_index,
+        };
+        let epoch_number = match self
+            .get_block_epoch_number(&tx_index.block_hash)
+            .or_else(|| self.data_man.block_epoch_number(&tx_index.block_hash))
+        {
+            None => return Ok(None),
+            Some(epoch_number) => epoch_number,
+        };
+        let epoch_block_hashes =
+            self.get_block_hashes_by_epoch(EpochNumber::Number(epoch_number))?;
+        Ok(Some((tx_index, epoch_block_hashes)))
+    }
+
+    /// Re-executes a packed transaction and returns its outcome together with
+    /// the opcode-level logs, or `None` if the transaction is not executed.
+    pub fn trace_transaction_steps(
+        &self, tx_hash: &H256,
+    ) -> RpcResult<Option<(ExecutionOutcome, Vec<StructLog>)>> {
+        let (tx_index, epoch_block_hashes) =
+            match self.transaction_epoch(tx_hash)? {
+                None => return Ok(None),
+                Some(r) => r,
+            };
+        let pivot_hash =
+            epoch_block_hashes.last().expect("pivot block always exist");
+        self.executor
+            .trace_transaction_steps(pivot_hash, &epoch_block_hashes, &tx_index)
+            .map(Some)
+    }
+
+    /// Re-executes a packed transaction and returns its outcome together with
+    /// the changes it makes to the state, or `None` if the transaction is not
+    /// executed.
+    pub fn replay_transaction(
+        &self, tx_hash: &H256,
+    ) -> RpcResult<Option<(ExecutionOutcome, StateDiff)>> {
+        let (tx_index, epoch_block_hashes) =
+            match self.transaction_epoch(tx_hash)? {
+                None => return Ok(None),
+                Some(r) => r,
+            };
+        let pivot_hash =
+            epoch_block_hashes.last().expect("pivot block always exist");
+        self.executor
+            .trace_transaction_state_diff(
+                pivot_hash,
+                &epoch_block_hashes,
+                &tx_index,
+            )
+            .map(Some)
+    }
+
+    /// Returns the traces of a block with the pivot hash of its epoch. If the
+    /// traces were not recorded or have been garbage-collected, the epoch is
+    /// re-executed on top of its parent state to compute them.
+    pub fn block_traces_or_replay(
+        &self, block_hash: &H256,
+    ) -> RpcResult<Option<DataVersionTuple<H256, BlockExecTraces>>> {
+        if let Some(traces) = self.data_man.block_traces_by_hash(block_hash) {
+            return Ok(Some(traces));
+        }
+
+        let epoch_number = match self
+            .get_block_epoch_number(block_hash)
+            .or_else(|| self.data_man.block_epoch_number(block_hash))
+        {
+            None => return Ok(None),
+            Some(epoch_number) => epoch_number,
+        };
+        let epoch_block_hashes =
+            self.get_block_hashes_by_epoch(EpochNumber::Number(epoch_number))?;
+        let block_index =
+            match epoch_block_hashes.iter().position(|h| h == block_hash) {
+                None => return Ok(None),
+                Some(block_index) => block_index,
+            };
+        let pivot_hash =
+            *epoch_block_hashes.last().expect("pivot block always exist");
+
+        debug!(
+            "Re-execute epoch {:?} for traces of block {:?}",
+            pivot_hash, block_hash
+        );
+        let mut epoch_traces = self
+            .executor
+            .compute_epoch_traces(&pivot_hash, &epoch_block_hashes)?;
+        Ok(Some(DataVersionTuple(
+            pivot_hash,
+            epoch_traces.swap_remove(block_index),
+        )))
+    }
+
     /// Get the number of processed blocks (i.e., the number of calls to
     /// on_new_block()
     pub fn get_processed_block_count(&self) -> usize {
         self.statistics.get_consensus_graph_processed_block_count()
     }
 
+    /// Check that the state of `epoch_number` is executed, and return the
+    /// height and the hash of its pivot block.
+    fn get_stated_pivot_epoch(
+        &self, epoch_number: EpochNumber, rpc_param_name: &str,
+    ) -> RpcResult<(u64, H256)> {
+        invalid_params_check(
+            rpc_param_name,
+            self.validate_stated_epoch(&epoch_number),
+        )?;
+        let height = invalid_params_check(
+            rpc_param_name,
+            self.get_height_from_epoch_number(epoch_number),
+        )?;
+        let hash =
+            self.inner.read().get_pivot_hash_from_epoch_number(height)?;
+        Ok((height, hash))
+    }
+
     fn get_state_db_by_height_and_hash(
         &self, height: u64, hash: &H256,
     ) -> RpcResult<StateDb> {
@@ -1506,8 +1724,7 @@ impl ConsensusGraphTrait for ConsensusGraph {
         } else {
             bail!("cannot get block hashes in the specified epoch, maybe it does not exist?");
         };
-        let state_db =
-            self.get_state_db_by_height_and_hash(height, &epoch_id)?;
+        let state_db = self.get_state_db_or_reconstruct(height, &epoch_id)?;
 
         Ok(State::new(state_db)?)
     }
@@ -1517,16 +1734,16 @@ impl ConsensusGraphTrait for ConsensusGraph {
     fn get_state_db_by_epoch_number(
         &self, epoch_number: EpochNumber, rpc_param_name: &str,
     ) -> RpcResult<StateDb> {
-        invalid_params_check(
-            rpc_param_name,
-            self.validate_stated_epoch(&epoch_number),
-        )?;
-        let height = invalid_params_check(
-            rpc_param_name,
-            self.get_height_from_epoch_number(epoch_number),
-        )?;
-        let hash =
-            self.inner.read().get_pivot_hash_from_epoch_number(height)?;
+        let (height, hash) =
+            self.get_stated_pivot_epoch(epoch_number, rpc_param_name)?;
+        self.get_state_db_or_reconstruct(height, &hash)
+    }
+
+    fn get_kept_state_db_by_epoch_number(
+        &self, epoch_number: EpochNumber, rpc_param_name: &str,
+    ) -> RpcResult<StateDb> {
+        let (height, hash) =
+            self.get_stated_pivot_epoch(epoch_number, rpc_param_name)?;
         self.get_state_db_by_height_and_hash(height, &hash)
     }
 
@@ -1602,7 +1819,8 @@ impl ConsensusGraphTrait for ConsensusGraph {
         self.txpool.set_ready();
         self.txpool
             .notify_new_best_info(self.best_info.read_recursive().clone())
-            .expect("No DB error")
+            .expect("No DB error");
+        self.txpool.restore_local_transactions();
     }
 
     /// Reset the information in consensus graph with only checkpoint
@@ -44,7 +44,7 @@ use super::{
 use crate::{
     bytes::Bytes,
     hash::keccak,
-    trace::{trace::ExecTrace, Tracer},
+    trace::{trace::ExecTrace, ExecStep, Tracer},
     vm::{
         self, ActionParams, ActionValue, CallType, ContractCreateResult,
         CreateContractAddress, GasLeft, MessageCallResult, ParamsType,
@@ -456,6 +456,30 @@ impl<Cost: CostType> Interpreter<Cost> {
                         Self::store_written(instruction, &self.stack),
                     );
                 }
+                if tracer.trace_steps() {
+                    tracer.prepare_trace_step(ExecStep {
+                        depth: context.depth(),
+                        pc: self.reader.position - 1,
+                        instruction: opcode,
+                        name: info.name,
+                        gas: self
+                            .gasometer
+                            .as_ref()
+                            .expect(GASOMETER_PROOF)
+                            .current_gas
+                            .as_u256(),
+                        gas_cost: requirements.gas_cost.as_u256(),
+                        stack: self.stack.peek_top(self.stack.size()),
+                        mem_written: Self::mem_written(
+                            instruction,
+                            &self.stack,
+                        ),
+                        store_written: Self::store_written(
+                            instruction,
+                            &self.stack,
+                        ),
+                    });
+                }
 
                 if let Err(e) = self
                     .gasometer
@@ -531,6 +555,9 @@ impl<Cost: CostType> Interpreter<Cost> {
                 &self.mem,
             );
         }
+        if tracer.trace_steps() {
+            tracer.prepare_trace_step_result(context.depth(), &self.mem);
+        }
 
         // Advance
         match result {
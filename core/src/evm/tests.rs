@@ -1354,6 +1354,53 @@ fn test_create_in_staticcall(factory: super::Factory) {
     assert_eq!(ctx.calls.len(), 0);
 }
 
+evm_test! {test_struct_logger: test_struct_logger_int}
+fn test_struct_logger(factory: super::Factory) {
+    // 60 ff - push1 0xff
+    // 60 00 - push1 0x00
+    // 52    - mstore
+    // 60 2a - push1 0x2a
+    // 60 01 - push1 0x01
+    // 55    - sstore
+    let code = "60ff600052602a600155".from_hex().unwrap();
+
+    let mut params = ActionParams::default();
+    params.gas = U256::from(100_000);
+    params.code = Some(Arc::new(code));
+    let mut ctx = MockContext::new();
+    let mut logger = trace::StructLogger::default();
+
+    {
+        let mut tracer = &mut logger;
+        let vm = factory.create(params, ctx.spec(), ctx.depth());
+        test_finalize(vm.exec(&mut ctx, &mut tracer).ok().unwrap()).unwrap();
+    }
+
+    let logs = logger.into_logs();
+    let ops: Vec<_> = logs.iter().map(|log| log.op).collect();
+    assert_eq!(
+        ops,
+        vec!["PUSH1", "PUSH1", "MSTORE", "PUSH1", "PUSH1", "SSTORE"]
+    );
+
+    assert_eq!(logs[0].pc, 0);
+    assert_eq!(logs[0].gas, U256::from(100_000));
+    assert_eq!(logs[0].gas_cost, U256::from(3));
+    assert_eq!(logs[1].gas, U256::from(99_997));
+
+    assert_eq!(logs[2].pc, 4);
+    assert_eq!(logs[2].stack, vec![U256::from(0xff), U256::zero()]);
+    let memory_diff = logs[2].memory_diff.as_ref().unwrap();
+    assert_eq!(memory_diff.offset, 0);
+    assert_eq!(memory_diff.data, H256::from_low_u64_be(0xff).as_bytes());
+    assert!(logs[2].storage_diff.is_none());
+
+    let storage_diff = logs[5].storage_diff.as_ref().unwrap();
+    assert_eq!(storage_diff.key, U256::from(1));
+    assert_eq!(storage_diff.value, U256::from(0x2a));
+    assert!(logs[5].memory_diff.is_none());
+}
+
 fn assert_set_contains<T: Debug + Eq + PartialEq + Hash>(
     set: &HashSet<T>, val: &T,
 ) {
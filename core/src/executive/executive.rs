@@ -17,7 +17,7 @@ use crate::{
     hash::keccak,
     machine::Machine,
     state::{cleanup_mode, CallStackInfo, State, Substate},
-    trace::{self, trace::ExecTrace, Tracer},
+    trace::{self, state_diff::StateDiff, trace::ExecTrace, Tracer},
     verification::VerificationConfig,
     vm::{
         self, ActionParams, ActionValue, CallType, CreateContractAddress, Env,
@@ -1437,3 +1437,17 @@ impl<
         }
     }
 }
+
+impl<'a> Executive<'a> {
+    /// Executes the transaction like `transact` and collects the account-level
+    /// changes it makes to the state.
+    pub fn transact_with_state_diff<T>(
+        &mut self, tx: &SignedTransaction, options: TransactOptions<T>,
+    ) -> DbResult<(ExecutionOutcome, StateDiff)>
+    where T: Tracer<Output = trace::trace::ExecTrace> {
+        let snapshot = self.state.cache_snapshot();
+        let outcome = self.transact(tx, options)?;
+        let state_diff = self.state.diff_since(snapshot)?;
+        Ok((outcome, state_diff))
+    }
+}
@@ -41,6 +41,7 @@ pub mod pow;
 pub mod rpc_errors;
 pub mod spec;
 pub mod state;
+pub mod state_dump;
 pub mod state_exposer;
 pub mod statistics;
 pub mod sync;
@@ -3,11 +3,15 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
+    block_data_manager::BlockRewardResult,
     consensus::SharedConsensusGraph,
     light_protocol::{message::WitnessInfoWithHeight, Error, ErrorKind},
 };
 use cfx_internal_common::StateRootWithAuxInfo;
-use cfx_parameters::consensus::DEFERRED_STATE_EPOCH_COUNT;
+use cfx_parameters::{
+    consensus::DEFERRED_STATE_EPOCH_COUNT,
+    consensus_internal::REWARD_EPOCH_COUNT,
+};
 use cfx_statedb::{StateDb, StateDbGetOriginalMethods};
 use cfx_storage::{
     state::{State, StateTrait},
@@ -264,6 +268,44 @@ impl LedgerInfo {
             .collect()
     }
 
+    /// Get the rewards of all blocks in `epoch`, in execution order.
+    /// Rewards are computed when epoch `epoch + REWARD_EPOCH_COUNT` is
+    /// executed.
+    #[inline]
+    pub fn block_rewards_of(
+        &self, epoch: u64,
+    ) -> Result<Vec<BlockRewardResult>, Error> {
+        if epoch == 0 {
+            return Ok(vec![]);
+        }
+
+        let reward_epoch = epoch
+            .checked_add(REWARD_EPOCH_COUNT)
+            .ok_or_else(|| Error::from(format!("Epoch {} overflows", epoch)))?;
+
+        let pivot = self.pivot_hash_of(reward_epoch)?;
+        let hashes = self.block_hashes_in(epoch)?;
+
+        hashes
+            .into_iter()
+            .map(|h| {
+                self.consensus
+                    .get_data_manager()
+                    .block_reward_result_by_hash_with_epoch(
+                        &h, &pivot, false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .ok_or_else(|| {
+                        ErrorKind::InternalError(format!(
+                            "Rewards of epoch {} not found",
+                            epoch
+                        ))
+                        .into()
+                    })
+            })
+            .collect()
+    }
+
     /// Get the aggregated bloom corresponding to the execution of `epoch`.
     #[inline]
     pub fn bloom_of(&self, epoch: u64) -> Result<Bloom, Error> {
@@ -91,6 +91,7 @@ where T: Default
 pub struct FullPeerFilter {
     msg_id: MsgId,
     min_best_epoch: Option<u64>,
+    min_protocol_version: Option<ProtocolVersion>,
 }
 
 impl FullPeerFilter {
@@ -98,6 +99,7 @@ impl FullPeerFilter {
         FullPeerFilter {
             msg_id,
             min_best_epoch: None,
+            min_protocol_version: None,
         }
     }
 
@@ -106,6 +108,13 @@ impl FullPeerFilter {
         self
     }
 
+    pub fn with_min_protocol_version(
+        mut self, min_protocol_version: ProtocolVersion,
+    ) -> Self {
+        self.min_protocol_version.replace(min_protocol_version);
+        self
+    }
+
     pub fn select(self, peers: Arc<Peers<FullPeerState>>) -> Option<NodeId> {
         self.select_all(peers)
             .choose(&mut rand::thread_rng())
@@ -118,6 +127,12 @@ impl FullPeerFilter {
                 return false;
             }
 
+            if let Some(min_protocol_version) = self.min_protocol_version {
+                if peer.protocol_version < min_protocol_version {
+                    return false;
+                }
+            }
+
             let min_best_epoch = self.min_best_epoch.unwrap_or_default();
             peer.best_epoch >= min_best_epoch
         })
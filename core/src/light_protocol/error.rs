@@ -10,7 +10,9 @@ use crate::{
 use cfx_internal_common::ChainIdParamsInner;
 use cfx_types::{H160, H256};
 use error_chain::ChainedError;
-use network::{node_table::NodeId, NetworkContext, UpdateNodeOperation};
+use network::{
+    node_table::NodeId, NetworkContext, ReputationEvent, UpdateNodeOperation,
+};
 use parking_lot::Mutex;
 use primitives::{account::AccountError, filter::FilterError, StateRoot};
 use rlp::DecoderError;
@@ -54,6 +56,11 @@ error_chain! {
             display("Internal error: {:?}", details),
         }
 
+        InvalidBlockRewards{ epoch: u64, reason: String } {
+            description("Invalid block rewards"),
+            display("Invalid block rewards for epoch {}: {}", epoch, reason),
+        }
+
         InvalidBloom{ epoch: u64, expected: H256, received: H256 } {
             description("Logs bloom hash validation failed"),
             display("Logs bloom hash validation for epoch {} failed, expected={:?}, received={:?}", epoch, expected, received),
@@ -183,6 +190,34 @@ pub fn handle(
     let reason = format!("{}", e.0);
     let mut op = None;
 
+    let reputation_event = match &e.0 {
+        ErrorKind::InvalidHeader
+        | ErrorKind::InvalidBlockRewards { .. }
+        | ErrorKind::InvalidBloom { .. }
+        | ErrorKind::InvalidLedgerProofSize { .. }
+        | ErrorKind::InvalidPreviousStateRoot { .. }
+        | ErrorKind::InvalidReceipts { .. }
+        | ErrorKind::InvalidStateProof { .. }
+        | ErrorKind::InvalidStateRoot { .. }
+        | ErrorKind::InvalidStorageRootProof { .. }
+        | ErrorKind::InvalidTxInfo { .. }
+        | ErrorKind::InvalidTxRoot { .. }
+        | ErrorKind::InvalidTxSignature { .. }
+        | ErrorKind::InvalidWitnessRoot { .. } => {
+            Some(ReputationEvent::InvalidBlock)
+        }
+        ErrorKind::InvalidMessageFormat
+        | ErrorKind::Decoder(_)
+        | ErrorKind::UnexpectedMessage { .. } => {
+            Some(ReputationEvent::InvalidMessage)
+        }
+        ErrorKind::UnexpectedResponse { .. } => {
+            Some(ReputationEvent::UselessResponse)
+        }
+        ErrorKind::Timeout(_) => Some(ReputationEvent::Timeout),
+        _ => None,
+    };
+
     // NOTE: do not use wildcard; this way, the compiler
     // will help covering all the cases.
     match &e.0 {
@@ -230,7 +265,8 @@ pub fn handle(
             op = Some(UpdateNodeOperation::Demotion)
         }
 
-        ErrorKind::InvalidBloom{..}
+        ErrorKind::InvalidBlockRewards{..}
+        | ErrorKind::InvalidBloom{..}
         | ErrorKind::InvalidLedgerProofSize{..}
         | ErrorKind::InvalidMessageFormat
         | ErrorKind::InvalidPreviousStateRoot{..}
@@ -299,6 +335,10 @@ pub fn handle(
         }
     };
 
+    if let Some(event) = reputation_event {
+        io.update_peer_reputation(peer, event, &reason);
+    }
+
     if disconnect {
         io.disconnect_peer(peer, op, reason.as_str());
     }
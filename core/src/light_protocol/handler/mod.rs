@@ -14,6 +14,7 @@ use crate::{
         message::{
             msgid, BlockHashes as GetBlockHashesResponse,
             BlockHeaders as GetBlockHeadersResponse,
+            BlockRewards as GetBlockRewardsResponse,
             BlockTxs as GetBlockTxsResponse, Blooms as GetBloomsResponse,
             NewBlockHashes, NodeType, Receipts as GetReceiptsResponse,
             SendRawTx, StateEntries as GetStateEntriesResponse,
@@ -51,8 +52,8 @@ use std::{
     time::{Duration, Instant},
 };
 use sync::{
-    BlockTxs, Blooms, Epochs, HashSource, Headers, Receipts, StateEntries,
-    StateRoots, StorageRoots, TxInfos, Txs, Witnesses,
+    BlockRewards, BlockTxs, Blooms, Epochs, HashSource, Headers, Receipts,
+    StateEntries, StateRoots, StorageRoots, TxInfos, Txs, Witnesses,
 };
 use throttling::token_bucket::TokenBucketManager;
 
@@ -67,6 +68,9 @@ const TOTAL_WEIGHT_IN_PAST_TIMER: TimerToken = 4;
 pub struct Handler {
     pub protocol_version: ProtocolVersion,
 
+    // block reward sync manager
+    pub block_rewards: BlockRewards,
+
     // block tx sync manager
     pub block_txs: Arc<BlockTxs>,
 
@@ -198,6 +202,12 @@ impl Handler {
             witnesses.clone(),
         );
 
+        let block_rewards = BlockRewards::new(
+            consensus.clone(),
+            peers.clone(),
+            request_id_allocator.clone(),
+        );
+
         let stopped = Arc::new(AtomicBool::new(false));
 
         let join_handle = Some(Self::start_witness_worker(
@@ -210,6 +220,7 @@ impl Handler {
         graph.recover_graph_from_db();
 
         Handler {
+            block_rewards,
             block_txs,
             blooms,
             consensus,
@@ -391,6 +402,7 @@ impl Handler {
             // sync messages
             msgid::BLOCK_HASHES => self.on_block_hashes(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::BLOCK_HEADERS => self.on_block_headers(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
+            msgid::BLOCK_REWARDS => self.on_block_rewards(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::BLOCK_TXS => self.on_block_txs(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::BLOOMS => self.on_blooms(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::NEW_BLOCK_HASHES => self.on_new_block_hashes(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
@@ -640,6 +652,28 @@ impl Handler {
         Ok(())
     }
 
+    fn on_block_rewards(
+        &self, io: &dyn NetworkContext, peer: &NodeId,
+        resp: GetBlockRewardsResponse,
+    ) -> Result<()>
+    {
+        debug!(
+            "received {} block rewards (request id = {})",
+            resp.rewards.len(),
+            resp.request_id
+        );
+        trace!("on_block_rewards resp={:?}", resp);
+
+        self.block_rewards.receive(
+            peer,
+            resp.request_id,
+            resp.rewards.into_iter(),
+        )?;
+
+        self.block_rewards.sync(io);
+        Ok(())
+    }
+
     fn on_block_txs(
         &self, io: &dyn NetworkContext, peer: &NodeId,
         resp: GetBlockTxsResponse,
@@ -861,6 +895,7 @@ impl Handler {
         };
 
         self.witnesses.sync(io);
+        self.block_rewards.sync(io);
         self.blooms.sync(io);
         self.receipts.sync(io);
         self.block_txs.sync(io);
@@ -872,6 +907,7 @@ impl Handler {
     }
 
     fn clean_up_requests(&self) {
+        self.block_rewards.clean_up();
         self.block_txs.clean_up();
         self.blooms.clean_up();
         self.epochs.clean_up();
@@ -1032,6 +1068,7 @@ impl NetworkProtocolHandler for Handler {
             REQUEST_CLEANUP_TIMER => self.clean_up_requests(),
             LOG_STATISTICS_TIMER => {
                 self.print_stats();
+                self.block_rewards.print_stats();
                 self.block_txs.print_stats();
                 self.blooms.print_stats();
                 self.epochs.print_stats();
@@ -0,0 +1,352 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+extern crate lru_time_cache;
+
+use super::common::{FutureItem, KeyOrdered, PendingItem, SyncManager};
+use crate::{
+    block_data_manager::BlockRewardResult,
+    consensus::SharedConsensusGraph,
+    light_protocol::{
+        common::{FullPeerState, LedgerInfo, Peers},
+        error::*,
+        message::{msgid, BlockRewardsWithEpoch, GetBlockRewards},
+        LIGHT_PROTO_V3,
+    },
+    machine::Machine,
+    message::{Message, RequestId},
+    UniqueId,
+};
+use cfx_parameters::light::{
+    BLOCK_REWARD_REQUEST_BATCH_SIZE, BLOCK_REWARD_REQUEST_TIMEOUT,
+    CACHE_TIMEOUT, MAX_BLOCK_REWARDS_IN_FLIGHT,
+};
+use cfx_types::U512;
+use futures::future::FutureExt;
+use lru_time_cache::LruCache;
+use network::{node_table::NodeId, NetworkContext};
+use parking_lot::RwLock;
+use std::{future::Future, sync::Arc};
+
+#[derive(Debug)]
+struct Statistics {
+    cached: usize,
+    in_flight: usize,
+    waiting: usize,
+}
+
+// prioritize higher epochs
+type MissingBlockRewards = KeyOrdered<u64>;
+
+type PendingBlockRewards = PendingItem<Vec<BlockRewardResult>, ClonableError>;
+
+pub struct BlockRewards {
+    // helper API for retrieving ledger information
+    ledger: LedgerInfo,
+
+    // chain parameters for computing the base block rewards
+    machine: Arc<Machine>,
+
+    // series of unique request ids
+    request_id_allocator: Arc<UniqueId>,
+
+    // sync and request manager
+    sync_manager: SyncManager<u64, MissingBlockRewards>,
+
+    // epoch block rewards received from full node
+    verified: Arc<RwLock<LruCache<u64, PendingBlockRewards>>>,
+}
+
+impl BlockRewards {
+    pub fn new(
+        consensus: SharedConsensusGraph, peers: Arc<Peers<FullPeerState>>,
+        request_id_allocator: Arc<UniqueId>,
+    ) -> Self
+    {
+        let machine = consensus.get_tx_pool().machine();
+        let ledger = LedgerInfo::new(consensus);
+        // full peers running v2 do not understand `GetBlockRewards`
+        let sync_manager =
+            SyncManager::new(peers.clone(), msgid::GET_BLOCK_REWARDS)
+                .with_min_protocol_version(LIGHT_PROTO_V3);
+
+        let cache = LruCache::with_expiry_duration(*CACHE_TIMEOUT);
+        let verified = Arc::new(RwLock::new(cache));
+
+        BlockRewards {
+            ledger,
+            machine,
+            request_id_allocator,
+            sync_manager,
+            verified,
+        }
+    }
+
+    #[inline]
+    pub fn print_stats(&self) {
+        debug!(
+            "block reward sync statistics: {:?}",
+            Statistics {
+                cached: self.verified.read().len(),
+                in_flight: self.sync_manager.num_in_flight(),
+                waiting: self.sync_manager.num_waiting(),
+            }
+        );
+    }
+
+    #[inline]
+    pub fn request(
+        &self, epoch: u64,
+    ) -> impl Future<Output = Result<Vec<BlockRewardResult>>> {
+        let mut verified = self.verified.write();
+
+        if epoch == 0 {
+            verified.insert(0, PendingItem::ready(vec![]));
+        }
+
+        if !verified.contains_key(&epoch) {
+            let missing = MissingBlockRewards::new(epoch);
+            self.sync_manager.insert_waiting(std::iter::once(missing));
+        }
+
+        verified
+            .entry(epoch)
+            .or_insert(PendingItem::pending())
+            .clear_error();
+
+        FutureItem::new(epoch, self.verified.clone())
+            .map(|res| res.map_err(|e| e.into()))
+    }
+
+    #[inline]
+    pub fn receive(
+        &self, peer: &NodeId, id: RequestId,
+        rewards: impl Iterator<Item = BlockRewardsWithEpoch>,
+    ) -> Result<()>
+    {
+        for BlockRewardsWithEpoch {
+            epoch,
+            epoch_rewards,
+        } in rewards
+        {
+            trace!(
+                "Validating block rewards {:?} with epoch {}",
+                epoch_rewards,
+                epoch
+            );
+
+            match self.sync_manager.check_if_requested(peer, id, &epoch)? {
+                None => continue,
+                Some(_) => self.validate_and_store(epoch, epoch_rewards)?,
+            };
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn validate_and_store(
+        &self, epoch: u64, rewards: Vec<BlockRewardResult>,
+    ) -> Result<()> {
+        // validate block rewards
+        if let Err(e) = self.validate_block_rewards(epoch, &rewards) {
+            // forward error to both rpc caller(s) and sync handler
+            // so we need to make it clonable
+            let e = ClonableError::from(e);
+
+            self.verified
+                .write()
+                .entry(epoch)
+                .or_insert(PendingItem::pending())
+                .set_error(e.clone());
+
+            bail!(e);
+        }
+
+        // store block rewards by epoch
+        self.verified
+            .write()
+            .entry(epoch)
+            .or_insert(PendingItem::pending())
+            .set(rewards);
+
+        self.sync_manager.remove_in_flight(&epoch);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn clean_up(&self) {
+        // remove timeout in-flight requests
+        let timeout = *BLOCK_REWARD_REQUEST_TIMEOUT;
+        let rewards = self.sync_manager.remove_timeout_requests(timeout);
+        trace!("Timeout block rewards ({}): {:?}", rewards.len(), rewards);
+        self.sync_manager.insert_waiting(rewards.into_iter());
+
+        // trigger cache cleanup
+        self.verified.write().get(&Default::default());
+    }
+
+    #[inline]
+    fn send_request(
+        &self, io: &dyn NetworkContext, peer: &NodeId, epochs: Vec<u64>,
+    ) -> Result<Option<RequestId>> {
+        if epochs.is_empty() {
+            return Ok(None);
+        }
+
+        let request_id = self.request_id_allocator.next();
+
+        trace!(
+            "send_request GetBlockRewards peer={:?} id={:?} epochs={:?}",
+            peer,
+            request_id,
+            epochs
+        );
+
+        let msg: Box<dyn Message> =
+            Box::new(GetBlockRewards { request_id, epochs });
+
+        msg.send(io, peer)?;
+        Ok(Some(request_id))
+    }
+
+    #[inline]
+    pub fn sync(&self, io: &dyn NetworkContext) {
+        self.sync_manager.sync(
+            MAX_BLOCK_REWARDS_IN_FLIGHT,
+            BLOCK_REWARD_REQUEST_BATCH_SIZE,
+            |peer, epochs| self.send_request(io, peer, epochs),
+        );
+    }
+
+    /// Block rewards are not committed to in the headers. Here we can only
+    /// check that they are consistent with the epoch; the transaction fees and
+    /// secondary rewards are checked against the receipts by the caller.
+    #[inline]
+    fn validate_block_rewards(
+        &self, epoch: u64, rewards: &[BlockRewardResult],
+    ) -> Result<()> {
+        let block_count = self.ledger.block_hashes_in(epoch)?.len();
+
+        // the base reward of the pivot block height, before the anticone
+        // penalty which depends on the (unverified) anticone difficulties
+        let max_base_reward =
+            self.machine.params().base_reward_in_ucfx(0, epoch);
+
+        check_block_rewards(epoch, block_count, max_base_reward, rewards)
+    }
+}
+
+fn check_block_rewards(
+    epoch: u64, block_count: usize, max_base_reward: U512,
+    rewards: &[BlockRewardResult],
+) -> Result<()>
+{
+    // check that we received one reward for each block in the epoch
+    if rewards.len() != block_count {
+        bail!(ErrorKind::InvalidBlockRewards {
+            epoch,
+            reason: format!(
+                "expected {} block rewards, received {}",
+                block_count,
+                rewards.len()
+            ),
+        });
+    }
+
+    for reward in rewards {
+        // check that the base reward does not exceed the base reward of
+        // the epoch
+        if U512::from(reward.base_reward) > max_base_reward {
+            bail!(ErrorKind::InvalidBlockRewards {
+                epoch,
+                reason: format!(
+                    "base reward of {:?} exceeds {}",
+                    reward, max_base_reward
+                ),
+            });
+        }
+
+        // check that each total reward covers its components
+        let components = reward.base_reward.checked_add(reward.tx_fee);
+
+        if components.map_or(true, |c| c > reward.total_reward) {
+            bail!(ErrorKind::InvalidBlockRewards {
+                epoch,
+                reason: format!("inconsistent block reward {:?}", reward),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_block_rewards;
+    use crate::{
+        block_data_manager::BlockRewardResult,
+        light_protocol::{Error, ErrorKind},
+    };
+    use cfx_types::{U256, U512};
+
+    const EPOCH: u64 = 10;
+
+    fn max_base_reward() -> U512 { U512::from(7_000_000_000_000_000_000u64) }
+
+    fn reward(base_reward: u64, tx_fee: u64) -> BlockRewardResult {
+        BlockRewardResult {
+            total_reward: U256::from(base_reward + tx_fee),
+            base_reward: U256::from(base_reward),
+            tx_fee: U256::from(tx_fee),
+        }
+    }
+
+    fn is_invalid(res: Result<(), Error>) -> bool {
+        match res {
+            Err(Error(ErrorKind::InvalidBlockRewards { epoch, .. }, _)) => {
+                epoch == EPOCH
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_valid_block_rewards() {
+        // full reward, penalized reward and no reward
+        let rewards = vec![
+            reward(7_000_000_000_000_000_000, 21_000),
+            reward(6_500_000_000_000_000_000, 0),
+            reward(0, 42_000),
+        ];
+
+        let res = check_block_rewards(EPOCH, 3, max_base_reward(), &rewards);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_tampered_block_rewards() {
+        // inflated base reward, with a total reward matching it
+        let rewards = vec![
+            reward(7_000_000_000_000_000_000, 21_000),
+            reward(7_000_000_000_000_000_001, 0),
+        ];
+
+        let res = check_block_rewards(EPOCH, 2, max_base_reward(), &rewards);
+        assert!(is_invalid(res));
+
+        // total reward not covering the base reward
+        let mut rewards = vec![reward(7_000_000_000_000_000_000, 21_000)];
+        rewards[0].total_reward = U256::from(21_000);
+
+        let res = check_block_rewards(EPOCH, 1, max_base_reward(), &rewards);
+        assert!(is_invalid(res));
+
+        // missing block reward
+        let rewards = vec![reward(7_000_000_000_000_000_000, 21_000)];
+
+        let res = check_block_rewards(EPOCH, 2, max_base_reward(), &rewards);
+        assert!(is_invalid(res));
+    }
+}
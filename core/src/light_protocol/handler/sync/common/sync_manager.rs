@@ -10,7 +10,7 @@ use crate::{
     },
     message::{MsgId, RequestId},
 };
-use network::node_table::NodeId;
+use network::{node_table::NodeId, service::ProtocolVersion};
 use parking_lot::{Mutex, RwLock};
 use std::{
     cmp::Ord,
@@ -54,6 +54,9 @@ pub struct SyncManager<Key, Item> {
 
     // used to filter peer to send request
     request_msg_id: MsgId,
+
+    // only peers running at least this version are sent requests
+    min_protocol_version: Option<ProtocolVersion>,
 }
 
 impl<Key, Item> SyncManager<Key, Item>
@@ -74,9 +77,19 @@ where
             sync_lock,
             waiting,
             request_msg_id,
+            min_protocol_version: None,
         }
     }
 
+    /// Only send requests to peers that negotiated `min_protocol_version` or
+    /// later, for requests introduced in that version.
+    pub fn with_min_protocol_version(
+        mut self, min_protocol_version: ProtocolVersion,
+    ) -> Self {
+        self.min_protocol_version = Some(min_protocol_version);
+        self
+    }
+
     #[inline]
     pub fn num_waiting(&self) -> usize { self.waiting.read().len() }
 
@@ -204,9 +217,12 @@ where
             }
 
             // select peer for batch
-            let peer = match FullPeerFilter::new(self.request_msg_id)
-                .select(self.peers.clone())
-            {
+            let mut filter = FullPeerFilter::new(self.request_msg_id);
+            if let Some(min_protocol_version) = self.min_protocol_version {
+                filter = filter.with_min_protocol_version(min_protocol_version);
+            }
+
+            let peer = match filter.select(self.peers.clone()) {
                 Some(peer) => peer,
                 None => {
                     warn!("No peers available");
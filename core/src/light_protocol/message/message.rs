@@ -4,7 +4,7 @@
 
 use super::protocol::*;
 use crate::{
-    light_protocol::{LIGHT_PROTO_V1, LIGHT_PROTO_V2},
+    light_protocol::{LIGHT_PROTO_V1, LIGHT_PROTO_V2, LIGHT_PROTO_V3},
     message::{GetMaybeRequestId, Message, MessageProtocolVersionBound, MsgId},
 };
 use network::service::ProtocolVersion;
@@ -39,6 +39,8 @@ build_msgid! {
     STATUS_PONG_V2 = 0x19
     GET_STORAGE_ROOTS = 0x1a
     STORAGE_ROOTS = 0x1b
+    GET_BLOCK_REWARDS = 0x1c
+    BLOCK_REWARDS = 0x1d
 
     THROTTLED = 0xfe
     INVALID = 0xff
@@ -47,29 +49,31 @@ build_msgid! {
 // generate `impl Message for _` for each message type
 build_msg_impl! { StatusPingDeprecatedV1, msgid::STATUS_PING_DEPRECATED, "StatusPing", LIGHT_PROTO_V1, LIGHT_PROTO_V1 }
 build_msg_impl! { StatusPongDeprecatedV1, msgid::STATUS_PONG_DEPRECATED, "StatusPong", LIGHT_PROTO_V1, LIGHT_PROTO_V1 }
-build_msg_impl! { StatusPingV2, msgid::STATUS_PING_V2, "StatusPingV2", LIGHT_PROTO_V2, LIGHT_PROTO_V2 }
-build_msg_impl! { StatusPongV2, msgid::STATUS_PONG_V2, "StatusPongV2", LIGHT_PROTO_V2, LIGHT_PROTO_V2 }
-build_msg_impl! { GetStateRoots, msgid::GET_STATE_ROOTS, "GetStateRoots", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { StateRoots, msgid::STATE_ROOTS, "StateRoots", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetStateEntries, msgid::GET_STATE_ENTRIES, "GetStateEntries", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { StateEntries, msgid::STATE_ENTRIES, "StateEntries", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetBlockHashesByEpoch, msgid::GET_BLOCK_HASHES_BY_EPOCH, "GetBlockHashesByEpoch", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { BlockHashes, msgid::BLOCK_HASHES, "BlockHashes", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetBlockHeaders, msgid::GET_BLOCK_HEADERS, "GetBlockHeaders", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { BlockHeaders, msgid::BLOCK_HEADERS, "BlockHeaders", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { NewBlockHashes, msgid::NEW_BLOCK_HASHES, "NewBlockHashes", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { SendRawTx, msgid::SEND_RAW_TX, "SendRawTx", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetReceipts, msgid::GET_RECEIPTS, "GetReceipts", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { Receipts, msgid::RECEIPTS, "Receipts", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetTxs, msgid::GET_TXS, "GetTxs", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { Txs, msgid::TXS, "Txs", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetWitnessInfo, msgid::GET_WITNESS_INFO, "GetWitnessInfo", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { WitnessInfo, msgid::WITNESS_INFO, "WitnessInfo", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetBlooms, msgid::GET_BLOOMS, "GetBlooms", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { Blooms, msgid::BLOOMS, "Blooms", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetBlockTxs, msgid::GET_BLOCK_TXS, "GetBlockTxs", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { BlockTxs, msgid::BLOCK_TXS, "BlockTxs", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetTxInfos, msgid::GET_TX_INFOS, "GetTxInfos", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { TxInfos, msgid::TX_INFOS, "TxInfos", LIGHT_PROTO_V1, LIGHT_PROTO_V2 }
-build_msg_impl! { GetStorageRoots, msgid::GET_STORAGE_ROOTS, "GetStorageRoots", LIGHT_PROTO_V2, LIGHT_PROTO_V2 }
-build_msg_impl! { StorageRoots, msgid::STORAGE_ROOTS, "StorageRoots", LIGHT_PROTO_V2, LIGHT_PROTO_V2 }
+build_msg_impl! { StatusPingV2, msgid::STATUS_PING_V2, "StatusPingV2", LIGHT_PROTO_V2, LIGHT_PROTO_V3 }
+build_msg_impl! { StatusPongV2, msgid::STATUS_PONG_V2, "StatusPongV2", LIGHT_PROTO_V2, LIGHT_PROTO_V3 }
+build_msg_impl! { GetStateRoots, msgid::GET_STATE_ROOTS, "GetStateRoots", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { StateRoots, msgid::STATE_ROOTS, "StateRoots", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetStateEntries, msgid::GET_STATE_ENTRIES, "GetStateEntries", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { StateEntries, msgid::STATE_ENTRIES, "StateEntries", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetBlockHashesByEpoch, msgid::GET_BLOCK_HASHES_BY_EPOCH, "GetBlockHashesByEpoch", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { BlockHashes, msgid::BLOCK_HASHES, "BlockHashes", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetBlockHeaders, msgid::GET_BLOCK_HEADERS, "GetBlockHeaders", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { BlockHeaders, msgid::BLOCK_HEADERS, "BlockHeaders", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { NewBlockHashes, msgid::NEW_BLOCK_HASHES, "NewBlockHashes", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { SendRawTx, msgid::SEND_RAW_TX, "SendRawTx", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetReceipts, msgid::GET_RECEIPTS, "GetReceipts", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { Receipts, msgid::RECEIPTS, "Receipts", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetTxs, msgid::GET_TXS, "GetTxs", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { Txs, msgid::TXS, "Txs", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetWitnessInfo, msgid::GET_WITNESS_INFO, "GetWitnessInfo", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { WitnessInfo, msgid::WITNESS_INFO, "WitnessInfo", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetBlooms, msgid::GET_BLOOMS, "GetBlooms", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { Blooms, msgid::BLOOMS, "Blooms", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetBlockTxs, msgid::GET_BLOCK_TXS, "GetBlockTxs", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { BlockTxs, msgid::BLOCK_TXS, "BlockTxs", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetTxInfos, msgid::GET_TX_INFOS, "GetTxInfos", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { TxInfos, msgid::TX_INFOS, "TxInfos", LIGHT_PROTO_V1, LIGHT_PROTO_V3 }
+build_msg_impl! { GetStorageRoots, msgid::GET_STORAGE_ROOTS, "GetStorageRoots", LIGHT_PROTO_V2, LIGHT_PROTO_V3 }
+build_msg_impl! { StorageRoots, msgid::STORAGE_ROOTS, "StorageRoots", LIGHT_PROTO_V2, LIGHT_PROTO_V3 }
+build_msg_impl! { GetBlockRewards, msgid::GET_BLOCK_REWARDS, "GetBlockRewards", LIGHT_PROTO_V3, LIGHT_PROTO_V3 }
+build_msg_impl! { BlockRewards, msgid::BLOCK_REWARDS, "BlockRewards", LIGHT_PROTO_V3, LIGHT_PROTO_V3 }
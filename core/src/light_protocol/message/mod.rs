@@ -8,11 +8,12 @@ mod protocol;
 pub use crate::NodeType;
 pub use message::msgid;
 pub use protocol::{
-    BlockHashes, BlockHeaders, BlockTxs, BlockTxsWithHash, BloomWithEpoch,
-    Blooms, GetBlockHashesByEpoch, GetBlockHeaders, GetBlockTxs, GetBlooms,
-    GetReceipts, GetStateEntries, GetStateRoots, GetStorageRoots, GetTxInfos,
-    GetTxs, GetWitnessInfo, NewBlockHashes, Receipts, ReceiptsWithEpoch,
-    SendRawTx, StateEntries, StateEntryProof, StateEntryWithKey, StateKey,
+    BlockHashes, BlockHeaders, BlockRewards, BlockRewardsWithEpoch, BlockTxs,
+    BlockTxsWithHash, BloomWithEpoch, Blooms, GetBlockHashesByEpoch,
+    GetBlockHeaders, GetBlockRewards, GetBlockTxs, GetBlooms, GetReceipts,
+    GetStateEntries, GetStateRoots, GetStorageRoots, GetTxInfos, GetTxs,
+    GetWitnessInfo, NewBlockHashes, Receipts, ReceiptsWithEpoch, SendRawTx,
+    StateEntries, StateEntryProof, StateEntryWithKey, StateKey,
     StateRootWithEpoch, StateRoots, StatusPingDeprecatedV1, StatusPingV2,
     StatusPongDeprecatedV1, StatusPongV2, StorageRootKey, StorageRootProof,
     StorageRootWithKey, StorageRoots, TxInfo, TxInfos, Txs, WitnessInfo,
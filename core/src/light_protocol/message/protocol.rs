@@ -6,7 +6,7 @@ use cfx_types::{Bloom, H160, H256};
 use rlp_derive::{RlpDecodable, RlpEncodable};
 
 use super::NodeType;
-use crate::message::RequestId;
+use crate::{block_data_manager::BlockRewardResult, message::RequestId};
 use cfx_internal_common::ChainIdParamsDeprecated;
 use cfx_storage::{NodeMerkleProof, StateProof, TrieProof};
 use primitives::{
@@ -314,3 +314,23 @@ pub struct StorageRoots {
     pub request_id: RequestId,
     pub roots: Vec<StorageRootWithKey>,
 }
+
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct GetBlockRewards {
+    pub request_id: RequestId,
+    pub epochs: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct BlockRewardsWithEpoch {
+    pub epoch: u64,
+
+    // rewards of the blocks in `epoch`, in execution order
+    pub epoch_rewards: Vec<BlockRewardResult>,
+}
+
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct BlockRewards {
+    pub request_id: RequestId,
+    pub rewards: Vec<BlockRewardsWithEpoch>,
+}
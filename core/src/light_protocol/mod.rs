@@ -13,12 +13,13 @@ pub mod query_service;
 use network::{service::ProtocolVersion, ProtocolId};
 
 const LIGHT_PROTOCOL_ID: ProtocolId = *b"clp"; // Conflux Light Protocol
-pub const LIGHT_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion(2);
+pub const LIGHT_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion(3);
 /// Support at most this number of old versions.
 const LIGHT_PROTOCOL_OLD_VERSIONS_TO_SUPPORT: u8 = 2;
 /// The version to pass to Message for their lifetime declaration.
 pub const LIGHT_PROTO_V1: ProtocolVersion = ProtocolVersion(1);
 pub const LIGHT_PROTO_V2: ProtocolVersion = ProtocolVersion(2);
+pub const LIGHT_PROTO_V3: ProtocolVersion = ProtocolVersion(3);
 
 use error::handle as handle_error;
 
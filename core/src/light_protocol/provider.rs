@@ -16,11 +16,12 @@ use crate::{
         message::{
             msgid, BlockHashes as GetBlockHashesResponse,
             BlockHeaders as GetBlockHeadersResponse,
+            BlockRewards as GetBlockRewardsResponse, BlockRewardsWithEpoch,
             BlockTxs as GetBlockTxsResponse, BlockTxsWithHash, BloomWithEpoch,
             Blooms as GetBloomsResponse, GetBlockHashesByEpoch,
-            GetBlockHeaders, GetBlockTxs, GetBlooms, GetReceipts,
-            GetStateEntries, GetStateRoots, GetStorageRoots, GetTxInfos,
-            GetTxs, GetWitnessInfo, NewBlockHashes, NodeType,
+            GetBlockHeaders, GetBlockRewards, GetBlockTxs, GetBlooms,
+            GetReceipts, GetStateEntries, GetStateRoots, GetStorageRoots,
+            GetTxInfos, GetTxs, GetWitnessInfo, NewBlockHashes, NodeType,
             Receipts as GetReceiptsResponse, ReceiptsWithEpoch, SendRawTx,
             StateEntries as GetStateEntriesResponse, StateEntryProof,
             StateEntryWithKey, StateKey, StateRootWithEpoch,
@@ -32,7 +33,7 @@ use crate::{
             WitnessInfo as GetWitnessInfoResponse,
         },
         LIGHT_PROTOCOL_ID, LIGHT_PROTOCOL_OLD_VERSIONS_TO_SUPPORT,
-        LIGHT_PROTOCOL_VERSION, LIGHT_PROTO_V1,
+        LIGHT_PROTOCOL_VERSION, LIGHT_PROTO_V1, LIGHT_PROTO_V3,
     },
     message::{decode_msg, decode_rlp_and_check_deprecation, Message, MsgId},
     sync::{message::Throttled, SynchronizationGraph},
@@ -193,6 +194,7 @@ impl Provider {
             msgid::GET_BLOCK_TXS => self.on_get_block_txs(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::GET_TX_INFOS => self.on_get_tx_infos(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::GET_STORAGE_ROOTS => self.on_get_storage_roots(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
+            msgid::GET_BLOCK_REWARDS => self.on_get_block_rewards(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             _ => bail!(ErrorKind::UnknownMessage{id: msg_id}),
         }
     }
@@ -883,6 +885,49 @@ impl Provider {
         Ok(())
     }
 
+    fn on_get_block_rewards(
+        &self, io: &dyn NetworkContext, peer: &NodeId, req: GetBlockRewards,
+    ) -> Result<()> {
+        debug!("on_get_block_rewards req={:?}", req);
+
+        // `BlockRewards` cannot be sent to peers that negotiated v2
+        if self.peer_version(peer)? < LIGHT_PROTO_V3 {
+            bail!(ErrorKind::UnexpectedMessage {
+                expected: vec![],
+                received: msgid::GET_BLOCK_REWARDS,
+            });
+        }
+
+        self.throttle(peer, &req)?;
+        let request_id = req.request_id;
+
+        let it = req.epochs.into_iter().take(MAX_ITEMS_TO_SEND).map(|epoch| {
+            self.ledger.block_rewards_of(epoch).map(|epoch_rewards| {
+                BlockRewardsWithEpoch {
+                    epoch,
+                    epoch_rewards,
+                }
+            })
+        });
+
+        let (rewards, errors) = partition_results(it);
+
+        if !errors.is_empty() {
+            debug!(
+                "Errors while serving GetBlockRewards request: {:?}",
+                errors
+            );
+        }
+
+        let msg: Box<dyn Message> = Box::new(GetBlockRewardsResponse {
+            request_id,
+            rewards,
+        });
+
+        msg.send(io, peer)?;
+        Ok(())
+    }
+
     fn broadcast(
         &self, io: &dyn NetworkContext, mut peers: Vec<NodeId>,
         msg: &dyn Message,
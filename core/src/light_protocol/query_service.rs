@@ -3,7 +3,9 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
+    block_data_manager::BlockRewardResult,
     consensus::SharedConsensusGraph,
+    executive::{ExecutionOutcome, Executive},
     light_protocol::{
         common::{FullPeerFilter, LedgerInfo},
         handler::sync::TxInfoValidated,
@@ -11,14 +13,19 @@ use crate::{
         Error, ErrorKind, Handler as LightHandler, LightNodeConfiguration,
         LIGHT_PROTOCOL_ID, LIGHT_PROTOCOL_VERSION,
     },
-    rpc_errors::{account_result_to_rpc_result, Error as RpcError},
-    state::COMMISSION_PRIVILEGE_SPECIAL_KEY,
+    rpc_errors::{
+        account_result_to_rpc_result, invalid_params_check, Error as RpcError,
+    },
+    state::{State, COMMISSION_PRIVILEGE_SPECIAL_KEY},
     sync::SynchronizationGraph,
+    verification::{VerificationConfig, VerifyTxLocalMode, VerifyTxMode},
+    vm::Env,
     ConsensusGraph, Notifications,
 };
 use cfx_addr::Network;
 use cfx_parameters::{
     consensus::DEFERRED_STATE_EPOCH_COUNT,
+    consensus_internal::REWARD_EPOCH_COUNT,
     internal_contract_addresses::{
         SPONSOR_WHITELIST_CONTROL_CONTRACT_ADDRESS,
         STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
@@ -26,11 +33,17 @@ use cfx_parameters::{
     light::{
         GAS_PRICE_BATCH_SIZE, GAS_PRICE_BLOCK_SAMPLE_SIZE,
         GAS_PRICE_TRANSACTION_SAMPLE_SIZE, LOG_FILTERING_LOOKAHEAD,
-        MAX_POLL_TIME, TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
+        MAX_POLL_TIME, MAX_VIRTUAL_CALL_ROUNDS,
+        TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
         TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM,
     },
 };
-use cfx_statedb::{ACCUMULATE_INTEREST_RATE_KEY, INTEREST_RATE_KEY};
+use cfx_statedb::{
+    Result as DbResult, StateDb, ACCUMULATE_INTEREST_RATE_KEY,
+    INTEREST_RATE_KEY, TOTAL_BANK_TOKENS_KEY, TOTAL_STORAGE_TOKENS_KEY,
+    TOTAL_TOKENS_KEY,
+};
+use cfx_storage::StorageManagerTrait;
 use cfx_types::{
     address_util::AddressUtil, BigEndianHash, Bloom, H160, H256,
     KECCAK_EMPTY_BLOOM, U256,
@@ -48,7 +61,12 @@ use primitives::{
     VoteStakeList,
 };
 use rlp::Rlp;
-use std::{collections::BTreeSet, future::Future, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
 
 pub struct TxInfo {
     pub tx: SignedTransaction,
@@ -98,6 +116,9 @@ pub struct QueryService {
 
     // shared network service
     network: Arc<NetworkService>,
+
+    // used to verify the transactions of virtual calls
+    verification_config: VerificationConfig,
 }
 
 impl QueryService {
@@ -107,6 +128,7 @@ impl QueryService {
         notifications: Arc<Notifications>, config: LightNodeConfiguration,
     ) -> Self
     {
+        let verification_config = graph.verification_config.clone();
         let handler = Arc::new(LightHandler::new(
             consensus.clone(),
             graph,
@@ -122,6 +144,7 @@ impl QueryService {
             handler,
             ledger,
             network,
+            verification_config,
         }
     }
 
@@ -216,6 +239,22 @@ impl QueryService {
         .map(|receipts| (epoch, receipts))
     }
 
+    async fn retrieve_block_rewards(
+        &self, epoch: u64,
+    ) -> Result<Vec<BlockRewardResult>, Error> {
+        trace!("retrieve_block_rewards epoch = {}", epoch);
+
+        with_timeout(
+            *MAX_POLL_TIME,
+            format!(
+                "Timeout while retrieving block rewards for epoch {:?}",
+                epoch
+            ),
+            self.handler.block_rewards.request(epoch),
+        )
+        .await
+    }
+
     pub async fn retrieve_block_txs(
         &self, hash: H256,
     ) -> Result<Vec<SignedTransaction>, Error> {
@@ -582,6 +621,113 @@ impl QueryService {
             .map(|opt| opt.unwrap_or_default())
     }
 
+    pub async fn get_total_issued_tokens(
+        &self, epoch: EpochNumber,
+    ) -> Result<U256, Error> {
+        debug!("get_total_issued_tokens epoch={:?}", epoch);
+        self.retrieve_staking_contract_entry(epoch, TOTAL_TOKENS_KEY)
+            .await
+    }
+
+    pub async fn get_total_staking_tokens(
+        &self, epoch: EpochNumber,
+    ) -> Result<U256, Error> {
+        debug!("get_total_staking_tokens epoch={:?}", epoch);
+        self.retrieve_staking_contract_entry(epoch, TOTAL_BANK_TOKENS_KEY)
+            .await
+    }
+
+    pub async fn get_total_storage_tokens(
+        &self, epoch: EpochNumber,
+    ) -> Result<U256, Error> {
+        debug!("get_total_storage_tokens epoch={:?}", epoch);
+        self.retrieve_staking_contract_entry(epoch, TOTAL_STORAGE_TOKENS_KEY)
+            .await
+    }
+
+    async fn retrieve_staking_contract_entry(
+        &self, epoch: EpochNumber, key: &[u8],
+    ) -> Result<U256, Error> {
+        let epoch = self.get_height_from_epoch_number(epoch)?;
+
+        let key = StorageKey::new_storage_key(
+            &STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
+            key,
+        )
+        .to_key_bytes();
+
+        self.retrieve_state_entry::<U256>(epoch, key)
+            .await
+            .map(|opt| opt.unwrap_or_default())
+    }
+
+    /// Get the rewards of all blocks in `epoch`, along with the block hashes.
+    ///
+    /// Block rewards are not committed to in the headers, so we check them
+    /// against the verified receipts of `epoch`: the transaction fees and
+    /// secondary rewards distributed cannot exceed the amounts collected.
+    pub async fn get_block_rewards(
+        &self, epoch: EpochNumber,
+    ) -> Result<Vec<(H256, BlockRewardResult)>, Error> {
+        debug!("get_block_rewards epoch={:?}", epoch);
+
+        let epoch = self.get_height_from_epoch_number(epoch)?;
+
+        // rewards are computed when executing a later epoch
+        let latest_verifiable = self.get_latest_verifiable_epoch_number()?;
+
+        if epoch + REWARD_EPOCH_COUNT > latest_verifiable {
+            bail!(format!("Rewards of epoch {} not calculated yet", epoch));
+        }
+
+        let hashes = self
+            .consensus
+            .get_block_hashes_by_epoch(EpochNumber::Number(epoch))?;
+
+        let (rewards, (_, receipts)) = future::try_join(
+            self.retrieve_block_rewards(epoch),
+            self.retrieve_receipts(epoch),
+        )
+        .await?;
+
+        let fees_collected = receipts
+            .iter()
+            .flat_map(|block_receipts| block_receipts.receipts.iter())
+            .fold(U256::zero(), |acc, r| acc.saturating_add(r.gas_fee));
+
+        let secondary_collected =
+            receipts.iter().fold(U256::zero(), |acc, block_receipts| {
+                acc.saturating_add(block_receipts.secondary_reward)
+            });
+
+        let fees_distributed = rewards
+            .iter()
+            .fold(U256::zero(), |acc, r| acc.saturating_add(r.tx_fee));
+
+        // `total_reward >= base_reward + tx_fee` is checked during sync
+        let secondary_distributed =
+            rewards.iter().fold(U256::zero(), |acc, r| {
+                acc.saturating_add(r.total_reward - r.base_reward - r.tx_fee)
+            });
+
+        if fees_distributed > fees_collected
+            || secondary_distributed > secondary_collected
+        {
+            bail!(ErrorKind::InvalidBlockRewards {
+                epoch,
+                reason: format!(
+                    "rewards do not match receipts: fees {}/{}, secondary rewards {}/{}",
+                    fees_distributed,
+                    fees_collected,
+                    secondary_distributed,
+                    secondary_collected
+                ),
+            });
+        }
+
+        Ok(hashes.into_iter().zip(rewards.into_iter()).collect())
+    }
+
     pub async fn get_tx_info(&self, hash: H256) -> Result<TxInfo, Error> {
         debug!("get_tx_info hash={:?}", hash);
 
@@ -614,6 +760,168 @@ impl QueryService {
         })
     }
 
+    /// Execute `tx` virtually on top of the state of `epoch`.
+    ///
+    /// Light nodes do not maintain the state locally. Instead, we execute the
+    /// transaction on an empty local state with a cache of state entries
+    /// retrieved (and verified) on demand. If an execution accesses entries we
+    /// have not retrieved yet, we retrieve them and execute again. As the
+    /// execution is deterministic, the first execution that does not access
+    /// any new entries produces the final outcome. An execution which fails
+    /// because of a missing entry, e.g. with `IncompleteDatabase` for the code
+    /// or the deposit list of an account, is retried the same way.
+    pub async fn call_virtual(
+        &self, tx: SignedTransaction, epoch: EpochNumber,
+    ) -> Result<ExecutionOutcome, RpcError> {
+        debug!("call_virtual tx={:?} epoch={:?}", tx.hash(), epoch);
+
+        let epoch = self.get_height_from_epoch_number(epoch)?;
+        let env = self.virtual_call_env(&tx, epoch)?;
+        self.verify_virtual_call(&tx, &env)?;
+        let mut entries = HashMap::new();
+
+        for round in 0..MAX_VIRTUAL_CALL_ROUNDS {
+            let missing: Vec<_> = {
+                let (result, accessed_keys) =
+                    self.execute_virtual(&tx, &env, &entries)?;
+
+                let missing: Vec<_> = accessed_keys
+                    .into_iter()
+                    .filter(|key| !entries.contains_key(key))
+                    .collect();
+
+                if missing.is_empty() {
+                    return Ok(result?);
+                }
+
+                missing
+            };
+
+            trace!(
+                "call_virtual round {}: retrieving {} state entries",
+                round,
+                missing.len()
+            );
+
+            let values =
+                future::try_join_all(missing.iter().map(|key| {
+                    self.retrieve_state_entry_raw(epoch, key.clone())
+                }))
+                .await?;
+
+            for (key, value) in missing.into_iter().zip(values) {
+                entries.insert(key, value.map(Into::into));
+            }
+        }
+
+        bail!(Error::from(ErrorKind::InternalError(format!(
+            "Virtual call accesses too many state entries: not finished after {} rounds",
+            MAX_VIRTUAL_CALL_ROUNDS
+        ))));
+    }
+
+    /// Verify `tx` like a full node does before executing it virtually.
+    fn verify_virtual_call(
+        &self, tx: &SignedTransaction, env: &Env,
+    ) -> Result<(), RpcError> {
+        let machine = self.consensus.get_tx_pool().machine();
+        let spec = machine.spec(env.number);
+
+        invalid_params_check(
+            "tx",
+            self.verification_config.verify_transaction_common(
+                tx,
+                tx.chain_id,
+                env.epoch_height,
+                &machine.params().transition_heights,
+                VerifyTxMode::Local(VerifyTxLocalMode::Full, &spec),
+            ),
+        )
+    }
+
+    fn virtual_call_env(
+        &self, tx: &SignedTransaction, epoch: u64,
+    ) -> Result<Env, Error> {
+        let hashes = self
+            .consensus
+            .get_block_hashes_by_epoch(EpochNumber::Number(epoch))?;
+
+        let pivot_hash = *hashes.last().expect("pivot block always exists");
+        let data_man = self.consensus.get_data_manager();
+
+        let pivot_header = match data_man.block_header_by_hash(&pivot_hash) {
+            Some(header) => header,
+            None => bail!(ErrorKind::InternalError(format!(
+                "Header of pivot block {:?} not found",
+                pivot_hash
+            ))),
+        };
+
+        let start_block_number =
+            match data_man.get_epoch_execution_context(&pivot_hash) {
+                Some(ctx) => ctx.start_block_number + hashes.len() as u64,
+                None => bail!(ErrorKind::InternalError(format!(
+                    "Execution context of epoch {:?} not found",
+                    pivot_hash
+                ))),
+            };
+
+        let transaction_epoch_bound = self
+            .consensus
+            .as_any()
+            .downcast_ref::<ConsensusGraph>()
+            .expect("downcast should succeed")
+            .config
+            .transaction_epoch_bound;
+
+        // use the same author for all executions so that they are consistent
+        let author = {
+            let mut address = H160::random();
+            address.set_user_account_type_bits();
+            address
+        };
+
+        Ok(Env {
+            number: start_block_number,
+            author,
+            timestamp: pivot_header.timestamp(),
+            difficulty: Default::default(),
+            accumulated_gas_used: U256::zero(),
+            last_hash: pivot_hash,
+            gas_limit: tx.gas.clone(),
+            epoch_height: pivot_header.height() + 1,
+            transaction_epoch_bound,
+        })
+    }
+
+    /// Execute `tx` on a local state that only contains `entries` and return
+    /// the result along with the keys of all entries the execution accessed,
+    /// which are also returned if the execution fails.
+    fn execute_virtual(
+        &self, tx: &SignedTransaction, env: &Env,
+        entries: &HashMap<Vec<u8>, Option<Arc<[u8]>>>,
+    ) -> Result<(DbResult<ExecutionOutcome>, Vec<Vec<u8>>), Error>
+    {
+        let data_man = self.consensus.get_data_manager();
+
+        // the underlying storage is empty, all entries that are not in
+        // `entries` will be read as non-existent
+        let storage = data_man.storage_manager.get_state_for_genesis_write();
+        let db = StateDb::new_with_loaded_entries(
+            storage,
+            entries.iter().map(|(k, v)| (k.clone(), v.clone())),
+        );
+        let mut state = State::new(db)?;
+
+        let machine = self.consensus.get_tx_pool().machine();
+        let spec = machine.spec(env.number);
+
+        let result = Executive::new(&mut state, env, machine.as_ref(), &spec)
+            .transact_virtual(tx);
+
+        Ok((result, state.accessed_storage_keys()))
+    }
+
     /// Relay raw transaction to all peers.
     // TODO(thegaram): consider returning TxStatus instead of bool,
     // e.g. Failed, Sent/Pending, Confirmed, etc.
@@ -8,6 +8,7 @@ use crate::{
     },
     machine::Machine,
     state::State,
+    state_dump::{check_state_dump, import_state, StateDumpReader},
     verification::{compute_receipts_root, compute_transaction_root},
     vm::{CreateContractAddress, Env},
 };
@@ -100,6 +101,24 @@ pub fn load_secrets_file(
     Ok(accounts)
 }
 
+/// Open a state dump file to import as the genesis state. All entries are
+/// checked first, so that a malformed file is reported here instead of
+/// failing the import halfway through building the genesis block.
+pub fn load_state_file(
+    path: &String,
+) -> Result<StateDumpReader<BufReader<File>>, String> {
+    let open = || {
+        let file = File::open(path)
+            .map_err(|e| format!("failed to open file: {:?}", e))?;
+        StateDumpReader::new(BufReader::new(file))
+            .map_err(|e| format!("failed to read state dump header: {:?}", e))
+    };
+    let entries = check_state_dump(open()?)
+        .map_err(|e| format!("invalid state file {}: {}", path, e))?;
+    info!("State file {} has {} entries", path, entries);
+    open()
+}
+
 pub fn initialize_internal_contract_accounts(
     state: &mut dyn StateOpsTrait, addresses: &[Address],
     contract_start_nonce: U256,
@@ -155,26 +174,44 @@ pub fn genesis_contract_address_two_year() -> Address {
 
 /// ` test_net_version` is used to update the genesis author so that after
 /// resetting, the chain of the older version will be discarded
+///
+/// If `genesis_state` is set, the state in the file is imported as the
+/// genesis state, which already contains the internal contracts and the
+/// genesis contracts, so they are not initialized again. `genesis_accounts`
+/// are still credited on top of the imported state.
 pub fn genesis_block(
     storage_manager: &Arc<StorageManager>,
     genesis_accounts: HashMap<Address, U256>, test_net_version: Address,
     initial_difficulty: U256, machine: Arc<Machine>, need_to_execute: bool,
     genesis_chain_id: Option<u32>,
+    genesis_state: Option<StateDumpReader<BufReader<File>>>,
 ) -> Block
 {
-    let mut state =
-        State::new(StateDb::new(storage_manager.get_state_for_genesis_write()))
-            .expect("Failed to initialize state");
+    let mut state_db =
+        StateDb::new(storage_manager.get_state_for_genesis_write());
+    let import_genesis_state = genesis_state.is_some();
+    if let Some(reader) = genesis_state {
+        let header = reader.header().clone();
+        let entries = import_state(reader, &mut state_db)
+            .expect("Failed to import genesis state");
+        info!(
+            "Imported {} entries of the state at epoch {} ({:?}) as genesis",
+            entries, header.epoch_number, header.epoch_hash
+        );
+    }
+    let mut state = State::new(state_db).expect("Failed to initialize state");
 
     let mut genesis_block_author = test_net_version;
     genesis_block_author.set_user_account_type_bits();
 
     let mut total_balance = U256::from(0);
-    initialize_internal_contract_accounts(
-        &mut state,
-        machine.internal_contracts().initialized_at_genesis(),
-        machine.spec(0).contract_start_nonce,
-    );
+    if !import_genesis_state {
+        initialize_internal_contract_accounts(
+            &mut state,
+            machine.internal_contracts().initialized_at_genesis(),
+            machine.spec(0).contract_start_nonce,
+        );
+    }
     for (addr, balance) in genesis_accounts {
         state
             .add_balance(
@@ -193,23 +230,29 @@ pub fn genesis_block(
 
     let genesis_token_count =
         U256::from(GENESIS_TOKEN_COUNT_IN_CFX) * U256::from(ONE_CFX_IN_DRIP);
-    state.add_total_issued(genesis_token_count);
+    if !import_genesis_state {
+        state.add_total_issued(genesis_token_count);
+    }
     let two_year_unlock_token_count =
         U256::from(TWO_YEAR_UNLOCK_TOKEN_COUNT_IN_CFX)
             * U256::from(ONE_CFX_IN_DRIP);
     let four_year_unlock_token_count =
         genesis_token_count - two_year_unlock_token_count;
 
-    let genesis_account_init_balance =
-        U256::from(ONE_CFX_IN_DRIP) * 100 + genesis_token_count;
-    state
-        .add_balance(
-            &genesis_account_address,
-            &genesis_account_init_balance,
-            CleanupMode::NoEmpty,
-            /* account_start_nonce = */ U256::zero(),
-        )
-        .unwrap();
+    // The balance of the genesis account is part of the imported state and
+    // of its total issued tokens, so it is not credited again.
+    if !import_genesis_state {
+        let genesis_account_init_balance =
+            U256::from(ONE_CFX_IN_DRIP) * 100 + genesis_token_count;
+        state
+            .add_balance(
+                &genesis_account_address,
+                &genesis_account_init_balance,
+                CleanupMode::NoEmpty,
+                /* account_start_nonce = */ U256::zero(),
+            )
+            .unwrap();
+    }
 
     let mut debug_record = Some(ComputeEpochDebugRecord::default());
 
@@ -343,7 +386,8 @@ pub fn genesis_block(
         ),
     ];
 
-    if need_to_execute {
+    // The genesis contracts are part of the imported state.
+    if need_to_execute && !import_genesis_state {
         const CREATE2FACTORY_TX_INDEX: usize = 1;
         /*
         const TWO_YEAR_UNLOCK_TX_INDEX: usize = 2;
@@ -389,9 +433,12 @@ pub fn genesis_block(
         }
     }
 
-    state
-        .clean_account(&genesis_account_address)
-        .expect("Clean account failed");
+    // The account is kept as it is in the imported state, e.g. its nonce.
+    if !import_genesis_state {
+        state
+            .clean_account(&genesis_account_address)
+            .expect("Clean account failed");
+    }
 
     let state_root = state
         .compute_state_root(/* debug_record = */ debug_record.as_mut())
@@ -503,3 +550,156 @@ pub fn load_file(
 
     Ok(accounts)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{genesis_block, load_state_file, GENESIS_ACCOUNT_ADDRESS_STR};
+    use crate::{
+        machine::new_machine_with_builtin,
+        state::State,
+        state_dump::{
+            StateDumpEntry, StateDumpHeader, StateDumpWriter,
+            STATE_DUMP_VERSION,
+        },
+        vm_factory::VmFactory,
+    };
+    use cfx_parameters::internal_contract_addresses::STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS;
+    use cfx_state::state_trait::StateOpsTrait;
+    use cfx_statedb::{StateDb, TOTAL_TOKENS_KEY};
+    use cfx_storage::{
+        state_manager::StateManagerTrait,
+        tests::new_state_manager_for_unit_test, StateIndex,
+    };
+    use cfx_types::{address_util::AddressUtil, Address, H256, U256};
+    use std::{collections::HashMap, fs::File, io::Write, sync::Arc};
+    use tempdir::TempDir;
+
+    fn user_address(low: u64) -> Address {
+        let mut address = Address::from_low_u64_be(low);
+        address.set_user_account_type_bits();
+        address
+    }
+
+    fn account(address: Address, balance: U256) -> StateDumpEntry {
+        StateDumpEntry::Account {
+            address,
+            balance,
+            nonce: 1.into(),
+            code_hash: Default::default(),
+            staking_balance: 0.into(),
+            collateral_for_storage: 0.into(),
+            accumulated_interest_return: 0.into(),
+            admin: Address::zero(),
+            sponsor_info: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_genesis_block_with_state() {
+        let (imported_1, imported_2, genesis_account) =
+            (user_address(1), user_address(2), user_address(3));
+        let entries = vec![
+            account(imported_1, 10.into()),
+            account(imported_2, 20.into()),
+            StateDumpEntry::Storage {
+                address: *STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
+                key: TOTAL_TOKENS_KEY.to_vec(),
+                value: 30.into(),
+                owner: None,
+            },
+        ];
+
+        let dir = TempDir::new("genesis_state").unwrap();
+        let path = dir.path().join("state.jsonl");
+        let header = StateDumpHeader {
+            version: STATE_DUMP_VERSION,
+            epoch_number: 100,
+            epoch_hash: H256::repeat_byte(1),
+            state_root: H256::repeat_byte(2),
+        };
+        let mut writer =
+            StateDumpWriter::new(File::create(&path).unwrap(), &header)
+                .unwrap();
+        for entry in &entries {
+            writer.write_entry(entry).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let storage_manager = new_state_manager_for_unit_test();
+        let mut genesis_accounts = HashMap::new();
+        genesis_accounts.insert(genesis_account, U256::from(5));
+        let genesis = genesis_block(
+            &storage_manager,
+            genesis_accounts,
+            user_address(4),
+            U256::from(10),
+            Arc::new(new_machine_with_builtin(
+                Default::default(),
+                VmFactory::new(1024 * 32),
+            )),
+            true, /* need_to_execute */
+            None,
+            Some(load_state_file(&path.to_str().unwrap().into()).unwrap()),
+        );
+
+        let state = State::new(StateDb::new(
+            storage_manager
+                .get_state_no_commit(
+                    StateIndex::new_for_test_only_delta_mpt(&genesis.hash()),
+                    /* try_open = */ false,
+                )
+                .unwrap()
+                .unwrap(),
+        ))
+        .unwrap();
+
+        // Only the configured genesis accounts are credited on top of the
+        // imported state, so that the total issued tokens still add up.
+        let genesis_contract_account =
+            GENESIS_ACCOUNT_ADDRESS_STR.parse::<Address>().unwrap();
+        let balances: Vec<U256> = [
+            imported_1,
+            imported_2,
+            genesis_account,
+            genesis_contract_account,
+        ]
+        .iter()
+        .map(|address| state.balance(address).unwrap())
+        .collect();
+        assert_eq!(
+            balances,
+            vec![10.into(), 20.into(), 5.into(), U256::zero()]
+        );
+        assert_eq!(
+            state.total_issued_tokens(),
+            balances.iter().fold(U256::zero(), |sum, b| sum + b)
+        );
+    }
+
+    #[test]
+    fn test_load_invalid_state_file() {
+        let dir = TempDir::new("genesis_state").unwrap();
+        let path = dir.path().join("state.jsonl");
+        let header = StateDumpHeader {
+            version: STATE_DUMP_VERSION,
+            epoch_number: 100,
+            epoch_hash: H256::repeat_byte(1),
+            state_root: H256::repeat_byte(2),
+        };
+        let mut file = File::create(&path).unwrap();
+        let mut writer = StateDumpWriter::new(&mut file, &header).unwrap();
+        writer
+            .write_entry(&account(user_address(1), 10.into()))
+            .unwrap();
+        writer.finish().unwrap();
+        // Truncated in the middle of an entry.
+        file.write_all(b"{\"type\":\"account\",\"address\"")
+            .unwrap();
+        drop(file);
+
+        let error = load_state_file(&path.to_str().unwrap().into())
+            .err()
+            .unwrap();
+        assert!(error.contains("entry 1"), "{}", error);
+    }
+}
@@ -4,11 +4,16 @@
 
 pub use self::{
     account_entry::{OverlayAccount, COMMISSION_PRIVILEGE_SPECIAL_KEY},
+    state_override::{apply_state_override, AccountOverride, StateOverride},
     substate::{cleanup_mode, CallStackInfo, Substate},
 };
 
 use self::account_entry::{AccountEntry, AccountState};
-use crate::{hash::KECCAK_EMPTY, transaction_pool::SharedTransactionPool};
+use crate::{
+    hash::KECCAK_EMPTY,
+    trace::state_diff::{AccountDiff, Diff, StateDiff},
+    transaction_pool::SharedTransactionPool,
+};
 use cfx_bytes::Bytes;
 use cfx_internal_common::{
     debug::ComputeEpochDebugRecord, StateRootWithAuxInfo,
@@ -38,7 +43,7 @@ use primitives::{
     StorageLayout, StorageValue, VoteStakeList,
 };
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -46,6 +51,7 @@ mod account_entry;
 #[cfg(test)]
 mod account_entry_tests;
 pub mod prefetcher;
+mod state_override;
 #[cfg(test)]
 mod state_tests;
 mod substate;
@@ -75,6 +81,10 @@ struct StakingState {
 
 pub type State = StateGeneric<StorageState>;
 
+/// Values of the cached accounts at some point of the execution. The changes
+/// made afterwards are collected by `StateGeneric::diff_since`.
+pub struct CacheSnapshot(HashMap<Address, Option<OverlayAccount>>);
+
 pub struct StateGeneric<StateDbStorage: StorageStateTrait> {
     db: StateDb<StateDbStorage>,
 
@@ -230,33 +240,7 @@ impl<StateDbStorage: StorageStateTrait> StateTrait
     ) -> DbResult<StateRootWithAuxInfo> {
         debug!("state.compute_state_root");
 
-        assert!(self.checkpoints.get_mut().is_empty());
-        assert!(self.staking_state_checkpoints.get_mut().is_empty());
-
-        let mut sorted_dirty_accounts =
-            self.cache.get_mut().drain().collect::<Vec<_>>();
-        sorted_dirty_accounts.sort_by(|a, b| a.0.cmp(&b.0));
-
-        let mut killed_addresses = Vec::new();
-        for (address, entry) in sorted_dirty_accounts.iter_mut() {
-            entry.state = AccountState::Committed;
-            match &mut entry.account {
-                None => {
-                    killed_addresses.push(*address);
-                    self.accounts_to_notify.push(Err(*address));
-                }
-                Some(account) => {
-                    account.commit(
-                        self,
-                        address,
-                        debug_record.as_deref_mut(),
-                    )?;
-                    self.accounts_to_notify.push(Ok(account.as_account()?));
-                }
-            }
-        }
-        self.recycle_storage(killed_addresses, debug_record.as_deref_mut())?;
-        self.commit_staking_state(debug_record.as_deref_mut())?;
+        self.commit_cache_to_db(debug_record.as_deref_mut())?;
         self.db.compute_state_root(debug_record)
     }
 
@@ -1034,6 +1018,177 @@ impl<StateDbStorage: StorageStateTrait> StateGeneric<StateDbStorage> {
         }
     }
 
+    /// Write the changes of the cached accounts and the staking state into
+    /// the state db, without applying them to the storage. It's the first
+    /// step of `compute_state_root`, and also ends an epoch executed in
+    /// memory so that the next epoch can be executed on top of it.
+    pub fn commit_cache_to_db(
+        &mut self, mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> DbResult<()> {
+        assert!(self.checkpoints.get_mut().is_empty());
+        assert!(self.staking_state_checkpoints.get_mut().is_empty());
+
+        let mut sorted_dirty_accounts =
+            self.cache.get_mut().drain().collect::<Vec<_>>();
+        sorted_dirty_accounts.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut killed_addresses = Vec::new();
+        for (address, entry) in sorted_dirty_accounts.iter_mut() {
+            entry.state = AccountState::Committed;
+            match &mut entry.account {
+                None => {
+                    killed_addresses.push(*address);
+                    self.accounts_to_notify.push(Err(*address));
+                }
+                Some(account) => {
+                    account.commit(
+                        self,
+                        address,
+                        debug_record.as_deref_mut(),
+                    )?;
+                    self.accounts_to_notify.push(Ok(account.as_account()?));
+                }
+            }
+        }
+        self.recycle_storage(killed_addresses, debug_record.as_deref_mut())?;
+        self.commit_staking_state(debug_record)
+    }
+
+    /// Return the keys of all storage entries accessed through this state.
+    pub fn accessed_storage_keys(&self) -> Vec<Vec<u8>> {
+        self.db.accessed_keys()
+    }
+
+    /// Return the storage entries accessed through this state with their
+    /// current values, see `StateDb::accessed_entries`.
+    pub fn accessed_storage_entries(
+        &self,
+    ) -> Vec<(Vec<u8>, Option<Arc<[u8]>>)> {
+        self.db.accessed_entries()
+    }
+
+    /// Save the current value of every cached account.
+    pub fn cache_snapshot(&self) -> CacheSnapshot {
+        CacheSnapshot(
+            self.cache
+                .read()
+                .iter()
+                .map(|(address, entry)| {
+                    (
+                        *address,
+                        entry.account.as_ref().map(OverlayAccount::clone_dirty),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Collect the account-level changes made since `snapshot` was taken.
+    /// Only dirty accounts can have changed; accounts missing in the snapshot
+    /// are compared with their values in db.
+    pub fn diff_since(&self, snapshot: CacheSnapshot) -> DbResult<StateDiff> {
+        let mut snapshot = snapshot.0;
+        let mut diff = StateDiff::new();
+        for (address, entry) in self.cache.read().iter() {
+            if !entry.is_dirty() {
+                continue;
+            }
+            let before = match snapshot.remove(address) {
+                Some(account) => account,
+                None => self
+                    .db
+                    .get_account(address)?
+                    .map(|acc| OverlayAccount::from_loaded(address, acc)),
+            };
+            let account_diff = self.account_diff(
+                address,
+                before.as_ref(),
+                entry.account.as_ref(),
+            )?;
+            if !account_diff.is_empty() {
+                diff.insert(*address, account_diff);
+            }
+        }
+        Ok(diff)
+    }
+
+    fn account_diff(
+        &self, address: &Address, before: Option<&OverlayAccount>,
+        after: Option<&OverlayAccount>,
+    ) -> DbResult<AccountDiff>
+    {
+        let non_zero = |v: U256| if v.is_zero() { None } else { Some(v) };
+        let mut storage = BTreeMap::new();
+        if let Some(after) = after {
+            for (key, value) in after.storage_value_write_cache() {
+                let original = match before {
+                    Some(before) => before.storage_at(&self.db, key)?,
+                    None => U256::zero(),
+                };
+                let key_diff = Diff::new(non_zero(original), non_zero(*value));
+                if !key_diff.is_same() {
+                    storage.insert(key.clone(), key_diff);
+                }
+            }
+        }
+
+        let code = match (before, after) {
+            (Some(before), Some(after))
+                if before.code_hash() == after.code_hash() =>
+            {
+                Diff::Same
+            }
+            _ => Diff::new(
+                self.code_for_diff(address, before)?,
+                self.code_for_diff(address, after)?,
+            ),
+        };
+
+        Ok(AccountDiff {
+            balance: Diff::new(
+                before.map(|acc| *acc.balance()),
+                after.map(|acc| *acc.balance()),
+            ),
+            nonce: Diff::new(
+                before.map(|acc| *acc.nonce()),
+                after.map(|acc| *acc.nonce()),
+            ),
+            code,
+            staking_balance: Diff::new(
+                before.map(|acc| *acc.staking_balance()),
+                after.map(|acc| *acc.staking_balance()),
+            ),
+            collateral_for_storage: Diff::new(
+                before.map(|acc| *acc.collateral_for_storage()),
+                after.map(|acc| *acc.collateral_for_storage()),
+            ),
+            sponsor_info: Diff::new(
+                before.map(|acc| acc.sponsor_info().clone()),
+                after.map(|acc| acc.sponsor_info().clone()),
+            ),
+            storage,
+        })
+    }
+
+    fn code_for_diff(
+        &self, address: &Address, account: Option<&OverlayAccount>,
+    ) -> DbResult<Option<Bytes>> {
+        let account = match account {
+            None => return Ok(None),
+            Some(account) => account,
+        };
+        if account.code_hash() == KECCAK_EMPTY {
+            return Ok(Some(vec![]));
+        }
+        if let Some(code) = account.code() {
+            return Ok(Some((*code).clone()));
+        }
+        Ok(self
+            .db
+            .get_code(address, &account.code_hash())?
+            .map(|info| (*info.code).clone()))
+    }
+
     #[cfg(test)]
     pub fn new_contract(
         &mut self, contract: &Address, balance: U256, nonce: U256,
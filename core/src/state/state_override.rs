@@ -0,0 +1,238 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::hash::keccak;
+use cfx_bytes::Bytes;
+use cfx_parameters::staking::COLLATERAL_DRIPS_PER_STORAGE_KEY;
+use cfx_statedb::{Result as DbResult, StateDbExt, StateDbGeneric};
+use cfx_storage::StorageStateTrait;
+use cfx_types::{Address, H256, U256};
+use primitives::{Account, CodeInfo, SponsorInfo, StorageKey, StorageValue};
+use std::{collections::HashMap, sync::Arc};
+
+/// Values replacing those of an account before a simulation. Fields left
+/// as `None` keep their values in the state.
+///
+/// The sender of a simulated transaction always gets the nonce of the
+/// transaction and a balance of at least `U256::MAX / 2`, see
+/// `Executive::transact_virtual`, so `balance` and `nonce` only take effect
+/// for the other accounts.
+#[derive(Debug, Clone, Default)]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<U256>,
+    /// Only applicable to contract addresses.
+    pub code: Option<Bytes>,
+    /// Storage slots to set. Slots not listed keep their values.
+    pub storage: HashMap<H256, U256>,
+    /// Only applicable to contract addresses.
+    pub sponsor_info: Option<SponsorInfo>,
+}
+
+pub type StateOverride = HashMap<Address, AccountOverride>;
+
+/// Writes the overrides into `db`. The writes are never committed, so `db`
+/// must be opened by `get_state_no_commit`.
+pub fn apply_state_override<Storage: StorageStateTrait>(
+    db: &mut StateDbGeneric<Storage>, state_override: &StateOverride,
+    account_start_nonce: &U256,
+) -> DbResult<()>
+{
+    for (address, account_override) in state_override {
+        let mut account = match db.get_account(address)? {
+            Some(account) => account,
+            None => Account::new_empty_with_balance(
+                address,
+                &U256::zero(),
+                account_start_nonce,
+            )?,
+        };
+        if let Some(balance) = account_override.balance {
+            account.balance = balance;
+        }
+        if let Some(nonce) = account_override.nonce {
+            account.nonce = nonce;
+        }
+        if let Some(sponsor_info) = &account_override.sponsor_info {
+            account.sponsor_info = sponsor_info.clone();
+        }
+        if let Some(code) = &account_override.code {
+            account.code_hash = keccak(code);
+            let code_info = CodeInfo {
+                code: Arc::new(code.clone()),
+                owner: *address,
+            };
+            db.set::<CodeInfo>(
+                StorageKey::new_code_key(address, &account.code_hash),
+                &code_info,
+                None,
+            )?;
+        }
+        db.set::<Account>(
+            StorageKey::new_account_key(address),
+            &account,
+            None,
+        )?;
+
+        for (key, value) in &account_override.storage {
+            let storage_key =
+                StorageKey::new_storage_key(address, key.as_ref());
+            let original = db.get::<StorageValue>(storage_key)?;
+            if value.is_zero() {
+                if let Some(original) = original {
+                    // Release the collateral of the deleted entry as the
+                    // executive does.
+                    let owner = original.owner.unwrap_or(*address);
+                    update_storage_collateral(
+                        db,
+                        &owner,
+                        false, /* occupy */
+                        account_start_nonce,
+                    )?;
+                    db.delete(storage_key, None)?;
+                }
+                continue;
+            }
+            // Keep the owner of an existing entry so that collateral is
+            // released to the right account. A new entry is owned by the
+            // contract, which pays its collateral.
+            let owner = match original {
+                Some(original) => original.owner,
+                None => {
+                    update_storage_collateral(
+                        db,
+                        address,
+                        true, /* occupy */
+                        account_start_nonce,
+                    )?;
+                    None
+                }
+            };
+            db.set::<StorageValue>(
+                storage_key,
+                &StorageValue {
+                    value: *value,
+                    owner,
+                },
+                None,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds or removes the collateral of one storage entry for `owner` and in
+/// `total_storage_tokens`, so that the entry can be released later by the
+/// simulated transactions. The collateral is not paid from the balance.
+fn update_storage_collateral<Storage: StorageStateTrait>(
+    db: &mut StateDbGeneric<Storage>, owner: &Address, occupy: bool,
+    account_start_nonce: &U256,
+) -> DbResult<()>
+{
+    let mut account = match db.get_account(owner)? {
+        Some(account) => account,
+        None => Account::new_empty_with_balance(
+            owner,
+            &U256::zero(),
+            account_start_nonce,
+        )?,
+    };
+    let mut total_storage_tokens = db.get_total_storage_tokens()?;
+    let collateral = *COLLATERAL_DRIPS_PER_STORAGE_KEY;
+    if occupy {
+        account.collateral_for_storage += collateral;
+        total_storage_tokens += collateral;
+    } else {
+        account.collateral_for_storage =
+            account.collateral_for_storage.saturating_sub(collateral);
+        total_storage_tokens = total_storage_tokens.saturating_sub(collateral);
+    }
+    db.set::<Account>(StorageKey::new_account_key(owner), &account, None)?;
+    db.set_total_storage_tokens(&total_storage_tokens, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_state_override, AccountOverride, StateOverride};
+    use crate::{state::State, vm::Spec};
+    use cfx_parameters::staking::COLLATERAL_DRIPS_PER_STORAGE_KEY;
+    use cfx_state::state_trait::StateOpsTrait;
+    use cfx_statedb::StateDb;
+    use cfx_storage::{
+        tests::new_state_manager_for_unit_test, StorageManagerTrait,
+    };
+    use cfx_types::{address_util::AddressUtil, Address, H256, U256};
+
+    fn storage_key(slot: u64) -> H256 { H256::from_low_u64_be(slot) }
+
+    #[test]
+    fn test_apply_state_override() {
+        let storage_manager = new_state_manager_for_unit_test();
+        let mut db =
+            StateDb::new(storage_manager.get_state_for_genesis_write());
+        let start_nonce = Spec::new_spec_for_test().account_start_nonce;
+
+        let mut contract = Address::zero();
+        contract.set_contract_type_bits();
+        let code = vec![0x60, 0x00, 0x54, 0x00];
+
+        let mut first = StateOverride::new();
+        first.insert(
+            contract,
+            AccountOverride {
+                balance: Some(1_000.into()),
+                nonce: Some(3.into()),
+                code: Some(code.clone()),
+                storage: vec![
+                    (storage_key(1), 5.into()),
+                    (storage_key(2), 7.into()),
+                ]
+                .into_iter()
+                .collect(),
+                sponsor_info: None,
+            },
+        );
+        apply_state_override(&mut db, &first, &start_nonce).unwrap();
+
+        // Fields left as `None` keep the overridden values and a zero value
+        // deletes the slot and releases its collateral.
+        let mut second = StateOverride::new();
+        second.insert(
+            contract,
+            AccountOverride {
+                balance: Some(2_000.into()),
+                storage: vec![(storage_key(2), U256::zero())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+        );
+        apply_state_override(&mut db, &second, &start_nonce).unwrap();
+
+        let state = State::new(db).unwrap();
+        assert_eq!(state.balance(&contract).unwrap(), U256::from(2_000));
+        assert_eq!(state.nonce(&contract).unwrap(), U256::from(3));
+        assert_eq!(*state.code(&contract).unwrap().unwrap(), code);
+        assert_eq!(
+            state
+                .storage_at(&contract, storage_key(1).as_ref())
+                .unwrap(),
+            U256::from(5)
+        );
+        assert_eq!(
+            state
+                .storage_at(&contract, storage_key(2).as_ref())
+                .unwrap(),
+            U256::zero()
+        );
+        assert_eq!(
+            state.collateral_for_storage(&contract).unwrap(),
+            *COLLATERAL_DRIPS_PER_STORAGE_KEY
+        );
+        assert_eq!(
+            state.total_storage_tokens(),
+            *COLLATERAL_DRIPS_PER_STORAGE_KEY
+        );
+    }
+}
@@ -5,7 +5,8 @@
 use super::{CleanupMode, CollateralCheckResult, State, Substate};
 use crate::{
     spec::genesis::DEV_GENESIS_KEY_PAIR,
-    test_helpers::get_state_for_genesis_write, vm::Spec,
+    test_helpers::get_state_for_genesis_write, trace::state_diff::Diff,
+    vm::Spec,
 };
 use cfx_parameters::{consensus::ONE_CFX_IN_DRIP, staking::*};
 use cfx_state::{
@@ -1752,3 +1753,41 @@ fn test_automatic_collateral_contract_account() {
     assert_eq!(state.total_storage_tokens(), U256::from(0));
     assert_eq!(state.bump_block_number_accumulate_interest(), U256::from(0));
 }
+
+#[test]
+fn diff_since_snapshot() {
+    let storage_manager = new_state_manager_for_unit_test();
+    let mut state = get_state_for_genesis_write(&storage_manager);
+    let start_nonce = Spec::new_spec_for_test().account_start_nonce;
+    let mut a = Address::zero();
+    a.set_user_account_type_bits();
+    let mut b = Address::from_low_u64_be(1);
+    b.set_user_account_type_bits();
+    state
+        .add_balance(&a, &U256::from(100), CleanupMode::NoEmpty, start_nonce)
+        .unwrap();
+
+    let snapshot = state.cache_snapshot();
+    state
+        .transfer_balance(
+            &a,
+            &b,
+            &U256::from(30),
+            CleanupMode::NoEmpty,
+            start_nonce,
+        )
+        .unwrap();
+    state.inc_nonce(&a, &start_nonce).unwrap();
+    let diff = state.diff_since(snapshot).unwrap();
+
+    assert_eq!(diff.len(), 2);
+    assert_eq!(
+        diff[&a].balance,
+        Diff::Changed(U256::from(100), U256::from(70))
+    );
+    assert_eq!(diff[&a].nonce, Diff::Changed(start_nonce, start_nonce + 1));
+    assert_eq!(diff[&b].balance, Diff::Born(U256::from(30)));
+    assert_eq!(diff[&b].nonce, Diff::Born(start_nonce));
+    assert_eq!(diff[&b].code, Diff::Born(vec![]));
+    assert!(diff[&a].code.is_same());
+}
@@ -0,0 +1,650 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Export of the full state at an epoch into a portable file.
+//!
+//! The file is in the JSON lines format. The first line is the
+//! [`StateDumpHeader`], which records the format version and the epoch of the
+//! state. Each of the following lines is a [`StateDumpEntry`], tagged by its
+//! `type`:
+//!
+//! * `account`: balance, nonce, code hash, staking and collateral of an
+//! account, and the admin and sponsors of a contract.
+//! * `storage`: a storage entry of a contract and its collateral owner.
+//! * `code`: the code of a contract and its collateral owner.
+//! * `storageLayout`: the storage layout of a contract.
+//! * `depositList`: the staking deposits of an account.
+//! * `voteList`: the locked staking votes of an account.
+//!
+//! A file can be imported as the genesis state of a new chain with
+//! [`import_state`], see the `genesis_state` configuration.
+//!
+//! Addresses are 0x-prefixed hex addresses rather than base32 addresses, so
+//! that the file does not depend on the network id. Numbers are 0x-prefixed
+//! hex quantities and binary data is 0x-prefixed hex strings. The entries are
+//! in no particular order.
+
+use cfx_statedb::StateDbGeneric;
+use cfx_storage::{
+    KVInserter, MptKeyValue, Result as StorageResult, StorageStateTrait,
+};
+use cfx_types::{Address, H256, U256};
+use primitives::{
+    account::AccountError, Account, CodeInfo, DepositInfo, DepositList,
+    SkipInputCheck, SponsorInfo, StorageKey, StorageValue, VoteStakeInfo,
+    VoteStakeList,
+};
+use rlp::Rlp;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, Write},
+    sync::Arc,
+};
+
+/// Version of the state dump format, increased on incompatible changes.
+pub const STATE_DUMP_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateDumpHeader {
+    pub version: u32,
+    pub epoch_number: u64,
+    /// Hash of the pivot block of the epoch.
+    pub epoch_hash: H256,
+    /// The state root committed in the blocks referring to the epoch.
+    pub state_root: H256,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StateDumpEntry {
+    #[serde(rename_all = "camelCase")]
+    Account {
+        address: Address,
+        balance: U256,
+        nonce: U256,
+        code_hash: H256,
+        staking_balance: U256,
+        collateral_for_storage: U256,
+        accumulated_interest_return: U256,
+        admin: Address,
+        sponsor_info: SponsorInfo,
+    },
+    #[serde(rename_all = "camelCase")]
+    Storage {
+        address: Address,
+        #[serde(with = "hex_bytes")]
+        key: Vec<u8>,
+        value: U256,
+        /// `None` if the entry is owned by the contract itself.
+        owner: Option<Address>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Code {
+        address: Address,
+        code_hash: H256,
+        #[serde(with = "hex_bytes")]
+        code: Vec<u8>,
+        owner: Address,
+    },
+    #[serde(rename_all = "camelCase")]
+    StorageLayout {
+        address: Address,
+        #[serde(with = "hex_bytes")]
+        layout: Vec<u8>,
+    },
+    #[serde(rename_all = "camelCase")]
+    DepositList {
+        address: Address,
+        deposits: Vec<DepositInfo>,
+    },
+    #[serde(rename_all = "camelCase")]
+    VoteList {
+        address: Address,
+        votes: Vec<VoteStakeInfo>,
+    },
+}
+
+impl StateDumpEntry {
+    /// Decode the key/value pair in the snapshot format. Returns `None` for
+    /// the keys which are not part of the state data.
+    pub fn from_key_value(
+        key: &[u8], value: &[u8],
+    ) -> Result<Option<Self>, AccountError> {
+        let rlp = Rlp::new(value);
+        let entry = match StorageKey::from_key_bytes::<SkipInputCheck>(key) {
+            StorageKey::AccountKey(address_bytes) => {
+                let account = Account::new_from_rlp(
+                    Address::from_slice(address_bytes),
+                    &rlp,
+                )?;
+                StateDumpEntry::Account {
+                    address: *account.address(),
+                    balance: account.balance,
+                    nonce: account.nonce,
+                    code_hash: account.code_hash,
+                    staking_balance: account.staking_balance,
+                    collateral_for_storage: account.collateral_for_storage,
+                    accumulated_interest_return: account
+                        .accumulated_interest_return,
+                    admin: account.admin,
+                    sponsor_info: account.sponsor_info,
+                }
+            }
+            StorageKey::StorageKey {
+                address_bytes,
+                storage_key,
+            } => {
+                let storage_value: StorageValue = rlp.as_val()?;
+                StateDumpEntry::Storage {
+                    address: Address::from_slice(address_bytes),
+                    key: storage_key.to_vec(),
+                    value: storage_value.value,
+                    owner: storage_value.owner,
+                }
+            }
+            StorageKey::CodeKey {
+                address_bytes,
+                code_hash_bytes,
+            } => {
+                let code_info: CodeInfo = rlp.as_val()?;
+                StateDumpEntry::Code {
+                    address: Address::from_slice(address_bytes),
+                    code_hash: H256::from_slice(code_hash_bytes),
+                    code: code_info.code.to_vec(),
+                    owner: code_info.owner,
+                }
+            }
+            StorageKey::StorageRootKey(address_bytes) => {
+                StateDumpEntry::StorageLayout {
+                    address: Address::from_slice(address_bytes),
+                    layout: value.to_vec(),
+                }
+            }
+            StorageKey::DepositListKey(address_bytes) => {
+                let deposit_list: DepositList = rlp.as_val()?;
+                StateDumpEntry::DepositList {
+                    address: Address::from_slice(address_bytes),
+                    deposits: deposit_list.0,
+                }
+            }
+            StorageKey::VoteListKey(address_bytes) => {
+                let vote_list: VoteStakeList = rlp.as_val()?;
+                StateDumpEntry::VoteList {
+                    address: Address::from_slice(address_bytes),
+                    votes: vote_list.0,
+                }
+            }
+            StorageKey::CodeRootKey(_) => return Ok(None),
+        };
+        Ok(Some(entry))
+    }
+
+    /// Encode the entry into the key and value in the snapshot format, the
+    /// reverse of `from_key_value`.
+    pub fn to_key_value(
+        &self,
+    ) -> Result<(StorageKey<'_>, Box<[u8]>), AccountError> {
+        let key_value = match self {
+            StateDumpEntry::Account {
+                address,
+                balance,
+                nonce,
+                code_hash,
+                staking_balance,
+                collateral_for_storage,
+                accumulated_interest_return,
+                admin,
+                sponsor_info,
+            } => {
+                let mut account =
+                    Account::new_empty_with_balance(address, balance, nonce)?;
+                account.code_hash = *code_hash;
+                account.staking_balance = *staking_balance;
+                account.collateral_for_storage = *collateral_for_storage;
+                account.accumulated_interest_return =
+                    *accumulated_interest_return;
+                account.admin = *admin;
+                account.sponsor_info = sponsor_info.clone();
+                (StorageKey::new_account_key(address), rlp::encode(&account))
+            }
+            StateDumpEntry::Storage {
+                address,
+                key,
+                value,
+                owner,
+            } => (
+                StorageKey::new_storage_key(address, key),
+                rlp::encode(&StorageValue {
+                    value: *value,
+                    owner: *owner,
+                }),
+            ),
+            StateDumpEntry::Code {
+                address,
+                code_hash,
+                code,
+                owner,
+            } => (
+                StorageKey::new_code_key(address, code_hash),
+                rlp::encode(&CodeInfo {
+                    code: Arc::new(code.clone()),
+                    owner: *owner,
+                }),
+            ),
+            StateDumpEntry::StorageLayout { address, layout } => {
+                (StorageKey::new_storage_root_key(address), layout.clone())
+            }
+            StateDumpEntry::DepositList { address, deposits } => (
+                StorageKey::new_deposit_list_key(address),
+                rlp::encode(&DepositList(deposits.clone())),
+            ),
+            StateDumpEntry::VoteList { address, votes } => (
+                StorageKey::new_vote_list_key(address),
+                rlp::encode(&VoteStakeList(votes.clone())),
+            ),
+        };
+        Ok((key_value.0, key_value.1.into_boxed_slice()))
+    }
+}
+
+/// Writes the key/value pairs of the state as JSON lines.
+pub struct StateDumpWriter<W: Write> {
+    writer: W,
+    entries: u64,
+}
+
+impl<W: Write> StateDumpWriter<W> {
+    pub fn new(mut writer: W, header: &StateDumpHeader) -> io::Result<Self> {
+        serde_json::to_writer(&mut writer, header)?;
+        writer.write_all(b"\n")?;
+        Ok(StateDumpWriter { writer, entries: 0 })
+    }
+
+    pub fn write_entry(&mut self, entry: &StateDumpEntry) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, entry)?;
+        self.writer.write_all(b"\n")?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Flush the writer and return the number of entries written.
+    pub fn finish(mut self) -> io::Result<u64> {
+        self.writer.flush()?;
+        Ok(self.entries)
+    }
+}
+
+impl<W: Write> KVInserter<MptKeyValue> for StateDumpWriter<W> {
+    fn push(&mut self, kv: MptKeyValue) -> StorageResult<()> {
+        let (key, value) = kv;
+        match StateDumpEntry::from_key_value(&key, &value)? {
+            Some(entry) => Ok(self.write_entry(&entry)?),
+            None => {
+                debug!("Skip key {:?} in state dump", key);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Dump all entries of `state` into `writer`, and return the number of
+/// entries.
+pub fn dump_state<S: StorageStateTrait, W: Write>(
+    state: &mut S, header: &StateDumpHeader, writer: W,
+) -> Result<u64, String> {
+    let mut dump_writer = StateDumpWriter::new(writer, header)
+        .map_err(|e| format!("Failed to write state dump header: {}", e))?;
+    state
+        .read_all(&mut dump_writer)
+        .map_err(|e| format!("Failed to dump state: {}", e))?;
+    dump_writer
+        .finish()
+        .map_err(|e| format!("Failed to write state dump: {}", e))
+}
+
+/// Reads the entries of a state dump file, after checking its header.
+pub struct StateDumpReader<R: BufRead> {
+    reader: R,
+    header: StateDumpHeader,
+    line: String,
+}
+
+impl<R: BufRead> StateDumpReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let header: StateDumpHeader = serde_json::from_str(&line)?;
+        if header.version != STATE_DUMP_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported state dump version {}, expected {}",
+                    header.version, STATE_DUMP_VERSION
+                ),
+            ));
+        }
+        Ok(StateDumpReader {
+            reader,
+            header,
+            line,
+        })
+    }
+
+    pub fn header(&self) -> &StateDumpHeader { &self.header }
+}
+
+impl<R: BufRead> Iterator for StateDumpReader<R> {
+    type Item = io::Result<StateDumpEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if !self.line.trim().is_empty() {
+                return Some(
+                    serde_json::from_str(&self.line).map_err(Into::into),
+                );
+            }
+        }
+    }
+}
+
+/// Write all entries of `reader` into `state_db`, and return the number of
+/// entries. The entries are written as they are, so the statistics of the
+/// state, e.g. the total issued tokens, are restored as well.
+pub fn import_state<S: StorageStateTrait, R: BufRead>(
+    reader: StateDumpReader<R>, state_db: &mut StateDbGeneric<S>,
+) -> Result<u64, String> {
+    for_each_key_value(reader, |entry, key, value| {
+        state_db
+            .set_raw(key, value, /* debug_record = */ None)
+            .map_err(|e| format!("Failed to import entry {:?}: {}", entry, e))
+    })
+}
+
+/// Check that all entries of `reader` can be imported without writing them
+/// anywhere, and return the number of entries.
+pub fn check_state_dump<R: BufRead>(
+    reader: StateDumpReader<R>,
+) -> Result<u64, String> {
+    for_each_key_value(reader, |_, _, _| Ok(()))
+}
+
+fn for_each_key_value<R: BufRead, F>(
+    reader: StateDumpReader<R>, mut f: F,
+) -> Result<u64, String>
+where F: FnMut(&StateDumpEntry, StorageKey, Box<[u8]>) -> Result<(), String>
+{
+    let mut entries = 0;
+    for entry in reader {
+        let entry = entry.map_err(|e| {
+            format!("Failed to read entry {} of state dump: {}", entries, e)
+        })?;
+        let (key, value) = entry
+            .to_key_value()
+            .map_err(|e| format!("Invalid entry {:?}: {}", entry, e))?;
+        f(&entry, key, value)?;
+        entries += 1;
+    }
+    Ok(entries)
+}
+
+mod hex_bytes {
+    use rustc_hex::{FromHex, ToHex};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &[u8], serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", bytes.to_hex::<String>()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.strip_prefix("0x")
+            .unwrap_or(&s)
+            .from_hex()
+            .map_err(|e| D::Error::custom(format!("invalid hex: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        import_state, StateDumpEntry, StateDumpHeader, StateDumpReader,
+        StateDumpWriter,
+    };
+    use cfx_statedb::StateDb;
+    use cfx_storage::{
+        state_manager::StateManagerTrait,
+        tests::new_state_manager_for_unit_test, KVInserter,
+    };
+    use cfx_types::{Address, H256, U256};
+    use primitives::{
+        Account, CodeInfo, StorageKey, StorageValue, VoteStakeInfo,
+        VoteStakeList,
+    };
+    use std::{str::FromStr, sync::Arc};
+
+    fn contract_address() -> Address {
+        Address::from_str("8000000000000000000000000000000000000001").unwrap()
+    }
+
+    fn header() -> StateDumpHeader {
+        StateDumpHeader {
+            version: super::STATE_DUMP_VERSION,
+            epoch_number: 100,
+            epoch_hash: H256::repeat_byte(1),
+            state_root: H256::repeat_byte(2),
+        }
+    }
+
+    #[test]
+    fn test_from_key_value() {
+        let address = contract_address();
+        let mut account =
+            Account::new_empty_with_balance(&address, &10.into(), &1.into())
+                .unwrap();
+        account.code_hash = H256::repeat_byte(1);
+        account.admin = Address::repeat_byte(2);
+        assert_eq!(
+            StateDumpEntry::from_key_value(
+                &StorageKey::new_account_key(&address).to_key_bytes(),
+                &rlp::encode(&account),
+            )
+            .unwrap(),
+            Some(StateDumpEntry::Account {
+                address,
+                balance: 10.into(),
+                nonce: 1.into(),
+                code_hash: H256::repeat_byte(1),
+                staking_balance: 0.into(),
+                collateral_for_storage: 0.into(),
+                accumulated_interest_return: 0.into(),
+                admin: Address::repeat_byte(2),
+                sponsor_info: Default::default(),
+            })
+        );
+
+        let storage_value = StorageValue {
+            value: U256::from(7),
+            owner: Some(Address::repeat_byte(3)),
+        };
+        assert_eq!(
+            StateDumpEntry::from_key_value(
+                &StorageKey::new_storage_key(&address, &[4; 32]).to_key_bytes(),
+                &rlp::encode(&storage_value),
+            )
+            .unwrap(),
+            Some(StateDumpEntry::Storage {
+                address,
+                key: vec![4; 32],
+                value: 7.into(),
+                owner: Some(Address::repeat_byte(3)),
+            })
+        );
+
+        let code_hash = H256::repeat_byte(5);
+        let code_info = CodeInfo {
+            code: Arc::new(vec![0x60, 0x00]),
+            owner: Address::repeat_byte(3),
+        };
+        assert_eq!(
+            StateDumpEntry::from_key_value(
+                &StorageKey::new_code_key(&address, &code_hash).to_key_bytes(),
+                &rlp::encode(&code_info),
+            )
+            .unwrap(),
+            Some(StateDumpEntry::Code {
+                address,
+                code_hash,
+                code: vec![0x60, 0x00],
+                owner: Address::repeat_byte(3),
+            })
+        );
+
+        let votes = vec![VoteStakeInfo {
+            amount: 100.into(),
+            unlock_block_number: 1000,
+        }];
+        assert_eq!(
+            StateDumpEntry::from_key_value(
+                &StorageKey::new_vote_list_key(&address).to_key_bytes(),
+                &rlp::encode(&VoteStakeList(votes.clone())),
+            )
+            .unwrap(),
+            Some(StateDumpEntry::VoteList { address, votes })
+        );
+
+        // Not an account.
+        assert!(StateDumpEntry::from_key_value(
+            &StorageKey::new_account_key(&address).to_key_bytes(),
+            &[0x01],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_writer() {
+        let header = header();
+        let address = contract_address();
+        let entry = StateDumpEntry::Storage {
+            address,
+            key: vec![0xab, 0xcd],
+            value: 255.into(),
+            owner: None,
+        };
+
+        let mut output = Vec::new();
+        let mut writer = StateDumpWriter::new(&mut output, &header).unwrap();
+        writer
+            .push((
+                StorageKey::new_storage_key(&address, &[0xab, 0xcd])
+                    .to_key_bytes(),
+                rlp::encode(&StorageValue {
+                    value: 255.into(),
+                    owner: None,
+                })
+                .into(),
+            ))
+            .unwrap();
+        assert_eq!(writer.finish().unwrap(), 1);
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<StateDumpHeader>(lines[0]).unwrap(),
+            header
+        );
+        assert_eq!(
+            lines[1],
+            "{\"type\":\"storage\",\"address\":\
+             \"0x8000000000000000000000000000000000000001\",\"key\":\
+             \"0xabcd\",\"value\":\"0xff\",\"owner\":null}"
+        );
+        assert_eq!(
+            serde_json::from_str::<StateDumpEntry>(lines[1]).unwrap(),
+            entry
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        let address = contract_address();
+        let entries = vec![
+            StateDumpEntry::Account {
+                address,
+                balance: 10.into(),
+                nonce: 1.into(),
+                code_hash: H256::repeat_byte(5),
+                staking_balance: 2.into(),
+                collateral_for_storage: 3.into(),
+                accumulated_interest_return: 4.into(),
+                admin: Address::repeat_byte(2),
+                sponsor_info: Default::default(),
+            },
+            StateDumpEntry::Storage {
+                address,
+                key: vec![4; 32],
+                value: 7.into(),
+                owner: Some(Address::repeat_byte(3)),
+            },
+            StateDumpEntry::Code {
+                address,
+                code_hash: H256::repeat_byte(5),
+                code: vec![0x60, 0x00],
+                owner: Address::repeat_byte(3),
+            },
+            StateDumpEntry::VoteList {
+                address,
+                votes: vec![VoteStakeInfo {
+                    amount: 100.into(),
+                    unlock_block_number: 1000,
+                }],
+            },
+        ];
+
+        let mut output = Vec::new();
+        let mut writer = StateDumpWriter::new(&mut output, &header()).unwrap();
+        for entry in &entries {
+            writer.write_entry(entry).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = StateDumpReader::new(&output[..]).unwrap();
+        assert_eq!(*reader.header(), header());
+        let storage_manager = new_state_manager_for_unit_test();
+        let mut state_db =
+            StateDb::new(storage_manager.get_state_for_genesis_write());
+        assert_eq!(import_state(reader, &mut state_db).unwrap(), 4);
+
+        for entry in &entries {
+            let (key, value) = entry.to_key_value().unwrap();
+            assert_eq!(
+                StateDumpEntry::from_key_value(&key.to_key_bytes(), &value)
+                    .unwrap()
+                    .as_ref(),
+                Some(entry)
+            );
+            assert_eq!(
+                state_db.get_raw(key).unwrap().as_deref(),
+                Some(&*value)
+            );
+        }
+
+        // Files of other versions are rejected.
+        let mut header = header();
+        header.version += 1;
+        let output = serde_json::to_string(&header).unwrap();
+        assert!(StateDumpReader::new(output.as_bytes()).is_err());
+    }
+}
@@ -9,20 +9,51 @@ use crate::{
     },
 };
 use network::{node_table::NodeId, NetworkContext};
+use std::cell::Cell;
+
+/// A response is considered fast if it arrives within this fraction of the
+/// request timeout.
+const FAST_RESPONSE_TIMEOUT_DIVISOR: u32 = 4;
 
 pub struct Context<'a> {
     pub io: &'a dyn NetworkContext,
     pub node_id: NodeId,
     pub manager: &'a SynchronizationProtocolHandler,
+    /// Set if the message handled is a fast response to a request, which
+    /// raises the reputation of the peer if the response is handled
+    /// successfully.
+    pub fast_response: Cell<bool>,
 }
 
 impl<'a> Context<'a> {
+    pub fn new(
+        io: &'a dyn NetworkContext, node_id: NodeId,
+        manager: &'a SynchronizationProtocolHandler,
+    ) -> Self
+    {
+        Context {
+            io,
+            node_id,
+            manager,
+            fast_response: Cell::new(false),
+        }
+    }
+
     pub fn match_request(
         &self, request_id: u64,
     ) -> Result<RequestMessage, Error> {
-        self.manager
+        let message = self
+            .manager
             .request_manager
-            .match_request(&self.node_id, request_id)
+            .match_request(&self.node_id, request_id)?;
+        if let Some(sent_at) = message.sent_at {
+            let timeout =
+                message.request.timeout(&self.manager.protocol_config);
+            self.fast_response.set(
+                sent_at.elapsed() * FAST_RESPONSE_TIMEOUT_DIVISOR <= timeout,
+            );
+        }
+        Ok(message)
     }
 
     pub fn send_response(&self, response: &dyn Message) -> Result<(), Error> {
@@ -101,6 +101,7 @@ impl Handleable for StatusV2 {
                 notified_capabilities: Default::default(),
                 throttling,
                 throttled_msgs: Default::default(),
+                reputation: ctx.io.get_peer_reputation(&ctx.node_id),
             };
 
             peer_state
@@ -219,6 +220,7 @@ impl Handleable for StatusV3 {
                 notified_capabilities: Default::default(),
                 throttling,
                 throttled_msgs: Default::default(),
+                reputation: ctx.io.get_peer_reputation(&ctx.node_id),
             };
 
             peer_state
@@ -27,7 +27,7 @@ use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use metrics::{
     register_meter_with_group, Gauge, GaugeUsize, Meter, MeterTimer,
 };
-use network::{node_table::NodeId, NetworkContext};
+use network::{node_table::NodeId, NetworkContext, ReputationEvent};
 use parking_lot::{Mutex, RwLock};
 use primitives::{SignedTransaction, TransactionWithSignature};
 pub use request_handler::{
@@ -864,8 +864,14 @@ impl RequestManager {
         debug!("resend_timeout_requests: start");
         let timeout_requests =
             self.request_handler.process_timeout_requests(io);
-        for req in timeout_requests {
+        for (peer, req) in timeout_requests {
             debug!("Timeout requests: {:?}", req);
+            self.syn.update_peer_reputation(
+                io,
+                &peer,
+                ReputationEvent::Timeout,
+                "request timeout",
+            );
             self.resend_request_to_another_peer(io, &req);
         }
     }
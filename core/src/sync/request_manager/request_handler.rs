@@ -158,9 +158,10 @@ impl RequestHandler {
         timeout_requests
     }
 
+    /// Returns the timeout requests along with the peers they were sent to.
     pub fn process_timeout_requests(
         &self, io: &dyn NetworkContext,
-    ) -> Vec<RequestMessage> {
+    ) -> Vec<(NodeId, RequestMessage)> {
         // Check if in-flight requests timeout
         let mut timeout_requests = Vec::new();
         let mut peers_to_disconnect = HashSet::new();
@@ -182,7 +183,7 @@ impl RequestHandler {
                     }
                 }
                 req.request.notify_timeout();
-                timeout_requests.push(req);
+                timeout_requests.push((peer_id, req));
             } else {
                 debug!("Timeout a removed request {:?}", sync_req);
             }
@@ -318,6 +319,7 @@ impl RequestContainer {
     )
     {
         request_message.request.set_request_id(request_id);
+        request_message.sent_at = Some(Instant::now());
         let res = request_message.request.send(io, &self.peer_id);
         let is_send_error = if let Err(e) = res {
             match e.kind() {
@@ -465,11 +467,18 @@ pub trait Request:
 pub struct RequestMessage {
     pub request: Box<dyn Request>,
     pub delay: Option<Duration>,
+    /// When the request was sent to the peer, used to measure how fast the
+    /// peer responds.
+    pub sent_at: Option<Instant>,
 }
 
 impl RequestMessage {
     pub fn new(request: Box<dyn Request>, delay: Option<Duration>) -> Self {
-        RequestMessage { request, delay }
+        RequestMessage {
+            request,
+            delay,
+            sent_at: None,
+        }
     }
 
     pub fn set_request_id(&mut self, request_id: u64) {
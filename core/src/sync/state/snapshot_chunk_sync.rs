@@ -365,12 +365,8 @@ impl SnapshotChunkSync {
         debug!("sync state status before updating: {:?}", *inner);
         self.check_timeout(
             &mut *inner,
-            &Context {
-                // node_id is not used here
-                node_id: Default::default(),
-                io,
-                manager: sync_handler,
-            },
+            // node_id is not used here
+            &Context::new(io, Default::default(), sync_handler),
         );
 
         // If we moves into the next era, we should force state_sync to change
@@ -2,12 +2,15 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::sync::{
-    message::DynamicCapability,
-    state::{SnapshotChunkSync, Status},
-    synchronization_protocol_handler::SynchronizationProtocolHandler,
-    synchronization_state::SynchronizationState,
-    SharedSynchronizationGraph,
+use crate::{
+    channel::Channel,
+    sync::{
+        message::DynamicCapability,
+        state::{SnapshotChunkSync, Status},
+        synchronization_protocol_handler::SynchronizationProtocolHandler,
+        synchronization_state::SynchronizationState,
+        SharedSynchronizationGraph,
+    },
 };
 use cfx_internal_common::StateAvailabilityBoundary;
 use cfx_parameters::sync::CATCH_UP_EPOCH_LAG_THRESHOLD;
@@ -102,6 +105,10 @@ impl SynchronizationPhaseManagerInner {
 
 pub struct SynchronizationPhaseManager {
     inner: RwLock<SynchronizationPhaseManagerInner>,
+
+    /// Channel used to publish the name of the new phase to PubSub whenever
+    /// the phase changes.
+    phase_changes: Arc<Channel<&'static str>>,
 }
 
 impl SynchronizationPhaseManager {
@@ -110,12 +117,14 @@ impl SynchronizationPhaseManager {
         sync_state: Arc<SynchronizationState>,
         sync_graph: SharedSynchronizationGraph,
         state_sync: Arc<SnapshotChunkSync>,
+        phase_changes: Arc<Channel<&'static str>>,
     ) -> Self
     {
         let sync_manager = SynchronizationPhaseManager {
             inner: RwLock::new(SynchronizationPhaseManagerInner::new(
                 initial_phase_type,
             )),
+            phase_changes,
         };
 
         sync_manager.register_phase(Arc::new(
@@ -162,6 +171,7 @@ impl SynchronizationPhaseManager {
     {
         self.inner.write().change_phase_to(phase_type);
         let current_phase = self.get_current_phase();
+        self.phase_changes.send(current_phase.name());
         current_phase.start(io, sync_handler);
     }
 
@@ -24,7 +24,7 @@ use crate::{
         SYNCHRONIZATION_PROTOCOL_OLD_VERSIONS_TO_SUPPORT,
         SYNCHRONIZATION_PROTOCOL_VERSION, SYNC_PROTO_V1, SYNC_PROTO_V2,
     },
-    NodeType,
+    NodeType, Notifications,
 };
 use cfx_internal_common::ChainIdParamsDeprecated;
 use cfx_parameters::{block::MAX_BLOCK_SIZE_IN_BYTES, sync::*};
@@ -36,7 +36,8 @@ use metrics::{register_meter_with_group, Meter, MeterTimer};
 use network::{
     node_table::NodeId, service::ProtocolVersion,
     throttling::THROTTLING_SERVICE, Error as NetworkError, HandlerWorkType,
-    NetworkContext, NetworkProtocolHandler, UpdateNodeOperation,
+    NetworkContext, NetworkProtocolHandler, ReputationEvent,
+    UpdateNodeOperation,
 };
 use parking_lot::{Mutex, RwLock};
 use primitives::{Block, BlockHeader, EpochId, SignedTransaction};
@@ -421,7 +422,7 @@ impl SynchronizationProtocolHandler {
         state_sync_config: StateSyncConfiguration,
         initial_sync_phase: SyncPhaseType,
         sync_graph: SharedSynchronizationGraph,
-        light_provider: Arc<LightProvider>,
+        light_provider: Arc<LightProvider>, notifications: Arc<Notifications>,
     ) -> Self
     {
         let sync_state = Arc::new(SynchronizationState::new(
@@ -453,6 +454,7 @@ impl SynchronizationProtocolHandler {
                 sync_state.clone(),
                 sync_graph.clone(),
                 state_sync.clone(),
+                notifications.sync_phase_changes.clone(),
             ),
             phase_manager_lock: Mutex::new(0),
             recover_public_queue,
@@ -559,11 +561,7 @@ impl SynchronizationProtocolHandler {
             }
         }
 
-        let ctx = Context {
-            node_id: *peer,
-            io,
-            manager: self,
-        };
+        let ctx = Context::new(io, *peer, self);
 
         if !handle_rlp_message(msg_id, &ctx, &rlp)? {
             warn!("Unknown message: peer={:?} msgid={:?}", peer, msg_id);
@@ -574,6 +572,13 @@ impl SynchronizationProtocolHandler {
                 Some(UpdateNodeOperation::Remove),
                 reason.as_str(),
             );
+        } else if ctx.fast_response.get() {
+            self.syn.update_peer_reputation(
+                io,
+                peer,
+                ReputationEvent::FastValidResponse,
+                "fast response",
+            );
         }
 
         Ok(())
@@ -589,6 +594,25 @@ impl SynchronizationProtocolHandler {
         let error_reason = format!("{:?}", e);
         let mut op = None;
 
+        let reputation_event = match e.0 {
+            ErrorKind::InvalidBlock => Some(ReputationEvent::InvalidBlock),
+            ErrorKind::InvalidGetBlockTxn(_)
+            | ErrorKind::InvalidStatus(_)
+            | ErrorKind::InvalidMessageFormat
+            | ErrorKind::UnexpectedResponse
+            | ErrorKind::InvalidTimestamp
+            | ErrorKind::InvalidSnapshotManifest(_)
+            | ErrorKind::InvalidSnapshotChunk(_)
+            | ErrorKind::Decoder(_)
+            | ErrorKind::UnexpectedMessage(_) => {
+                Some(ReputationEvent::InvalidMessage)
+            }
+            ErrorKind::EmptySnapshotChunk => {
+                Some(ReputationEvent::UselessResponse)
+            }
+            _ => None,
+        };
+
         // NOTE, DO NOT USE WILDCARD IN THE FOLLOWING MATCH STATEMENT!
         // COMPILER WILL HELP TO FIND UNHANDLED ERROR CASES.
         match e.0 {
@@ -708,6 +732,10 @@ impl SynchronizationProtocolHandler {
             );
         }
 
+        if let Some(event) = reputation_event {
+            self.syn.update_peer_reputation(io, peer, event, &reason);
+        }
+
         if disconnect {
             io.disconnect_peer(peer, op, reason.as_str());
         }
@@ -984,11 +1012,7 @@ impl SynchronizationProtocolHandler {
             headers.push((*header).clone());
             block_headers_resp.headers = headers;
 
-            let ctx = Context {
-                node_id: io.self_node_id(),
-                io,
-                manager: self,
-            };
+            let ctx = Context::new(io, io.self_node_id(), self);
 
             ctx.send_response(&block_headers_resp)
                 .expect("send response should not be error");
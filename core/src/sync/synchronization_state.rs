@@ -15,11 +15,12 @@ use crate::{
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use network::{
     node_table::NodeId, service::ProtocolVersion, Error as NetworkError,
-    ErrorKind as NetworkErrorKind,
+    ErrorKind as NetworkErrorKind, NetworkContext, ReputationEvent,
 };
 use parking_lot::RwLock;
-use rand::prelude::SliceRandom;
+use rand::Rng;
 use std::{
+    cmp::Ordering,
     collections::{HashMap, HashSet},
     sync::Arc,
     time::{Duration, Instant},
@@ -58,6 +59,11 @@ pub struct SynchronizationPeerState {
     // should not send requests to the remote peer. Otherwise, the remote peer
     // may disconnect the TCP connection.
     pub throttled_msgs: ThrottledManager<MsgId>,
+
+    // Reputation of the peer maintained by the network service, refreshed
+    // when the reputation is updated by the sync protocol. Peers of higher
+    // reputation are more likely to be selected to send requests to.
+    pub reputation: i32,
 }
 
 impl SynchronizationPeerState {
@@ -172,6 +178,21 @@ impl SynchronizationState {
         }
     }
 
+    /// Updates the reputation of the peer maintained by the network service,
+    /// and refreshes the reputation cached for peer selection. Note, the peer
+    /// may be disconnected synchronously, so the locks used on disconnection
+    /// must not be held.
+    pub fn update_peer_reputation(
+        &self, io: &dyn NetworkContext, node_id: &NodeId,
+        event: ReputationEvent, reason: &str,
+    )
+    {
+        io.update_peer_reputation(node_id, event, reason);
+        if let Some(state) = self.peers.read().get(node_id) {
+            state.write().reputation = io.get_peer_reputation(node_id);
+        }
+    }
+
     /// Retrieves the heartbeat timeout peers, including handshaking timeout
     /// peers and inactive peers after handshake.
     pub fn get_heartbeat_timeout_peers(
@@ -312,6 +333,15 @@ impl<'a> PeerFilter<'a> {
     }
 
     pub fn select_all(self, syn: &SynchronizationState) -> Vec<NodeId> {
+        self.select_all_with_reputation(syn)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn select_all_with_reputation(
+        self, syn: &SynchronizationState,
+    ) -> Vec<(NodeId, i32)> {
         let mut peers = Vec::new();
 
         let check_state = self.throttle_msg_ids.is_some()
@@ -319,7 +349,10 @@ impl<'a> PeerFilter<'a> {
             || self.min_best_epoch.is_some();
 
         for (id, peer) in syn.peers.read().iter() {
-            let peer_node_type = peer.read().node_type.clone();
+            let (peer_node_type, reputation) = {
+                let peer = peer.read();
+                (peer.node_type.clone(), peer.reputation)
+            };
             if let Some(ref preferred_node_type) = self.preferred_node_type {
                 if *preferred_node_type != peer_node_type {
                     continue;
@@ -369,20 +402,66 @@ impl<'a> PeerFilter<'a> {
                 }
             }
 
-            peers.push(*id);
+            peers.push((*id, reputation));
         }
 
         peers
     }
 
     pub fn select(self, syn: &SynchronizationState) -> Option<NodeId> {
-        self.select_all(syn).choose(&mut random::new()).cloned()
+        self.select_n(1, syn).pop()
     }
 
+    /// Randomly select `n` peers, preferring the peers of higher reputation.
     pub fn select_n(self, n: usize, syn: &SynchronizationState) -> Vec<NodeId> {
-        let mut peers = self.select_all(syn);
-        peers.shuffle(&mut random::new());
+        let mut peers = weighted_shuffle(self.select_all_with_reputation(syn));
         peers.truncate(n);
         peers
     }
 }
+
+/// Shuffle the peers with weighted random sampling without replacement
+/// (Efraimidis and Spirakis): each peer is ordered by `u ^ (1 / weight)` for
+/// a uniformly random `u`, so that the peers of higher reputation are more
+/// likely to come first.
+fn weighted_shuffle(peers: Vec<(NodeId, i32)>) -> Vec<NodeId> {
+    let mut rng = random::new();
+    let mut keyed: Vec<(f64, NodeId)> = peers
+        .into_iter()
+        .map(|(id, reputation)| {
+            let weight = selection_weight(reputation);
+            (rng.gen::<f64>().powf(1.0 / weight), id)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    keyed.into_iter().map(|(_, id)| id).collect()
+}
+
+/// The selection weight doubles for every 100 points of reputation, e.g. a
+/// peer of reputation -200 is selected a quarter as often as a fresh peer.
+fn selection_weight(reputation: i32) -> f64 {
+    2f64.powf(reputation as f64 / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::weighted_shuffle;
+    use network::node_table::NodeId;
+
+    #[test]
+    fn test_weighted_shuffle() {
+        let good = NodeId::random();
+        let bad = NodeId::random();
+
+        let mut good_first = 0;
+        for _ in 0..1000 {
+            let peers = weighted_shuffle(vec![(bad, -300), (good, 0)]);
+            assert_eq!(peers.len(), 2);
+            if peers[0] == good {
+                good_first += 1;
+            }
+        }
+        // The good peer comes first with probability 8 / 9.
+        assert!(good_first > 800, "good_first = {}", good_first);
+    }
+}
@@ -137,6 +137,7 @@ pub fn initialize_data_manager(
         machine.clone(),
         false, /* need_to_execute */
         None,
+        None,
     ));
 
     let data_man = Arc::new(BlockDataManager::new(
@@ -171,11 +172,13 @@ pub fn initialize_synchronization_graph_with_data_manager(
         machine.clone(),
     );
 
+    let notifications = Notifications::init();
     let txpool = Arc::new(TransactionPool::new(
         TxPoolConfig::default(),
         verification_config.clone(),
         data_man.clone(),
         machine.clone(),
+        notifications.clone(),
     ));
     let statistics = Arc::new(Statistics::new());
 
@@ -194,7 +197,6 @@ pub fn initialize_synchronization_graph_with_data_manager(
         enable_state_expose: false,
         is_consortium: false,
     };
-    let notifications = Notifications::init();
     let consensus = Arc::new(ConsensusGraph::new(
         ConsensusConfig {
             chain_id: ChainIdParamsInner::new_simple(0),
@@ -217,6 +219,8 @@ pub fn initialize_synchronization_graph_with_data_manager(
             get_logs_filter_max_epoch_range: None,
             sync_state_starting_epoch: None,
             sync_state_epoch_gap: None,
+            gas_price_oracle: Default::default(),
+            historical_state: Default::default(),
         },
         txpool.clone(),
         statistics.clone(),
@@ -226,6 +230,7 @@ pub fn initialize_synchronization_graph_with_data_manager(
         notifications.clone(),
         ConsensusExecutionConfiguration {
             executive_trace: false,
+            persist_replayed_trace: false,
         },
         verification_config.clone(),
         NodeType::Archive,
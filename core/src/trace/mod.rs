@@ -13,10 +13,36 @@ use crate::{
 use cfx_types::{Address, U256};
 
 pub mod error_unwind;
+pub mod state_diff;
+pub mod struct_logger;
 pub mod trace;
 pub mod trace_filter;
 
 pub use error_unwind::ErrorUnwind;
+pub use struct_logger::StructLogger;
+
+/// Interpreter state right before an instruction is executed. Only reported to
+/// tracers which ask for opcode-level steps.
+pub struct ExecStep<'a> {
+    /// Depth of the current call frame.
+    pub depth: usize,
+    /// Program counter of the instruction.
+    pub pc: usize,
+    /// Opcode of the instruction.
+    pub instruction: u8,
+    /// Mnemonic name of the instruction.
+    pub name: &'static str,
+    /// Gas left before the instruction is executed.
+    pub gas: U256,
+    /// Gas charged for the instruction.
+    pub gas_cost: U256,
+    /// Stack before the instruction is executed, bottom first.
+    pub stack: &'a [U256],
+    /// Memory range `(offset, size)` the instruction writes to, if any.
+    pub mem_written: Option<(usize, usize)>,
+    /// Storage `(key, value)` the instruction writes, if any.
+    pub store_written: Option<(U256, U256)>,
+}
 
 /// This trait is used by executive to build traces.
 pub trait Tracer: Send {
@@ -42,6 +68,17 @@ pub trait Tracer: Send {
         &mut self, from: Address, to: Address, value: U256,
     );
 
+    /// Whether the interpreter should report every executed instruction
+    /// through `prepare_trace_step` and `prepare_trace_step_result`.
+    fn trace_steps(&self) -> bool { false }
+
+    /// Prepares opcode-level trace for the instruction about to execute.
+    fn prepare_trace_step(&mut self, _step: ExecStep) {}
+
+    /// Prepares opcode-level result trace for the last instruction executed
+    /// at `depth`, given the memory after its execution.
+    fn prepare_trace_step_result(&mut self, _depth: usize, _mem: &[u8]) {}
+
     /// Consumes self and returns all traces.
     fn drain(self) -> Vec<Self::Output>;
 }
@@ -0,0 +1,63 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_bytes::Bytes;
+use cfx_types::{Address, U256};
+use primitives::SponsorInfo;
+use std::collections::BTreeMap;
+
+/// Change of a single value made by a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diff<T> {
+    /// The value is not changed.
+    Same,
+    /// The value does not exist before the transaction.
+    Born(T),
+    /// The value does not exist after the transaction.
+    Died(T),
+    /// The value is changed from the first to the second.
+    Changed(T, T),
+}
+
+impl<T: PartialEq> Diff<T> {
+    pub fn new(before: Option<T>, after: Option<T>) -> Self {
+        match (before, after) {
+            (None, None) => Diff::Same,
+            (None, Some(after)) => Diff::Born(after),
+            (Some(before), None) => Diff::Died(before),
+            (Some(before), Some(after)) if before == after => Diff::Same,
+            (Some(before), Some(after)) => Diff::Changed(before, after),
+        }
+    }
+
+    pub fn is_same(&self) -> bool { matches!(self, Diff::Same) }
+}
+
+/// Changes made by a transaction to a single account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountDiff {
+    pub balance: Diff<U256>,
+    pub nonce: Diff<U256>,
+    pub code: Diff<Bytes>,
+    pub staking_balance: Diff<U256>,
+    pub collateral_for_storage: Diff<U256>,
+    pub sponsor_info: Diff<SponsorInfo>,
+    /// Changed storage entries. A zero value is treated as non-existent.
+    pub storage: BTreeMap<Vec<u8>, Diff<U256>>,
+}
+
+impl AccountDiff {
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_same()
+            && self.nonce.is_same()
+            && self.code.is_same()
+            && self.staking_balance.is_same()
+            && self.collateral_for_storage.is_same()
+            && self.sponsor_info.is_same()
+            && self.storage.is_empty()
+    }
+}
+
+/// Changes made by a transaction to all the accounts it modified.
+pub type StateDiff = BTreeMap<Address, AccountDiff>;
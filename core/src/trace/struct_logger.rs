@@ -0,0 +1,132 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{trace::ExecTrace, ExecStep, Tracer};
+use crate::{
+    executive::ExecutiveResult,
+    vm::{ActionParams, Result as VmResult},
+};
+use cfx_types::{Address, U256};
+
+/// Memory written by a single instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDiff {
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+/// Storage entry written by a single instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageDiff {
+    pub key: U256,
+    pub value: U256,
+}
+
+/// Opcode-level log of a single executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLog {
+    pub depth: usize,
+    pub pc: usize,
+    pub instruction: u8,
+    pub op: &'static str,
+    pub gas: U256,
+    pub gas_cost: U256,
+    pub stack: Vec<U256>,
+    pub memory_diff: Option<MemoryDiff>,
+    pub storage_diff: Option<StorageDiff>,
+}
+
+/// Records every instruction executed by the interpreter.
+///
+/// The logger is lent to the executive as `&mut StructLogger` so that the logs
+/// are still available once the transaction has been executed.
+#[derive(Default)]
+pub struct StructLogger {
+    logs: Vec<StructLog>,
+
+    // `(depth, log index, memory written)` of the steps waiting for their
+    // results, innermost last. A step never gets its result if the
+    // instruction fails, so stale entries are dropped based on depth.
+    pending: Vec<(usize, usize, Option<(usize, usize)>)>,
+}
+
+impl StructLogger {
+    /// Consumes self and returns the logs of all executed instructions.
+    pub fn into_logs(self) -> Vec<StructLog> { self.logs }
+
+    /// Drops pending steps at `depth` or deeper.
+    fn drop_pending_from(&mut self, depth: usize) {
+        while let Some((d, _, _)) = self.pending.last() {
+            if *d < depth {
+                break;
+            }
+            self.pending.pop();
+        }
+    }
+}
+
+impl Tracer for &mut StructLogger {
+    type Output = ExecTrace;
+
+    fn prepare_trace_call(&mut self, _: &ActionParams) {}
+
+    fn prepare_trace_call_result(&mut self, _: &VmResult<ExecutiveResult>) {}
+
+    fn prepare_trace_create(&mut self, _: &ActionParams) {}
+
+    fn prepare_trace_create_result(&mut self, _: &VmResult<ExecutiveResult>) {}
+
+    fn prepare_internal_transfer_action(
+        &mut self, _: Address, _: Address, _: U256,
+    ) {
+    }
+
+    fn trace_steps(&self) -> bool { true }
+
+    fn prepare_trace_step(&mut self, step: ExecStep) {
+        // a pending step at the same depth belongs to a finished frame
+        self.drop_pending_from(step.depth);
+
+        self.pending
+            .push((step.depth, self.logs.len(), step.mem_written));
+
+        self.logs.push(StructLog {
+            depth: step.depth,
+            pc: step.pc,
+            instruction: step.instruction,
+            op: step.name,
+            gas: step.gas,
+            gas_cost: step.gas_cost,
+            stack: step.stack.to_vec(),
+            memory_diff: None,
+            storage_diff: step
+                .store_written
+                .map(|(key, value)| StorageDiff { key, value }),
+        });
+    }
+
+    fn prepare_trace_step_result(&mut self, depth: usize, mem: &[u8]) {
+        self.drop_pending_from(depth + 1);
+
+        match self.pending.last() {
+            Some((d, _, _)) if *d == depth => {}
+            _ => return,
+        }
+
+        let (_, index, mem_written) =
+            self.pending.pop().expect("checked above; qed");
+
+        if let Some((offset, size)) = mem_written {
+            if offset + size <= mem.len() {
+                self.logs[index].memory_diff = Some(MemoryDiff {
+                    offset,
+                    data: mem[offset..offset + size].to_vec(),
+                });
+            }
+        }
+    }
+
+    /// The logs are read through `StructLogger::into_logs`.
+    fn drain(self) -> Vec<ExecTrace> { vec![] }
+}
@@ -0,0 +1,191 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use primitives::TransactionWithSignature;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// The file keeping the local transactions of the transaction pool across
+/// restarts. Each record is a RLP encoded transaction prefixed by its length
+/// as a 4-byte big-endian integer.
+pub struct TransactionJournal {
+    path: PathBuf,
+    /// Opened on the first append after the journal is created or rotated.
+    writer: Option<File>,
+    rotated_at: Instant,
+    /// The size of the records written by the last rotation and the appends
+    /// after it.
+    size: usize,
+}
+
+impl TransactionJournal {
+    pub fn new(path: PathBuf) -> Self {
+        TransactionJournal {
+            path,
+            writer: None,
+            rotated_at: Instant::now(),
+            size: 0,
+        }
+    }
+
+    pub fn elapsed_since_rotation(&self) -> Duration {
+        self.rotated_at.elapsed()
+    }
+
+    pub fn size(&self) -> usize { self.size }
+
+    /// Read all the transactions in the journal. The reading stops at the
+    /// first truncated or undecodable record, which may be left by a crash
+    /// during appending.
+    pub fn load(&self) -> io::Result<Vec<TransactionWithSignature>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut transactions = Vec::new();
+        let mut offset = 0;
+        while offset + 4 <= data.len() {
+            let mut len_bytes = [0u8; 4];
+            len_bytes.copy_from_slice(&data[offset..offset + 4]);
+            let len = u32::from_be_bytes(len_bytes) as usize;
+            offset += 4;
+            if offset + len > data.len() {
+                warn!(
+                    "Truncated record in transaction journal {:?}",
+                    self.path
+                );
+                break;
+            }
+            match rlp::decode(&data[offset..offset + len]) {
+                Ok(tx) => transactions.push(tx),
+                Err(e) => {
+                    warn!(
+                        "Undecodable record in transaction journal {:?}: {:?}",
+                        self.path, e
+                    );
+                    break;
+                }
+            }
+            offset += len;
+        }
+        Ok(transactions)
+    }
+
+    /// Append `transactions` to the journal.
+    pub fn append<'a>(
+        &mut self,
+        transactions: impl IntoIterator<Item = &'a TransactionWithSignature>,
+    ) -> io::Result<()>
+    {
+        if self.writer.is_none() {
+            self.writer = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+        let writer = self.writer.as_mut().expect("opened above");
+        for tx in transactions {
+            let record = encode_record(tx);
+            writer.write_all(&record)?;
+            self.size += record.len();
+        }
+        writer.flush()
+    }
+
+    /// Replace the content of the journal with `transactions`.
+    pub fn rotate<'a>(
+        &mut self,
+        transactions: impl IntoIterator<Item = &'a TransactionWithSignature>,
+    ) -> io::Result<()>
+    {
+        self.writer = None;
+        let new_path = self.path.with_extension("new");
+        let mut size = 0;
+        {
+            let mut file = File::create(&new_path)?;
+            for tx in transactions {
+                let record = encode_record(tx);
+                file.write_all(&record)?;
+                size += record.len();
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&new_path, &self.path)?;
+        self.rotated_at = Instant::now();
+        self.size = size;
+        Ok(())
+    }
+}
+
+fn encode_record(tx: &TransactionWithSignature) -> Vec<u8> {
+    let encoded = rlp::encode(tx);
+    let mut record = (encoded.len() as u32).to_be_bytes().to_vec();
+    record.extend_from_slice(&encoded);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TransactionJournal;
+    use cfx_types::{Address, U256};
+    use keylib::{Generator, Random};
+    use primitives::{Action, Transaction, TransactionWithSignature};
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+    };
+    use tempdir::TempDir;
+
+    fn new_test_tx(nonce: u64) -> TransactionWithSignature {
+        Transaction {
+            nonce: nonce.into(),
+            gas_price: U256::one(),
+            gas: 21000.into(),
+            action: Action::Call(Address::random()),
+            value: U256::zero(),
+            storage_limit: 0,
+            epoch_height: 0,
+            chain_id: 0,
+            data: Vec::new(),
+        }
+        .sign(Random.generate().unwrap().secret())
+        .transaction
+    }
+
+    #[test]
+    fn test_append_load_and_rotate() {
+        let dir = TempDir::new("tx_journal").unwrap();
+        let path = dir.path().join("transactions.rlp");
+        let txs: Vec<_> = (0..3).map(new_test_tx).collect();
+
+        let mut journal = TransactionJournal::new(path.clone());
+        assert_eq!(journal.load().unwrap(), vec![]);
+        journal.append(&txs[..2]).unwrap();
+        journal.append(&txs[2..]).unwrap();
+        assert_eq!(journal.load().unwrap(), txs);
+
+        journal.rotate(&txs[1..2]).unwrap();
+        assert_eq!(journal.load().unwrap(), vec![txs[1].clone()]);
+        assert_eq!(journal.size() as u64, fs::metadata(&path).unwrap().len());
+
+        // A partially written record is ignored.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0, 0, 1, 0, 1])
+            .unwrap();
+        assert_eq!(journal.load().unwrap(), vec![txs[1].clone()]);
+    }
+}
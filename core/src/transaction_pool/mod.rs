@@ -9,15 +9,21 @@ mod test_treap;
 
 mod account_cache;
 mod garbage_collector;
+mod journal;
 mod nonce_pool;
 mod transaction_pool_inner;
 
 extern crate rand;
 
-pub use self::{impls::TreapMap, transaction_pool_inner::TransactionStatus};
+pub use self::{
+    impls::TreapMap,
+    nonce_pool::ReplacementError,
+    transaction_pool_inner::{InsertError, TransactionStatus, TxReplacement},
+};
 use crate::{
-    block_data_manager::BlockDataManager, consensus::BestInformation,
-    machine::Machine, state::State, verification::VerificationConfig,
+    block_data_manager::BlockDataManager, channel::Channel,
+    consensus::BestInformation, machine::Machine, state::State,
+    verification::VerificationConfig, Notifications,
 };
 
 use crate::{
@@ -30,6 +36,7 @@ use cfx_parameters::block::DEFAULT_TARGET_BLOCK_GAS_LIMIT;
 use cfx_statedb::{Result as StateDbResult, StateDb};
 use cfx_storage::{StateIndex, StorageManagerTrait};
 use cfx_types::{Address, H256, U256};
+use journal::TransactionJournal;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use metrics::{
     register_meter_with_group, Gauge, GaugeUsize, Lock, Meter, MeterTimer,
@@ -40,12 +47,14 @@ use primitives::{Account, SignedTransaction, TransactionWithSignature};
 use std::{
     cmp::{max, min},
     collections::hash_map::HashMap,
-    mem,
+    io, mem,
     ops::DerefMut,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 use transaction_pool_inner::TransactionPoolInner;
 
@@ -82,6 +91,10 @@ lazy_static! {
         Lock::register("txpool_notify_modified_info");
 }
 
+/// The local transaction journal is rewritten with only the pooled local
+/// transactions if it has not been rewritten for this long.
+const LOCAL_TX_JOURNAL_ROTATE_INTERVAL: Duration = Duration::from_secs(3600);
+
 pub struct TxPoolConfig {
     pub capacity: usize,
     pub min_tx_price: u64,
@@ -89,6 +102,18 @@ pub struct TxPoolConfig {
     pub tx_weight_scaling: u64,
     pub tx_weight_exp: u8,
     pub target_block_gas_limit: u64,
+    /// The minimum percentage by which the gas price of a transaction has to
+    /// be higher to replace the pending transaction with the same nonce.
+    pub replace_price_bump: u8,
+    /// The file to keep the local transactions across restarts. Local
+    /// transactions are not journaled if it is `None`.
+    pub local_tx_journal: Option<PathBuf>,
+    /// The maximum number of local transactions in the pool. Local
+    /// transactions beyond it are rejected.
+    pub max_local_transactions: usize,
+    /// The maximum total RLP size of the local transactions in the pool. The
+    /// journal is rewritten once it grows beyond twice of it.
+    pub max_local_transaction_bytes: usize,
 }
 
 impl MallocSizeOf for TxPoolConfig {
@@ -108,6 +133,10 @@ impl Default for TxPoolConfig {
             tx_weight_scaling: 1,
             tx_weight_exp: 1,
             target_block_gas_limit: DEFAULT_TARGET_BLOCK_GAS_LIMIT,
+            replace_price_bump: 0,
+            local_tx_journal: None,
+            max_local_transactions: 1000,
+            max_local_transaction_bytes: 10 * 1024 * 1024,
         }
     }
 }
@@ -123,6 +152,11 @@ pub struct TransactionPool {
     set_tx_requests: Mutex<Vec<Arc<SignedTransaction>>>,
     recycle_tx_requests: Mutex<Vec<Arc<SignedTransaction>>>,
     machine: Arc<Machine>,
+    local_tx_journal: Option<Mutex<TransactionJournal>>,
+
+    /// Channel used to publish the hashes of newly accepted transactions to
+    /// PubSub.
+    new_pending_transactions: Arc<Channel<H256>>,
 
     /// If it's `false`, operations on the tx pool will be ignored to save
     /// memory/CPU cost.
@@ -156,6 +190,7 @@ impl TransactionPool {
     pub fn new(
         config: TxPoolConfig, verification_config: VerificationConfig,
         data_man: Arc<BlockDataManager>, machine: Arc<Machine>,
+        notifications: Arc<Notifications>,
     ) -> Self
     {
         let genesis_hash = data_man.true_genesis.hash();
@@ -163,7 +198,12 @@ impl TransactionPool {
             config.capacity,
             config.tx_weight_scaling,
             config.tx_weight_exp,
+            config.replace_price_bump,
         );
+        let local_tx_journal = config
+            .local_tx_journal
+            .clone()
+            .map(|path| Mutex::new(TransactionJournal::new(path)));
         let best_executed_state = Mutex::new(
             Self::best_executed_state(
                 &data_man,
@@ -185,6 +225,10 @@ impl TransactionPool {
             set_tx_requests: Mutex::new(Default::default()),
             recycle_tx_requests: Mutex::new(Default::default()),
             machine,
+            local_tx_journal,
+            new_pending_transactions: notifications
+                .new_pending_transactions
+                .clone(),
             ready_for_mining: AtomicBool::new(false),
         }
     }
@@ -214,6 +258,14 @@ impl TransactionPool {
         self.inner.read().get_account_pending_info(address)
     }
 
+    /// Return the recent replacements of transactions sent by `address`, or by
+    /// all the senders if it is `None`, from the oldest to the newest.
+    pub fn get_replacement_history(
+        &self, address: Option<&Address>,
+    ) -> Vec<TxReplacement> {
+        self.inner.read().replacement_history(address)
+    }
+
     /// Return `(pending_txs, first_tx_status, pending_count)`.
     pub fn get_account_pending_transactions(
         &self, address: &Address, maybe_start_nonce: Option<U256>,
@@ -256,8 +308,31 @@ impl TransactionPool {
     /// cannot be inserted to the tx pool, it will be included in the returned
     /// `failure` and will not be propagated.
     pub fn insert_new_transactions(
-        &self, mut transactions: Vec<TransactionWithSignature>,
-    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, String>) {
+        &self, transactions: Vec<TransactionWithSignature>,
+    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, InsertError>) {
+        self.insert_transactions(transactions, false /* local */)
+    }
+
+    /// Try to insert `transactions` submitted through the RPC of this node
+    /// into transaction pool. Unlike `insert_new_transactions`, their gas
+    /// prices are not checked against `min_tx_price`, they are not garbage
+    /// collected before being executed, and they are written to the local
+    /// transaction journal.
+    pub fn insert_new_local_transactions(
+        &self, transactions: Vec<TransactionWithSignature>,
+    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, InsertError>) {
+        let (passed_transactions, failure) =
+            self.insert_transactions(transactions, true /* local */);
+        if let Err(e) = self.journal_local_transactions(&passed_transactions)
+        {
+            warn!("Failed to write local transactions to journal: {}", e);
+        }
+        (passed_transactions, failure)
+    }
+
+    fn insert_transactions(
+        &self, mut transactions: Vec<TransactionWithSignature>, local: bool,
+    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, InsertError>) {
         INSERT_TPS.mark(1);
         INSERT_TXS_TPS.mark(transactions.len());
         let _timer = MeterTimer::time_func(TX_POOL_INSERT_TIMER.as_ref());
@@ -286,6 +361,7 @@ impl TransactionPool {
             match self.verify_transaction_tx_pool(
                 tx,
                 /* basic_check = */ true,
+                local,
                 chain_id,
                 best_height,
                 transitions,
@@ -295,7 +371,7 @@ impl TransactionPool {
                 Err(e) => {
                     let removed = transactions.swap_remove(index);
                     debug!("failed to insert tx into pool (validation failed), hash = {:?}, error = {:?}", removed.hash, e);
-                    failure.insert(removed.hash, e);
+                    failure.insert(removed.hash, e.into());
                 }
             }
         }
@@ -308,7 +384,7 @@ impl TransactionPool {
         if quota < transactions.len() {
             for tx in transactions.split_off(quota) {
                 trace!("failed to insert tx into pool (quota not enough), hash = {:?}", tx.hash);
-                failure.insert(tx.hash, "txpool is full".into());
+                failure.insert(tx.hash, InsertError::PoolFull);
             }
         }
 
@@ -330,6 +406,19 @@ impl TransactionPool {
                 let mut to_prop = self.to_propagate_trans.write();
 
                 for tx in signed_trans {
+                    if local && self.local_transactions_full(&inner, &tx) {
+                        debug!(
+                            "tx {:?} fails to be inserted to pool, too many local transactions",
+                            &tx.hash
+                        );
+                        failure.insert(
+                            tx.hash(),
+                            "too many local transactions in the pool"
+                                .to_string()
+                                .into(),
+                        );
+                        continue;
+                    }
                     if let Err(e) = self.add_transaction_with_readiness_check(
                         &mut *inner,
                         &account_cache,
@@ -344,6 +433,9 @@ impl TransactionPool {
                         failure.insert(tx.hash(), e);
                         continue;
                     }
+                    if local {
+                        inner.mark_local(tx.hash());
+                    }
                     passed_transactions.push(tx.clone());
                     if !to_prop.contains_key(&tx.hash) {
                         to_prop.insert(tx.hash, tx);
@@ -364,9 +456,27 @@ impl TransactionPool {
         INSERT_TXS_SUCCESS_TPS.mark(passed_transactions.len());
         INSERT_TXS_FAILURE_TPS.mark(failure.len());
 
+        self.notify_new_pending_transactions(&passed_transactions);
+
         (passed_transactions, failure)
     }
 
+    /// Publish the hashes of `transactions` to the `new_pending_transactions`
+    /// channel. This is a no-op only if no receiver subscribes to the channel,
+    /// i.e. the pubsub RPC is not started. Once it's started, the hashes are
+    /// sent even if no pubsub client subscribes to `newPendingTransactions`.
+    fn notify_new_pending_transactions(
+        &self, transactions: &[Arc<SignedTransaction>],
+    ) {
+        if self.new_pending_transactions.num_subscriptions() == 0 {
+            return;
+        }
+
+        for tx in transactions {
+            self.new_pending_transactions.send(tx.hash());
+        }
+    }
+
     /// Try to insert `signed_transaction` into transaction pool.
     ///
     /// If some tx is already in our tx_cache, it will be ignored and will not
@@ -375,7 +485,7 @@ impl TransactionPool {
     /// `failure` and will not be propagated.
     pub fn insert_new_signed_transactions(
         &self, mut signed_transactions: Vec<Arc<SignedTransaction>>,
-    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, String>) {
+    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, InsertError>) {
         INSERT_TPS.mark(1);
         INSERT_TXS_TPS.mark(signed_transactions.len());
         let _timer = MeterTimer::time_func(TX_POOL_INSERT_TIMER.as_ref());
@@ -402,7 +512,8 @@ impl TransactionPool {
         while let Some(tx) = signed_transactions.get(index) {
             match self.verify_transaction_tx_pool(
                 &tx.transaction,
-                true, /* basic_check = */
+                true,  /* basic_check = */
+                false, /* local = */
                 chain_id,
                 best_height,
                 transitions,
@@ -412,7 +523,7 @@ impl TransactionPool {
                 Err(e) => {
                     let removed = signed_transactions.swap_remove(index);
                     debug!("failed to insert tx into pool (validation failed), hash = {:?}, error = {:?}", removed.hash, e);
-                    failure.insert(removed.hash, e);
+                    failure.insert(removed.hash, e.into());
                 }
             }
         }
@@ -425,7 +536,7 @@ impl TransactionPool {
         if quota < signed_transactions.len() {
             for tx in signed_transactions.split_off(quota) {
                 trace!("failed to insert tx into pool (quota not enough), hash = {:?}", tx.hash);
-                failure.insert(tx.hash, "txpool is full".into());
+                failure.insert(tx.hash, InsertError::PoolFull);
             }
         }
 
@@ -479,11 +590,11 @@ impl TransactionPool {
     }
 
     /// verify transactions based on the rules that have nothing to do with
-    /// readiness
+    /// readiness. The gas price of `local` transactions is not checked.
     fn verify_transaction_tx_pool(
         &self, transaction: &TransactionWithSignature, basic_check: bool,
-        chain_id: u32, best_height: u64, transitions: &TransitionsEpochHeight,
-        spec: &Spec,
+        local: bool, chain_id: u32, best_height: u64,
+        transitions: &TransitionsEpochHeight, spec: &Spec,
     ) -> Result<(), String>
     {
         let _timer = MeterTimer::time_func(TX_POOL_VERIFY_TIMER.as_ref());
@@ -521,7 +632,7 @@ impl TransactionPool {
         }
 
         // check transaction gas price
-        if transaction.gas_price < self.config.min_tx_price.into() {
+        if !local && transaction.gas_price < self.config.min_tx_price.into() {
             trace!("Transaction {} discarded due to below minimal gas price: price {}", transaction.hash(), transaction.gas_price);
             return Err(format!(
                 "transaction gas price {} less than the minimum value {}",
@@ -538,7 +649,7 @@ impl TransactionPool {
     pub fn add_transaction_with_readiness_check(
         &self, inner: &mut TransactionPoolInner, account_cache: &AccountCache,
         transaction: Arc<SignedTransaction>, packed: bool, force: bool,
-    ) -> Result<(), String>
+    ) -> Result<(), InsertError>
     {
         inner.insert_transaction_with_readiness_check(
             account_cache,
@@ -548,6 +659,67 @@ impl TransactionPool {
         )
     }
 
+    /// Reinsert the transactions in the local transaction journal into
+    /// transaction pool, and rewrite the journal with those accepted. This
+    /// should be called after the node catches up, so that the transactions
+    /// can be verified against the latest state.
+    pub fn restore_local_transactions(&self) {
+        let mut journal = match &self.local_tx_journal {
+            Some(journal) => journal.lock(),
+            None => return,
+        };
+        let transactions = match journal.load() {
+            Ok(transactions) => transactions,
+            Err(e) => {
+                warn!("Failed to load local transaction journal: {}", e);
+                return;
+            }
+        };
+        let (passed_transactions, failure) =
+            self.insert_transactions(transactions, true /* local */);
+        info!(
+            "Restored {} local transactions from journal, {} discarded",
+            passed_transactions.len(),
+            failure.len()
+        );
+        if let Err(e) = self.rotate_local_tx_journal(&mut journal) {
+            warn!("Failed to rewrite local transaction journal: {}", e);
+        }
+    }
+
+    fn journal_local_transactions(
+        &self, transactions: &[Arc<SignedTransaction>],
+    ) -> io::Result<()> {
+        let mut journal = match &self.local_tx_journal {
+            Some(journal) if !transactions.is_empty() => journal.lock(),
+            _ => return Ok(()),
+        };
+        if journal.elapsed_since_rotation() >= LOCAL_TX_JOURNAL_ROTATE_INTERVAL
+            || journal.size() > 2 * self.config.max_local_transaction_bytes
+        {
+            // The new transactions are already in the pool.
+            self.rotate_local_tx_journal(&mut journal)
+        } else {
+            journal.append(transactions.iter().map(|tx| &tx.transaction))
+        }
+    }
+
+    /// Whether `tx` would exceed the limits of the local transactions.
+    fn local_transactions_full(
+        &self, inner: &TransactionPoolInner, tx: &SignedTransaction,
+    ) -> bool {
+        inner.local_transaction_count() >= self.config.max_local_transactions
+            || inner.local_transaction_bytes() + tx.rlp_size()
+                > self.config.max_local_transaction_bytes
+    }
+
+    fn rotate_local_tx_journal(
+        &self, journal: &mut TransactionJournal,
+    ) -> io::Result<()> {
+        let local_transactions = self.inner.read().local_transactions();
+        journal.rotate(local_transactions.iter().map(|tx| &tx.transaction))
+    }
+
     pub fn get_to_be_propagated_transactions(
         &self,
     ) -> HashMap<H256, Arc<SignedTransaction>> {
@@ -712,9 +884,14 @@ impl TransactionPool {
                 &tx.nonce, &tx.sender,
                 account_cache.get_nonce(&tx.sender)?, tx.hash);
 
+            // Local transactions are garbage collected once executed, so
+            // check the recently executed ones as well.
+            let local = inner.was_local(&tx.hash())
+                && !self.local_transactions_full(inner, &tx);
             if let Err(e) = self.verify_transaction_tx_pool(
                 &tx,
                 /* basic_check = */ false,
+                local,
                 chain_id,
                 best_height,
                 transitions,
@@ -725,14 +902,20 @@ impl TransactionPool {
                     tx.hash(), e
                 );
             }
-            self.add_transaction_with_readiness_check(
-                inner,
-                &account_cache,
-                tx,
-                false,
-                true,
-            )
-            .ok();
+            let tx_hash = tx.hash();
+            if self
+                .add_transaction_with_readiness_check(
+                    inner,
+                    &account_cache,
+                    tx,
+                    false,
+                    true,
+                )
+                .is_ok()
+                && local
+            {
+                inner.mark_local(tx_hash);
+            }
         }
         debug!(
             "notify_new_best_info: {:?}",
@@ -1,4 +1,6 @@
-use crate::transaction_pool::transaction_pool_inner::PendingReason;
+use crate::transaction_pool::transaction_pool_inner::{
+    InsertError, PendingReason,
+};
 use cfx_parameters::staking::DRIPS_PER_STORAGE_COLLATERAL_UNIT;
 use cfx_types::{U128, U256, U512};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
@@ -8,7 +10,13 @@ use primitives::{
 };
 use rand::{RngCore, SeedableRng};
 use rand_xorshift::XorShiftRng;
-use std::{cmp::Ordering, mem, ops::Deref, sync::Arc};
+use serde::Serialize;
+use std::{
+    cmp::{max, Ordering},
+    fmt, mem,
+    ops::Deref,
+    sync::Arc,
+};
 
 #[derive(Clone, Debug, PartialEq, DeriveMallocSizeOf)]
 pub struct TxWithReadyInfo {
@@ -23,19 +31,71 @@ impl TxWithReadyInfo {
 
     pub fn get_arc_tx(&self) -> &Arc<SignedTransaction> { &self.transaction }
 
-    pub fn should_replace(&self, x: &Self, force: bool) -> bool {
+    /// Check if `self` can replace `x`, the transaction with the same nonce in
+    /// the pool. Unless `force` is set, the gas price of `self` has to be at
+    /// least `price_bump` percent higher than that of `x`.
+    pub fn check_replace(
+        &self, x: &Self, force: bool, price_bump: u8,
+    ) -> Result<(), ReplacementError> {
         if force {
-            return true;
+            return Ok(());
         }
         if x.is_already_packed() {
-            return false;
+            return Err(ReplacementError::AlreadyPacked);
         }
         if self.is_already_packed() {
-            return true;
+            return Ok(());
+        }
+        // Without a required bump, a transaction with the same gas price
+        // received in a later epoch is still accepted.
+        if price_bump == 0
+            && self.gas_price == x.gas_price
+            && self.epoch_height > x.epoch_height
+        {
+            return Ok(());
+        }
+        let min_gas_price = min_replacement_gas_price(&x.gas_price, price_bump);
+        if self.gas_price >= min_gas_price {
+            Ok(())
+        } else {
+            Err(ReplacementError::Underpriced { min_gas_price })
+        }
+    }
+}
+
+/// The minimum gas price to replace a transaction with `gas_price`, which is
+/// `price_bump` percent higher and at least strictly higher.
+pub fn min_replacement_gas_price(gas_price: &U256, price_bump: u8) -> U256 {
+    let bumped =
+        gas_price.saturating_mul(U256::from(100 + price_bump as u64)) / 100;
+    max(bumped, gas_price.saturating_add(U256::one()))
+}
+
+/// The reason why a transaction can not replace the transaction with the same
+/// sender and nonce in the pool.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "reason")]
+pub enum ReplacementError {
+    /// The transaction in the pool is already packed.
+    AlreadyPacked,
+    /// The gas price is lower than `min_gas_price`.
+    #[serde(rename_all = "camelCase")]
+    Underpriced { min_gas_price: U256 },
+}
+
+impl fmt::Display for ReplacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplacementError::AlreadyPacked => write!(
+                f,
+                "Tx with same nonce already packed and can not be replaced"
+            ),
+            ReplacementError::Underpriced { min_gas_price } => write!(
+                f,
+                "Tx with same nonce already inserted. To replace it, you need to specify a gas price >= {}",
+                min_gas_price
+            ),
         }
-        self.gas_price > x.gas_price
-            || self.gas_price == x.gas_price
-                && self.epoch_height > x.epoch_height
     }
 }
 
@@ -49,8 +109,8 @@ impl Deref for TxWithReadyInfo {
 pub enum InsertResult {
     /// new item added
     NewAdded,
-    /// failed to update with lower gas price tx
-    Failed(String),
+    /// failed to insert, e.g. failed to update with lower gas price tx
+    Failed(InsertError),
     /// succeeded to update with higher gas price tx
     Updated(TxWithReadyInfo),
 }
@@ -155,10 +215,11 @@ impl NoncePoolNode {
     }
 
     /// insert a new TxWithReadyInfo. if the corresponding nonce already exists,
-    /// will replace with higher gas price transaction
+    /// will replace with higher gas price transaction, see
+    /// `TxWithReadyInfo::check_replace`
     pub fn insert(
         node: &mut Option<Box<NoncePoolNode>>, tx: &TxWithReadyInfo,
-        priority: u64, force: bool,
+        priority: u64, force: bool, price_bump: u8,
     ) -> InsertResult
     {
         if node.is_none() {
@@ -167,15 +228,16 @@ impl NoncePoolNode {
         }
         let cmp = tx.nonce().cmp(&node.as_ref().unwrap().tx.nonce);
         if cmp == Ordering::Equal {
-            let result = {
-                if tx.should_replace(&node.as_ref().unwrap().tx, force) {
-                    InsertResult::Updated(mem::replace(
-                        &mut node.as_mut().unwrap().tx,
-                        tx.clone(),
-                    ))
-                } else {
-                    InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price > {}", &node.as_ref().unwrap().tx.gas_price))
-                }
+            let result = match tx.check_replace(
+                &node.as_ref().unwrap().tx,
+                force,
+                price_bump,
+            ) {
+                Ok(()) => InsertResult::Updated(mem::replace(
+                    &mut node.as_mut().unwrap().tx,
+                    tx.clone(),
+                )),
+                Err(e) => InsertResult::Failed(InsertError::Replacement(e)),
             };
             node.as_mut().unwrap().update();
             result
@@ -186,6 +248,7 @@ impl NoncePoolNode {
                 tx,
                 priority,
                 force,
+                price_bump,
             );
             if node.as_ref().unwrap().priority
                 < node.as_ref().unwrap().child[d].as_ref().unwrap().priority
@@ -374,9 +437,15 @@ impl NoncePool {
     // FIXME: later we should limit the number of txs from one sender.
     //  the FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET roughly doing this job
     pub fn insert(
-        &mut self, tx: &TxWithReadyInfo, force: bool,
+        &mut self, tx: &TxWithReadyInfo, force: bool, price_bump: u8,
     ) -> InsertResult {
-        NoncePoolNode::insert(&mut self.root, tx, self.rng.next_u64(), force)
+        NoncePoolNode::insert(
+            &mut self.root,
+            tx,
+            self.rng.next_u64(),
+            force,
+            price_bump,
+        )
     }
 
     pub fn get_tx_by_nonce(&self, nonce: U256) -> Option<TxWithReadyInfo> {
@@ -511,7 +580,9 @@ impl NoncePool {
 
 #[cfg(test)]
 mod nonce_pool_test {
-    use super::{InsertResult, NoncePool, TxWithReadyInfo};
+    use super::{
+        InsertError, InsertResult, NoncePool, ReplacementError, TxWithReadyInfo,
+    };
     use crate::transaction_pool::nonce_pool::NoncePoolNode;
     use cfx_parameters::staking::DRIPS_PER_STORAGE_COLLATERAL_UNIT;
     use cfx_types::{Address, U128, U256};
@@ -730,17 +801,23 @@ mod nonce_pool_test {
         assert_eq!(nonce_pool.is_empty(), true);
         for i in 0..10 {
             assert_eq!(
-                nonce_pool.insert(&tx1[i as usize], false /* force */),
+                nonce_pool.insert(&tx1[i as usize], false /* force */, 0),
                 InsertResult::NewAdded
             );
             assert_eq!(
                 nonce_pool.get_tx_by_nonce(U256::from(i)),
                 Some(tx1[i].clone())
             );
-            assert_eq!(nonce_pool.insert(&tx2[i as usize], false /* force */),
-                       InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price > {}", &tx1[i as usize].gas_price)));
             assert_eq!(
-                nonce_pool.insert(&tx2[i as usize], true /* force */),
+                nonce_pool.insert(&tx2[i as usize], false /* force */, 0),
+                InsertResult::Failed(InsertError::Replacement(
+                    ReplacementError::Underpriced {
+                        min_gas_price: tx1[i as usize].gas_price + 1
+                    }
+                ))
+            );
+            assert_eq!(
+                nonce_pool.insert(&tx2[i as usize], true /* force */, 0),
                 InsertResult::Updated(tx1[i as usize].clone())
             );
             assert_eq!(nonce_pool.is_empty(), false);
@@ -798,7 +875,7 @@ mod nonce_pool_test {
 
         for i in vec![0, 1, 3, 4] {
             assert_eq!(
-                nonce_pool.insert(&tx[i], false /* force */),
+                nonce_pool.insert(&tx[i], false /* force */, 0),
                 InsertResult::NewAdded
             );
             assert_eq!(
@@ -824,7 +901,7 @@ mod nonce_pool_test {
             None
         );
         assert_eq!(
-            nonce_pool.insert(&tx[2], false /* force */),
+            nonce_pool.insert(&tx[2], false /* force */, 0),
             InsertResult::NewAdded
         );
         assert_eq!(
@@ -919,12 +996,12 @@ mod nonce_pool_test {
             let nonce: usize = rng.next_u64() as usize % count;
             if mock_nonce_pool.contains_key(&nonce.into()) {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], true /* force */),
+                    nonce_pool.insert(&tx[nonce], true /* force */, 0),
                     InsertResult::Updated(tx[nonce].clone())
                 );
             } else {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], false /* force */),
+                    nonce_pool.insert(&tx[nonce], false /* force */, 0),
                     InsertResult::NewAdded
                 );
                 mock_nonce_pool.insert(nonce.into(), tx[nonce].clone());
@@ -966,12 +1043,12 @@ mod nonce_pool_test {
             let nonce: usize = rng.next_u64() as usize % count;
             if mock_nonce_pool.contains_key(&nonce.into()) {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], true /* force */),
+                    nonce_pool.insert(&tx[nonce], true /* force */, 0),
                     InsertResult::Updated(tx[nonce].clone())
                 );
             } else {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], false /* force */),
+                    nonce_pool.insert(&tx[nonce], false /* force */, 0),
                     InsertResult::NewAdded
                 );
                 mock_nonce_pool.insert(nonce.into(), tx[nonce].clone());
@@ -999,4 +1076,55 @@ mod nonce_pool_test {
             );
         }
     }
+
+    #[test]
+    fn test_replace_price_bump() {
+        let me = Random.generate().unwrap();
+        let new_tx = |gas_price: u64, packed: bool| {
+            new_test_tx_with_ready_info(
+                &me,
+                0.into(),
+                50000.into(),
+                gas_price.into(),
+                10000.into(),
+                0,
+                packed,
+            )
+        };
+        let mut nonce_pool = NoncePool::new();
+        let tx = new_tx(100, false);
+        assert_eq!(nonce_pool.insert(&tx, false, 10), InsertResult::NewAdded);
+
+        let underpriced = InsertResult::Failed(InsertError::Replacement(
+            ReplacementError::Underpriced {
+                min_gas_price: 110.into(),
+            },
+        ));
+        assert_eq!(
+            nonce_pool.insert(&new_tx(100, false), false, 10),
+            underpriced
+        );
+        assert_eq!(
+            nonce_pool.insert(&new_tx(109, false), false, 10),
+            underpriced
+        );
+        let replacement = new_tx(110, false);
+        assert_eq!(
+            nonce_pool.insert(&replacement, false, 10),
+            InsertResult::Updated(tx)
+        );
+        assert_eq!(nonce_pool.get_tx_by_nonce(0.into()), Some(replacement));
+
+        let packed = new_tx(110, true);
+        assert_eq!(
+            nonce_pool.insert(&packed, true, 10),
+            InsertResult::Updated(replacement)
+        );
+        assert_eq!(
+            nonce_pool.insert(&new_tx(1000, false), false, 10),
+            InsertResult::Failed(InsertError::Replacement(
+                ReplacementError::AlreadyPacked
+            ))
+        );
+    }
 }
@@ -2,7 +2,7 @@ use super::{
     account_cache::AccountCache,
     garbage_collector::GarbageCollector,
     impls::TreapMap,
-    nonce_pool::{InsertResult, NoncePool, TxWithReadyInfo},
+    nonce_pool::{InsertResult, NoncePool, ReplacementError, TxWithReadyInfo},
 };
 use crate::{
     machine::Machine,
@@ -21,7 +21,8 @@ use primitives::{
 use rlp::*;
 use serde::Serialize;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
@@ -35,6 +36,11 @@ const FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET: u32 = 2000;
 // By default, the capacity of tx pool is 500K, so the maximum TPS is
 // 500K / 100 = 5K
 const TIME_WINDOW: u64 = 100;
+/// The number of the most recent replacements kept in the transaction pool.
+const REPLACEMENT_HISTORY_SIZE: usize = 1000;
+/// The number of the most recently garbage-collected executed local
+/// transactions which are still considered local if they are recycled.
+const EXECUTED_LOCAL_TRANSACTIONS_SIZE: usize = 1000;
 
 lazy_static! {
     static ref TX_POOL_RECALCULATE: Arc<dyn Meter> =
@@ -61,12 +67,16 @@ lazy_static! {
 #[derive(DeriveMallocSizeOf)]
 struct DeferredPool {
     buckets: HashMap<Address, NoncePool>,
+    /// The minimum percentage by which the gas price of a transaction has to
+    /// be higher to replace the transaction with the same nonce.
+    replace_price_bump: u8,
 }
 
 impl DeferredPool {
-    fn new() -> Self {
+    fn new(replace_price_bump: u8) -> Self {
         DeferredPool {
             buckets: Default::default(),
+            replace_price_bump,
         }
     }
 
@@ -76,7 +86,7 @@ impl DeferredPool {
         // It's safe to create a new bucket, cause inserting to a empty bucket
         // will always be success
         let bucket = self.buckets.entry(tx.sender).or_insert(NoncePool::new());
-        bucket.insert(&tx, force)
+        bucket.insert(&tx, force, self.replace_price_bump)
     }
 
     fn contain_address(&self, addr: &Address) -> bool {
@@ -163,6 +173,14 @@ impl DeferredPool {
         }
     }
 
+    fn get_tx_by_nonce(
+        &self, addr: &Address, nonce: U256,
+    ) -> Option<TxWithReadyInfo> {
+        self.buckets
+            .get(addr)
+            .and_then(|bucket| bucket.get_tx_by_nonce(nonce))
+    }
+
     fn check_tx_packed(&self, addr: Address, nonce: U256) -> bool {
         if let Some(bucket) = self.buckets.get(&addr) {
             if let Some(tx_with_ready_info) = bucket.get_tx_by_nonce(nonce) {
@@ -277,6 +295,46 @@ pub enum PendingReason {
     NotEnoughCash,
 }
 
+/// The reason why a transaction is not inserted into the transaction pool.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InsertError {
+    PoolFull,
+    /// The transaction can not replace the transaction with the same sender
+    /// and nonce in the pool.
+    Replacement(ReplacementError),
+    Other(String),
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InsertError::PoolFull => write!(f, "Transaction Pool is full"),
+            InsertError::Replacement(e) => write!(f, "{}", e),
+            InsertError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<String> for InsertError {
+    fn from(e: String) -> Self { InsertError::Other(e) }
+}
+
+/// A transaction which replaced, or failed to replace, the transaction with
+/// the same sender and nonce in the pool.
+#[derive(Clone, Debug, DeriveMallocSizeOf)]
+pub struct TxReplacement {
+    pub sender: Address,
+    pub nonce: U256,
+    pub replaced_hash: H256,
+    pub replaced_gas_price: U256,
+    pub hash: H256,
+    pub gas_price: U256,
+    pub timestamp: u64,
+    /// `None` if the replacement succeeded.
+    #[ignore_malloc_size_of = "small enum without heap allocation"]
+    pub rejected: Option<ReplacementError>,
+}
+
 #[derive(DeriveMallocSizeOf)]
 pub struct TransactionPoolInner {
     capacity: usize,
@@ -299,17 +357,32 @@ pub struct TransactionPoolInner {
     /// It should contain the same transaction set as `deferred_pool`.
     txs: HashMap<H256, Arc<SignedTransaction>>,
     tx_sponsored_gas_map: HashMap<H256, (U256, u64)>,
+    /// The hashes of the transactions in `txs` which are submitted through
+    /// the RPC of this node. Unexecuted local transactions are never garbage
+    /// collected.
+    local_transactions: HashSet<H256>,
+    /// The total RLP size of the transactions in `local_transactions`.
+    local_transaction_bytes: usize,
+    /// The hashes of the most recent local transactions which are garbage
+    /// collected after being executed. They are still local if they are
+    /// recycled after a chain reorganization.
+    executed_local_transactions: VecDeque<H256>,
+    /// The most recent replacements of transactions, including the rejected
+    /// ones, in the order they happened.
+    replacement_history: VecDeque<TxReplacement>,
 }
 
 impl TransactionPoolInner {
     pub fn new(
         capacity: usize, tx_weight_scaling: u64, tx_weight_exp: u8,
-    ) -> Self {
+        replace_price_bump: u8,
+    ) -> Self
+    {
         TransactionPoolInner {
             capacity,
             total_received_count: 0,
             unpacked_transaction_count: 0,
-            deferred_pool: DeferredPool::new(),
+            deferred_pool: DeferredPool::new(replace_price_bump),
             ready_account_pool: ReadyAccountPool::new(
                 tx_weight_scaling,
                 tx_weight_exp,
@@ -318,6 +391,10 @@ impl TransactionPoolInner {
             garbage_collector: GarbageCollector::default(),
             txs: HashMap::new(),
             tx_sponsored_gas_map: HashMap::new(),
+            local_transactions: HashSet::new(),
+            local_transaction_bytes: 0,
+            executed_local_transactions: VecDeque::new(),
+            replacement_history: VecDeque::new(),
         }
     }
 
@@ -327,6 +404,10 @@ impl TransactionPoolInner {
         self.ready_nonces_and_balances.clear();
         self.garbage_collector.clear();
         self.txs.clear();
+        self.local_transactions.clear();
+        self.local_transaction_bytes = 0;
+        self.executed_local_transactions.clear();
+        self.replacement_history.clear();
         self.total_received_count = 0;
         self.unpacked_transaction_count = 0;
     }
@@ -345,6 +426,88 @@ impl TransactionPoolInner {
         self.txs.get(tx_hash).map(|x| x.clone())
     }
 
+    /// Mark a transaction in the pool as submitted through the RPC of this
+    /// node.
+    pub fn mark_local(&mut self, tx_hash: H256) {
+        if let Some(tx) = self.txs.get(&tx_hash) {
+            if self.local_transactions.insert(tx_hash) {
+                self.local_transaction_bytes += tx.rlp_size();
+            }
+        }
+    }
+
+    /// Returns whether `tx` was a local transaction.
+    fn unmark_local(&mut self, tx: &SignedTransaction) -> bool {
+        if self.local_transactions.remove(&tx.hash()) {
+            self.local_transaction_bytes -= tx.rlp_size();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn local_transaction_count(&self) -> usize {
+        self.local_transactions.len()
+    }
+
+    pub fn local_transaction_bytes(&self) -> usize {
+        self.local_transaction_bytes
+    }
+
+    pub fn is_local(&self, tx_hash: &H256) -> bool {
+        self.local_transactions.contains(tx_hash)
+    }
+
+    /// Whether a transaction is local or is a recently executed local
+    /// transaction which has been garbage collected.
+    pub fn was_local(&self, tx_hash: &H256) -> bool {
+        self.is_local(tx_hash)
+            || self.executed_local_transactions.contains(tx_hash)
+    }
+
+    /// Returns the local transactions in the pool ordered by sender and nonce.
+    pub fn local_transactions(&self) -> Vec<Arc<SignedTransaction>> {
+        let mut txs: Vec<_> = self
+            .local_transactions
+            .iter()
+            .filter_map(|hash| self.txs.get(hash).cloned())
+            .collect();
+        txs.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+        txs
+    }
+
+    /// Returns the recent replacements of transactions sent by `address`, or
+    /// by all the senders if it is `None`.
+    pub fn replacement_history(
+        &self, address: Option<&Address>,
+    ) -> Vec<TxReplacement> {
+        self.replacement_history
+            .iter()
+            .filter(|r| address.map_or(true, |address| r.sender == *address))
+            .cloned()
+            .collect()
+    }
+
+    fn record_replacement(
+        &mut self, replaced: &SignedTransaction,
+        transaction: &SignedTransaction, rejected: Option<ReplacementError>,
+    )
+    {
+        if self.replacement_history.len() == REPLACEMENT_HISTORY_SIZE {
+            self.replacement_history.pop_front();
+        }
+        self.replacement_history.push_back(TxReplacement {
+            sender: transaction.sender(),
+            nonce: transaction.nonce(),
+            replaced_hash: replaced.hash(),
+            replaced_gas_price: replaced.gas_price,
+            hash: transaction.hash(),
+            gas_price: transaction.gas_price,
+            timestamp: self.get_current_timestamp(),
+            rejected,
+        });
+    }
+
     pub fn is_full(&self) -> bool {
         return self.total_deferred() >= self.capacity;
     }
@@ -396,6 +559,16 @@ impl TransactionPoolInner {
             // We have to garbage collect an unexecuted transaction.
             // TODO: Implement more heuristic strategies
             if lowest_nonce >= ready_nonce {
+                // Unexecuted local transactions are kept. The sender stays in
+                // `garbage_collector` to be checked again after `TIME_WINDOW`.
+                let lowest_tx = self
+                    .deferred_pool
+                    .get_tx_by_nonce(&addr, lowest_nonce)
+                    .expect("lowest nonce exists");
+                if self.is_local(&lowest_tx.hash()) {
+                    self.garbage_collector.insert(&addr, 0, current_timestamp);
+                    continue;
+                }
                 assert_eq!(victim.count, 0);
                 GC_UNEXECUTED_COUNTER.inc(1);
                 warn!("an unexecuted tx is garbage-collected.");
@@ -444,6 +617,16 @@ impl TransactionPoolInner {
 
             // maintain txs
             self.txs.remove(&removed_tx.hash());
+            if self.unmark_local(&removed_tx) && lowest_nonce < ready_nonce
+            {
+                if self.executed_local_transactions.len()
+                    == EXECUTED_LOCAL_TRANSACTIONS_SIZE
+                {
+                    self.executed_local_transactions.pop_front();
+                }
+                self.executed_local_transactions
+                    .push_back(removed_tx.hash());
+            }
             self.tx_sponsored_gas_map.remove(&removed_tx.hash());
         }
 
@@ -473,7 +656,7 @@ impl TransactionPoolInner {
         ) {
             self.collect_garbage();
             if self.is_full() {
-                return InsertResult::Failed("Transaction Pool is full".into());
+                return InsertResult::Failed(InsertError::PoolFull);
             }
         }
         let result = {
@@ -523,12 +706,31 @@ impl TransactionPoolInner {
                     self.unpacked_transaction_count += 1;
                 }
             }
+            InsertResult::Failed(InsertError::Replacement(e)) => {
+                if let Some(pooled_tx) = self
+                    .deferred_pool
+                    .get_tx_by_nonce(&transaction.sender(), transaction.nonce())
+                {
+                    self.record_replacement(
+                        &pooled_tx,
+                        &transaction,
+                        Some(e.clone()),
+                    );
+                }
+            }
             InsertResult::Failed(_) => {}
             InsertResult::Updated(replaced_tx) => {
+                // Packed transactions from blocks replace themselves.
+                if replaced_tx.hash() != transaction.hash() {
+                    self.record_replacement(&replaced_tx, &transaction, None);
+                }
                 if !replaced_tx.is_already_packed() {
                     self.unpacked_transaction_count -= 1;
                 }
                 self.txs.remove(&replaced_tx.hash());
+                if replaced_tx.hash() != transaction.hash() {
+                    self.unmark_local(&replaced_tx);
+                }
                 self.txs.insert(transaction.hash(), transaction.clone());
                 self.tx_sponsored_gas_map.remove(&replaced_tx.hash());
                 self.tx_sponsored_gas_map.insert(
@@ -868,7 +1070,7 @@ impl TransactionPoolInner {
     pub fn insert_transaction_with_readiness_check(
         &mut self, account_cache: &AccountCache,
         transaction: Arc<SignedTransaction>, packed: bool, force: bool,
-    ) -> Result<(), String>
+    ) -> Result<(), InsertError>
     {
         let _timer = MeterTimer::time_func(TX_POOL_INNER_INSERT_TIMER.as_ref());
         let mut sponsored_gas = U256::from(0);
@@ -950,7 +1152,8 @@ impl TransactionPoolInner {
             return Err(format!(
                 "Transaction {:?} is discarded due to in too distant future",
                 transaction.hash()
-            ));
+            )
+            .into());
         } else if !packed /* Because we may get slightly out-dated state for transaction pool, we should allow transaction pool to set already past-nonce transactions to packed. */
             && transaction.nonce < state_nonce
         {
@@ -961,7 +1164,8 @@ impl TransactionPoolInner {
             return Err(format!(
                 "Transaction {:?} is discarded due to a too stale nonce",
                 transaction.hash()
-            ));
+            )
+            .into());
         }
 
         let result = self.insert_transaction_without_readiness_check(
@@ -971,8 +1175,8 @@ impl TransactionPoolInner {
             Some((state_nonce, state_balance)),
             (sponsored_gas, sponsored_storage),
         );
-        if let InsertResult::Failed(info) = result {
-            return Err(format!("Failed imported to deferred pool: {}", info));
+        if let InsertResult::Failed(e) = result {
+            return Err(e);
         }
 
         self.recalculate_readiness_with_state(
@@ -989,7 +1193,10 @@ impl TransactionPoolInner {
 
 #[cfg(test)]
 mod test_transaction_pool_inner {
-    use super::{DeferredPool, InsertResult, TxWithReadyInfo};
+    use super::{
+        DeferredPool, InsertError, InsertResult, ReplacementError,
+        TransactionPoolInner, TxWithReadyInfo,
+    };
     use cfx_types::{Address, U256};
     use keylib::{Generator, KeyPair, Random};
     use primitives::{Action, SignedTransaction, Transaction};
@@ -1030,7 +1237,7 @@ mod test_transaction_pool_inner {
 
     #[test]
     fn test_deferred_pool_insert_and_remove() {
-        let mut deferred_pool = DeferredPool::new();
+        let mut deferred_pool = DeferredPool::new(0);
 
         // insert txs of same sender
         let alice = Random.generate().unwrap();
@@ -1092,7 +1299,11 @@ mod test_transaction_pool_inner {
 
         assert_eq!(
             deferred_pool.insert(bob_tx2.clone(), false /* force */),
-            InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price > {}", bob_tx2_new.gas_price))
+            InsertResult::Failed(InsertError::Replacement(
+                ReplacementError::Underpriced {
+                    min_gas_price: bob_tx2_new.gas_price + 1
+                }
+            ))
         );
 
         assert_eq!(
@@ -1124,7 +1335,7 @@ mod test_transaction_pool_inner {
 
     #[test]
     fn test_deferred_pool_recalculate_readiness() {
-        let mut deferred_pool = super::DeferredPool::new();
+        let mut deferred_pool = super::DeferredPool::new(0);
 
         let alice = Random.generate().unwrap();
 
@@ -1242,4 +1453,103 @@ mod test_transaction_pool_inner {
             None
         );
     }
+
+    fn insert_with_state_nonce(
+        inner: &mut TransactionPoolInner, tx: &Arc<SignedTransaction>,
+        state_nonce: usize,
+    ) -> InsertResult
+    {
+        inner.insert_transaction_without_readiness_check(
+            tx.clone(),
+            false, /* packed */
+            false, /* force */
+            Some((state_nonce.into(), U256::from(1_000_000_000_000u64))),
+            (0.into(), 0),
+        )
+    }
+
+    // Let the transactions of `sender` be garbage collected as if they had
+    // been waiting for a long time.
+    fn make_collectable(inner: &mut TransactionPoolInner, sender: &Address) {
+        inner.garbage_collector.insert(sender, 0, 0);
+    }
+
+    #[test]
+    fn test_collect_garbage_keeps_local_transactions() {
+        let mut inner = TransactionPoolInner::new(2, 1, 1, 0);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let dave = Random.generate().unwrap();
+
+        let local_tx = new_test_tx(&alice, 0, 1, 0);
+        let remote_tx = new_test_tx(&bob, 0, 1, 0);
+        assert_eq!(
+            insert_with_state_nonce(&mut inner, &local_tx, 0),
+            InsertResult::NewAdded
+        );
+        assert_eq!(
+            insert_with_state_nonce(&mut inner, &remote_tx, 0),
+            InsertResult::NewAdded
+        );
+        inner.mark_local(local_tx.hash());
+        make_collectable(&mut inner, &alice.address());
+        make_collectable(&mut inner, &bob.address());
+
+        // Only the unexecuted remote transaction is garbage collected.
+        let new_tx = new_test_tx(&carol, 0, 1, 0);
+        assert_eq!(
+            insert_with_state_nonce(&mut inner, &new_tx, 0),
+            InsertResult::NewAdded
+        );
+        assert!(inner.get(&local_tx.hash()).is_some());
+        assert!(inner.get(&remote_tx.hash()).is_none());
+
+        // Nothing else can be collected to make room.
+        make_collectable(&mut inner, &alice.address());
+        let dave_tx = new_test_tx(&dave, 0, 1, 0);
+        assert_eq!(
+            insert_with_state_nonce(&mut inner, &dave_tx, 0),
+            InsertResult::Failed(InsertError::PoolFull)
+        );
+        assert!(inner.is_local(&local_tx.hash()));
+        // The sender of the local transaction is checked again later.
+        assert!(inner
+            .garbage_collector
+            .get_timestamp(&alice.address())
+            .is_some());
+    }
+
+    #[test]
+    fn test_executed_local_transactions_stay_local() {
+        let mut inner = TransactionPoolInner::new(1, 1, 1, 0);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+
+        let local_tx = new_test_tx(&alice, 0, 1, 0);
+        assert_eq!(
+            insert_with_state_nonce(&mut inner, &local_tx, 0),
+            InsertResult::NewAdded
+        );
+        inner.mark_local(local_tx.hash());
+        assert_eq!(inner.local_transaction_count(), 1);
+        assert_eq!(inner.local_transaction_bytes(), local_tx.rlp_size());
+
+        // The local transaction is executed and then garbage collected.
+        inner.update_nonce_and_balance(
+            &alice.address(),
+            1.into(),
+            U256::from(1_000_000_000_000u64),
+        );
+        let bob_tx = new_test_tx(&bob, 0, 1, 0);
+        assert_eq!(
+            insert_with_state_nonce(&mut inner, &bob_tx, 0),
+            InsertResult::NewAdded
+        );
+        assert!(inner.get(&local_tx.hash()).is_none());
+        assert!(!inner.is_local(&local_tx.hash()));
+        assert_eq!(inner.local_transaction_bytes(), 0);
+        // It is still local if it is recycled.
+        assert!(inner.was_local(&local_tx.hash()));
+    }
 }
@@ -93,6 +93,43 @@ mod impls {
             }
         }
 
+        /// Create a `StateDb` whose cache is populated with `entries`.
+        /// Reading any of these keys will not access `storage`.
+        pub fn new_with_loaded_entries(
+            storage: Storage,
+            entries: impl IntoIterator<Item = (Vec<u8>, Option<Arc<[u8]>>)>,
+        ) -> Self
+        {
+            StateDb {
+                accessed_entries: RwLock::new(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, EntryValue::new(value)))
+                        .collect(),
+                ),
+                storage,
+                checkpoints: Default::default(),
+            }
+        }
+
+        /// Return the keys of all entries that have been loaded or modified
+        /// through this `StateDb`, including the pre-loaded ones.
+        pub fn accessed_keys(&self) -> Vec<Vec<u8>> {
+            self.accessed_entries.read().keys().cloned().collect()
+        }
+
+        /// Return the current values of all entries that have been loaded or
+        /// modified through this `StateDb`. Loaded into a `StateDb` of the
+        /// same storage by `new_with_loaded_entries`, they reproduce the
+        /// current state without committing it.
+        pub fn accessed_entries(&self) -> Vec<(Vec<u8>, Option<Arc<[u8]>>)> {
+            self.accessed_entries
+                .read()
+                .iter()
+                .map(|(key, entry)| (key.clone(), entry.current_value.clone()))
+                .collect()
+        }
+
         /// Set `key` to `value` in latest checkpoint if not set previously.
         fn update_checkpoint(&mut self, key: &Key, value: Option<Value>) {
             if let Some(checkpoint) = self.checkpoints.last_mut() {
@@ -293,6 +330,32 @@ mod impls {
             Ok(deleted_kvs)
         }
 
+        /// Read at most `limit` storage entries of `address` in key order,
+        /// starting from `start_key` (included). The entries are read from
+        /// the storage directly, so the modifications in this `StateDb` are
+        /// not visible. Returns the storage keys with their values, and the
+        /// storage key to continue from if there are more. The storage
+        /// layout, which is stored under the prefix of the storage keys, is
+        /// skipped.
+        pub fn get_original_storage_range(
+            &mut self, address: &Address, start_key: &[u8], limit: usize,
+        ) -> Result<(Vec<(Vec<u8>, StorageValue)>, Option<Vec<u8>>)>
+        {
+            let key_prefix = StorageKey::new_storage_key(address, &[]);
+            let prefix_len = key_prefix.to_key_bytes().len();
+            let (kvs, next_key) = self.storage.read_range(
+                key_prefix,
+                StorageKey::new_storage_key(address, start_key),
+                limit,
+            )?;
+
+            let mut entries = Vec::with_capacity(kvs.len());
+            for (k, v) in kvs {
+                entries.push((k[prefix_len..].to_vec(), rlp::decode(&v)?));
+            }
+            Ok((entries, next_key.map(|k| k[prefix_len..].to_vec())))
+        }
+
         /// Load the storage layout for state commits.
         /// Modification to storage layout is the same as modification of
         /// any other key-values. But as required by MPT structure we
@@ -637,6 +700,7 @@ mod impls {
     use parking_lot::RwLock;
     use primitives::{
         EpochId, SkipInputCheck, StorageKey, StorageLayout, StorageRoot,
+        StorageValue,
     };
     use std::{
         collections::{btree_map::Entry::Occupied, BTreeMap},
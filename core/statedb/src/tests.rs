@@ -5,8 +5,10 @@
 use super::StateDbGeneric;
 use cfx_internal_common::StateRootWithAuxInfo;
 use cfx_storage::{
-    utils::access_mode, ErrorKind, MptKeyValue, Result, StorageStateTrait,
+    utils::access_mode, ErrorKind, KVInserter, MptKeyValue, Result,
+    StorageStateTrait,
 };
+use cfx_types::Address;
 use primitives::{EpochId, StorageKey, MERKLE_NULL_NODE};
 use std::{cell::RefCell, collections::HashMap};
 
@@ -103,6 +105,40 @@ impl StorageStateTrait for MockStorage {
         Err(ErrorKind::Msg("No state root".to_owned()).into())
     }
 
+    fn read_all<DUMPER: KVInserter<MptKeyValue>>(
+        &mut self, dumper: &mut DUMPER,
+    ) -> Result<()> {
+        for (k, v) in &self.contents {
+            *self.num_reads.borrow_mut() += 1;
+            dumper.push((k.clone(), v.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn read_range(
+        &mut self, access_key_prefix: StorageKey, start_key: StorageKey,
+        limit: usize,
+    ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>
+    {
+        let prefix = access_key_prefix.to_key_bytes();
+        let start_key = start_key.to_key_bytes();
+
+        let mut kvs: Vec<_> = self
+            .contents
+            .iter()
+            .filter(|(k, _)| {
+                k.starts_with(&prefix[..]) && **k >= start_key && **k != prefix
+            })
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        kvs.sort();
+        *self.num_reads.get_mut() += kvs.len() as u64;
+
+        let next_key = kvs.get(limit).map(|(k, _)| k.clone());
+        kvs.truncate(limit);
+        Ok((kvs, next_key))
+    }
+
     fn set(&mut self, access_key: StorageKey, value: Box<[u8]>) -> Result<()> {
         *self.num_writes.get_mut() += 1;
         let key = access_key.to_key_bytes();
@@ -296,3 +332,76 @@ fn test_checkpoint_evict_memory() {
     // value is removed from state-db
     assert_eq!(state_db.get_from_cache(&key(b"00")), None);
 }
+
+#[test]
+fn test_loaded_entries() {
+    let storage = MockStorage::empty();
+
+    let mut state_db = StateDbTest::new_with_loaded_entries(
+        storage,
+        vec![(key(b"00"), Some(value(b"v0").into())), (key(b"11"), None)],
+    );
+
+    // pre-loaded entries are served from the cache
+    assert_eq!(
+        state_db.get_raw(storage_key(b"00")).unwrap(),
+        Some(value(b"v0").into())
+    );
+    assert_eq!(state_db.get_raw(storage_key(b"11")).unwrap(), None);
+    assert_eq!(state_db.get_storage_mut().get_num_reads(), 0);
+
+    // other entries are read from storage
+    assert_eq!(state_db.get_raw(storage_key(b"22")).unwrap(), None);
+    assert_eq!(state_db.get_storage_mut().get_num_reads(), 1);
+
+    let mut keys = state_db.accessed_keys();
+    keys.sort();
+    assert_eq!(keys, vec![key(b"00"), key(b"11"), key(b"22")]);
+}
+
+#[test]
+fn test_get_original_storage_range() {
+    let address = Address::repeat_byte(1);
+    let entry = |value: u64| primitives::StorageValue {
+        value: value.into(),
+        owner: None,
+    };
+
+    let mut contents = RawStorage::new();
+    contents.insert(
+        StorageKey::new_storage_root_key(&address).to_key_bytes(),
+        value(b"layout"),
+    );
+    for (k, v) in &[(b"a", 1), (b"b", 2), (b"c", 3)] {
+        contents.insert(
+            StorageKey::new_storage_key(&address, *k).to_key_bytes(),
+            rlp::encode(&entry(*v)).into(),
+        );
+    }
+    contents.insert(
+        StorageKey::new_storage_key(&Address::repeat_byte(2), b"a")
+            .to_key_bytes(),
+        rlp::encode(&entry(4)).into(),
+    );
+    let mut state_db = StateDbTest::new(MockStorage::with_contents(contents));
+    let mut storage_range = |start_key: &[u8]| {
+        let (entries, next_key) = state_db
+            .get_original_storage_range(&address, start_key, 2)
+            .unwrap();
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|(k, v)| (k, v.value.as_u64()))
+            .collect();
+        (entries, next_key)
+    };
+
+    // the storage layout is skipped
+    assert_eq!(
+        storage_range(&[]),
+        (
+            vec![(b"a".to_vec(), 1), (b"b".to_vec(), 2)],
+            Some(b"c".to_vec())
+        )
+    );
+    assert_eq!(storage_range(b"c"), (vec![(b"c".to_vec(), 3)], None));
+}
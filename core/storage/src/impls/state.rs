@@ -426,6 +426,191 @@ impl StateTrait for State {
         }
     }
 
+    /// The key/value pairs in Delta Trie and Intermediate Trie are loaded into
+    /// memory, which is bounded by the number of epochs between snapshots,
+    /// and the key/value pairs in Snapshot DB are streamed into `dumper`.
+    fn read_all<DUMPER: KVInserter<MptKeyValue>>(
+        &mut self, dumper: &mut DUMPER,
+    ) -> Result<()> {
+        self.ensure_temp_slab_for_db_load();
+
+        let mut delta_trie_kvs = Vec::new();
+        self.dump(&mut delta_trie_kvs)?;
+
+        let mut intermediate_trie_kvs = Vec::new();
+        if self.maybe_intermediate_trie_key_padding.is_some() {
+            if let Some(intermediate_trie) = &self.maybe_intermediate_trie {
+                DeltaMptIterator {
+                    mpt: intermediate_trie.clone(),
+                    maybe_root_node: self.intermediate_trie_root.clone(),
+                }
+                .iterate(&mut intermediate_trie_kvs)?;
+            }
+        }
+
+        // Values in Delta Trie override those in Intermediate Trie, which
+        // override those in Snapshot DB. Empty values are tombstones.
+        let mut overrides = HashMap::new();
+        for (k, v) in delta_trie_kvs {
+            overrides
+                .insert(StorageKey::from_delta_mpt_key(&k).to_key_bytes(), v);
+        }
+        for (k, v) in intermediate_trie_kvs {
+            overrides
+                .entry(StorageKey::from_delta_mpt_key(&k).to_key_bytes())
+                .or_insert(v);
+        }
+
+        let mut kv_iterator = self.snapshot_db.snapshot_kv_iterator()?.take();
+        let mut kvs = kv_iterator.iter_range(&[], None)?.take();
+        while let Some((k, v)) = kvs.next()? {
+            match overrides.remove(&k) {
+                None => dumper.push((k, v))?,
+                Some(v) => {
+                    if v.len() > 0 {
+                        dumper.push((k, v))?;
+                    }
+                }
+            }
+        }
+
+        for (k, v) in overrides {
+            if v.len() > 0 {
+                dumper.push((k, v))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keys in Delta Trie and Intermediate Trie are ordered by the hash of
+    /// the storage key, so that they can't be seeked to `start_key`. Instead,
+    /// the subtree under the prefix is walked and only the `limit + 1`
+    /// smallest keys from `start_key` are kept, deletions included. They are
+    /// merged in key order with the key/value pairs streamed from Snapshot
+    /// DB, up to the largest key kept by a trie which had more keys, where
+    /// the page ends even if it is shorter than `limit`.
+    fn read_range(
+        &mut self, access_key_prefix: StorageKey, start_key: StorageKey,
+        limit: usize,
+    ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>
+    {
+        self.ensure_temp_slab_for_db_load();
+
+        let key_prefix = access_key_prefix.to_key_bytes();
+        let lower_bound_incl = start_key.to_key_bytes();
+        let upper_bound_excl = to_key_prefix_iter_upper_bound(&key_prefix);
+
+        let mut delta_trie_kvs =
+            SmallestKeyValues::new(&lower_bound_incl, limit + 1);
+        if let Some(root_node) = &self.delta_trie_root {
+            let delta_mpt_key_prefix = access_key_prefix
+                .to_delta_mpt_key_bytes(&self.delta_trie_key_padding);
+            SubTrieVisitor::new(
+                &self.delta_trie,
+                root_node.clone(),
+                &mut self.owned_node_set,
+            )?
+            .traversal_into(
+                &delta_mpt_key_prefix,
+                &delta_mpt_key_prefix,
+                &mut delta_trie_kvs,
+            )?;
+        }
+
+        let mut intermediate_trie_kvs =
+            SmallestKeyValues::new(&lower_bound_incl, limit + 1);
+        if let (
+            Some(root_node),
+            Some(intermediate_trie),
+            Some(intermediate_trie_key_padding),
+        ) = (
+            &self.intermediate_trie_root,
+            &self.maybe_intermediate_trie,
+            &self.maybe_intermediate_trie_key_padding,
+        ) {
+            let intermediate_mpt_key_prefix = access_key_prefix
+                .to_delta_mpt_key_bytes(intermediate_trie_key_padding);
+            SubTrieVisitor::new(
+                intermediate_trie,
+                root_node.clone(),
+                &mut self.owned_node_set,
+            )?
+            .traversal_into(
+                &intermediate_mpt_key_prefix,
+                &intermediate_mpt_key_prefix,
+                &mut intermediate_trie_kvs,
+            )?;
+        }
+
+        // The keys beyond the largest key kept by a truncated trie are
+        // unknown.
+        let end_key = match (
+            delta_trie_kvs.truncated_at(),
+            intermediate_trie_kvs.truncated_at(),
+        ) {
+            (Some(a), Some(b)) => Some(a.min(b).clone()),
+            (a, b) => a.or(b).cloned(),
+        };
+
+        // Values in Delta Trie override those in Intermediate Trie, which
+        // override those in Snapshot DB. Empty values are tombstones.
+        let mut overrides = delta_trie_kvs.kvs;
+        for (k, v) in intermediate_trie_kvs.kvs {
+            overrides.entry(k).or_insert(v);
+        }
+        let mut overrides = overrides.into_iter().peekable();
+
+        let mut kv_iterator = self.snapshot_db.snapshot_kv_iterator()?.take();
+        let mut snapshot_kvs = kv_iterator
+            .iter_range(
+                lower_bound_incl.as_slice(),
+                upper_bound_excl.as_ref().map(|v| &**v),
+            )?
+            .take();
+        let mut next_snapshot_kv = snapshot_kvs.next()?;
+
+        let mut result = Vec::new();
+        loop {
+            // Take the smallest key of both sides.
+            let (take_snapshot, take_override) =
+                match (&next_snapshot_kv, overrides.peek()) {
+                    (None, None) => return Ok((result, None)),
+                    (Some(_), None) => (true, false),
+                    (None, Some(_)) => (false, true),
+                    (Some((snapshot_key, _)), Some((override_key, _))) => (
+                        snapshot_key <= override_key,
+                        override_key <= snapshot_key,
+                    ),
+                };
+            let snapshot_kv = if take_snapshot {
+                let kv = next_snapshot_kv.take();
+                next_snapshot_kv = snapshot_kvs.next()?;
+                kv
+            } else {
+                None
+            };
+            // The override wins if the keys are equal.
+            let (k, v) = if take_override {
+                overrides.next().unwrap()
+            } else {
+                snapshot_kv.unwrap()
+            };
+            if end_key.as_ref().map_or(false, |end_key| k >= *end_key) {
+                return Ok((result, end_key));
+            }
+            // The value stored under the prefix itself, e.g. the storage
+            // layout of an account, is not part of the range.
+            if v.is_empty() || k == key_prefix {
+                continue;
+            }
+            if result.len() == limit {
+                return Ok((result, Some(k)));
+            }
+            result.push((k, v));
+        }
+    }
+
     fn compute_state_root(&mut self) -> Result<StateRootWithAuxInfo> {
         self.ensure_temp_slab_for_db_load();
 
@@ -892,6 +1077,51 @@ impl State {
     }
 }
 
+/// Keeps the `capacity` smallest key/value pairs pushed from a Delta MPT, in
+/// the snapshot key format, whose keys are not less than `lower_bound_incl`.
+struct SmallestKeyValues<'a> {
+    lower_bound_incl: &'a [u8],
+    capacity: usize,
+    kvs: BTreeMap<Vec<u8>, Box<[u8]>>,
+    truncated: bool,
+}
+
+impl<'a> SmallestKeyValues<'a> {
+    fn new(lower_bound_incl: &'a [u8], capacity: usize) -> Self {
+        Self {
+            lower_bound_incl,
+            capacity,
+            kvs: BTreeMap::new(),
+            truncated: false,
+        }
+    }
+
+    /// The largest key kept if larger keys were dropped.
+    fn truncated_at(&self) -> Option<&Vec<u8>> {
+        if self.truncated {
+            self.kvs.keys().next_back()
+        } else {
+            None
+        }
+    }
+}
+
+impl KVInserter<MptKeyValue> for SmallestKeyValues<'_> {
+    fn push(&mut self, (k, v): MptKeyValue) -> Result<()> {
+        let k = StorageKey::from_delta_mpt_key(&k).to_key_bytes();
+        if k.as_slice() < self.lower_bound_incl {
+            return Ok(());
+        }
+        self.kvs.insert(k, v);
+        if self.kvs.len() > self.capacity {
+            let largest_key = self.kvs.keys().next_back().unwrap().clone();
+            self.kvs.remove(&largest_key);
+            self.truncated = true;
+        }
+        Ok(())
+    }
+}
+
 use crate::{
     impls::{
         delta_mpt::{node_memory_manager::ActualSlabIndex, *},
@@ -918,7 +1148,7 @@ use primitives::{
 use rustc_hex::ToHex;
 use std::{
     cell::UnsafeCell,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     hint::unreachable_unchecked,
     sync::{atomic::Ordering, Arc},
 };
@@ -55,6 +55,20 @@ impl StateManager {
         })
     }
 
+    /// Open the storage of a stopped node without modifying it, see
+    /// `StorageManager::new_arc_readonly`.
+    pub fn new_readonly(
+        conf: StorageConfiguration, open_delta_mpts: bool,
+    ) -> Result<Self> {
+        let storage_manager =
+            StorageManager::new_arc_readonly(conf, open_delta_mpts)?;
+
+        Ok(Self {
+            storage_manager,
+            number_committed_nodes: Default::default(),
+        })
+    }
+
     pub fn log_usage(&self) {
         self.storage_manager.log_usage();
         debug!(
@@ -585,6 +599,47 @@ impl StateManagerTrait for StateManager {
         )
     }
 
+    fn get_state_readonly_at_snapshot(
+        self: &Arc<Self>, snapshot_epoch_id: &EpochId, try_open: bool,
+    ) -> Result<Option<State>> {
+        let snapshot = match self
+            .storage_manager
+            .wait_for_snapshot(snapshot_epoch_id, try_open)?
+        {
+            None => return Ok(None),
+            Some(guarded_snapshot) => guarded_snapshot.into().1,
+        };
+        let snapshot_merkle_root = match self
+            .storage_manager
+            .get_snapshot_info_at_epoch(snapshot_epoch_id)
+        {
+            None => return Ok(None),
+            Some(snapshot_info) => snapshot_info.merkle_root,
+        };
+        // Without a root nothing is read from the delta MPT, it's only
+        // required to construct the state.
+        let delta_mpt =
+            self.storage_manager.get_delta_mpt(snapshot_epoch_id)?;
+
+        let maybe_state_trees = Self::get_state_trees_internal(
+            snapshot,
+            snapshot_epoch_id,
+            snapshot_merkle_root,
+            /* maybe_intermediate_trie = */ None,
+            /* maybe_intermediate_trie_key_padding = */ None,
+            /* intermediate_epoch_id = */ snapshot_epoch_id,
+            MERKLE_NULL_NODE,
+            delta_mpt,
+            /* maybe_delta_mpt_key_padding = */ None,
+            /* epoch_id = */ snapshot_epoch_id,
+            /* delta_root = */ None,
+            /* maybe_height = */ None,
+            /* maybe_delta_trie_height = */ None,
+        )?;
+        Ok(maybe_state_trees
+            .map(|state_trees| State::new(self.clone(), state_trees)))
+    }
+
     // Currently we use epoch number to decide whether or not to
     // start a new delta trie. The value of parent_epoch_id is only
     // known after the computation is done.
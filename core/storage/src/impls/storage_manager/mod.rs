@@ -2,6 +2,8 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+/// Offline inspection and verification of the snapshots on disk.
+pub mod snapshot_inspection;
 mod snapshot_manager;
 /// Storage manager manages the lifecycle of SnapshotMPTS and DeltaMPTs.
 pub mod storage_manager;
@@ -0,0 +1,215 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// Whether a snapshot info is kept when the node starts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotInfoStatus {
+    Kept,
+    /// The snapshot is missing, so the snapshot info is removed.
+    SnapshotMissing,
+    /// The Delta MPT and the Intermediate Delta MPT are missing, so the
+    /// snapshot info and the snapshot are removed.
+    DeltaMptMissing,
+}
+
+/// The merkle roots of a snapshot to check against its snapshot info.
+#[derive(Debug)]
+pub struct SnapshotMerkleRootCheck {
+    /// The merkle root recorded in the snapshot info.
+    pub expected: MerkleHash,
+    /// The merkle root stored in the snapshot MPT.
+    pub mpt_merkle_root: MerkleHash,
+    /// The merkle root recomputed from the snapshot key-values.
+    pub recomputed_merkle_root: MerkleHash,
+    pub key_value_count: u64,
+}
+
+impl SnapshotMerkleRootCheck {
+    pub fn is_valid(&self) -> bool {
+        self.mpt_merkle_root == self.expected
+            && self.recomputed_merkle_root == self.expected
+    }
+}
+
+/// Number and total size of the key-values of a type of `StorageKey`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyValueStats {
+    pub count: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+}
+
+impl KeyValueStats {
+    fn add(&mut self, key: &[u8], value: &[u8]) {
+        self.count += 1;
+        self.key_bytes += key.len() as u64;
+        self.value_bytes += value.len() as u64;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotKvDiff {
+    /// The key-value only exists in the new snapshot.
+    Added(MptKeyValue),
+    /// The key-value only exists in the old snapshot.
+    Removed(MptKeyValue),
+    Changed {
+        key: Vec<u8>,
+        old_value: Box<[u8]>,
+        new_value: Box<[u8]>,
+    },
+}
+
+/// Name of the `StorageKey` type of a key in the snapshot.
+pub fn key_type_name(key: &[u8]) -> &'static str {
+    match StorageKey::from_key_bytes::<CheckInput>(key) {
+        Ok(StorageKey::AccountKey(_)) => "account",
+        Ok(StorageKey::StorageRootKey(_)) => "storage_root",
+        Ok(StorageKey::StorageKey { .. }) => "storage",
+        Ok(StorageKey::CodeRootKey(_)) => "code_root",
+        Ok(StorageKey::CodeKey { .. }) => "code",
+        Ok(StorageKey::DepositListKey(_)) => "deposit_list",
+        Ok(StorageKey::VoteListKey(_)) => "vote_list",
+        Err(_) => "unknown",
+    }
+}
+
+/// Recompute the merkle root of the snapshot from its key-values, and
+/// collect it with the merkle roots of the snapshot info and the snapshot
+/// MPT, which all match for an intact snapshot.
+pub fn check_snapshot_merkle_root(
+    storage_manager: &StorageManager, snapshot_epoch_id: &EpochId,
+) -> Result<SnapshotMerkleRootCheck> {
+    // The snapshot info is registered once the snapshot is made.
+    let snapshot_db = open_snapshot(storage_manager, snapshot_epoch_id)?;
+    let snapshot_info =
+        match storage_manager.get_snapshot_info_at_epoch(snapshot_epoch_id) {
+            Some(snapshot_info) => snapshot_info,
+            None => bail!(ErrorKind::SnapshotNotFound),
+        };
+    let mpt_merkle_root =
+        snapshot_db.open_snapshot_mpt_shared()?.get_merkle_root();
+
+    // The merger never reads back the nodes it writes when all the keys are
+    // inserted in order, so the new MPT doesn't need to be kept.
+    let mut key_value_count = 0;
+    let mut kv_iterator = snapshot_db.snapshot_kv_iterator()?.take();
+    let kvs = kv_iterator.iter_range(&[], None)?.take().map(|kv| {
+        key_value_count += 1;
+        Ok(kv)
+    });
+    let mut mpt = SimpleMpt::new_discard_write();
+    let recomputed_merkle_root = MptMerger::new(None, &mut mpt)
+        .merge_insertion_deletion_separated(
+            fallible_iterator::convert(
+                std::iter::empty::<Result<(Vec<u8>, ())>>(),
+            ),
+            kvs,
+        )?;
+
+    Ok(SnapshotMerkleRootCheck {
+        expected: snapshot_info.merkle_root,
+        mpt_merkle_root,
+        recomputed_merkle_root,
+        key_value_count,
+    })
+}
+
+/// Statistics of the snapshot key-values by the type of `StorageKey`.
+pub fn snapshot_key_value_stats(
+    storage_manager: &StorageManager, snapshot_epoch_id: &EpochId,
+) -> Result<BTreeMap<&'static str, KeyValueStats>> {
+    let snapshot_db = open_snapshot(storage_manager, snapshot_epoch_id)?;
+    let mut kv_iterator = snapshot_db.snapshot_kv_iterator()?.take();
+    let mut kvs = kv_iterator.iter_range(&[], None)?.take();
+
+    let mut stats = BTreeMap::<&'static str, KeyValueStats>::new();
+    while let Some((key, value)) = kvs.next()? {
+        stats
+            .entry(key_type_name(&key))
+            .or_default()
+            .add(&key, &value);
+    }
+    Ok(stats)
+}
+
+/// Compare the key-values of two snapshots in key order, and push the
+/// differences into `diffs`.
+pub fn diff_snapshots<DiffInserter: KVInserter<SnapshotKvDiff>>(
+    storage_manager: &StorageManager, old_snapshot_epoch_id: &EpochId,
+    new_snapshot_epoch_id: &EpochId, diffs: &mut DiffInserter,
+) -> Result<()>
+{
+    let old_snapshot_db =
+        open_snapshot(storage_manager, old_snapshot_epoch_id)?;
+    let new_snapshot_db =
+        open_snapshot(storage_manager, new_snapshot_epoch_id)?;
+    let mut old_kv_iterator = old_snapshot_db.snapshot_kv_iterator()?.take();
+    let mut old_kvs = old_kv_iterator.iter_range(&[], None)?.take();
+    let mut new_kv_iterator = new_snapshot_db.snapshot_kv_iterator()?.take();
+    let mut new_kvs = new_kv_iterator.iter_range(&[], None)?.take();
+
+    let mut old_kv = old_kvs.next()?;
+    let mut new_kv = new_kvs.next()?;
+    loop {
+        let ordering = match (&old_kv, &new_kv) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((old_key, _)), Some((new_key, _))) => old_key.cmp(new_key),
+        };
+        match ordering {
+            Ordering::Less => {
+                diffs.push(SnapshotKvDiff::Removed(old_kv.take().unwrap()))?;
+                old_kv = old_kvs.next()?;
+            }
+            Ordering::Greater => {
+                diffs.push(SnapshotKvDiff::Added(new_kv.take().unwrap()))?;
+                new_kv = new_kvs.next()?;
+            }
+            Ordering::Equal => {
+                let (key, old_value) = old_kv.take().unwrap();
+                let (_, new_value) = new_kv.take().unwrap();
+                if old_value != new_value {
+                    diffs.push(SnapshotKvDiff::Changed {
+                        key,
+                        old_value,
+                        new_value,
+                    })?;
+                }
+                old_kv = old_kvs.next()?;
+                new_kv = new_kvs.next()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_snapshot(
+    storage_manager: &StorageManager, snapshot_epoch_id: &EpochId,
+) -> Result<Arc<SnapshotDb>> {
+    match storage_manager
+        .wait_for_snapshot(snapshot_epoch_id, /* try_open = */ false)?
+    {
+        Some(snapshot_db) => Ok(snapshot_db.into().1),
+        None => bail!(ErrorKind::SnapshotNotFound),
+    }
+}
+
+use super::storage_manager::StorageManager;
+use crate::{
+    impls::{
+        errors::*,
+        merkle_patricia_trie::{MptKeyValue, MptMerger},
+        state_manager::SnapshotDb,
+    },
+    storage_db::{
+        KeyValueDbIterableTrait, OpenSnapshotMptTrait, SnapshotDbTrait,
+        SnapshotMptTraitRead,
+    },
+    KVInserter, SimpleMpt,
+};
+use fallible_iterator::FallibleIterator;
+use primitives::{CheckInput, EpochId, MerkleHash, StorageKey};
+use std::{cmp::Ordering, collections::BTreeMap, sync::Arc};
@@ -46,6 +46,14 @@ impl PersistedSnapshotInfoMap {
         Ok(())
     }
 
+    // Unsafe because the in mem map isn't in sync with the db.
+    unsafe fn insert_in_mem_only(
+        &mut self, epoch: &EpochId, snapshot_info: SnapshotInfo,
+    ) {
+        self.snapshot_info_map_by_epoch
+            .insert(epoch.clone(), snapshot_info);
+    }
+
     // Unsafe because the in mem map isn't in sync with the db.
     unsafe fn remove_in_mem_only(
         &mut self, epoch: &EpochId,
@@ -214,6 +222,52 @@ impl StorageManager {
             SNAPSHOT_KVDB_STATEMENTS.clone(),
             false, /* unsafe_mode */
         )?;
+
+        let storage_manager =
+            Self::new_arc_impl(storage_conf, snapshot_info_db)?;
+        storage_manager.load_persist_state()?;
+        Ok(storage_manager)
+    }
+
+    /// Open the storage of a stopped node for offline inspection. Unlike
+    /// `new_arc`, nothing on disk is created or removed: the snapshot infos
+    /// which `load_persist_state` would drop are kept, see
+    /// `get_snapshot_info_status`. The Delta MPTs are only opened if
+    /// `open_delta_mpts` is true, since they are only needed to read the
+    /// states after the snapshots.
+    pub fn new_arc_readonly(
+        storage_conf: StorageConfiguration, open_delta_mpts: bool,
+    ) -> Result<Arc<Self>> {
+        for path in &[
+            &storage_conf.path_delta_mpts_dir,
+            &storage_conf.path_snapshot_dir,
+            &storage_conf.path_snapshot_info_db,
+        ] {
+            if !path.exists() {
+                bail!(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} is not found", path.display()),
+                ));
+            }
+        }
+
+        let snapshot_info_db = KvdbSqlite::open(
+            &storage_conf.path_snapshot_info_db,
+            /* readonly = */ true,
+            SNAPSHOT_KVDB_STATEMENTS.clone(),
+        )?;
+
+        let storage_manager =
+            Self::new_arc_impl(storage_conf, snapshot_info_db)?;
+        storage_manager.load_persist_state_readonly(open_delta_mpts)?;
+        Ok(storage_manager)
+    }
+
+    fn new_arc_impl(
+        storage_conf: StorageConfiguration,
+        snapshot_info_db: KvdbSqlite<Box<[u8]>>,
+    ) -> Result<Arc<Self>>
+    {
         let snapshot_info_map =
             PersistedSnapshotInfoMap::new(snapshot_info_db)?;
 
@@ -290,11 +344,6 @@ impl StorageManager {
             }
         )?);
 
-        new_storage_manager_result
-            .as_ref()
-            .unwrap()
-            .load_persist_state()?;
-
         new_storage_manager_result
     }
 
@@ -399,6 +448,20 @@ impl StorageManager {
             .map(Clone::clone)
     }
 
+    /// All snapshot infos ordered by height, including those kept only to
+    /// provide sync whose snapshot is removed.
+    pub fn get_snapshot_infos(&self) -> Vec<(EpochId, SnapshotInfo)> {
+        let mut snapshot_infos: Vec<(EpochId, SnapshotInfo)> = self
+            .snapshot_info_map_by_epoch
+            .read()
+            .get_map()
+            .iter()
+            .map(|(epoch, info)| (epoch.clone(), info.clone()))
+            .collect();
+        snapshot_infos.sort_by_key(|(epoch, info)| (info.height, *epoch));
+        snapshot_infos
+    }
+
     pub fn get_delta_mpt(
         &self, snapshot_epoch_id: &EpochId,
     ) -> Result<Arc<DeltaMpt>> {
@@ -1345,21 +1408,9 @@ impl StorageManager {
 
         let mut delta_mpts = HashMap::new();
         for (snapshot_epoch_id, delta_db) in delta_dbs {
-            let mpt_id = self.delta_mpts_id_gen.lock().allocate()?;
-            self.delta_mpt_open_db_lru.import(
-                &snapshot_epoch_id,
-                mpt_id,
-                delta_db,
-            )?;
             delta_mpts.insert(
                 snapshot_epoch_id.clone(),
-                Arc::new(DeltaMpt::new(
-                    self.delta_mpt_open_db_lru.clone(),
-                    snapshot_epoch_id.clone(),
-                    unsafe { shared_from_this(self) },
-                    mpt_id,
-                    self.delta_mpts_node_memory_manager.clone(),
-                )?),
+                self.import_delta_db(&snapshot_epoch_id, delta_db)?,
             );
         }
 
@@ -1389,6 +1440,117 @@ impl StorageManager {
                 .destroy_snapshot(&snapshot_epoch_id)?;
         }
 
+        self.restore_current_snapshots(snapshot_info_map, &delta_mpts);
+        Ok(())
+    }
+
+    /// Load the snapshot infos and the Delta MPTs like `load_persist_state`,
+    /// but without removing anything.
+    fn load_persist_state_readonly(&self, open_delta_mpts: bool) -> Result<()> {
+        let snapshot_info_map = &mut *self.snapshot_info_map_by_epoch.write();
+
+        self.snapshot_associated_mpts_by_epoch
+            .write()
+            .insert(NULL_EPOCH, (None, None));
+        if snapshot_info_map.get(&NULL_EPOCH).is_none() {
+            unsafe {
+                snapshot_info_map.insert_in_mem_only(
+                    &NULL_EPOCH,
+                    SnapshotInfo::genesis_snapshot_info(),
+                );
+            }
+        }
+
+        let mut delta_mpts = HashMap::new();
+        if open_delta_mpts {
+            for (snapshot_epoch_id, snapshot_info) in
+                snapshot_info_map.get_map()
+            {
+                // The Delta MPT and the Intermediate Delta MPT.
+                for epoch_id in &[
+                    snapshot_epoch_id,
+                    &snapshot_info.parent_snapshot_epoch_id,
+                ] {
+                    if delta_mpts.contains_key(*epoch_id) {
+                        continue;
+                    }
+                    if let Some(delta_db) = self.delta_db_manager.get_delta_db(
+                        &self.delta_db_manager.get_delta_db_name(epoch_id),
+                    )? {
+                        delta_mpts.insert(
+                            (*epoch_id).clone(),
+                            self.import_delta_db(epoch_id, delta_db)?,
+                        );
+                    }
+                }
+            }
+        }
+
+        self.restore_current_snapshots(snapshot_info_map, &delta_mpts);
+        Ok(())
+    }
+
+    /// Whether `load_persist_state` keeps the snapshot info at the next start
+    /// of the node, judging by the snapshot and the Delta MPTs on disk.
+    pub fn get_snapshot_info_status(
+        &self, snapshot_epoch_id: &EpochId, snapshot_info: &SnapshotInfo,
+    ) -> SnapshotInfoStatus {
+        if *snapshot_epoch_id == NULL_EPOCH {
+            return SnapshotInfoStatus::Kept;
+        }
+        if snapshot_info.snapshot_info_kept_to_provide_sync
+            != SnapshotKeptToProvideSyncStatus::InfoOnly
+            && !self
+                .snapshot_manager
+                .get_snapshot_db_manager()
+                .get_snapshot_db_path(snapshot_epoch_id)
+                .exists()
+        {
+            return SnapshotInfoStatus::SnapshotMissing;
+        }
+        let delta_db_exists = |epoch_id: &EpochId| {
+            self.delta_db_manager
+                .get_delta_db_path(
+                    &self.delta_db_manager.get_delta_db_name(epoch_id),
+                )
+                .exists()
+        };
+        // A freshly made snapshot only has the Intermediate Delta MPT.
+        if snapshot_info.snapshot_info_kept_to_provide_sync
+            == SnapshotKeptToProvideSyncStatus::No
+            && !delta_db_exists(snapshot_epoch_id)
+            && !delta_db_exists(&snapshot_info.parent_snapshot_epoch_id)
+        {
+            return SnapshotInfoStatus::DeltaMptMissing;
+        }
+        SnapshotInfoStatus::Kept
+    }
+
+    fn import_delta_db(
+        &self, snapshot_epoch_id: &EpochId,
+        delta_db: <DeltaDbManager as DeltaDbManagerTrait>::DeltaDb,
+    ) -> Result<Arc<DeltaMpt>>
+    {
+        let mpt_id = self.delta_mpts_id_gen.lock().allocate()?;
+        self.delta_mpt_open_db_lru.import(
+            snapshot_epoch_id,
+            mpt_id,
+            delta_db,
+        )?;
+        Ok(Arc::new(DeltaMpt::new(
+            self.delta_mpt_open_db_lru.clone(),
+            snapshot_epoch_id.clone(),
+            unsafe { shared_from_this(self) },
+            mpt_id,
+            self.delta_mpts_node_memory_manager.clone(),
+        )?))
+    }
+
+    fn restore_current_snapshots(
+        &self, snapshot_info_map: &PersistedSnapshotInfoMap,
+        delta_mpts: &HashMap<EpochId, Arc<DeltaMpt>>,
+    )
+    {
         // Restore current_snapshots.
         let mut snapshots = snapshot_info_map
             .get_map()
@@ -1415,8 +1577,6 @@ impl StorageManager {
                 ),
             );
         }
-
-        Ok(())
     }
 }
 
@@ -1530,7 +1690,10 @@ use crate::{
             },
             snapshot_db_sqlite::test_lib::check_key_value_load,
         },
-        storage_manager::snapshot_manager::SnapshotManager,
+        storage_manager::{
+            snapshot_inspection::SnapshotInfoStatus,
+            snapshot_manager::SnapshotManager,
+        },
     },
     snapshot_manager::SnapshotManagerTrait,
     storage_db::{
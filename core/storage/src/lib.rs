@@ -175,6 +175,7 @@ pub use self::{
             snapshot_db_manager_sqlite::SnapshotDbManagerSqlite,
             sqlite::SqliteConnection,
         },
+        storage_manager::snapshot_inspection,
     },
     state::{
         State as StorageState, StateTrait as StorageStateTrait,
@@ -30,6 +30,23 @@ pub trait StateTrait {
     fn delete_all<AM: access_mode::AccessMode>(
         &mut self, access_key_prefix: StorageKey,
     ) -> Result<Option<Vec<MptKeyValue>>>;
+    /// Read all key/value pairs of the state, merged from the snapshot, the
+    /// intermediate trie and the delta trie, and push them into `dumper` in
+    /// no particular order. Keys are in the snapshot format, i.e. the output
+    /// of `StorageKey::to_key_bytes`.
+    fn read_all<DUMPER: KVInserter<MptKeyValue>>(
+        &mut self, dumper: &mut DUMPER,
+    ) -> Result<()>;
+    /// Read at most `limit` key/value pairs prefixed by `access_key_prefix`
+    /// in key order, starting from `start_key` (included), which has the
+    /// same prefix. The value stored under the prefix itself is skipped.
+    /// Keys are in the snapshot format. Returns the key/value pairs and the
+    /// key to continue from if there are more, which may be returned with
+    /// less than `limit` pairs.
+    fn read_range(
+        &mut self, access_key_prefix: StorageKey, start_key: StorageKey,
+        limit: usize,
+    ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>;
 
     // Finalize
     /// It's costly to compute state root however it's only necessary to compute
@@ -57,6 +74,6 @@ use super::{
         errors::*, node_merkle_proof::NodeMerkleProof, state_proof::StateProof,
     },
     utils::access_mode,
-    MptKeyValue, StateRootWithAuxInfo,
+    KVInserter, MptKeyValue, StateRootWithAuxInfo,
 };
 use primitives::{EpochId, NodeMerkleTriplet, StaticBool, StorageKey};
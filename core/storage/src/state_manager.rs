@@ -37,6 +37,12 @@ pub trait StateManagerTrait {
         self: &Arc<Self>, parent_epoch_id: StateIndex,
     ) -> Result<Option<State>>;
     fn get_state_for_genesis_write(self: &Arc<Self>) -> State;
+    /// The state of the snapshot epoch read from the snapshot alone, without
+    /// any delta MPT. It's for reading only, the changes must never be
+    /// committed. Returns None when the snapshot isn't available.
+    fn get_state_readonly_at_snapshot(
+        self: &Arc<Self>, snapshot_epoch_id: &EpochId, try_open: bool,
+    ) -> Result<Option<State>>;
 }
 
 impl StateIndex {
@@ -10,6 +10,8 @@ mod proofs;
 #[cfg(test)]
 mod sharded_iter_merger;
 #[cfg(test)]
+mod snapshot_inspection;
+#[cfg(test)]
 mod state;
 
 #[cfg(test)]
@@ -236,6 +238,85 @@ fn generate_account_keys(number_of_keys: usize) -> Vec<Vec<u8>> {
 #[cfg(test)]
 fn get_rng_for_test() -> ChaChaRng { ChaChaRng::from_seed([123; 32]) }
 
+/// An epoch committed by `commit_epochs`, with the key-values expected in its
+/// state.
+#[cfg(test)]
+struct CommittedEpoch {
+    epoch_id: EpochId,
+    state_root: StateRootWithAuxInfo,
+    expected_state: BTreeMap<Vec<u8>, Box<[u8]>>,
+}
+
+/// Commit a chain of `epoch_count` epochs from the genesis, where the first
+/// byte of the id of the i-th epoch is i + 1. `epoch_changes(i)` returns the
+/// account keys set in the i-th epoch, or deleted if the value is `None`.
+#[cfg(test)]
+fn commit_epochs<F>(
+    state_manager: &FakeStateManager, snapshot_epoch_count: u32,
+    epoch_count: u8, mut epoch_changes: F,
+) -> Vec<CommittedEpoch>
+where
+    F: FnMut(u8) -> Vec<(Vec<u8>, Option<Box<[u8]>>)>,
+{
+    let mut epochs = Vec::<CommittedEpoch>::new();
+    let mut expected_state = BTreeMap::new();
+    for epoch in 0..epoch_count {
+        let mut state = match epochs.last() {
+            None => state_manager.get_state_for_genesis_write(),
+            Some(parent) => state_manager
+                .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                    &parent.epoch_id,
+                    &parent.state_root,
+                    epoch as u64,
+                    snapshot_epoch_count,
+                ))
+                .unwrap()
+                .unwrap(),
+        };
+        for (key, maybe_value) in epoch_changes(epoch) {
+            match maybe_value {
+                Some(value) => {
+                    state
+                        .set(StorageKey::AccountKey(&key), value.clone())
+                        .unwrap();
+                    expected_state.insert(key, value);
+                }
+                None => {
+                    state.delete(StorageKey::AccountKey(&key)).unwrap();
+                    expected_state.remove(&key);
+                }
+            }
+        }
+
+        let mut epoch_id = H256::default();
+        epoch_id.as_bytes_mut()[0] = epoch + 1;
+        let state_root = state.compute_state_root().unwrap();
+        state.commit(epoch_id).unwrap();
+        epochs.push(CommittedEpoch {
+            epoch_id,
+            state_root,
+            expected_state: expected_state.clone(),
+        });
+    }
+    epochs
+}
+
+#[cfg(test)]
+/// The snapshots which the states of `epochs` are based on, in order and
+/// without the genesis snapshot.
+fn snapshot_epoch_ids(epochs: &[CommittedEpoch]) -> Vec<EpochId> {
+    let mut snapshot_epoch_ids = vec![];
+    for epoch in epochs {
+        let snapshot_epoch_id = epoch.state_root.aux_info.snapshot_epoch_id;
+        if snapshot_epoch_id != NULL_EPOCH
+            && !snapshot_epoch_ids.contains(&snapshot_epoch_id)
+        {
+            snapshot_epoch_ids.push(snapshot_epoch_id);
+        }
+    }
+    snapshot_epoch_ids
+}
+
 // Kept for debugging.
 #[allow(dead_code)]
 pub fn print_mpt_key(key: &[u8]) {
@@ -259,16 +340,28 @@ use crate::{
     },
     KVInserter,
 };
+#[cfg(test)]
+use crate::{
+    state::StateTrait,
+    state_manager::{StateIndex, StateManagerTrait},
+    StateRootWithAuxInfo,
+};
+#[cfg(test)]
+use cfx_types::H256;
 use fallible_iterator::FallibleIterator;
 use kvdb::{DBTransaction, DBValue, KeyValueDB};
 use parity_util_mem::{MallocSizeOf, MallocSizeOfOps};
 use primitives::StorageKey;
+#[cfg(test)]
+use primitives::{EpochId, NULL_EPOCH};
 #[cfg(any(test, feature = "testonly_code"))]
 use rand::random;
 #[cfg(test)]
 use rand::{seq::SliceRandom, Rng, SeedableRng};
 #[cfg(test)]
 use rand_chacha::ChaChaRng;
+#[cfg(test)]
+use std::collections::BTreeMap;
 #[cfg(any(test, feature = "testonly_code"))]
 use std::{
     fs,
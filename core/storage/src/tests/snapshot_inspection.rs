@@ -0,0 +1,209 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+#[test]
+fn test_snapshot_inspection() {
+    let snapshot_epoch_count = 1;
+    let state_manager =
+        new_state_manager_for_unit_test_with_snapshot_epoch_count(
+            snapshot_epoch_count,
+        );
+    let keys = generate_account_keys(1000);
+
+    // Each epoch adds 100 keys, changes 50 keys and deletes 50 keys.
+    let epochs =
+        commit_epochs(&state_manager, snapshot_epoch_count, 6, |epoch| {
+            if epoch == 0 {
+                return keys[0..400]
+                    .iter()
+                    .map(|key| (key.clone(), Some(key[..].into())))
+                    .collect();
+            }
+            let i = epoch as usize * 100;
+            let added = keys[300 + i..400 + i]
+                .iter()
+                .map(|key| (key.clone(), Some(key[..].into())));
+            let changed = keys[i - 100..i - 50]
+                .iter()
+                .map(|key| (key.clone(), Some(vec![epoch; 8].into())));
+            let deleted = keys[i - 50..i].iter().map(|key| (key.clone(), None));
+            added.chain(changed).chain(deleted).collect()
+        });
+    let expected_state_at = |epoch_id: &EpochId| {
+        &epochs[epoch_id.as_bytes()[0] as usize - 1].expected_state
+    };
+
+    let storage_manager = state_manager.get_storage_manager();
+    let snapshot_epoch_ids = snapshot_epoch_ids(&epochs);
+    for snapshot_epoch_id in &snapshot_epoch_ids {
+        let state_root = &epochs
+            .iter()
+            .find(|epoch| {
+                epoch.state_root.aux_info.snapshot_epoch_id
+                    == *snapshot_epoch_id
+            })
+            .unwrap()
+            .state_root;
+        let check =
+            check_snapshot_merkle_root(storage_manager, snapshot_epoch_id)
+                .unwrap();
+        assert!(check.is_valid(), "{:?}", check);
+        assert_eq!(
+            check.recomputed_merkle_root,
+            state_root.state_root.snapshot_root
+        );
+        assert_eq!(
+            check.key_value_count,
+            expected_state_at(snapshot_epoch_id).len() as u64
+        );
+    }
+    assert!(snapshot_epoch_ids.len() >= 2);
+
+    let snapshot_infos = storage_manager.get_snapshot_infos();
+    for snapshot_epoch_id in &snapshot_epoch_ids {
+        assert!(snapshot_infos
+            .iter()
+            .any(|(epoch_id, _)| epoch_id == snapshot_epoch_id));
+    }
+    assert!(snapshot_infos
+        .windows(2)
+        .all(|infos| infos[0].1.height <= infos[1].1.height));
+
+    for snapshot_epoch_id in &snapshot_epoch_ids {
+        let expected_state = expected_state_at(snapshot_epoch_id);
+        let stats =
+            snapshot_key_value_stats(storage_manager, snapshot_epoch_id)
+                .unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(
+            stats["account"],
+            KeyValueStats {
+                count: expected_state.len() as u64,
+                key_bytes: expected_state.keys().map(|k| k.len() as u64).sum(),
+                value_bytes: expected_state
+                    .values()
+                    .map(|v| v.len() as u64)
+                    .sum(),
+            }
+        );
+    }
+
+    let old_snapshot_epoch_id = snapshot_epoch_ids.first().unwrap();
+    let new_snapshot_epoch_id = snapshot_epoch_ids.last().unwrap();
+    let old_state = expected_state_at(old_snapshot_epoch_id);
+    let new_state = expected_state_at(new_snapshot_epoch_id);
+    let mut expected_diffs = vec![];
+    let all_keys: BTreeSet<&Vec<u8>> =
+        old_state.keys().chain(new_state.keys()).collect();
+    for key in all_keys {
+        match (old_state.get(key), new_state.get(key)) {
+            (Some(old_value), None) => expected_diffs.push(
+                SnapshotKvDiff::Removed((key.clone(), old_value.clone())),
+            ),
+            (None, Some(new_value)) => expected_diffs
+                .push(SnapshotKvDiff::Added((key.clone(), new_value.clone()))),
+            (Some(old_value), Some(new_value)) if old_value != new_value => {
+                expected_diffs.push(SnapshotKvDiff::Changed {
+                    key: key.clone(),
+                    old_value: old_value.clone(),
+                    new_value: new_value.clone(),
+                })
+            }
+            _ => {}
+        }
+    }
+    assert!(!expected_diffs.is_empty());
+
+    let mut diffs = vec![];
+    diff_snapshots(
+        storage_manager,
+        old_snapshot_epoch_id,
+        new_snapshot_epoch_id,
+        &mut diffs,
+    )
+    .unwrap();
+    assert_eq!(diffs, expected_diffs);
+
+    let mut diffs = vec![];
+    diff_snapshots(
+        storage_manager,
+        new_snapshot_epoch_id,
+        new_snapshot_epoch_id,
+        &mut diffs,
+    )
+    .unwrap();
+    assert!(diffs.is_empty());
+
+    // Lose the files of a snapshot. Opening the storage read-only reports
+    // the snapshot info which would be dropped, but doesn't remove it.
+    let lost_snapshot_epoch_id = old_snapshot_epoch_id;
+    fs::remove_dir_all(
+        storage_manager
+            .get_snapshot_manager()
+            .get_snapshot_db_manager()
+            .get_snapshot_db_path(lost_snapshot_epoch_id),
+    )
+    .unwrap();
+    let storage_conf = StorageConfiguration::new_default(
+        &state_manager.data_dir,
+        snapshot_epoch_count,
+    );
+    for _ in 0..2 {
+        let readonly_state_manager =
+            StateManager::new_readonly(storage_conf.clone(), false).unwrap();
+        let readonly_storage_manager =
+            readonly_state_manager.get_storage_manager();
+        let snapshot_infos = readonly_storage_manager.get_snapshot_infos();
+        for snapshot_epoch_id in &snapshot_epoch_ids {
+            let (_, snapshot_info) = snapshot_infos
+                .iter()
+                .find(|(epoch_id, _)| epoch_id == snapshot_epoch_id)
+                .unwrap();
+            let expected_status = if snapshot_epoch_id == lost_snapshot_epoch_id
+            {
+                SnapshotInfoStatus::SnapshotMissing
+            } else {
+                SnapshotInfoStatus::Kept
+            };
+            assert_eq!(
+                readonly_storage_manager
+                    .get_snapshot_info_status(snapshot_epoch_id, snapshot_info),
+                expected_status
+            );
+        }
+
+        let check = check_snapshot_merkle_root(
+            readonly_storage_manager,
+            new_snapshot_epoch_id,
+        )
+        .unwrap();
+        assert!(check.is_valid(), "{:?}", check);
+    }
+
+    // Nothing is created for a missing storage.
+    let missing_conf = StorageConfiguration::new_default(
+        &(state_manager.data_dir.clone() + "_missing"),
+        snapshot_epoch_count,
+    );
+    assert!(StateManager::new_readonly(missing_conf.clone(), false).is_err());
+    assert!(!missing_conf.path_storage_dir.exists());
+}
+
+use crate::{
+    snapshot_inspection::{
+        check_snapshot_merkle_root, diff_snapshots, snapshot_key_value_stats,
+        KeyValueStats, SnapshotInfoStatus, SnapshotKvDiff,
+    },
+    snapshot_manager::SnapshotManagerTrait,
+    state_manager::*,
+    storage_db::SnapshotDbManagerTrait,
+    tests::{
+        commit_epochs, generate_account_keys,
+        new_state_manager_for_unit_test_with_snapshot_epoch_count,
+        snapshot_epoch_ids,
+    },
+    StorageConfiguration,
+};
+use primitives::EpochId;
+use std::{collections::BTreeSet, fs};
@@ -149,6 +149,81 @@ fn test_get_set_at_second_commit() {
     state_1.commit(epoch_id_1).unwrap();
 }
 
+#[test]
+fn test_read_range() {
+    let state_manager = new_state_manager_for_unit_test();
+    let keys: Vec<Vec<u8>> = generate_keys(TEST_NUMBER_OF_KEYS);
+    let set_size = TEST_NUMBER_OF_KEYS / 10;
+    let (keys_0, keys_1_new, keys_1_overwritten, keys_1_deleted) = (
+        &keys[0..set_size * 3],
+        &keys[set_size * 3..set_size * 4],
+        &keys[0..set_size],
+        &keys[set_size..set_size * 2],
+    );
+    // A value stored under the prefix itself.
+    let prefix_key = keys[0][0..1].to_vec();
+
+    let epochs = commit_epochs(&state_manager, 10, 2, |epoch| {
+        if epoch == 0 {
+            return keys_0
+                .iter()
+                .map(|key| (key.clone(), Some(key[..].into())))
+                .collect();
+        }
+        let mut changes: Vec<(Vec<u8>, Option<Box<[u8]>>)> = keys_1_new
+            .iter()
+            .chain(keys_1_overwritten)
+            .map(|key| {
+                (key.clone(), Some(vec![&key[..], &key[..]].concat().into()))
+            })
+            .collect();
+        changes.extend(keys_1_deleted.iter().map(|key| (key.clone(), None)));
+        changes.push((prefix_key.clone(), Some(prefix_key[..].into())));
+        changes
+    });
+    let epoch_1 = &epochs[1];
+    let mut state_1 = state_manager
+        .get_state_no_commit(
+            StateIndex::new_for_readonly(
+                &epoch_1.epoch_id,
+                &epoch_1.state_root,
+            ),
+            /* try_open = */ false,
+        )
+        .unwrap()
+        .unwrap();
+
+    // Page through the keys under a prefix, which end early at the largest
+    // key kept from the Delta MPT.
+    for prefix in &[&[][..], &prefix_key[..]] {
+        let limit = 100;
+        let mut kvs = Vec::new();
+        let mut start_key = prefix.to_vec();
+        loop {
+            let (page, next_key) = state_1
+                .read_range(
+                    StorageKey::AccountKey(prefix),
+                    StorageKey::AccountKey(&start_key),
+                    limit,
+                )
+                .expect("Failed to read range.");
+            assert!(page.len() <= limit);
+            kvs.extend(page);
+            match next_key {
+                Some(next_key) => start_key = next_key,
+                None => break,
+            }
+        }
+        let expected_kvs: Vec<_> = epoch_1
+            .expected_state
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix) && k != prefix)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        assert_eq!(kvs, expected_kvs);
+    }
+}
+
 #[test]
 fn test_snapshot_random_read_performance() {
     let state_manager = new_state_manager_for_unit_test();
@@ -532,6 +607,66 @@ fn test_set_delete_all() {
     assert_eq!(state_root, empty_state_root);
 }
 
+#[test]
+fn test_read_all() {
+    let state_manager = new_state_manager_for_unit_test();
+    let keys: Vec<Vec<u8>> = generate_keys(TEST_NUMBER_OF_KEYS);
+    let set_size = TEST_NUMBER_OF_KEYS / 10;
+    let (keys_0, keys_1_new, keys_1_overwritten, keys_1_deleted) = (
+        &keys[0..set_size * 3],
+        &keys[set_size * 3..set_size * 4],
+        &keys[0..set_size],
+        &keys[set_size..set_size * 2],
+    );
+
+    let mut state_0 = state_manager.get_state_for_genesis_write();
+    for key in keys_0 {
+        state_0
+            .set(StorageKey::AccountKey(key), key[..].into())
+            .expect("Failed to insert key.");
+    }
+    let mut epoch_id_0 = H256::default();
+    epoch_id_0.as_bytes_mut()[0] = 1;
+    state_0.compute_state_root().unwrap();
+    state_0.commit(epoch_id_0).unwrap();
+
+    let mut state_1 = state_manager
+        .get_state_for_next_epoch(StateIndex::new_for_test_only_delta_mpt(
+            &epoch_id_0,
+        ))
+        .unwrap()
+        .unwrap();
+    let mut expected: HashMap<Vec<u8>, Box<[u8]>> = keys_0
+        .iter()
+        .map(|key| (key.clone(), key[..].into()))
+        .collect();
+    for key in keys_1_new.iter().chain(keys_1_overwritten) {
+        let value: Box<[u8]> = vec![&key[..], &key[..]].concat().into();
+        state_1
+            .set(StorageKey::AccountKey(key), value.clone())
+            .expect("Failed to insert key.");
+        expected.insert(key.clone(), value);
+    }
+    for key in keys_1_deleted {
+        state_1
+            .delete(StorageKey::AccountKey(key))
+            .expect("Failed to delete key.");
+        expected.remove(key);
+    }
+
+    let mut kvs = Vec::new();
+    state_1
+        .read_all(&mut kvs)
+        .expect("Failed to read all keys.");
+    assert_eq!(kvs.len(), expected.len());
+    assert_eq!(kvs.into_iter().collect::<HashMap<_, _>>(), expected);
+
+    let mut epoch_id_1 = H256::default();
+    epoch_id_1.as_bytes_mut()[0] = 2;
+    state_1.compute_state_root().unwrap();
+    state_1.commit(epoch_id_1).unwrap();
+}
+
 #[test]
 fn test_set_order() {
     let mut rng = get_rng_for_test();
@@ -692,12 +827,60 @@ fn test_set_order_concurrent() {
     }
 }
 
+#[test]
+fn test_get_state_readonly_at_snapshot() {
+    let snapshot_epoch_count = 1;
+    let state_manager =
+        new_state_manager_for_unit_test_with_snapshot_epoch_count(
+            snapshot_epoch_count,
+        );
+    let keys = generate_account_keys(500);
+
+    // Each epoch sets 100 new keys and deletes 50 keys of the previous epoch.
+    let epochs =
+        commit_epochs(&state_manager, snapshot_epoch_count, 5, |epoch| {
+            let i = epoch as usize * 100;
+            let set = keys[i..i + 100]
+                .iter()
+                .map(|key| (key.clone(), Some(key[..].into())));
+            let deleted = keys[i.saturating_sub(50)..i]
+                .iter()
+                .map(|key| (key.clone(), None));
+            set.chain(deleted).collect()
+        });
+    let snapshot_epoch_ids = snapshot_epoch_ids(&epochs);
+    assert!(!snapshot_epoch_ids.is_empty());
+
+    for snapshot_epoch_id in &snapshot_epoch_ids {
+        let state = state_manager
+            .get_state_readonly_at_snapshot(
+                snapshot_epoch_id,
+                /* try_open = */ false,
+            )
+            .unwrap()
+            .unwrap();
+        let expected_state = &epochs
+            .iter()
+            .find(|epoch| epoch.epoch_id == *snapshot_epoch_id)
+            .unwrap()
+            .expected_state;
+        for key in &keys {
+            assert_eq!(
+                state.get(StorageKey::AccountKey(key)).unwrap(),
+                expected_state.get(key).cloned()
+            );
+        }
+    }
+}
+
 use crate::{
     state::*,
     state_manager::*,
     tests::{
-        generate_keys, get_rng_for_test, new_state_manager_for_unit_test,
-        FakeStateManager, TEST_NUMBER_OF_KEYS,
+        commit_epochs, generate_account_keys, generate_keys, get_rng_for_test,
+        new_state_manager_for_unit_test,
+        new_state_manager_for_unit_test_with_snapshot_epoch_count,
+        snapshot_epoch_ids, FakeStateManager, TEST_NUMBER_OF_KEYS,
     },
     utils::access_mode,
     StateRootWithAuxInfo,
@@ -711,6 +894,7 @@ use rand::{
 };
 use rlp::Rlp;
 use std::{
+    collections::HashMap,
     sync::Arc,
     thread,
     time::{Duration, Instant},
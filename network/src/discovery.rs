@@ -123,6 +123,14 @@ impl Discovery {
         }
     }
 
+    pub fn set_ip_filter(&mut self, ip_filter: IpFilter) {
+        self.ip_filter = ip_filter;
+    }
+
+    pub fn set_public_endpoint(&mut self, public: NodeEndpoint) {
+        self.public_endpoint = public;
+    }
+
     fn is_allowed(&self, entry: &NodeEntry) -> bool {
         entry.endpoint.is_allowed(&self.ip_filter) && entry.id != self.id
     }
@@ -0,0 +1,526 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Discovery of bootnodes from node lists published under DNS names, in the
+//! format of EIP-1459 except that the leaves are node URLs
+//! (`cfxnode://<id>@<ip>:<port>`) instead of ENRs.
+//!
+//! A tree is located by `enrtree://<public key>@<domain>`, where the public
+//! key is that of the signer in hex, the same format as node IDs. The TXT
+//! record of `<domain>` is the signed root:
+//!
+//! `enrtree-root:v1 e=<nodes root> l=<links root> seq=<n> sig=<signature>`
+//!
+//! where the signature is the URL-safe base64 of the 65-byte signature of the
+//! keccak hash of the record text before ` sig=`. The other records are at
+//! `<hash>.<domain>`, where the hash is the base32 of the first 16 bytes of
+//! the keccak hash of the record, and are either branches
+//! (`enrtree-branch:<hash>,<hash>,...`), node URLs, or links to other trees.
+
+use crate::{
+    hash::keccak,
+    node_table::{Node, NodeEntry},
+    Error,
+};
+use keylib::{verify_public, Public, Signature};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    str::FromStr,
+};
+use trust_dns_resolver::{error::ResolveErrorKind, Resolver};
+
+const ROOT_PREFIX: &str = "enrtree-root:v1";
+const BRANCH_PREFIX: &str = "enrtree-branch:";
+const LINK_PREFIX: &str = "enrtree://";
+const NODE_PREFIX: &str = "cfxnode://";
+
+/// Maximum number of records resolved for a tree in a sync, so that a
+/// malicious tree cannot keep the sync running forever.
+const MAX_TREE_RECORDS: usize = 2048;
+/// Maximum number of trees, including the linked ones, synced in a sync.
+const MAX_TREES: usize = 32;
+/// Size of the hash prefix naming a record.
+const RECORD_HASH_SIZE: usize = 16;
+
+/// Resolves the TXT records of a domain name.
+pub trait TxtResolver: Send {
+    /// Returns the text of each TXT record of `name`, or an empty list if
+    /// there is none.
+    fn resolve_txt(&self, name: &str) -> Result<Vec<String>, Error>;
+}
+
+/// Resolver with the system configuration, e.g. `/etc/resolv.conf` on unix.
+pub struct SystemResolver {
+    resolver: Resolver,
+}
+
+impl SystemResolver {
+    pub fn new() -> Result<Self, Error> {
+        let resolver = Resolver::from_system_conf().map_err(|e| {
+            Error::from(format!("failed to create DNS resolver: {}", e))
+        })?;
+        Ok(SystemResolver { resolver })
+    }
+}
+
+impl TxtResolver for SystemResolver {
+    fn resolve_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        let lookup = match self.resolver.txt_lookup(name) {
+            Ok(lookup) => lookup,
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => return Ok(vec![]),
+                _ => bail!(format!("failed to resolve {}: {}", name, e)),
+            },
+        };
+
+        // A record may be split into several character strings.
+        Ok(lookup
+            .iter()
+            .map(|txt| {
+                txt.txt_data()
+                    .iter()
+                    .map(|data| String::from_utf8_lossy(data))
+                    .collect::<String>()
+            })
+            .collect())
+    }
+}
+
+/// Location of a tree, `enrtree://<public key>@<domain>`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TreeLink {
+    pub public: Public,
+    pub domain: String,
+}
+
+impl FromStr for TreeLink {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let link = s
+            .strip_prefix(LINK_PREFIX)
+            .ok_or_else(|| format!("invalid tree link {}", s))?;
+        let delimiter_index = link
+            .find('@')
+            .ok_or_else(|| format!("invalid tree link {}", s))?;
+        let public = link[..delimiter_index]
+            .parse()
+            .map_err(|_| format!("invalid public key in tree link {}", s))?;
+        let domain = link[delimiter_index + 1..].trim_end_matches('.');
+        if domain.is_empty() {
+            bail!(format!("empty domain in tree link {}", s));
+        }
+
+        Ok(TreeLink {
+            public,
+            domain: domain.into(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct TreeRoot {
+    nodes_root: String,
+    links_root: String,
+    seq: u64,
+}
+
+impl TreeRoot {
+    /// Parses the root record and verifies that it is signed by `public`.
+    fn parse(record: &str, public: &Public) -> Result<Self, Error> {
+        let sig_index = record
+            .find(" sig=")
+            .ok_or_else(|| format!("unsigned tree root {}", record))?;
+        let (content, sig) = (&record[..sig_index], &record[sig_index + 5..]);
+
+        let sig = base64::decode_config(
+            sig.trim_end_matches('='),
+            base64::URL_SAFE_NO_PAD,
+        )
+        .map_err(|e| format!("invalid signature of tree root: {:?}", e))?;
+        if sig.len() != 65 {
+            bail!(format!("invalid signature length {}", sig.len()));
+        }
+        let mut sig_bytes = [0u8; 65];
+        sig_bytes.copy_from_slice(&sig);
+        match verify_public(
+            public,
+            &Signature::from(sig_bytes),
+            &keccak(content),
+        ) {
+            Ok(true) => {}
+            _ => bail!(format!("invalid signature of tree root {}", record)),
+        }
+
+        let mut fields = content.split_whitespace();
+        if fields.next() != Some(ROOT_PREFIX) {
+            bail!(format!("invalid tree root {}", record));
+        }
+        let (mut nodes_root, mut links_root, mut seq) = (None, None, None);
+        for field in fields {
+            if let Some(v) = field.strip_prefix("e=") {
+                nodes_root = Some(v.to_string());
+            } else if let Some(v) = field.strip_prefix("l=") {
+                links_root = Some(v.to_string());
+            } else if let Some(v) = field.strip_prefix("seq=") {
+                seq = v.parse().ok();
+            }
+        }
+
+        match (nodes_root, links_root, seq) {
+            (Some(nodes_root), Some(links_root), Some(seq)) => Ok(TreeRoot {
+                nodes_root,
+                links_root,
+                seq,
+            }),
+            _ => bail!(format!("incomplete tree root {}", record)),
+        }
+    }
+}
+
+enum TreeEntry {
+    Branch(Vec<String>),
+    Link(TreeLink),
+    Node(NodeEntry),
+}
+
+impl FromStr for TreeEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(children) = s.strip_prefix(BRANCH_PREFIX) {
+            Ok(TreeEntry::Branch(
+                children
+                    .split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(Into::into)
+                    .collect(),
+            ))
+        } else if s.starts_with(LINK_PREFIX) {
+            Ok(TreeEntry::Link(s.parse()?))
+        } else if s.starts_with(NODE_PREFIX) {
+            let node = Node::from_str(s)?;
+            Ok(TreeEntry::Node(NodeEntry {
+                id: node.id,
+                endpoint: node.endpoint,
+            }))
+        } else {
+            bail!(format!("unknown tree entry {}", s))
+        }
+    }
+}
+
+/// Returns the name of `record` under the domain of its tree.
+pub fn record_hash(record: &str) -> String {
+    base32::encode(
+        base32::Alphabet::RFC4648 { padding: false },
+        &keccak(record).as_bytes()[..RECORD_HASH_SIZE],
+    )
+}
+
+/// The content of a synced tree, reused until the sequence number of its
+/// root changes.
+struct SyncedTree {
+    seq: u64,
+    nodes: Vec<NodeEntry>,
+    links: Vec<TreeLink>,
+}
+
+pub struct DnsDiscovery<R: TxtResolver> {
+    resolver: R,
+    links: Vec<TreeLink>,
+    trees: HashMap<TreeLink, SyncedTree>,
+}
+
+impl<R: TxtResolver> DnsDiscovery<R> {
+    pub fn new(resolver: R, links: Vec<TreeLink>) -> Self {
+        DnsDiscovery {
+            resolver,
+            links,
+            trees: HashMap::new(),
+        }
+    }
+
+    /// Resolves the configured trees and the trees linked by them, and
+    /// returns all their nodes. A tree failing to resolve is skipped, and
+    /// the trees not linked anymore are forgotten.
+    pub fn sync(&mut self) -> Vec<NodeEntry> {
+        let mut queue: VecDeque<TreeLink> =
+            self.links.iter().cloned().collect();
+        let mut visited = HashSet::new();
+        let mut nodes = Vec::new();
+
+        while let Some(link) = queue.pop_front() {
+            if visited.len() >= MAX_TREES {
+                debug!("DNS discovery reached the maximum number of trees");
+                break;
+            }
+            if !visited.insert(link.clone()) {
+                continue;
+            }
+
+            if let Err(e) = self.sync_tree(&link) {
+                debug!("Failed to sync tree {:?}: {:?}", link, e);
+            }
+            if let Some(tree) = self.trees.get(&link) {
+                nodes.extend(tree.nodes.iter().cloned());
+                queue.extend(tree.links.iter().cloned());
+            }
+        }
+
+        self.trees.retain(|link, _| visited.contains(link));
+        nodes
+    }
+
+    fn sync_tree(&mut self, link: &TreeLink) -> Result<(), Error> {
+        let records = self.resolver.resolve_txt(&link.domain)?;
+        let record = records
+            .iter()
+            .find(|record| record.starts_with(ROOT_PREFIX))
+            .ok_or_else(|| format!("tree root not found at {}", link.domain))?;
+        let root = TreeRoot::parse(record, &link.public)?;
+
+        if let Some(tree) = self.trees.get(link) {
+            if root.seq == tree.seq {
+                return Ok(());
+            }
+            // Keep the synced tree rather than rolling back to an older one
+            // replayed by the DNS servers.
+            if root.seq < tree.seq {
+                bail!(format!(
+                    "stale tree root at {}, seq = {}, synced seq = {}",
+                    link.domain, root.seq, tree.seq
+                ));
+            }
+        }
+
+        let mut tree = SyncedTree {
+            seq: root.seq,
+            nodes: Vec::new(),
+            links: Vec::new(),
+        };
+        let mut budget = MAX_TREE_RECORDS;
+        self.traverse(link, &root.nodes_root, &mut tree, &mut budget)?;
+        self.traverse(link, &root.links_root, &mut tree, &mut budget)?;
+        debug!(
+            "Synced tree {}, seq = {}, nodes = {}, links = {}",
+            link.domain,
+            tree.seq,
+            tree.nodes.len(),
+            tree.links.len()
+        );
+
+        self.trees.insert(link.clone(), tree);
+        Ok(())
+    }
+
+    fn traverse(
+        &self, link: &TreeLink, hash: &str, tree: &mut SyncedTree,
+        budget: &mut usize,
+    ) -> Result<(), Error>
+    {
+        let mut pending = vec![hash.to_string()];
+        while let Some(hash) = pending.pop() {
+            if *budget == 0 {
+                bail!(format!("too many records in tree {}", link.domain));
+            }
+            *budget -= 1;
+
+            let name = format!("{}.{}", hash, link.domain);
+            let record = self
+                .resolver
+                .resolve_txt(&name)?
+                .into_iter()
+                .find(|record| record_hash(record).eq_ignore_ascii_case(&hash))
+                .ok_or_else(|| format!("record not found at {}", name))?;
+
+            match record.parse::<TreeEntry>()? {
+                TreeEntry::Branch(children) => pending.extend(children),
+                TreeEntry::Link(linked) => tree.links.push(linked),
+                TreeEntry::Node(node) => tree.nodes.push(node),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record_hash, DnsDiscovery, TreeLink, TxtResolver};
+    use crate::{hash::keccak, Error};
+    use keylib::{sign, Generator, KeyPair, Random};
+    use std::{collections::HashMap, sync::Arc};
+
+    /// Resolver stand-in serving the records of a map.
+    #[derive(Clone, Default)]
+    struct MapResolver {
+        records: Arc<parking_lot::Mutex<HashMap<String, Vec<String>>>>,
+    }
+
+    impl MapResolver {
+        fn insert(&self, name: &str, record: &str) {
+            self.records
+                .lock()
+                .entry(name.into())
+                .or_insert_with(Vec::new)
+                .push(record.into());
+        }
+
+        fn set(&self, name: &str, record: &str) {
+            self.records.lock().insert(name.into(), vec![record.into()]);
+        }
+    }
+
+    impl TxtResolver for MapResolver {
+        fn resolve_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+            Ok(self.records.lock().get(name).cloned().unwrap_or_default())
+        }
+    }
+
+    /// Publishes `entries` under `domain` and returns the root record.
+    fn publish(
+        resolver: &MapResolver, key: &KeyPair, domain: &str, seq: u64,
+        nodes: &[&str], links: &[&str],
+    ) -> String
+    {
+        let add_branch = |entries: &[&str]| {
+            let hashes: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    let hash = record_hash(entry);
+                    resolver.insert(&format!("{}.{}", hash, domain), entry);
+                    hash
+                })
+                .collect();
+            let branch = format!("enrtree-branch:{}", hashes.join(","));
+            let hash = record_hash(&branch);
+            resolver.insert(&format!("{}.{}", hash, domain), &branch);
+            hash
+        };
+        let nodes_root = add_branch(nodes);
+        let links_root = add_branch(links);
+
+        let content = format!(
+            "enrtree-root:v1 e={} l={} seq={}",
+            nodes_root, links_root, seq
+        );
+        let sig = sign(key.secret(), &keccak(&content)).unwrap();
+        let root = format!(
+            "{} sig={}",
+            content,
+            base64::encode_config(&sig[..], base64::URL_SAFE_NO_PAD)
+        );
+        resolver.set(domain, &root);
+        root
+    }
+
+    fn node_url(port: u16) -> String {
+        format!(
+            "cfxnode://{:x}@127.0.0.1:{}",
+            Random.generate().unwrap().public(),
+            port
+        )
+    }
+
+    fn tree_link(key: &KeyPair, domain: &str) -> String {
+        format!("enrtree://{:x}@{}", key.public(), domain)
+    }
+
+    #[test]
+    fn test_sync() {
+        let resolver = MapResolver::default();
+        let key = Random.generate().unwrap();
+        let linked_key = Random.generate().unwrap();
+
+        let (node1, node2, node3) = (node_url(1), node_url(2), node_url(3));
+        let linked = tree_link(&linked_key, "linked.example.org");
+        publish(
+            &resolver,
+            &key,
+            "nodes.example.org",
+            1,
+            &[&node1, &node2],
+            &[&linked],
+        );
+        publish(
+            &resolver,
+            &linked_key,
+            "linked.example.org",
+            1,
+            &[&node3],
+            &[],
+        );
+
+        let link = tree_link(&key, "nodes.example.org").parse().unwrap();
+        let mut discovery = DnsDiscovery::new(resolver.clone(), vec![link]);
+        let mut ports: Vec<u16> = discovery
+            .sync()
+            .iter()
+            .map(|n| n.endpoint.address.port())
+            .collect();
+        ports.sort();
+        assert_eq!(ports, vec![1, 2, 3]);
+
+        // A new sequence number replaces the nodes of the tree.
+        publish(&resolver, &key, "nodes.example.org", 2, &[&node1], &[]);
+        assert_eq!(discovery.sync().len(), 1);
+
+        // An older root is rejected.
+        publish(
+            &resolver,
+            &key,
+            "nodes.example.org",
+            1,
+            &[&node1, &node2],
+            &[&linked],
+        );
+        assert_eq!(discovery.sync().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_tree() {
+        let resolver = MapResolver::default();
+        let key = Random.generate().unwrap();
+        let node = node_url(1);
+        let root =
+            publish(&resolver, &key, "nodes.example.org", 1, &[&node], &[]);
+
+        // signed by another key
+        let other_key = Random.generate().unwrap();
+        let link: TreeLink =
+            tree_link(&other_key, "nodes.example.org").parse().unwrap();
+        assert!(DnsDiscovery::new(resolver.clone(), vec![link])
+            .sync()
+            .is_empty());
+
+        // tampered root
+        let link: TreeLink =
+            tree_link(&key, "nodes.example.org").parse().unwrap();
+        resolver.set("nodes.example.org", &root.replace("seq=1", "seq=2"));
+        assert!(DnsDiscovery::new(resolver.clone(), vec![link.clone()])
+            .sync()
+            .is_empty());
+
+        // tampered node record
+        resolver.set("nodes.example.org", &root);
+        let name = format!("{}.nodes.example.org", record_hash(&node));
+        resolver.set(&name, &node_url(2));
+        assert!(DnsDiscovery::new(resolver, vec![link]).sync().is_empty());
+    }
+
+    #[test]
+    fn test_parse_link() {
+        let key = Random.generate().unwrap();
+        let link: TreeLink =
+            tree_link(&key, "nodes.example.org.").parse().unwrap();
+        assert_eq!(link.public, *key.public());
+        assert_eq!(link.domain, "nodes.example.org");
+
+        assert!("enrtree://nodes.example.org".parse::<TreeLink>().is_err());
+        assert!("cfxnode://1234@nodes.example.org"
+            .parse::<TreeLink>()
+            .is_err());
+    }
+}
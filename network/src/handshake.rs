@@ -19,12 +19,15 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    connection::Connection, node_table::NodeId, service::HostMetadata, Error,
-    ErrorKind,
+    connection::Connection, node_table::NodeId, service::HostMetadata,
+    session_cipher::SessionSecret, Error, ErrorKind,
 };
 use cfx_types::{Public, H256};
 use io::{IoContext, StreamToken};
-use keylib::{crypto::ecies, Secret};
+use keylib::{
+    crypto::{ecdh, ecies},
+    Secret,
+};
 use mio::tcp::TcpStream;
 use priority_send_queue::SendQueuePriority;
 use std::{
@@ -64,6 +67,9 @@ pub struct Handshake {
     state: HandshakeState,
     /// nonce for verification
     nonce: H256,
+    /// nonce of the remote peer, which is only known to both peers and used
+    /// to derive the session cipher
+    remote_nonce: H256,
 }
 
 impl Handshake {
@@ -76,6 +82,7 @@ impl Handshake {
             connection: Connection::new(token, socket),
             state: HandshakeState::New,
             nonce: H256::random(),
+            remote_nonce: H256::zero(),
         }
     }
 
@@ -98,6 +105,31 @@ impl Handshake {
     /// Check if handshake is complete
     pub fn done(&self) -> bool { self.state == HandshakeState::StartSession }
 
+    /// Derive the secret of the session cipher from the ECDH secret of the
+    /// node keys and the nonces exchanged. Returns `None` if the cryptography
+    /// is bypassed, in which case no nonces are exchanged.
+    pub fn session_secret(
+        &self, secret: &Secret, originated: bool,
+    ) -> Result<Option<SessionSecret>, Error> {
+        if BYPASS_CRYPTOGRAPHY.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let shared = ecdh::agree(secret, &self.id)?;
+        let (initiator_nonce, responder_nonce) = if originated {
+            (self.nonce, self.remote_nonce)
+        } else {
+            (self.remote_nonce, self.nonce)
+        };
+
+        Ok(Some(SessionSecret::new(
+            shared,
+            initiator_nonce,
+            responder_nonce,
+            originated,
+        )))
+    }
+
     /// Readable IO handler. Drives the state change.
     pub fn readable<Message>(
         &mut self, io: &IoContext<Message>, host: &HostMetadata,
@@ -189,6 +221,7 @@ impl Handshake {
 
         let (remote_public, remote_nonce) = auth.split_at(NodeId::len_bytes());
         self.id.assign_from_slice(remote_public);
+        self.remote_nonce.assign_from_slice(remote_nonce);
 
         self.write_ack_of_auth(io, remote_nonce)
     }
@@ -265,6 +298,7 @@ impl Handshake {
             return Err(ErrorKind::BadProtocol.into());
         }
 
+        self.remote_nonce.assign_from_slice(remote_nonce);
         self.write_ack_of_ack(io, remote_nonce)
     }
 
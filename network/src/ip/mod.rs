@@ -3,6 +3,7 @@ mod node_limit;
 mod node_tag_index;
 mod sample;
 mod sessions_limit;
+mod subnet_blacklist;
 mod util;
 
 pub use self::{
@@ -11,4 +12,5 @@ pub use self::{
     sessions_limit::{
         new_session_ip_limit, SessionIpLimit, SessionIpLimitConfig,
     },
+    subnet_blacklist::SubnetBlacklist,
 };
@@ -0,0 +1,194 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::ip_utils::SocketAddrExt;
+use ipnetwork::IpNetwork;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Subnets blacklisted by the administrator, which are refused to connect.
+/// Each subnet is blacklisted until the specified time, or forever if not
+/// specified.
+pub struct SubnetBlacklist {
+    subnets: Vec<(IpNetwork, Option<SystemTime>)>,
+    path: Option<PathBuf>,
+}
+
+impl SubnetBlacklist {
+    pub fn new(dir: Option<String>, filename: &str) -> Self {
+        let path = dir.map(|dir| {
+            let mut buf = PathBuf::from(dir);
+            buf.push(filename);
+            buf
+        });
+
+        let mut blacklist = SubnetBlacklist {
+            subnets: Vec::new(),
+            path,
+        };
+        blacklist.load_from_file();
+        blacklist
+    }
+
+    /// Blacklist the subnet, or update the expiry if already blacklisted.
+    pub fn insert(&mut self, subnet: IpNetwork, until: Option<SystemTime>) {
+        self.remove(&subnet);
+        self.subnets.push((subnet, until));
+    }
+
+    pub fn remove(&mut self, subnet: &IpNetwork) -> bool {
+        let len = self.subnets.len();
+        self.subnets.retain(|(s, _)| s != subnet);
+        self.subnets.len() != len
+    }
+
+    /// Check if the specified IP address is in any blacklisted subnet.
+    /// Expired subnets are removed.
+    pub fn contains(&mut self, ip: &IpAddr) -> bool {
+        self.clear_expired();
+        self.subnets.iter().any(|(subnet, _)| ip.is_within(subnet))
+    }
+
+    pub fn all(&mut self) -> Vec<(IpNetwork, Option<SystemTime>)> {
+        self.clear_expired();
+        self.subnets.clone()
+    }
+
+    fn clear_expired(&mut self) {
+        let now = SystemTime::now();
+        self.subnets
+            .retain(|(_, until)| until.map_or(true, |until| until > now));
+    }
+
+    fn load_from_file(&mut self) {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return,
+        };
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                debug!("subnet blacklist file not found: {:?}", e);
+                return;
+            }
+        };
+
+        let subnets: Vec<json::Subnet> = match serde_json::from_reader(file) {
+            Ok(subnets) => subnets,
+            Err(e) => {
+                warn!("Error reading subnet blacklist file: {:?}", e);
+                return;
+            }
+        };
+
+        for s in subnets {
+            match IpNetwork::from_str(&s.subnet) {
+                Ok(subnet) => self.insert(
+                    subnet,
+                    s.until.map(|t| UNIX_EPOCH + Duration::from_secs(t)),
+                ),
+                Err(e) => warn!("Invalid blacklisted subnet {:?}", e),
+            }
+        }
+    }
+
+    /// Save the blacklisted subnets into file.
+    pub fn save(&mut self) {
+        let path = match self.path {
+            Some(ref path) => Path::new(path),
+            None => return,
+        };
+
+        if let Some(dir) = path.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                warn!("Error creating subnet blacklist directory: {:?}", e);
+                return;
+            }
+        }
+
+        self.clear_expired();
+        let subnets: Vec<json::Subnet> = self
+            .subnets
+            .iter()
+            .map(|(subnet, until)| json::Subnet {
+                subnet: subnet.to_string(),
+                until: until.and_then(|t| {
+                    t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+                }),
+            })
+            .collect();
+
+        match fs::File::create(&path) {
+            Ok(file) => {
+                if let Err(e) = serde_json::to_writer_pretty(file, &subnets) {
+                    warn!("Error writing subnet blacklist file: {:?}", e);
+                }
+            }
+            Err(e) => {
+                warn!("Error creating subnet blacklist file: {:?}", e);
+            }
+        }
+    }
+}
+
+mod json {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Subnet {
+        pub subnet: String,
+        /// Unix time in seconds.
+        pub until: Option<u64>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubnetBlacklist;
+    use std::time::{Duration, SystemTime};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_contains_and_expire() {
+        let mut blacklist = SubnetBlacklist::new(None, "subnets.json");
+        blacklist.insert("10.0.0.0/8".parse().unwrap(), None);
+        blacklist.insert(
+            "192.168.0.0/16".parse().unwrap(),
+            Some(SystemTime::now() - Duration::from_secs(1)),
+        );
+
+        assert!(blacklist.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(!blacklist.contains(&"11.1.2.3".parse().unwrap()));
+        // expired
+        assert!(!blacklist.contains(&"192.168.1.1".parse().unwrap()));
+        assert_eq!(blacklist.all().len(), 1);
+
+        assert!(blacklist.remove(&"10.0.0.0/8".parse().unwrap()));
+        assert!(!blacklist.contains(&"10.1.2.3".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new("subnet_blacklist").unwrap();
+        let dir_str = Some(dir.path().to_str().unwrap().to_string());
+        let until = SystemTime::now() + Duration::from_secs(3600);
+
+        let mut blacklist = SubnetBlacklist::new(dir_str.clone(), "s.json");
+        blacklist.insert("10.0.0.0/8".parse().unwrap(), None);
+        blacklist.insert("::1/128".parse().unwrap(), Some(until));
+        blacklist.save();
+
+        let mut loaded = SubnetBlacklist::new(dir_str, "s.json");
+        assert!(loaded.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(loaded.contains(&"::1".parse().unwrap()));
+        assert_eq!(loaded.all().len(), 2);
+    }
+}
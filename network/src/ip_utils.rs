@@ -23,24 +23,42 @@
 use crate::{node_table::NodeEndpoint, NatType};
 use igd::{search_gateway, PortMappingProtocol, SearchOptions};
 use ipnetwork::IpNetwork;
-use natpmp::{Natpmp, Protocol, Response};
+use natpmp::Protocol;
 use std::{
     io,
     net::{
         IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6,
-        TcpStream,
+        TcpStream, UdpSocket,
     },
     thread,
     time::Duration,
 };
 
-const NAT_PMP_PORT_MAPPING_LIFETIME: u32 = 3600 * 24;
-// Waiting duration in milliseconds for response from router after sending port
-// mapping request. 50 milliseconds might be enough for low RTT.
-const NAT_PMP_PORT_MAPPING_WAITING_DURATION: u64 = 50;
 const NAT_PMP_PORT_MAPPING_MAX_RETRY: u64 = 5;
 const NAT_PMP_PORT_MAPPING_RETRY_WAITING_DURATION_MS: u64 = 250;
 
+/// Where to search the NAT gateway.
+#[derive(Clone, Debug)]
+pub struct GatewayAddress {
+    /// Address the UPnP search request is broadcast to.
+    pub upnp_broadcast: SocketAddr,
+    /// Address of the NAT-PMP gateway, or port 5351 of the default gateway if
+    /// `None`.
+    pub natpmp_gateway: Option<SocketAddrV4>,
+}
+
+impl Default for GatewayAddress {
+    fn default() -> Self {
+        GatewayAddress {
+            upnp_broadcast: SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::new(239, 255, 255, 250),
+                1900,
+            )),
+            natpmp_gateway: None,
+        }
+    }
+}
+
 /// Socket address extension for rustc beta. To be replaces with now unstable
 /// API
 pub trait SocketAddrExt {
@@ -323,186 +341,258 @@ pub fn select_public_address(port: u16) -> SocketAddr {
     SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port))
 }
 
-fn search_upnp(local: &NodeEndpoint) -> Option<NodeEndpoint> {
+fn upnp_search_options(
+    local_ip: Ipv4Addr, broadcast_address: SocketAddr,
+) -> SearchOptions {
+    SearchOptions {
+        timeout: Some(Duration::new(5, 0)),
+        // igd 0.7 used port 0 by default.
+        // Let's not change this behaviour
+        bind_addr: SocketAddr::V4(SocketAddrV4::new(local_ip, 0)),
+        broadcast_address,
+    }
+}
+
+/// NAT-PMP (RFC 6886) client. Unlike `natpmp::Natpmp`, the gateway may
+/// listen on any port.
+struct NatPmpClient {
+    socket: UdpSocket,
+}
+
+impl NatPmpClient {
+    fn new(gateway: Option<SocketAddrV4>) -> io::Result<Self> {
+        let gateway = match gateway {
+            Some(gateway) => gateway,
+            None => SocketAddrV4::new(
+                natpmp::get_default_gateway().map_err(|e| {
+                    io::Error::new(io::ErrorKind::Other, e.to_string())
+                })?,
+                natpmp::NATPMP_PORT,
+            ),
+        };
+        let socket =
+            UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.connect(gateway)?;
+        Ok(NatPmpClient { socket })
+    }
+
+    /// Send `request` and return the `result_len` bytes following the header
+    /// of its response. The request is resent if no response is received in
+    /// time.
+    fn request(
+        &self, request: &[u8], result_len: usize,
+    ) -> io::Result<Vec<u8>> {
+        let response_op = 128 + request[1];
+        self.socket.set_read_timeout(Some(Duration::from_millis(
+            NAT_PMP_PORT_MAPPING_RETRY_WAITING_DURATION_MS,
+        )))?;
+        for _ in 0..=NAT_PMP_PORT_MAPPING_MAX_RETRY {
+            self.socket.send(request)?;
+            let mut buf = [0u8; 16];
+            loop {
+                let read = match self.socket.recv(&mut buf) {
+                    Ok(read) => read,
+                    Err(ref e)
+                        if e.kind() == io::ErrorKind::WouldBlock
+                            || e.kind() == io::ErrorKind::TimedOut =>
+                    {
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                };
+                // Skip the late responses to the previous requests.
+                if read < 8 + result_len || buf[1] != response_op {
+                    continue;
+                }
+                let result_code = u16::from_be_bytes([buf[2], buf[3]]);
+                if result_code != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("NAT-PMP result code {}", result_code),
+                    ));
+                }
+                return Ok(buf[8..8 + result_len].to_vec());
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "no response from the NAT-PMP gateway",
+        ))
+    }
+
+    fn public_address(&self) -> io::Result<Ipv4Addr> {
+        let result = self.request(&[0, 0], 4)?;
+        Ok(Ipv4Addr::new(result[0], result[1], result[2], result[3]))
+    }
+
+    /// Map `private_port` to `public_port` for `lifetime` seconds, or remove
+    /// the mapping if `lifetime` is 0. Returns the public port assigned.
+    fn map_port(
+        &self, protocol: Protocol, private_port: u16, public_port: u16,
+        lifetime: u32,
+    ) -> io::Result<u16>
+    {
+        let op = match protocol {
+            Protocol::UDP => 1,
+            Protocol::TCP => 2,
+        };
+        let mut request = vec![0, op, 0, 0];
+        request.extend_from_slice(&private_port.to_be_bytes());
+        request.extend_from_slice(&public_port.to_be_bytes());
+        request.extend_from_slice(&lifetime.to_be_bytes());
+        let result = self.request(&request, 8)?;
+        Ok(u16::from_be_bytes([result[2], result[3]]))
+    }
+}
+
+fn search_upnp(
+    local: &NodeEndpoint, external: Option<&NodeEndpoint>, lease: Duration,
+    broadcast_address: SocketAddr,
+) -> Option<NodeEndpoint>
+{
     if let SocketAddr::V4(ref local_addr) = local.address {
         let local_ip = *local_addr.ip();
         let local_port = local_addr.port();
         let local_udp_port = local.udp_port;
+        let (external_port, external_udp_port) =
+            preferred_ports(local, external);
+        let lease = lease.as_secs() as u32;
 
-        let search_options = SearchOptions {
-            timeout: Some(Duration::new(5, 0)),
-            // igd 0.7 used port 0 by default.
-            // Let's not change this behaviour
-            bind_addr: SocketAddr::V4(SocketAddrV4::new(local_ip, 0)),
-            ..Default::default()
-        };
+        let search_options = upnp_search_options(local_ip, broadcast_address);
         let search_gateway_child = ::std::thread::spawn(move || {
-            match search_gateway(search_options) {
-                Err(ref err) => debug!("Gateway search error: {}", err),
-                Ok(gateway) => match gateway.get_external_ip() {
+            let gateway = search_gateway(search_options)
+                .map_err(|err| debug!("Gateway search error: {}", err))
+                .ok()?;
+            let external_addr = gateway
+                .get_external_ip()
+                .map_err(|err| debug!("IP request error: {}", err))
+                .ok()?;
+            debug!("UPnP gets external ip: {}", external_addr);
+            let client_ip = {
+                // Connect to the gateway to find our LAN local
+                // address from the socket, like `miniupnpc`.
+                // Note that using local_ip (0.0.0.0) will cause
+                // NOT_AUTHORIZED error.
+                let client_sock = TcpStream::connect(&gateway.addr).ok()?;
+                match client_sock.local_addr() {
+                    Ok(SocketAddr::V4(v4_addr)) => v4_addr.ip().clone(),
+                    _ => return None,
+                }
+            };
+
+            // Map the preferred external port, so that the external endpoint
+            // keeps unchanged when the mapping is renewed, and fall back to
+            // any port chosen by the gateway.
+            let map_port = |protocol: PortMappingProtocol,
+                            local_port: u16,
+                            external_port: u16,
+                            description: &str| {
+                let local_addr = SocketAddrV4::new(client_ip, local_port);
+                match gateway.add_port(
+                    protocol,
+                    external_port,
+                    local_addr,
+                    lease,
+                    description,
+                ) {
+                    Ok(()) => Some(external_port),
                     Err(ref err) => {
-                        debug!("IP request error: {}", err);
+                        debug!(
+                            "Port mapping of port {} error: {}",
+                            external_port, err
+                        );
+                        gateway
+                            .add_any_port(
+                                protocol,
+                                local_addr,
+                                lease,
+                                description,
+                            )
+                            .map_err(|err| {
+                                debug!("Port mapping error: {}", err)
+                            })
+                            .ok()
                     }
-                    Ok(external_addr) => {
-                        debug!("UPnP gets external ip: {}", external_addr);
-                        let client_ip = {
-                            // Connect to the gateway to find our LAN local
-                            // address from the socket, like `miniupnpc`.
-                            // Note that using local_ip (0.0.0.0) will cause
-                            // NOT_AUTHORIZED error.
-                            let client_sock =
-                                TcpStream::connect(&gateway.addr).ok()?;
-                            match client_sock.local_addr() {
-                                Ok(SocketAddr::V4(v4_addr)) => {
-                                    v4_addr.ip().clone()
-                                }
-                                _ => return None,
-                            }
-                        };
-                        match gateway.add_any_port(
-                            PortMappingProtocol::TCP,
-                            SocketAddrV4::new(client_ip, local_port),
-                            0,
-                            "Conflux Node/TCP",
-                        ) {
-                            Err(ref err) => {
-                                debug!("Port mapping error: {}", err);
-                            }
-                            Ok(tcp_port) => {
-                                debug!("UPnP gets tcp port: {}", tcp_port);
-                                match gateway.add_any_port(
-                                    PortMappingProtocol::UDP,
-                                    SocketAddrV4::new(
-                                        client_ip,
-                                        local_udp_port,
-                                    ),
-                                    0,
-                                    "Conflux Node/UDP",
-                                ) {
-                                    Err(ref err) => {
-                                        debug!("Port mapping error: {}", err);
-                                    }
-                                    Ok(udp_port) => {
-                                        debug!(
-                                            "UPnP gets udp port: {}",
-                                            udp_port
-                                        );
-                                        return Some(NodeEndpoint {
-                                            address: SocketAddr::V4(
-                                                SocketAddrV4::new(
-                                                    external_addr,
-                                                    tcp_port,
-                                                ),
-                                            ),
-                                            udp_port,
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                },
-            }
-            None
+                }
+            };
+
+            let tcp_port = map_port(
+                PortMappingProtocol::TCP,
+                local_port,
+                external_port,
+                "Conflux Node/TCP",
+            )?;
+            debug!("UPnP gets tcp port: {}", tcp_port);
+            let udp_port = map_port(
+                PortMappingProtocol::UDP,
+                local_udp_port,
+                external_udp_port,
+                "Conflux Node/UDP",
+            )?;
+            debug!("UPnP gets udp port: {}", udp_port);
+
+            Some(NodeEndpoint {
+                address: SocketAddr::V4(SocketAddrV4::new(
+                    external_addr,
+                    tcp_port,
+                )),
+                udp_port,
+            })
         });
         return search_gateway_child.join().ok()?;
     }
     None
 }
 
-fn search_natpmp(local: &NodeEndpoint) -> Option<NodeEndpoint> {
+fn search_natpmp(
+    local: &NodeEndpoint, external: Option<&NodeEndpoint>, lease: Duration,
+    gateway: Option<SocketAddrV4>,
+) -> Option<NodeEndpoint>
+{
     if let SocketAddr::V4(ref local_addr) = local.address {
         let local_port = local_addr.port();
         let local_udp_port = local.udp_port;
+        let (external_port, external_udp_port) =
+            preferred_ports(local, external);
+        let lease = lease.as_secs() as u32;
 
         let search_gateway_child = ::std::thread::spawn(move || {
-            let mut n = Natpmp::new()?;
-
-            // this function call want to receive `Response::Gateway` response
-            // from router, if other then it is an Error.
-            n.send_public_address_request()?;
-            ::std::thread::sleep(Duration::from_millis(
-                NAT_PMP_PORT_MAPPING_WAITING_DURATION,
-            ));
-            let mut retry = NAT_PMP_PORT_MAPPING_MAX_RETRY;
-            let gw = loop {
-                break match n.read_response_or_retry() {
-                    Ok(Response::Gateway(gw)) => Ok(gw),
-                    Err(natpmp::Error::NATPMP_TRYAGAIN) => {
-                        if retry == 0 {
-                            debug!("natpmp retry timeout");
-                            return Err(
-                                natpmp::Error::NATPMP_ERR_UNDEFINEDERROR.into(),
-                            );
-                        } else {
-                            retry -= 1;
-                            thread::sleep(Duration::from_millis(
-                                NAT_PMP_PORT_MAPPING_RETRY_WAITING_DURATION_MS,
-                            ));
-                            continue;
-                        }
-                    }
-                    Err(e) => {
-                        debug!("IP request error: {}", e);
-                        Err(e)
-                    }
-                    _ => Err(natpmp::Error::NATPMP_ERR_UNDEFINEDERROR.into()),
-                }?;
-            };
-
-            // this function call want to receive `Response::TCP` response from
-            // router, if other then it is an Error.
-            n.send_port_mapping_request(
-                Protocol::TCP,
-                local_port,
-                local_port,
-                NAT_PMP_PORT_MAPPING_LIFETIME,
-            )?;
-            ::std::thread::sleep(Duration::from_millis(
-                NAT_PMP_PORT_MAPPING_WAITING_DURATION,
-            ));
-            let tcp_r = match n.read_response_or_retry() {
-                Ok(Response::TCP(tcp)) => Ok(tcp),
-                Err(e) => {
+            let n = NatPmpClient::new(gateway)?;
+            let public_address = n.public_address().map_err(|e| {
+                debug!("IP request error: {}", e);
+                e
+            })?;
+            let tcp_port = n
+                .map_port(Protocol::TCP, local_port, external_port, lease)
+                .map_err(|e| {
                     debug!("Port mapping for TCP error: {}", e);
-                    Err(e)
-                }
-                _ => Err(natpmp::Error::NATPMP_ERR_UNDEFINEDERROR.into()),
-            }?;
-
-            // this function call want to receive `Response::UDP` response from
-            // router, if other then it is an Error.
-            n.send_port_mapping_request(
-                Protocol::UDP,
-                local_udp_port,
-                local_udp_port,
-                NAT_PMP_PORT_MAPPING_LIFETIME,
-            )?;
-            ::std::thread::sleep(Duration::from_millis(
-                NAT_PMP_PORT_MAPPING_WAITING_DURATION,
-            ));
-            let udp_r = match n.read_response_or_retry() {
-                Ok(Response::UDP(udp)) => Ok(udp),
-                Err(e) => {
+                    e
+                })?;
+            let udp_port = n
+                .map_port(
+                    Protocol::UDP,
+                    local_udp_port,
+                    external_udp_port,
+                    lease,
+                )
+                .map_err(|e| {
                     debug!("Port mapping for UDP error: {}", e);
-                    Err(e)
-                }
-                _ => Err(natpmp::Error::NATPMP_ERR_UNDEFINEDERROR.into()),
-            }?;
+                    e
+                })?;
 
             Ok(NodeEndpoint {
                 address: SocketAddr::V4(SocketAddrV4::new(
-                    *gw.public_address(),
-                    tcp_r.public_port(),
+                    public_address,
+                    tcp_port,
                 )),
-                udp_port: udp_r.public_port(),
+                udp_port,
             })
         });
 
         return search_gateway_child
             .join()
             .ok()?
-            .map_err(|e: natpmp::Error| {
+            .map_err(|e: io::Error| {
                 debug!("NAT PMP port mapping error: {:?}", e)
             })
             .ok();
@@ -510,18 +600,91 @@ fn search_natpmp(local: &NodeEndpoint) -> Option<NodeEndpoint> {
     None
 }
 
-/// Port mapping using ether UPnP or Nat-PMP.
-/// NAT PMP has higher priority than UPnP.
+/// Remove the UPnP mapping of the ports of `external`.
+fn remove_upnp(
+    local: &NodeEndpoint, external: &NodeEndpoint,
+    broadcast_address: SocketAddr,
+) -> bool
+{
+    let local_ip = match local.address {
+        SocketAddr::V4(ref local_addr) => *local_addr.ip(),
+        SocketAddr::V6(_) => return false,
+    };
+    let (external_port, external_udp_port) =
+        (external.address.port(), external.udp_port);
+
+    let search_options = upnp_search_options(local_ip, broadcast_address);
+    let remove_child = thread::spawn(move || {
+        let gateway = search_gateway(search_options)
+            .map_err(|err| debug!("Gateway search error: {}", err))
+            .ok()?;
+        gateway
+            .remove_port(PortMappingProtocol::TCP, external_port)
+            .map_err(|err| debug!("Port unmapping error: {}", err))
+            .ok()?;
+        gateway
+            .remove_port(PortMappingProtocol::UDP, external_udp_port)
+            .map_err(|err| debug!("Port unmapping error: {}", err))
+            .ok()
+    });
+    remove_child.join().ok().flatten().is_some()
+}
+
+/// Remove the NAT-PMP mapping of the ports of `local`, by requesting a
+/// mapping with zero lifetime.
+fn remove_natpmp(local: &NodeEndpoint, gateway: Option<SocketAddrV4>) -> bool {
+    let local_port = local.address.port();
+    let local_udp_port = local.udp_port;
+
+    let remove_child = thread::spawn(move || {
+        let n = NatPmpClient::new(gateway)?;
+        n.map_port(Protocol::TCP, local_port, 0, 0)?;
+        n.map_port(Protocol::UDP, local_udp_port, 0, 0)?;
+        Ok(())
+    });
+    match remove_child.join() {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            let e: io::Error = e;
+            debug!("NAT PMP port unmapping error: {:?}", e);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// The external ports to request: the ports of the previous mapping if it is
+/// being renewed, or the local ports otherwise.
+fn preferred_ports(
+    local: &NodeEndpoint, external: Option<&NodeEndpoint>,
+) -> (u16, u16) {
+    match external {
+        Some(external) => (external.address.port(), external.udp_port),
+        None => (local.address.port(), local.udp_port),
+    }
+}
+
+/// Port mapping using ether UPnP or Nat-PMP, which expires after `lease`
+/// unless renewed. `external` is the endpoint of the previous mapping to
+/// renew, if any. Returns the external endpoint and the method mapping it.
+/// UPnP has higher priority than NAT PMP.
 pub fn map_external_address(
-    local: &NodeEndpoint, nat_type: &NatType,
-) -> Option<NodeEndpoint> {
+    local: &NodeEndpoint, nat_type: &NatType, external: Option<&NodeEndpoint>,
+    lease: Duration, gateway: &GatewayAddress,
+) -> Option<(NodeEndpoint, NatType)>
+{
+    let upnp = || {
+        search_upnp(local, external, lease, gateway.upnp_broadcast)
+            .map(|endpoint| (endpoint, NatType::UPnP))
+    };
+    let natpmp = || {
+        search_natpmp(local, external, lease, gateway.natpmp_gateway)
+            .map(|endpoint| (endpoint, NatType::NatPMP))
+    };
     match *nat_type {
-        NatType::Any => match search_upnp(local) {
-            Some(end_point) => Some(end_point),
-            None => search_natpmp(local),
-        },
-        NatType::NatPMP => search_natpmp(local),
-        NatType::UPnP => search_upnp(local),
+        NatType::Any => upnp().or_else(natpmp),
+        NatType::NatPMP => natpmp(),
+        NatType::UPnP => upnp(),
         _ => {
             trace!("Can't map external address using NAT");
             None
@@ -529,6 +692,20 @@ pub fn map_external_address(
     }
 }
 
+/// Remove the mapping of `local` to `external` created with `nat_type`,
+/// rather than leaving it on the gateway until the lease expires.
+pub fn unmap_external_address(
+    local: &NodeEndpoint, nat_type: &NatType, external: &NodeEndpoint,
+    gateway: &GatewayAddress,
+) -> bool
+{
+    match *nat_type {
+        NatType::UPnP => remove_upnp(local, external, gateway.upnp_broadcast),
+        NatType::NatPMP => remove_natpmp(local, gateway.natpmp_gateway),
+        _ => false,
+    }
+}
+
 #[test]
 fn can_select_public_address() {
     let pub_address = select_public_address(40477);
@@ -545,6 +722,9 @@ fn can_map_external_address_or_fail() {
             udp_port: 40478,
         },
         &NatType::Any,
+        None,
+        Duration::from_secs(60),
+        &GatewayAddress::default(),
     );
 }
 
@@ -39,44 +39,54 @@ pub type PeerId = usize;
 
 mod connection;
 mod discovery;
+mod dns_discovery;
 mod error;
 mod handshake;
 mod ip;
 mod ip_utils;
+mod nat_mapping;
 mod node_database;
 pub mod node_table;
+mod peer_reputation;
 pub mod service;
 mod session;
+mod session_cipher;
+mod session_compression;
 mod session_manager;
 pub mod throttling;
 
 pub use crate::{
+    dns_discovery::TreeLink,
     error::{DisconnectReason, Error, ErrorKind, ThrottlingReason},
     ip::SessionIpLimitConfig,
     node_table::Node,
+    peer_reputation::{ReputationConfig, ReputationEvent},
     service::NetworkService,
     session::SessionDetails,
 };
 pub use io::TimerToken;
+pub use ipnetwork::IpNetwork;
 
 use crate::{
+    ip_utils::SocketAddrExt,
     node_table::NodeId,
     service::{
         ProtocolVersion, DEFAULT_CONNECTION_LIFETIME_FOR_PROMOTION,
         DEFAULT_DISCOVERY_REFRESH_TIMEOUT, DEFAULT_DISCOVERY_ROUND_TIMEOUT,
-        DEFAULT_FAST_DISCOVERY_REFRESH_TIMEOUT, DEFAULT_HOUSEKEEPING_TIMEOUT,
-        DEFAULT_NODE_TABLE_TIMEOUT,
+        DEFAULT_DNS_DISCOVERY_INTERVAL, DEFAULT_FAST_DISCOVERY_REFRESH_TIMEOUT,
+        DEFAULT_HOUSEKEEPING_TIMEOUT, DEFAULT_NAT_LEASE,
+        DEFAULT_NODE_TABLE_TIMEOUT, DEFAULT_SESSION_COMPRESSION_THRESHOLD,
     },
 };
 use cfx_addr::Network;
-use ipnetwork::{IpNetwork, IpNetworkError};
+use ipnetwork::IpNetworkError;
 use keylib::Secret;
 use priority_send_queue::SendQueuePriority;
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use serde_derive::{Deserialize, Serialize};
 use std::{
     cmp::Ordering,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
     str::{self, FromStr},
     sync::Arc,
     time::{Duration, SystemTime, UNIX_EPOCH},
@@ -101,9 +111,18 @@ pub struct NetworkConfiguration {
     pub udp_port: Option<u16>,
     /// Enable NAT configuration
     pub nat_enabled: bool,
+    /// Method to map the P2P ports on the NAT gateway
+    pub nat_type: NatType,
+    /// Lease of the port mapping, which is renewed every half of the lease
+    pub nat_lease: Duration,
     /// Enable discovery
     pub discovery_enabled: bool,
     pub boot_nodes: Vec<String>,
+    /// Links (`enrtree://<public key>@<domain>`) to the signed node trees
+    /// published in DNS, which are synced into the node table periodically.
+    pub dns_bootnodes: Vec<String>,
+    /// Period between the syncs of the DNS node trees
+    pub dns_discovery_interval: Duration,
     /// Use provided node key instead of default
     pub use_secret: Option<Secret>,
     /// Maximum number of outgoing peers
@@ -138,6 +157,14 @@ pub struct NetworkConfiguration {
     /// Maximum number of P2P nodes for subnet B (ip/16).
     pub subnet_quota: usize,
     pub session_ip_limit_config: SessionIpLimitConfig,
+    /// Encrypt the packets of the sessions with peers that support it
+    pub session_encryption: SessionEncryption,
+    /// Compress the packets of the sessions with peers that support it
+    pub session_compression: bool,
+    /// Minimum size of the packets to compress
+    pub session_compression_threshold: usize,
+    /// Thresholds of the peer reputation to disconnect or ban peers
+    pub reputation_config: ReputationConfig,
 
     pub discovery_config: DiscoveryConfiguration,
 }
@@ -155,8 +182,12 @@ impl NetworkConfiguration {
             public_address: None,
             udp_port: None,
             nat_enabled: true,
+            nat_type: NatType::Any,
+            nat_lease: DEFAULT_NAT_LEASE,
             discovery_enabled: false,
             boot_nodes: Vec::new(),
+            dns_bootnodes: Vec::new(),
+            dns_discovery_interval: DEFAULT_DNS_DISCOVERY_INTERVAL,
             use_secret: None,
             max_outgoing_peers: 0,
             max_outgoing_peers_archive: 0,
@@ -175,6 +206,11 @@ impl NetworkConfiguration {
             test_mode: false,
             subnet_quota: 32,
             session_ip_limit_config: SessionIpLimitConfig::default(),
+            session_encryption: SessionEncryption::Disabled,
+            session_compression: false,
+            session_compression_threshold:
+                DEFAULT_SESSION_COMPRESSION_THRESHOLD,
+            reputation_config: ReputationConfig::default(),
             discovery_config,
         }
     }
@@ -223,7 +259,7 @@ impl DiscoveryConfiguration {
 }
 
 /// Type of NAT resolving method
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum NatType {
     Nothing,
     Any,
@@ -231,6 +267,51 @@ pub enum NatType {
     NatPMP,
 }
 
+impl FromStr for NatType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(NatType::Nothing),
+            "any" => Ok(NatType::Any),
+            "upnp" => Ok(NatType::UPnP),
+            "natpmp" => Ok(NatType::NatPMP),
+            _ => Err(format!(
+                "Invalid NAT type {:?}, expected one of none, any, upnp and \
+                 natpmp",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether to encrypt the packets of sessions with a `SessionCipher`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SessionEncryption {
+    Disabled,
+    /// Encrypt the sessions with peers that enable it as well
+    Enabled,
+    /// Disconnect the peers not enabling it
+    Required,
+}
+
+impl FromStr for SessionEncryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(SessionEncryption::Disabled),
+            "enabled" => Ok(SessionEncryption::Enabled),
+            "required" => Ok(SessionEncryption::Required),
+            _ => Err(format!(
+                "Invalid session encryption {:?}, expected one of disabled, \
+                 enabled and required",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum NetworkIoMessage {
     Start,
@@ -319,6 +400,15 @@ pub trait NetworkContext {
 
     fn insert_peer_node_tag(&self, peer: NodeId, key: &str, value: &str);
 
+    /// Update the reputation of the peer, which is disconnected or banned if
+    /// the reputation drops to the configured thresholds.
+    fn update_peer_reputation(
+        &self, node_id: &NodeId, event: ReputationEvent, reason: &str,
+    );
+
+    /// Returns the current reputation of the peer.
+    fn get_peer_reputation(&self, node_id: &NodeId) -> i32;
+
     fn is_peer_self(&self, _node_id: &NodeId) -> bool;
 
     fn self_node_id(&self) -> NodeId;
@@ -403,6 +493,26 @@ impl Default for IpFilter {
 }
 
 impl IpFilter {
+    /// Creates a filter that only allows the addresses in `allow`, or all the
+    /// addresses if `allow` is empty, except those in `deny`.
+    pub fn new(allow: Vec<IpNetwork>, deny: Vec<IpNetwork>) -> Self {
+        IpFilter {
+            predefined: if allow.is_empty() {
+                AllowIP::All
+            } else {
+                AllowIP::None
+            },
+            custom_allow: allow,
+            custom_block: deny,
+        }
+    }
+
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        (self.predefined.is_allowed(ip)
+            || self.custom_allow.iter().any(|ipnet| ip.is_within(ipnet)))
+            && !self.custom_block.iter().any(|ipnet| ip.is_within(ipnet))
+    }
+
     /// Attempt to parse the peer mode from a string.
     pub fn parse(s: &str) -> Result<IpFilter, IpNetworkError> {
         let mut filter = IpFilter::default();
@@ -440,6 +550,17 @@ pub enum AllowIP {
     None,
 }
 
+impl AllowIP {
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        match self {
+            AllowIP::All => true,
+            AllowIP::Private => ip.is_usable_private(),
+            AllowIP::Public => ip.is_usable_public(),
+            AllowIP::None => false,
+        }
+    }
+}
+
 pub fn parse_msg_id_leb128_2_bytes_at_most(msg: &mut &[u8]) -> u16 {
     let buf = *msg;
 
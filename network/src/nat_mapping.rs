@@ -0,0 +1,588 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    ip_utils::{map_external_address, unmap_external_address, GatewayAddress},
+    node_table::NodeEndpoint,
+    NatType,
+};
+use std::time::Duration;
+
+/// Maps the ports of the local endpoint on the NAT gateway.
+pub trait PortMapper: Send {
+    /// Map the ports of `local` for `lease`, preferring the ports of
+    /// `external` if renewing a previous mapping. Returns the external
+    /// endpoint, or `None` if failed.
+    fn map_ports(
+        &mut self, local: &NodeEndpoint, external: Option<&NodeEndpoint>,
+        lease: Duration,
+    ) -> Option<NodeEndpoint>;
+
+    /// Remove the mapping of the ports of `local` to `external`. Returns
+    /// whether it is removed.
+    fn unmap_ports(
+        &mut self, local: &NodeEndpoint, external: &NodeEndpoint,
+    ) -> bool;
+}
+
+/// Maps the ports with UPnP IGD or NAT-PMP.
+pub struct GatewayMapper {
+    nat_type: NatType,
+    gateway: GatewayAddress,
+    /// The method of the current mapping, to remove it the same way.
+    mapped_by: Option<NatType>,
+}
+
+impl GatewayMapper {
+    pub fn new(nat_type: NatType) -> Self {
+        GatewayMapper {
+            nat_type,
+            gateway: GatewayAddress::default(),
+            mapped_by: None,
+        }
+    }
+}
+
+impl PortMapper for GatewayMapper {
+    fn map_ports(
+        &mut self, local: &NodeEndpoint, external: Option<&NodeEndpoint>,
+        lease: Duration,
+    ) -> Option<NodeEndpoint>
+    {
+        let (external, mapped_by) = map_external_address(
+            local,
+            &self.nat_type,
+            external,
+            lease,
+            &self.gateway,
+        )?;
+        self.mapped_by = Some(mapped_by);
+        Some(external)
+    }
+
+    fn unmap_ports(
+        &mut self, local: &NodeEndpoint, external: &NodeEndpoint,
+    ) -> bool {
+        match self.mapped_by.take() {
+            Some(nat_type) => unmap_external_address(
+                local,
+                &nat_type,
+                external,
+                &self.gateway,
+            ),
+            None => false,
+        }
+    }
+}
+
+/// The port mapping of the local endpoint, which expires unless renewed
+/// periodically, e.g. every half of the lease. The mapping is removed from
+/// the gateway when dropped.
+pub struct NatMapping<M: PortMapper> {
+    mapper: M,
+    local: NodeEndpoint,
+    lease: Duration,
+    external: Option<NodeEndpoint>,
+}
+
+impl<M: PortMapper> NatMapping<M> {
+    pub fn new(mapper: M, local: NodeEndpoint, lease: Duration) -> Self {
+        NatMapping {
+            mapper,
+            local,
+            lease,
+            external: None,
+        }
+    }
+
+    pub fn lease(&self) -> Duration { self.lease }
+
+    pub fn external(&self) -> Option<&NodeEndpoint> { self.external.as_ref() }
+
+    /// Create or renew the mapping. Returns the external endpoint if it is
+    /// mapped for the first time or changed, e.g. the gateway assigned
+    /// another port or its public address changed.
+    ///
+    /// If the renewal fails, the previous endpoint is kept, which may still
+    /// be reachable until the lease expires.
+    pub fn renew(&mut self) -> Option<NodeEndpoint> {
+        let external = match self.mapper.map_ports(
+            &self.local,
+            self.external.as_ref(),
+            self.lease,
+        ) {
+            Some(external) => external,
+            None => {
+                debug!("Failed to map the ports of {:?}", self.local);
+                return None;
+            }
+        };
+
+        if self.external.as_ref() == Some(&external) {
+            return None;
+        }
+
+        info!("NAT mapped to external address {}", external.address);
+        self.external = Some(external.clone());
+        Some(external)
+    }
+}
+
+impl<M: PortMapper> Drop for NatMapping<M> {
+    fn drop(&mut self) {
+        if let Some(external) = self.external.take() {
+            if self.mapper.unmap_ports(&self.local, &external) {
+                info!("NAT mapping to {} removed", external.address);
+            } else {
+                debug!("Failed to remove the NAT mapping to {:?}", external);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GatewayMapper, NatMapping, PortMapper};
+    use crate::{ip_utils::GatewayAddress, node_table::NodeEndpoint, NatType};
+    use parking_lot::Mutex;
+    use std::{
+        collections::VecDeque,
+        io::{Read, Write},
+        net::{
+            Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream,
+            UdpSocket,
+        },
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    /// Stand-in of the gateway that returns the prepared results, and records
+    /// the preferred external endpoints of the requests and the endpoints
+    /// unmapped.
+    #[derive(Clone, Default)]
+    struct MockMapper {
+        results: Arc<Mutex<VecDeque<Option<NodeEndpoint>>>>,
+        requests: Arc<Mutex<Vec<Option<NodeEndpoint>>>>,
+        unmapped: Arc<Mutex<Vec<NodeEndpoint>>>,
+    }
+
+    impl PortMapper for MockMapper {
+        fn map_ports(
+            &mut self, _local: &NodeEndpoint, external: Option<&NodeEndpoint>,
+            _lease: Duration,
+        ) -> Option<NodeEndpoint>
+        {
+            self.requests.lock().push(external.cloned());
+            self.results.lock().pop_front().unwrap_or(None)
+        }
+
+        fn unmap_ports(
+            &mut self, _local: &NodeEndpoint, external: &NodeEndpoint,
+        ) -> bool {
+            self.unmapped.lock().push(external.clone());
+            true
+        }
+    }
+
+    fn endpoint(address: &str) -> NodeEndpoint {
+        let address: SocketAddr = address.parse().unwrap();
+        NodeEndpoint {
+            address,
+            udp_port: address.port(),
+        }
+    }
+
+    #[test]
+    fn test_renew() {
+        let mapper = MockMapper::default();
+        let first = endpoint("1.2.3.4:32323");
+        let second = endpoint("1.2.3.5:32324");
+        mapper.results.lock().extend(vec![
+            None,
+            Some(first.clone()),
+            Some(first.clone()),
+            None,
+            Some(second.clone()),
+        ]);
+
+        let mut mapping = NatMapping::new(
+            mapper.clone(),
+            endpoint("192.168.1.2:32323"),
+            Duration::from_secs(7200),
+        );
+
+        // failed to map
+        assert_eq!(mapping.renew(), None);
+        assert_eq!(mapping.external(), None);
+        // mapped
+        assert_eq!(mapping.renew(), Some(first.clone()));
+        // renewed, unchanged
+        assert_eq!(mapping.renew(), None);
+        // failed to renew, keep the previous endpoint
+        assert_eq!(mapping.renew(), None);
+        assert_eq!(mapping.external(), Some(&first));
+        // changed
+        assert_eq!(mapping.renew(), Some(second.clone()));
+        assert_eq!(mapping.external(), Some(&second));
+
+        // The renewals prefer the ports of the previous mapping.
+        assert_eq!(
+            *mapper.requests.lock(),
+            vec![
+                None,
+                None,
+                Some(first.clone()),
+                Some(first.clone()),
+                Some(first),
+            ]
+        );
+
+        // The current mapping is removed on drop.
+        assert!(mapper.unmapped.lock().is_empty());
+        drop(mapping);
+        assert_eq!(*mapper.unmapped.lock(), vec![second]);
+    }
+
+    #[test]
+    fn test_drop_unmapped() {
+        let mapper = MockMapper::default();
+        let mut mapping = NatMapping::new(
+            mapper.clone(),
+            endpoint("192.168.1.2:32323"),
+            Duration::from_secs(7200),
+        );
+        assert_eq!(mapping.renew(), None);
+        drop(mapping);
+        assert!(mapper.unmapped.lock().is_empty());
+    }
+
+    const EXTERNAL_IP: Ipv4Addr = Ipv4Addr::new(1, 2, 3, 4);
+
+    fn gateway_mapper(
+        nat_type: NatType, gateway: GatewayAddress,
+    ) -> GatewayMapper {
+        GatewayMapper {
+            nat_type,
+            gateway,
+            mapped_by: None,
+        }
+    }
+
+    /// Returns the text of the first element `name` in `xml`.
+    fn xml_text<'a>(xml: &'a str, name: &str) -> &'a str {
+        let start = xml.find(&format!("<{}>", name)).unwrap() + name.len() + 2;
+        let end = xml.find(&format!("</{}>", name)).unwrap();
+        &xml[start..end]
+    }
+
+    const UPNP_ROOT_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+<device>
+<deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+<serviceList>
+<service>
+<serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+<SCPDURL>/scpd.xml</SCPDURL>
+<controlURL>/control</controlURL>
+</service>
+</serviceList>
+</device>
+</root>"#;
+
+    fn upnp_action(name: &str, arguments: &[&str]) -> String {
+        let arguments: Vec<String> = arguments
+            .iter()
+            .map(|argument| {
+                format!(
+                    "<argument><name>{}</name><direction>in</direction>\
+                     </argument>",
+                    argument
+                )
+            })
+            .collect();
+        format!(
+            "<action><name>{}</name><argumentList>{}</argumentList></action>",
+            name,
+            arguments.join("")
+        )
+    }
+
+    fn upnp_service_description() -> String {
+        format!(
+            "<?xml version=\"1.0\"?>\
+             <scpd xmlns=\"urn:schemas-upnp-org:service-1-0\">\
+             <actionList>{}{}{}</actionList></scpd>",
+            upnp_action("GetExternalIPAddress", &[]),
+            upnp_action(
+                "AddPortMapping",
+                &[
+                    "NewRemoteHost",
+                    "NewExternalPort",
+                    "NewProtocol",
+                    "NewInternalPort",
+                    "NewInternalClient",
+                    "NewEnabled",
+                    "NewPortMappingDescription",
+                    "NewLeaseDuration",
+                ]
+            ),
+            upnp_action(
+                "DeletePortMapping",
+                &["NewRemoteHost", "NewExternalPort", "NewProtocol"]
+            ),
+        )
+    }
+
+    /// Stand-in of a UPnP IGD on the loopback interface, which answers the
+    /// search requests and serves its description and the SOAP actions over
+    /// HTTP. Records the port mapping actions as "action protocol port".
+    struct MockUpnpGateway {
+        search_address: SocketAddr,
+        actions: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockUpnpGateway {
+        fn start() -> Self {
+            let http = TcpListener::bind("127.0.0.1:0").unwrap();
+            let location =
+                format!("http://{}/root.xml", http.local_addr().unwrap());
+            let search = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let search_address = search.local_addr().unwrap();
+            thread::spawn(move || loop {
+                let mut buf = [0u8; 1500];
+                let (_, from) = search.recv_from(&mut buf).unwrap();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\n\
+                     ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+                     LOCATION: {}\r\n\r\n",
+                    location
+                );
+                search.send_to(response.as_bytes(), from).unwrap();
+            });
+
+            let actions = Arc::new(Mutex::new(Vec::new()));
+            let recorded = actions.clone();
+            thread::spawn(move || {
+                for stream in http.incoming() {
+                    let recorded = recorded.clone();
+                    thread::spawn(move || {
+                        Self::serve(stream.unwrap(), &recorded)
+                    });
+                }
+            });
+
+            MockUpnpGateway {
+                search_address,
+                actions,
+            }
+        }
+
+        fn serve(mut stream: TcpStream, actions: &Mutex<Vec<String>>) {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let header_end = loop {
+                let read = stream.read(&mut buf).unwrap_or(0);
+                if read == 0 {
+                    // Connected only to find the local address.
+                    return;
+                }
+                request.extend_from_slice(&buf[..read]);
+                if let Some(end) =
+                    request.windows(4).position(|w| w == b"\r\n\r\n")
+                {
+                    break end + 4;
+                }
+            };
+            let header =
+                String::from_utf8_lossy(&request[..header_end]).into_owned();
+            let header_value = |name: &str| {
+                header.lines().find_map(|line| {
+                    let colon = line.find(':')?;
+                    if line[..colon].eq_ignore_ascii_case(name) {
+                        Some(line[colon + 1..].trim().to_string())
+                    } else {
+                        None
+                    }
+                })
+            };
+            let content_length = header_value("Content-Length")
+                .map_or(0, |length| length.parse().unwrap());
+            while request.len() < header_end + content_length {
+                let read = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+            let body = String::from_utf8_lossy(&request[header_end..]);
+
+            let path = header.split_whitespace().nth(1).unwrap();
+            let response = match path {
+                "/root.xml" => UPNP_ROOT_DESCRIPTION.to_string(),
+                "/scpd.xml" => upnp_service_description(),
+                _ => {
+                    let soap_action = header_value("SOAPAction").unwrap();
+                    let action = soap_action
+                        .trim_matches('"')
+                        .rsplit('#')
+                        .next()
+                        .unwrap()
+                        .to_string();
+                    let result = match action.as_str() {
+                        "GetExternalIPAddress" => format!(
+                            "<NewExternalIPAddress>{}</NewExternalIPAddress>",
+                            EXTERNAL_IP
+                        ),
+                        _ => {
+                            actions.lock().push(format!(
+                                "{} {} {}",
+                                action,
+                                xml_text(&body, "NewProtocol"),
+                                xml_text(&body, "NewExternalPort"),
+                            ));
+                            String::new()
+                        }
+                    };
+                    format!(
+                        "<?xml version=\"1.0\"?>\
+                         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\">\
+                         <s:Body><u:{action}Response \
+                         xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\">\
+                         {result}</u:{action}Response></s:Body></s:Envelope>",
+                        action = action,
+                        result = result
+                    )
+                }
+            };
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_upnp_gateway() {
+        let gateway = MockUpnpGateway::start();
+        let mapper = gateway_mapper(
+            NatType::Any,
+            GatewayAddress {
+                upnp_broadcast: gateway.search_address,
+                natpmp_gateway: None,
+            },
+        );
+        let mut mapping = NatMapping::new(
+            mapper,
+            endpoint("127.0.0.1:32323"),
+            Duration::from_secs(7200),
+        );
+
+        let external = mapping.renew().expect("mapped");
+        assert_eq!(
+            external.address,
+            SocketAddr::new(EXTERNAL_IP.into(), 32323)
+        );
+        assert_eq!(external.udp_port, 32323);
+        assert_eq!(mapping.mapper.mapped_by, Some(NatType::UPnP));
+
+        drop(mapping);
+        assert_eq!(
+            *gateway.actions.lock(),
+            vec![
+                "AddPortMapping TCP 32323",
+                "AddPortMapping UDP 32323",
+                "DeletePortMapping TCP 32323",
+                "DeletePortMapping UDP 32323",
+            ]
+        );
+    }
+
+    /// Stand-in of a NAT-PMP gateway on the loopback interface, which grants
+    /// the requested ports. Records the mapping requests as "protocol private
+    /// port, public port, lifetime".
+    struct MockNatPmpGateway {
+        address: SocketAddrV4,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockNatPmpGateway {
+        fn start() -> Self {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let address = match socket.local_addr().unwrap() {
+                SocketAddr::V4(address) => address,
+                SocketAddr::V6(_) => unreachable!(),
+            };
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            thread::spawn(move || loop {
+                let mut buf = [0u8; 16];
+                let (read, from) = socket.recv_from(&mut buf).unwrap();
+                let op = buf[1];
+                let mut response = vec![0, 128 + op, 0, 0, 0, 0, 0, 1];
+                match op {
+                    0 if read == 2 => {
+                        response.extend_from_slice(&EXTERNAL_IP.octets())
+                    }
+                    1 | 2 if read == 12 => {
+                        let port =
+                            |i: usize| u16::from_be_bytes([buf[i], buf[i + 1]]);
+                        let lifetime = u32::from_be_bytes([
+                            buf[8], buf[9], buf[10], buf[11],
+                        ]);
+                        recorded.lock().push(format!(
+                            "{} {} {} {}",
+                            if op == 1 { "UDP" } else { "TCP" },
+                            port(4),
+                            port(6),
+                            lifetime
+                        ));
+                        response.extend_from_slice(&buf[4..12]);
+                    }
+                    _ => continue,
+                }
+                socket.send_to(&response, from).unwrap();
+            });
+            MockNatPmpGateway { address, requests }
+        }
+    }
+
+    #[test]
+    fn test_natpmp_gateway() {
+        let gateway = MockNatPmpGateway::start();
+        let mapper = gateway_mapper(
+            NatType::NatPMP,
+            GatewayAddress {
+                natpmp_gateway: Some(gateway.address),
+                ..Default::default()
+            },
+        );
+        let mut mapping = NatMapping::new(
+            mapper,
+            endpoint("127.0.0.1:32323"),
+            Duration::from_secs(7200),
+        );
+
+        let external = mapping.renew().expect("mapped");
+        assert_eq!(
+            external.address,
+            SocketAddr::new(EXTERNAL_IP.into(), 32323)
+        );
+        assert_eq!(external.udp_port, 32323);
+        assert_eq!(mapping.mapper.mapped_by, Some(NatType::NatPMP));
+
+        // A mapping with zero lifetime is requested to remove it.
+        drop(mapping);
+        assert_eq!(
+            *gateway.requests.lock(),
+            vec![
+                "TCP 32323 32323 7200",
+                "UDP 32323 32323 7200",
+                "TCP 32323 0 0",
+                "UDP 32323 0 0",
+            ]
+        );
+    }
+}
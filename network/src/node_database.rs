@@ -3,16 +3,28 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    ip::{NodeIpLimit, NodeTagIndex, ValidateInsertResult},
-    node_table::{Node, NodeContact, NodeEntry, NodeId, NodeTable},
+    ip::{NodeIpLimit, NodeTagIndex, SubnetBlacklist, ValidateInsertResult},
+    node_table::{
+        Node, NodeContact, NodeEndpoint, NodeEntry, NodeId, NodeTable,
+    },
     IpFilter,
 };
 use io::StreamToken;
-use std::{collections::HashSet, net::IpAddr, time::Duration};
+use ipnetwork::IpNetwork;
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, SystemTime},
+};
 
 const TRUSTED_NODES_FILE: &str = "trusted_nodes.json";
 const UNTRUSTED_NODES_FILE: &str = "untrusted_nodes.json";
 const BLACKLISTED_NODES_FILE: &str = "blacklisted_nodes.json";
+const BLACKLISTED_SUBNETS_FILE: &str = "blacklisted_subnets.json";
+
+/// Nodes blacklisted by the administrator without an expiry are blacklisted
+/// until this time since the unix epoch, i.e. forever.
+pub const BLACKLISTED_FOREVER: Duration = Duration::from_secs(u32::MAX as u64);
 
 /// Node database maintains all P2P nodes in trusted and untrusted node tables,
 /// and supports to limit the number of nodes for the same IP address.
@@ -91,6 +103,8 @@ pub struct NodeDatabase {
     blacklisted_nodes: NodeTable,
     // Maximum duration to blacklist a node since last contact.
     blacklisted_lifetime: Duration,
+    // Subnets blacklisted by the administrator.
+    blacklisted_subnets: SubnetBlacklist,
 
     // IP address/subnet index for trusted and untrusted nodes.
     ip_limit: NodeIpLimit,
@@ -116,8 +130,15 @@ impl NodeDatabase {
         let mut db = NodeDatabase {
             trusted_nodes,
             untrusted_nodes,
-            blacklisted_nodes: NodeTable::new(path, BLACKLISTED_NODES_FILE),
+            blacklisted_nodes: NodeTable::new(
+                path.clone(),
+                BLACKLISTED_NODES_FILE,
+            ),
             blacklisted_lifetime: Duration::from_secs(7 * 24 * 3600),
+            blacklisted_subnets: SubnetBlacklist::new(
+                path,
+                BLACKLISTED_SUBNETS_FILE,
+            ),
             ip_limit,
             trusted_node_tag_index,
         };
@@ -349,6 +370,9 @@ impl NodeDatabase {
 
         for id in self.ip_limit.sample_trusted(count) {
             if let Some(node) = self.get(&id, true /* trusted_only */) {
+                if !node.endpoint.is_allowed(filter) {
+                    continue;
+                }
                 entries.push(NodeEntry {
                     id,
                     endpoint: node.endpoint.clone(),
@@ -363,7 +387,12 @@ impl NodeDatabase {
         &self, count: u32, filter: &IpFilter,
     ) -> HashSet<NodeId> {
         if self.ip_limit.is_enabled() {
-            self.ip_limit.sample_trusted(count)
+            let mut ids = self.ip_limit.sample_trusted(count);
+            ids.retain(|id| {
+                self.get(id, true /* trusted_only */)
+                    .map_or(false, |node| node.endpoint.is_allowed(filter))
+            });
+            ids
         } else {
             self.trusted_nodes.sample_node_ids(count, filter)
         }
@@ -391,6 +420,27 @@ impl NodeDatabase {
             .collect()
     }
 
+    /// Returns all the trusted nodes.
+    pub fn trusted_nodes(&self) -> Vec<Node> {
+        self.trusted_nodes
+            .all()
+            .iter()
+            .filter_map(|id| self.trusted_nodes.get(id))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns all the blacklisted nodes, including the expired ones that are
+    /// not evaluated yet.
+    pub fn blacklisted_nodes(&self) -> Vec<Node> {
+        self.blacklisted_nodes
+            .all()
+            .iter()
+            .filter_map(|id| self.blacklisted_nodes.get(id))
+            .cloned()
+            .collect()
+    }
+
     /// Persist trust and untrusted node tables and clear all useless nodes.
     pub fn save(&mut self) {
         self.trusted_nodes.save();
@@ -400,6 +450,7 @@ impl NodeDatabase {
         self.untrusted_nodes.clear_useless();
 
         self.blacklisted_nodes.save();
+        self.blacklisted_subnets.save();
     }
 
     /// Promote untrusted nodes to trusted with the given duration.
@@ -566,6 +617,38 @@ impl NodeDatabase {
         );
     }
 
+    /// Remove the tag of the specified key from node.
+    pub fn remove_tag(&mut self, id: &NodeId, key: &str) -> bool {
+        let (trusted, node) = if let Some(node) = self.trusted_nodes.get_mut(id)
+        {
+            (true, node)
+        } else if let Some(node) = self.untrusted_nodes.get_mut(id) {
+            (false, node)
+        } else {
+            return false;
+        };
+
+        let removed = match node.tags.remove(key) {
+            Some(removed) => removed,
+            None => return false,
+        };
+
+        if trusted && self.ip_limit.is_enabled() {
+            let subnet = self
+                .ip_limit
+                .subnet(id)
+                .expect("node index should always exist");
+            self.trusted_node_tag_index.remove(
+                id,
+                subnet,
+                &key.into(),
+                &removed,
+            );
+        }
+
+        true
+    }
+
     /// Set the specified node to blacklisted.
     pub fn set_blacklisted(&mut self, id: &NodeId) {
         // update the last failure time
@@ -581,6 +664,64 @@ impl NodeDatabase {
         }
     }
 
+    /// Set the specified node to blacklisted until the specified time, or
+    /// forever if not specified. Unlike `set_blacklisted`, the node need not
+    /// exist in database.
+    pub fn set_blacklisted_until(
+        &mut self, id: &NodeId, until: Option<SystemTime>,
+    ) {
+        let mut node = match self.remove(id) {
+            Some(node) => node,
+            None => Node::new(
+                *id,
+                NodeEndpoint {
+                    address: SocketAddr::new(
+                        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                        0,
+                    ),
+                    udp_port: 0,
+                },
+            ),
+        };
+
+        node.last_contact = Some(NodeContact::failure());
+        node.blacklisted_until =
+            Some(until.unwrap_or_else(|| {
+                SystemTime::UNIX_EPOCH + BLACKLISTED_FOREVER
+            }));
+        self.blacklisted_nodes
+            .add_node(node, false /* preserve_last_contact */);
+    }
+
+    /// Remove the specified node from blacklisted node table.
+    pub fn remove_blacklisted(&mut self, id: &NodeId) -> bool {
+        self.blacklisted_nodes.remove_with_id(id).is_some()
+    }
+
+    /// Blacklist the specified subnet until the specified time, or forever if
+    /// not specified.
+    pub fn set_subnet_blacklisted(
+        &mut self, subnet: IpNetwork, until: Option<SystemTime>,
+    ) {
+        self.blacklisted_subnets.insert(subnet, until);
+    }
+
+    pub fn remove_blacklisted_subnet(&mut self, subnet: &IpNetwork) -> bool {
+        self.blacklisted_subnets.remove(subnet)
+    }
+
+    /// Returns the blacklisted subnets and their expiry.
+    pub fn blacklisted_subnets(
+        &mut self,
+    ) -> Vec<(IpNetwork, Option<SystemTime>)> {
+        self.blacklisted_subnets.all()
+    }
+
+    /// Check if the specified IP address is in a blacklisted subnet.
+    pub fn is_ip_blacklisted(&mut self, ip: &IpAddr) -> bool {
+        self.blacklisted_subnets.contains(ip)
+    }
+
     /// Check if the specified node is blacklisted.
     /// If blacklisted for a long time, it will be removed from blacklisted node
     /// table.
@@ -590,6 +731,14 @@ impl NodeDatabase {
             None => return false,
         };
 
+        if let Some(until) = node.blacklisted_until {
+            if until <= SystemTime::now() {
+                self.blacklisted_nodes.remove_with_id(id);
+                return false;
+            }
+            return true;
+        }
+
         let last_contact = match node.last_contact {
             Some(contact) => contact.time(),
             None => {
@@ -616,7 +765,10 @@ impl NodeDatabase {
 mod tests {
     use super::NodeDatabase;
     use crate::node_table::{NodeEndpoint, NodeEntry, NodeId};
-    use std::{str::FromStr, time::Duration};
+    use std::{
+        str::FromStr,
+        time::{Duration, SystemTime},
+    };
 
     fn new_entry(addr: &str) -> NodeEntry {
         NodeEntry {
@@ -797,4 +949,58 @@ mod tests {
         assert_eq!(db.evaluate_blacklisted(&n.id), false);
         assert_eq!(db.get(&n.id, false), None);
     }
+
+    #[test]
+    fn test_blacklisted_until() {
+        let mut db = NodeDatabase::new(None, 2);
+
+        // unknown node
+        let id = NodeId::random();
+        db.set_blacklisted_until(&id, None);
+        assert_eq!(db.evaluate_blacklisted(&id), true);
+        assert_eq!(db.blacklisted_nodes().len(), 1);
+        assert_eq!(db.remove_blacklisted(&id), true);
+        assert_eq!(db.evaluate_blacklisted(&id), false);
+
+        let n = new_entry("127.0.0.1:999");
+        db.insert_trusted(n.clone());
+        db.set_blacklisted_until(
+            &n.id,
+            Some(SystemTime::now() + Duration::from_millis(1)),
+        );
+        assert_eq!(db.get(&n.id, false), None);
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(db.evaluate_blacklisted(&n.id), false);
+        assert!(db.blacklisted_nodes().is_empty());
+    }
+
+    #[test]
+    fn test_blacklisted_subnet() {
+        let mut db = NodeDatabase::new(None, 2);
+        let subnet = "10.0.0.0/8".parse().unwrap();
+
+        db.set_subnet_blacklisted(subnet, None);
+        assert_eq!(db.is_ip_blacklisted(&"10.0.0.1".parse().unwrap()), true);
+        assert_eq!(db.is_ip_blacklisted(&"11.0.0.1".parse().unwrap()), false);
+
+        assert_eq!(db.remove_blacklisted_subnet(&subnet), true);
+        assert_eq!(db.is_ip_blacklisted(&"10.0.0.1".parse().unwrap()), false);
+    }
+
+    #[test]
+    fn test_remove_tag() {
+        let mut db = NodeDatabase::new(None, 2);
+
+        let n = new_entry("127.0.0.1:999");
+        db.insert_trusted(n.clone());
+        db.set_tag(n.id, "k", "v");
+        assert_eq!(
+            db.get(&n.id, true).unwrap().tags.get("k"),
+            Some(&"v".into())
+        );
+
+        assert_eq!(db.remove_tag(&n.id, "k"), true);
+        assert_eq!(db.remove_tag(&n.id, "k"), false);
+        assert!(db.get(&n.id, true).unwrap().tags.is_empty());
+    }
 }
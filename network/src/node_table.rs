@@ -2,7 +2,7 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::{ip_utils::*, AllowIP, Error, ErrorKind, IpFilter};
+use crate::{AllowIP, Error, ErrorKind, IpFilter};
 use cfx_types::H512;
 use enum_map::EnumMap;
 use io::*;
@@ -55,24 +55,11 @@ impl NodeEndpoint {
     }
 
     pub fn is_allowed(&self, filter: &IpFilter) -> bool {
-        (self.is_allowed_by_predefined(&filter.predefined)
-            || filter
-                .custom_allow
-                .iter()
-                .any(|ipnet| self.address.ip().is_within(ipnet)))
-            && !filter
-                .custom_block
-                .iter()
-                .any(|ipnet| self.address.ip().is_within(ipnet))
+        filter.is_allowed(&self.address.ip())
     }
 
     pub fn is_allowed_by_predefined(&self, filter: &AllowIP) -> bool {
-        match filter {
-            AllowIP::All => true,
-            AllowIP::Private => self.address.ip().is_usable_private(),
-            AllowIP::Public => self.address.ip().is_usable_public(),
-            AllowIP::None => false,
-        }
+        filter.is_allowed(&self.address.ip())
     }
 
     pub fn from_rlp(rlp: &Rlp) -> Result<Self, DecoderError> {
@@ -271,6 +258,9 @@ pub struct Node {
     //     - good network nodes first
     // 2. Refuse incoming connection from node with special tags.
     pub tags: HashMap<String, String>,
+    // Set when a node is blacklisted by the administrator with an expiry.
+    // Otherwise, blacklisted nodes expire some time after the last contact.
+    pub blacklisted_until: Option<SystemTime>,
 }
 
 impl Node {
@@ -282,6 +272,7 @@ impl Node {
             last_connected: None,
             stream_token: None,
             tags: Default::default(),
+            blacklisted_until: None,
         }
     }
 }
@@ -331,6 +322,7 @@ impl FromStr for Node {
             last_connected: None,
             stream_token: None,
             tags: Default::default(),
+            blacklisted_until: None,
         })
     }
 }
@@ -445,7 +437,7 @@ impl NodeTable {
     }
 
     pub fn sample_nodes(
-        &self, count: u32, _filter: &IpFilter,
+        &self, count: u32, filter: &IpFilter,
     ) -> Vec<NodeEntry> {
         let mut nodes: Vec<NodeEntry> = Vec::new();
         for _i in 0..count {
@@ -456,6 +448,9 @@ impl NodeTable {
             if !node_rep_vec.is_empty() {
                 let idx = rng.gen::<usize>() % node_rep_vec.len();
                 let n = &node_rep_vec[idx];
+                if !n.endpoint.is_allowed(filter) {
+                    continue;
+                }
                 nodes.push(NodeEntry {
                     id: n.id,
                     endpoint: n.endpoint.clone(),
@@ -475,7 +470,7 @@ impl NodeTable {
 
     /// Return a random sample set of nodes inside the table
     pub fn sample_node_ids(
-        &self, count: u32, _filter: &IpFilter,
+        &self, count: u32, filter: &IpFilter,
     ) -> HashSet<NodeId> {
         let mut node_id_set: HashSet<NodeId> = HashSet::new();
         let mut rng = rand::thread_rng();
@@ -486,7 +481,8 @@ impl NodeTable {
             if !node_rep_vec.is_empty() {
                 let idx = rng.gen::<usize>() % node_rep_vec.len();
                 let n = &node_rep_vec[idx];
-                if !node_id_set.contains(&n.id) {
+                if n.endpoint.is_allowed(filter) && !node_id_set.contains(&n.id)
+                {
                     node_id_set.insert(n.id);
                 }
             }
@@ -923,6 +919,8 @@ mod json {
         pub url: String,
         pub last_contact: Option<NodeContact>,
         pub tags: HashMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub blacklisted_until: Option<u64>,
     }
 
     impl Node {
@@ -932,6 +930,9 @@ mod json {
                     node.last_contact =
                         self.last_contact.map(NodeContact::into_node_contact);
                     node.tags = self.tags;
+                    node.blacklisted_until = self
+                        .blacklisted_until
+                        .map(|s| time::UNIX_EPOCH + Duration::from_secs(s));
                     Some(node)
                 }
                 _ => None,
@@ -960,6 +961,9 @@ mod json {
                 url: format!("{}", node),
                 last_contact,
                 tags: node.tags.clone(),
+                blacklisted_until: node.blacklisted_until.and_then(|t| {
+                    t.duration_since(time::UNIX_EPOCH).ok().map(|d| d.as_secs())
+                }),
             }
         }
     }
@@ -0,0 +1,224 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::node_table::NodeId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Upper bound of the reputation, so that a peer cannot build up enough
+/// reputation to misbehave for a long time before being disconnected.
+pub const MAX_REPUTATION: i32 = 100;
+/// Lower bound of the reputation.
+pub const MIN_REPUTATION: i32 = -1000;
+
+/// Behaviors of a peer reported by the protocol handlers, which raise or lower
+/// the reputation of the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReputationEvent {
+    /// The peer sent an invalid block, header or proof.
+    InvalidBlock,
+    /// The peer sent a malformed, unexpected or otherwise invalid message.
+    InvalidMessage,
+    /// A request to the peer timed out.
+    Timeout,
+    /// The peer responded with nothing useful, e.g. an empty chunk.
+    UselessResponse,
+    /// The peer responded with valid data well within the timeout.
+    FastValidResponse,
+}
+
+impl ReputationEvent {
+    pub fn score_delta(&self) -> i32 {
+        match self {
+            ReputationEvent::InvalidBlock => -100,
+            ReputationEvent::InvalidMessage => -50,
+            ReputationEvent::Timeout => -20,
+            ReputationEvent::UselessResponse => -10,
+            ReputationEvent::FastValidResponse => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReputationConfig {
+    /// Peers are disconnected once their reputation drops to this threshold.
+    pub disconnect_threshold: i32,
+    /// Peers are blacklisted for `ban_duration` once their reputation drops
+    /// to this threshold.
+    pub ban_threshold: i32,
+    pub ban_duration: Duration,
+    /// The reputation decays towards zero by half in this duration, so that
+    /// the old behaviors of a peer are forgiven gradually.
+    pub decay_half_life: Duration,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        ReputationConfig {
+            disconnect_threshold: -200,
+            ban_threshold: -500,
+            ban_duration: Duration::from_secs(3600),
+            decay_half_life: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Action to take on a peer whose reputation crossed a threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReputationAction {
+    Disconnect,
+    Ban,
+}
+
+struct Reputation {
+    score: f64,
+    updated: Instant,
+}
+
+/// Numeric reputations of the peers, shared by all the protocols and kept
+/// across reconnections of the peers.
+pub struct PeerReputations {
+    config: ReputationConfig,
+    reputations: HashMap<NodeId, Reputation>,
+}
+
+impl PeerReputations {
+    pub fn new(config: ReputationConfig) -> Self {
+        PeerReputations {
+            config,
+            reputations: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ReputationConfig { &self.config }
+
+    /// Returns the current reputation of the peer, 0 if unknown.
+    pub fn get(&self, id: &NodeId) -> i32 {
+        match self.reputations.get(id) {
+            Some(r) => self.decayed(r, Instant::now()).round() as i32,
+            None => 0,
+        }
+    }
+
+    /// Apply `event` to the reputation of the peer, and return the action to
+    /// take if the reputation dropped to a threshold.
+    pub fn update(
+        &mut self, id: &NodeId, event: ReputationEvent,
+    ) -> Option<ReputationAction> {
+        let now = Instant::now();
+        let score = match self.reputations.get(id) {
+            Some(r) => self.decayed(r, now),
+            None => 0.0,
+        } + event.score_delta() as f64;
+        let score = score.max(MIN_REPUTATION as f64).min(MAX_REPUTATION as f64);
+        let rounded = score.round() as i32;
+
+        if rounded <= self.config.ban_threshold {
+            // The peer starts over once the ban expires.
+            self.reputations.remove(id);
+            return Some(ReputationAction::Ban);
+        }
+
+        self.reputations.insert(
+            id.clone(),
+            Reputation {
+                score,
+                updated: now,
+            },
+        );
+
+        if event.score_delta() < 0
+            && rounded <= self.config.disconnect_threshold
+        {
+            Some(ReputationAction::Disconnect)
+        } else {
+            None
+        }
+    }
+
+    /// Forget the reputations decayed to about zero.
+    pub fn clear_decayed(&mut self) {
+        let now = Instant::now();
+        let half_life = self.config.decay_half_life;
+        self.reputations.retain(|_, r| {
+            decay(r.score, now - r.updated, half_life).abs() >= 1.0
+        });
+    }
+
+    fn decayed(&self, r: &Reputation, now: Instant) -> f64 {
+        decay(r.score, now - r.updated, self.config.decay_half_life)
+    }
+}
+
+fn decay(score: f64, elapsed: Duration, half_life: Duration) -> f64 {
+    if half_life.as_secs_f64() == 0.0 {
+        return 0.0;
+    }
+    score * 0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decay, PeerReputations, ReputationAction, ReputationConfig,
+        ReputationEvent, MAX_REPUTATION,
+    };
+    use crate::node_table::NodeId;
+    use std::time::Duration;
+
+    #[test]
+    fn test_thresholds() {
+        let mut reputations = PeerReputations::new(ReputationConfig::default());
+        let id = NodeId::random();
+
+        assert_eq!(
+            reputations.update(&id, ReputationEvent::InvalidBlock),
+            None
+        );
+        assert_eq!(reputations.get(&id), -100);
+        assert_eq!(
+            reputations.update(&id, ReputationEvent::InvalidBlock),
+            Some(ReputationAction::Disconnect)
+        );
+        // Good behaviors never disconnect the peer.
+        assert_eq!(
+            reputations.update(&id, ReputationEvent::FastValidResponse),
+            None
+        );
+
+        for _ in 0..3 {
+            assert_eq!(
+                reputations.update(&id, ReputationEvent::InvalidBlock),
+                Some(ReputationAction::Disconnect)
+            );
+        }
+        assert_eq!(
+            reputations.update(&id, ReputationEvent::InvalidBlock),
+            Some(ReputationAction::Ban)
+        );
+        assert_eq!(reputations.get(&id), 0);
+    }
+
+    #[test]
+    fn test_max_reputation() {
+        let mut reputations = PeerReputations::new(ReputationConfig::default());
+        let id = NodeId::random();
+
+        for _ in 0..100 {
+            reputations.update(&id, ReputationEvent::FastValidResponse);
+        }
+        assert_eq!(reputations.get(&id), MAX_REPUTATION);
+    }
+
+    #[test]
+    fn test_decay() {
+        let half_life = Duration::from_secs(600);
+        assert_eq!(decay(-400.0, Duration::from_secs(0), half_life), -400.0);
+        assert_eq!(decay(-400.0, half_life, half_life), -200.0);
+        assert_eq!(decay(-400.0, half_life * 2, half_life), -100.0);
+        assert_eq!(decay(-400.0, half_life, Duration::from_secs(0)), 0.0);
+    }
+}
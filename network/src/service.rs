@@ -5,21 +5,25 @@
 use super::DisconnectReason;
 use crate::{
     discovery::Discovery,
+    dns_discovery::{DnsDiscovery, SystemResolver, TreeLink},
     handshake::BYPASS_CRYPTOGRAPHY,
     io::*,
-    ip_utils::{map_external_address, select_public_address},
-    node_database::NodeDatabase,
+    ip_utils::{select_public_address, SocketAddrExt},
+    nat_mapping::{GatewayMapper, NatMapping},
+    node_database::{NodeDatabase, BLACKLISTED_FOREVER},
     node_table::*,
     parse_msg_id_leb128_2_bytes_at_most,
+    peer_reputation::{PeerReputations, ReputationAction},
     session::{self, Session, SessionData, SessionDetails},
     session_manager::SessionManager,
     Error, ErrorKind, HandlerWorkType, IpFilter, NatType, NetworkConfiguration,
     NetworkContext as NetworkContextTrait, NetworkIoMessage,
     NetworkProtocolHandler, PeerInfo, ProtocolId, ProtocolInfo,
-    UpdateNodeOperation, NODE_TAG_ARCHIVE, NODE_TAG_NODE_TYPE,
+    ReputationEvent, UpdateNodeOperation, NODE_TAG_ARCHIVE, NODE_TAG_NODE_TYPE,
 };
 use cfx_addr::Network;
 use cfx_bytes::Bytes;
+use ipnetwork::IpNetwork;
 use keccak_hash::keccak;
 use keylib::{sign, Generator, KeyPair, Random, Secret};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
@@ -34,11 +38,16 @@ use std::{
     fmt::Formatter,
     fs,
     io::{self, Read, Write},
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
     path::{Path, PathBuf},
     str::FromStr,
-    sync::{atomic::Ordering as AtomicOrdering, Arc},
-    time::{Duration, Instant},
+    sync::{
+        atomic::Ordering as AtomicOrdering,
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 const MAX_SESSIONS: usize = 2048;
@@ -77,6 +86,12 @@ pub const DEFAULT_NODE_TABLE_TIMEOUT: Duration = Duration::from_secs(300);
 // to trusted.
 pub const DEFAULT_CONNECTION_LIFETIME_FOR_PROMOTION: Duration =
     Duration::from_secs(3 * 24 * 3600);
+// Packets smaller than this are not worth compressing.
+pub const DEFAULT_SESSION_COMPRESSION_THRESHOLD: usize = 1024;
+// How often the node trees published in DNS are synced.
+pub const DEFAULT_DNS_DISCOVERY_INTERVAL: Duration = Duration::from_secs(1800);
+// The lease of the port mapping on the NAT gateway.
+pub const DEFAULT_NAT_LEASE: Duration = Duration::from_secs(7200);
 const DEFAULT_CHECK_SESSIONS_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(
@@ -193,6 +208,8 @@ impl NetworkService {
                 .as_ref()
                 .unwrap()
                 .register_handler(inner.clone())?;
+            NetworkServiceInner::start_dns_discovery(&inner)?;
+            NetworkServiceInner::start_nat_renewal(&inner)?;
             self.inner = Some(inner);
         }
         Ok(())
@@ -234,6 +251,8 @@ impl NetworkService {
                 .as_ref()
                 .unwrap()
                 .register_handler(inner.clone())?;
+            NetworkServiceInner::start_dns_discovery(&inner)?;
+            NetworkServiceInner::start_nat_renewal(&inner)?;
             self.inner = Some(inner);
         }
 
@@ -401,6 +420,172 @@ impl NetworkService {
             inner.node_db.write().save();
         }
     }
+
+    fn started(
+        &self,
+    ) -> Result<(&NetworkServiceInner, IoContext<NetworkIoMessage>), Error>
+    {
+        match (&self.inner, &self.io_service) {
+            (Some(inner), Some(io_service)) => {
+                Ok((inner, IoContext::new(io_service.channel(), 0)))
+            }
+            _ => Err("Network service not started yet!".into()),
+        }
+    }
+
+    /// Returns all the trusted nodes.
+    pub fn trusted_nodes(&self) -> Result<Vec<Node>, Error> {
+        let (inner, _) = self.started()?;
+        Ok(inner.node_db.read().trusted_nodes())
+    }
+
+    /// Add a trusted node and persist it in the node database.
+    pub fn add_trusted_node(&self, node: NodeEntry) -> Result<(), Error> {
+        let (inner, _) = self.started()?;
+        let mut node_db = inner.node_db.write();
+        node_db.insert_trusted(node);
+        node_db.save();
+        Ok(())
+    }
+
+    /// Remove a node from the node database, disconnecting it if connected.
+    pub fn remove_trusted_node(&self, id: &NodeId) -> Result<(), Error> {
+        let (inner, _) = self.started()?;
+        inner.drop_node(*id)?;
+        inner.node_db.write().save();
+        Ok(())
+    }
+
+    /// Returns the blacklisted nodes along with their expiry, which is `None`
+    /// if blacklisted forever.
+    pub fn blacklisted_nodes(
+        &self,
+    ) -> Result<Vec<(Node, Option<SystemTime>)>, Error> {
+        let (inner, _) = self.started()?;
+        let forever = UNIX_EPOCH + BLACKLISTED_FOREVER;
+        let nodes = inner
+            .node_db
+            .read()
+            .blacklisted_nodes()
+            .into_iter()
+            .map(|node| {
+                let until = node.blacklisted_until.filter(|t| *t < forever);
+                (node, until)
+            })
+            .collect();
+        Ok(nodes)
+    }
+
+    /// Returns the blacklisted subnets along with their expiry, which is
+    /// `None` if blacklisted forever.
+    pub fn blacklisted_subnets(
+        &self,
+    ) -> Result<Vec<(IpNetwork, Option<SystemTime>)>, Error> {
+        let (inner, _) = self.started()?;
+        Ok(inner.node_db.write().blacklisted_subnets())
+    }
+
+    /// Blacklist a node until the specified time, or forever if not
+    /// specified, disconnecting it if connected.
+    pub fn blacklist_node(
+        &self, id: &NodeId, until: Option<SystemTime>,
+    ) -> Result<(), Error> {
+        let (inner, io) = self.started()?;
+        inner.node_db.write().set_blacklisted_until(id, until);
+        inner.kill_connection(
+            id,
+            &io,
+            true,          /* remote */
+            None,          /* op */
+            "blacklisted", // reason
+        );
+        inner.node_db.write().save();
+        Ok(())
+    }
+
+    /// Remove a node from the blacklist. Returns `false` if not blacklisted.
+    pub fn unblacklist_node(&self, id: &NodeId) -> Result<bool, Error> {
+        let (inner, _) = self.started()?;
+        let mut node_db = inner.node_db.write();
+        let removed = node_db.remove_blacklisted(id);
+        node_db.save();
+        Ok(removed)
+    }
+
+    /// Blacklist a subnet until the specified time, or forever if not
+    /// specified, disconnecting the connected nodes in it.
+    pub fn blacklist_subnet(
+        &self, subnet: IpNetwork, until: Option<SystemTime>,
+    ) -> Result<(), Error> {
+        let (inner, io) = self.started()?;
+        inner.node_db.write().set_subnet_blacklisted(subnet, until);
+
+        let tokens: Vec<StreamToken> = inner
+            .sessions
+            .all()
+            .iter()
+            .map(|s| s.read())
+            .filter(|s| s.address().ip().is_within(&subnet))
+            .map(|s| s.token())
+            .collect();
+        for token in tokens {
+            inner.kill_connection_by_token(
+                token,
+                &io,
+                true,          /* remote */
+                None,          /* op */
+                "blacklisted", // reason
+            );
+        }
+
+        inner.node_db.write().save();
+        Ok(())
+    }
+
+    /// Remove a subnet from the blacklist. Returns `false` if not
+    /// blacklisted.
+    pub fn unblacklist_subnet(
+        &self, subnet: &IpNetwork,
+    ) -> Result<bool, Error> {
+        let (inner, _) = self.started()?;
+        let mut node_db = inner.node_db.write();
+        let removed = node_db.remove_blacklisted_subnet(subnet);
+        node_db.save();
+        Ok(removed)
+    }
+
+    pub fn ip_filter(&self) -> Result<IpFilter, Error> {
+        let (inner, _) = self.started()?;
+        Ok(inner.get_ip_filter())
+    }
+
+    /// Replace the IP filter, disconnecting the peers not allowed by it. The
+    /// change is not persisted.
+    pub fn set_ip_filter(&self, ip_filter: IpFilter) -> Result<(), Error> {
+        let (inner, io) = self.started()?;
+        inner.set_ip_filter(ip_filter, &io);
+        Ok(())
+    }
+
+    /// Set the tag of a node, or remove it if `value` is `None`. Returns
+    /// `false` if the node does not exist.
+    pub fn set_node_tag(
+        &self, id: NodeId, key: &str, value: Option<&str>,
+    ) -> Result<bool, Error> {
+        let (inner, _) = self.started()?;
+        let mut node_db = inner.node_db.write();
+        if node_db.get(&id, false /* trusted_only */).is_none() {
+            return Ok(false);
+        }
+        match value {
+            Some(value) => node_db.set_tag(id, key, value),
+            None => {
+                node_db.remove_tag(&id, key);
+            }
+        }
+        node_db.save();
+        Ok(true)
+    }
 }
 
 type SharedSession = Arc<RwLock<Session>>;
@@ -414,8 +599,9 @@ pub struct HostMetadata {
     pub local_address: SocketAddr,
     /// Local address + discovery port
     pub local_endpoint: NodeEndpoint,
-    /// Public address + discovery port
-    pub public_endpoint: NodeEndpoint,
+    /// Public address + discovery port, which changes if the port mapping on
+    /// the NAT gateway changes.
+    pub public_endpoint: RwLock<NodeEndpoint>,
 }
 
 impl HostMetadata {
@@ -447,6 +633,16 @@ pub struct NetworkServiceInner {
     pub node_db: RwLock<NodeDatabase>,
     reserved_nodes: RwLock<HashSet<NodeId>>,
     dropped_nodes: RwLock<HashSet<NodeId>>,
+    /// Allowed and denied addresses of peers, initialized from the
+    /// configuration and changeable at runtime.
+    ip_filter: RwLock<IpFilter>,
+    reputations: RwLock<PeerReputations>,
+    /// Port mapping on the NAT gateway, taken by the renewal thread once the
+    /// service is started.
+    nat_mapping: Mutex<Option<NatMapping<GatewayMapper>>>,
+    /// Wakes up the renewal thread to stop, when dropped along with the
+    /// service. The thread then removes the mapping from the gateway.
+    nat_renewal_stop: Mutex<Option<mpsc::Sender<()>>>,
 
     is_consortium: bool,
 
@@ -560,6 +756,7 @@ impl NetworkServiceInner {
             UdpSocket::bind(&udp_addr).expect("Error binding UDP socket");
 
         let public_address = config.public_address;
+        let mut nat_mapping = None;
         let public_endpoint = match public_address {
             None => {
                 let public_address =
@@ -568,17 +765,16 @@ impl NetworkServiceInner {
                     address: public_address,
                     udp_port: local_endpoint.udp_port,
                 };
-                if config.nat_enabled {
-                    match map_external_address(&local_endpoint, &NatType::Any) {
-                        Some(endpoint) => {
-                            info!(
-                                "NAT mapped to external address {}",
-                                endpoint.address
-                            );
-                            endpoint
-                        }
-                        None => public_endpoint,
-                    }
+                if config.nat_enabled && config.nat_type != NatType::Nothing {
+                    // If the mapping fails, it is retried on renewal.
+                    let mut mapping = NatMapping::new(
+                        GatewayMapper::new(config.nat_type),
+                        local_endpoint.clone(),
+                        config.nat_lease,
+                    );
+                    let endpoint = mapping.renew();
+                    nat_mapping = Some(mapping);
+                    endpoint.unwrap_or(public_endpoint)
                 } else {
                     public_endpoint
                 }
@@ -613,7 +809,7 @@ impl NetworkServiceInner {
                 minimum_peer_protocol_version: Default::default(),
                 local_address: listen_address,
                 local_endpoint,
-                public_endpoint,
+                public_endpoint: RwLock::new(public_endpoint),
             },
             config: config.clone(),
             udp_channel: RwLock::new(UdpChannel::new()),
@@ -635,6 +831,12 @@ impl NetworkServiceInner {
             )),
             reserved_nodes: RwLock::new(HashSet::new()),
             dropped_nodes: RwLock::new(HashSet::new()),
+            ip_filter: RwLock::new(config.ip_filter.clone()),
+            reputations: RwLock::new(PeerReputations::new(
+                config.reputation_config.clone(),
+            )),
+            nat_mapping: Mutex::new(nat_mapping),
+            nat_renewal_stop: Mutex::new(None),
             is_consortium: config.is_consortium,
             delayed_queue: None,
         };
@@ -682,7 +884,90 @@ impl NetworkServiceInner {
         }
     }
 
-    pub fn get_ip_filter(&self) -> &IpFilter { &self.config.ip_filter }
+    pub fn get_ip_filter(&self) -> IpFilter { self.ip_filter.read().clone() }
+
+    pub fn is_ip_filter_allowed(&self, ip: &IpAddr) -> bool {
+        self.ip_filter.read().is_allowed(ip)
+    }
+
+    /// Replace the IP filter and disconnect the peers not allowed by the new
+    /// filter.
+    fn set_ip_filter(
+        &self, ip_filter: IpFilter, io: &IoContext<NetworkIoMessage>,
+    ) {
+        *self.ip_filter.write() = ip_filter.clone();
+        if let Some(discovery) = self.discovery.lock().as_mut() {
+            discovery.set_ip_filter(ip_filter.clone());
+        }
+
+        let tokens: Vec<StreamToken> = self
+            .sessions
+            .all()
+            .iter()
+            .map(|s| s.read())
+            .filter(|s| !ip_filter.is_allowed(&s.address().ip()))
+            .map(|s| s.token())
+            .collect();
+        for token in tokens {
+            self.kill_connection_by_token(
+                token,
+                io,
+                true,          /* remote */
+                None,          /* op */
+                "ip filtered", // reason
+            );
+        }
+    }
+
+    /// Apply the reputation event of the peer, and disconnect or ban the peer
+    /// if its reputation dropped to the configured thresholds.
+    fn update_peer_reputation(
+        &self, node_id: &NodeId, event: ReputationEvent, reason: &str,
+        io: &IoContext<NetworkIoMessage>,
+    )
+    {
+        let (action, ban_duration) = {
+            let mut reputations = self.reputations.write();
+            (
+                reputations.update(node_id, event),
+                reputations.config().ban_duration,
+            )
+        };
+
+        match action {
+            Some(ReputationAction::Disconnect) => {
+                debug!(
+                    "Disconnect peer {:?} for low reputation, event = {:?}",
+                    node_id, event
+                );
+                self.kill_connection(
+                    node_id,
+                    io,
+                    true,
+                    Some(UpdateNodeOperation::Failure),
+                    &format!("low reputation: {}", reason),
+                );
+            }
+            Some(ReputationAction::Ban) => {
+                info!(
+                    "Ban peer {:?} for {:?} for low reputation, event = {:?}",
+                    node_id, ban_duration, event
+                );
+                self.node_db.write().set_blacklisted_until(
+                    node_id,
+                    Some(SystemTime::now() + ban_duration),
+                );
+                self.kill_connection(
+                    node_id,
+                    io,
+                    true,
+                    None,
+                    &format!("low reputation: {}", reason),
+                );
+            }
+            None => {}
+        }
+    }
 
     fn add_boot_node(&self, id: &str) {
         match Node::from_str(id) {
@@ -698,6 +983,95 @@ impl NetworkServiceInner {
         }
     }
 
+    /// Spawn a thread syncing the node trees published in DNS into the node
+    /// database periodically. The thread exits once the service is dropped.
+    fn start_dns_discovery(
+        inner: &Arc<NetworkServiceInner>,
+    ) -> Result<(), Error> {
+        let links = inner
+            .config
+            .dns_bootnodes
+            .iter()
+            .map(|link| link.parse())
+            .collect::<Result<Vec<TreeLink>, Error>>()?;
+        if links.is_empty() {
+            return Ok(());
+        }
+
+        let mut dns_discovery =
+            DnsDiscovery::new(SystemResolver::new()?, links);
+        let interval = inner.config.dns_discovery_interval;
+        let weak = Arc::downgrade(inner);
+        thread::Builder::new().name("dns_discovery".into()).spawn(
+            move || loop {
+                let nodes = dns_discovery.sync();
+                match weak.upgrade() {
+                    Some(inner) => inner.add_dns_nodes(nodes),
+                    None => break,
+                }
+                thread::sleep(interval);
+            },
+        )?;
+        Ok(())
+    }
+
+    fn add_dns_nodes(&self, nodes: Vec<NodeEntry>) {
+        let ip_filter = self.get_ip_filter();
+        let mut node_db = self.node_db.write();
+        let mut added = 0;
+        for node in nodes {
+            if node.id == *self.metadata.id()
+                || !node.endpoint.is_allowed(&ip_filter)
+            {
+                continue;
+            }
+            node_db.insert_trusted(node);
+            added += 1;
+        }
+        debug!("Added {} nodes from DNS discovery", added);
+    }
+
+    /// Renew the port mapping on the NAT gateway every half of the lease, and
+    /// advertise the external endpoint if it changed. The mapping is removed
+    /// once the service is dropped.
+    fn start_nat_renewal(
+        inner: &Arc<NetworkServiceInner>,
+    ) -> Result<(), Error> {
+        let mut mapping = match inner.nat_mapping.lock().take() {
+            Some(mapping) => mapping,
+            None => return Ok(()),
+        };
+
+        let interval = mapping.lease() / 2;
+        let (stop, stop_requested) = mpsc::channel::<()>();
+        *inner.nat_renewal_stop.lock() = Some(stop);
+        let weak = Arc::downgrade(inner);
+        thread::Builder::new()
+            .name("nat_renewal".into())
+            .spawn(move || loop {
+                match stop_requested.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // The sender is dropped along with the service, and the
+                    // mapping is removed when dropped on return.
+                    _ => break,
+                }
+                if let Some(endpoint) = mapping.renew() {
+                    match weak.upgrade() {
+                        Some(inner) => inner.set_public_endpoint(endpoint),
+                        None => break,
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    fn set_public_endpoint(&self, endpoint: NodeEndpoint) {
+        if let Some(discovery) = self.discovery.lock().as_mut() {
+            discovery.set_public_endpoint(endpoint.clone());
+        }
+        *self.metadata.public_endpoint.write() = endpoint;
+    }
+
     fn add_reserved_node(&mut self, id: &str) -> Result<(), Error> {
         let n = Node::from_str(id)?;
         self.node_db.write().insert_trusted(NodeEntry {
@@ -713,7 +1087,7 @@ impl NetworkServiceInner {
     ) -> Result<(), Error> {
         // Initialize discovery
         if let Some(discovery) = self.discovery.lock().as_mut() {
-            let allow_ips = self.config.ip_filter.clone();
+            let allow_ips = self.get_ip_filter();
             let nodes = self.node_db.read().sample_trusted_nodes(
                 self.config.discovery_config.discover_node_count,
                 &allow_ips,
@@ -826,7 +1200,7 @@ impl NetworkServiceInner {
             };
             samples = self.node_db.read().sample_trusted_node_ids(
                 egress_attempt_count as u32,
-                &self.config.ip_filter,
+                &self.get_ip_filter(),
             );
         }
 
@@ -934,6 +1308,19 @@ impl NetworkServiceInner {
                 return;
             }
 
+            if !self.is_ip_filter_allowed(&address.ip()) {
+                debug!(
+                    "Abort connect. IP filtered, id = {:?}, address = {:?}",
+                    id, address
+                );
+                return;
+            }
+
+            if self.node_db.write().is_ip_blacklisted(&address.ip()) {
+                debug!("Abort connect. Subnet blacklisted, id = {:?}, address = {:?}", id, address);
+                return;
+            }
+
             match TcpStream::connect(&address) {
                 Ok(socket) => {
                     trace!("{}: connecting to {:?}", id, address);
@@ -1605,6 +1992,7 @@ impl IoHandler<NetworkIoMessage> for NetworkServiceInner {
                 trace!("Refreshing node table");
                 self.try_promote_untrusted();
                 self.node_db.write().save();
+                self.reputations.write().clear_decayed();
             }
             CHECK_SESSIONS => self.on_check_sessions(io),
             SEND_DELAYED_MESSAGES => {
@@ -2044,6 +2432,17 @@ impl<'a> NetworkContextTrait for NetworkContext<'a> {
             .write()
             .set_tag(peer, key, value);
     }
+
+    fn update_peer_reputation(
+        &self, node_id: &NodeId, event: ReputationEvent, reason: &str,
+    ) {
+        self.network_service
+            .update_peer_reputation(node_id, event, reason, self.io);
+    }
+
+    fn get_peer_reputation(&self, node_id: &NodeId) -> i32 {
+        self.network_service.reputations.read().get(node_id)
+    }
 }
 
 fn save_key(path: &Path, key: &Secret) {
@@ -0,0 +1,307 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{hash::keccak, Error, ErrorKind};
+use cfx_types::H256;
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use keylib::Secret;
+use priority_send_queue::SendQueuePriority;
+
+/// Size of the explicit packet counter appended to every encrypted packet.
+const COUNTER_SIZE: usize = 8;
+/// Size of the Poly1305 authentication tag.
+const TAG_SIZE: usize = 16;
+/// Number of lanes in which packets are written in order: one per priority
+/// of the send queue, and one for the packets written immediately.
+const LANES: usize = 4;
+const LANE_IMMEDIATE: u8 = 3;
+
+/// Secrets agreed in the handshake, from which the `SessionCipher` is derived
+/// once the peers exchanged the extensions of their Hello packets.
+pub struct SessionSecret {
+    shared: Secret,
+    initiator_nonce: H256,
+    responder_nonce: H256,
+    originated: bool,
+}
+
+impl SessionSecret {
+    /// Create the secret from the ECDH secret of the node keys and the
+    /// handshake nonces of the initiator and the responder of the session.
+    pub fn new(
+        shared: Secret, initiator_nonce: H256, responder_nonce: H256,
+        originated: bool,
+    ) -> Self
+    {
+        SessionSecret {
+            shared,
+            initiator_nonce,
+            responder_nonce,
+            originated,
+        }
+    }
+
+    /// Derive the cipher, binding the extensions of the Hello packets sent
+    /// and received, so that the peers fail to open each other's packets if
+    /// the Hello packets were tampered with.
+    pub fn cipher(
+        &self, local_extensions: &[Vec<u8>], remote_extensions: &[Vec<u8>],
+    ) -> SessionCipher {
+        let (initiator_extensions, responder_extensions) = if self.originated
+        {
+            (local_extensions, remote_extensions)
+        } else {
+            (remote_extensions, local_extensions)
+        };
+
+        let mut transcript = Vec::with_capacity(32 * 3);
+        transcript.extend_from_slice(self.shared.as_bytes());
+        transcript.extend_from_slice(self.initiator_nonce.as_bytes());
+        transcript.extend_from_slice(self.responder_nonce.as_bytes());
+        for extensions in &[initiator_extensions, responder_extensions] {
+            transcript.push(extensions.len() as u8);
+            for extension in extensions.iter() {
+                transcript.push(extension.len() as u8);
+                transcript.extend_from_slice(extension);
+            }
+        }
+
+        let derive_key = |direction: &[u8]| {
+            let mut material = transcript.clone();
+            material.extend_from_slice(direction);
+            ChaCha20Poly1305::new(Key::from_slice(keccak(&material).as_bytes()))
+        };
+
+        let initiator_key = derive_key(b"initiator");
+        let responder_key = derive_key(b"responder");
+        let (sealer, opener) = if self.originated {
+            (initiator_key, responder_key)
+        } else {
+            (responder_key, initiator_key)
+        };
+
+        SessionCipher {
+            sealer,
+            opener,
+            send_counters: [0; LANES],
+            receive_counters: [None; LANES],
+        }
+    }
+}
+
+/// ChaCha20-Poly1305 cipher of a session, negotiated by the HELLO packets.
+///
+/// Each direction has its own key, derived from the ECDH secret of the node
+/// keys, the nonces exchanged in the handshake and the extensions of the
+/// Hello packets, so that the keys are fresh for every session and a packet
+/// counter never repeats under the same key.
+///
+/// The counter is sent along with the packet instead of being tracked by both
+/// sides, because packets are not written in the order they are encrypted:
+/// the send queue reorders them by priority. Instead, packets are written in
+/// order within a lane, i.e. a priority of the send queue, so each lane has
+/// its own counter, and a packet whose counter is not greater than the last
+/// one opened in its lane is rejected as a replay.
+pub struct SessionCipher {
+    sealer: ChaCha20Poly1305,
+    opener: ChaCha20Poly1305,
+    send_counters: [u64; LANES],
+    receive_counters: [Option<u64>; LANES],
+}
+
+impl SessionCipher {
+    /// Encrypt `packet` to be sent with `priority`, or immediately if `None`,
+    /// authenticating the unencrypted `header` as well. Returns the
+    /// ciphertext followed by the lane and the counter of the packet.
+    pub fn seal(
+        &mut self, packet: &[u8], header: &[u8],
+        priority: Option<SendQueuePriority>,
+    ) -> Result<Vec<u8>, Error>
+    {
+        let lane = match priority {
+            Some(priority) => priority as u8,
+            None => LANE_IMMEDIATE,
+        };
+        let counter = self.send_counters[lane as usize].to_be_bytes();
+        self.send_counters[lane as usize] += 1;
+
+        let mut sealed = self
+            .sealer
+            .encrypt(
+                &nonce(lane, &counter),
+                Payload {
+                    msg: packet,
+                    aad: header,
+                },
+            )
+            .map_err(|_| Error::from(ErrorKind::BadProtocol))?;
+        sealed.push(lane);
+        sealed.extend_from_slice(&counter);
+        Ok(sealed)
+    }
+
+    /// Decrypt the output of `seal` of the remote peer, rejecting the packets
+    /// opened before.
+    pub fn open(
+        &mut self, sealed: &[u8], header: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if sealed.len() < TAG_SIZE + 1 + COUNTER_SIZE {
+            debug!("failed to open encrypted packet, too short");
+            bail!(ErrorKind::BadProtocol);
+        }
+
+        let (ciphertext, lane_counter) =
+            sealed.split_at(sealed.len() - 1 - COUNTER_SIZE);
+        let (lane, counter) = (lane_counter[0], &lane_counter[1..]);
+        if lane as usize >= LANES {
+            debug!("failed to open encrypted packet, invalid lane {}", lane);
+            bail!(ErrorKind::BadProtocol);
+        }
+
+        let packet = self
+            .opener
+            .decrypt(
+                &nonce(lane, counter),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| {
+                debug!("failed to open encrypted packet, invalid tag");
+                Error::from(ErrorKind::BadProtocol)
+            })?;
+
+        let mut counter_bytes = [0u8; COUNTER_SIZE];
+        counter_bytes.copy_from_slice(counter);
+        let counter = u64::from_be_bytes(counter_bytes);
+        let last_counter = &mut self.receive_counters[lane as usize];
+        if last_counter.map_or(false, |last| counter <= last) {
+            debug!(
+                "failed to open encrypted packet, replayed counter {} in lane {}",
+                counter, lane
+            );
+            bail!(ErrorKind::BadProtocol);
+        }
+        *last_counter = Some(counter);
+
+        Ok(packet)
+    }
+}
+
+fn nonce(lane: u8, counter: &[u8]) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[12 - COUNTER_SIZE - 1] = lane;
+    nonce[12 - COUNTER_SIZE..].copy_from_slice(counter);
+    Nonce::clone_from_slice(&nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionCipher, SessionSecret};
+    use cfx_types::H256;
+    use keylib::Secret;
+    use priority_send_queue::SendQueuePriority;
+
+    const HIGH: Option<SendQueuePriority> = Some(SendQueuePriority::High);
+    const LOW: Option<SendQueuePriority> = Some(SendQueuePriority::Low);
+
+    fn secret_pair() -> (SessionSecret, SessionSecret) {
+        let shared = Secret::from(H256::random());
+        let (initiator_nonce, responder_nonce) =
+            (H256::random(), H256::random());
+        (
+            SessionSecret::new(
+                shared.clone(),
+                initiator_nonce,
+                responder_nonce,
+                true,
+            ),
+            SessionSecret::new(shared, initiator_nonce, responder_nonce, false),
+        )
+    }
+
+    fn cipher_pair() -> (SessionCipher, SessionCipher) {
+        let (initiator, responder) = secret_pair();
+        let (initiator_extensions, responder_extensions) =
+            (vec![vec![1], vec![2]], vec![vec![1]]);
+        (
+            initiator.cipher(&initiator_extensions, &responder_extensions),
+            responder.cipher(&responder_extensions, &initiator_extensions),
+        )
+    }
+
+    #[test]
+    fn test_seal_open() {
+        let (mut initiator, mut responder) = cipher_pair();
+
+        let sealed = initiator.seal(b"hello", b"header", HIGH).unwrap();
+        assert_ne!(&sealed[..5], b"hello");
+        assert_eq!(responder.open(&sealed, b"header").unwrap(), b"hello");
+
+        let sealed = responder.seal(b"world", b"header", None).unwrap();
+        assert_eq!(initiator.open(&sealed, b"header").unwrap(), b"world");
+
+        // Packets of one direction cannot be opened as the other direction.
+        let sealed = initiator.seal(b"hello", b"header", HIGH).unwrap();
+        assert!(initiator.open(&sealed, b"header").is_err());
+    }
+
+    #[test]
+    fn test_open_tampered() {
+        let (mut initiator, mut responder) = cipher_pair();
+
+        let sealed = initiator.seal(b"hello", b"header", HIGH).unwrap();
+        assert!(responder.open(&sealed, b"other header").is_err());
+        assert!(responder.open(&sealed[1..], b"header").is_err());
+
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert!(responder.open(&tampered, b"header").is_err());
+
+        // The lane is authenticated as well.
+        let mut tampered = sealed.clone();
+        let lane = tampered.len() - 9;
+        tampered[lane] = 1;
+        assert!(responder.open(&tampered, b"header").is_err());
+
+        // Each packet is encrypted with a new nonce.
+        let resealed = initiator.seal(b"hello", b"header", HIGH).unwrap();
+        assert_ne!(sealed, resealed);
+    }
+
+    #[test]
+    fn test_open_replayed() {
+        let (mut initiator, mut responder) = cipher_pair();
+
+        let low = initiator.seal(b"low", b"header", LOW).unwrap();
+        let high1 = initiator.seal(b"high1", b"header", HIGH).unwrap();
+        let high2 = initiator.seal(b"high2", b"header", HIGH).unwrap();
+
+        // Packets of different lanes may be reordered.
+        assert_eq!(responder.open(&high1, b"header").unwrap(), b"high1");
+        assert_eq!(responder.open(&low, b"header").unwrap(), b"low");
+        assert!(responder.open(&low, b"header").is_err());
+
+        // But not within a lane.
+        assert_eq!(responder.open(&high2, b"header").unwrap(), b"high2");
+        assert!(responder.open(&high1, b"header").is_err());
+        assert!(responder.open(&high2, b"header").is_err());
+    }
+
+    #[test]
+    fn test_extensions_bound() {
+        let (initiator, responder) = secret_pair();
+        let mut initiator =
+            initiator.cipher(&[vec![1], vec![2]], &[vec![1], vec![2]]);
+        // The responder received a Hello packet with an extension stripped.
+        let mut responder = responder.cipher(&[vec![1], vec![2]], &[vec![1]]);
+
+        let sealed = initiator.seal(b"hello", b"header", HIGH).unwrap();
+        assert!(responder.open(&sealed, b"header").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{connection::MAX_PAYLOAD_SIZE, Error, ErrorKind};
+use lazy_static::lazy_static;
+use metrics::{register_meter_with_group, Histogram, Meter, Sample};
+use snap::raw::{decompress_len, Decoder, Encoder};
+use std::sync::Arc;
+
+lazy_static! {
+    static ref COMPRESS_IN_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "compress_in");
+    static ref COMPRESS_OUT_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "compress_out");
+    static ref DECOMPRESS_IN_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "decompress_in");
+    static ref DECOMPRESS_OUT_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "decompress_out");
+    /// Size of the compressed packets in percentage of the original size.
+    static ref COMPRESSION_RATIO: Arc<dyn Histogram> =
+        Sample::ExpDecay(0.015).register_with_group(
+            "network_compression",
+            "compression_ratio",
+            1024
+        );
+}
+
+/// Compress `packet` with snappy if it is not smaller than `threshold`.
+/// Returns `None` if the packet is not compressed, e.g. it is too small or
+/// the compressed data is not smaller.
+pub fn compress(packet: &[u8], threshold: usize) -> Option<Vec<u8>> {
+    // The remote peer refuses to decompress oversized packets, which could not
+    // be sent uncompressed either.
+    if packet.len() < threshold || packet.len() > MAX_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let compressed = match Encoder::new().compress_vec(packet) {
+        Ok(compressed) => compressed,
+        Err(e) => {
+            debug!("failed to compress packet: {:?}", e);
+            return None;
+        }
+    };
+
+    COMPRESS_IN_METER.mark(packet.len());
+    COMPRESS_OUT_METER.mark(compressed.len());
+    COMPRESSION_RATIO.update((compressed.len() * 100 / packet.len()) as u64);
+
+    if compressed.len() < packet.len() {
+        Some(compressed)
+    } else {
+        None
+    }
+}
+
+/// Decompress the output of `compress` of the remote peer.
+pub fn decompress(compressed: &[u8]) -> Result<Vec<u8>, Error> {
+    let len = decompress_len(compressed).map_err(|e| {
+        debug!("failed to decompress packet: {:?}", e);
+        Error::from(ErrorKind::BadProtocol)
+    })?;
+    if len > MAX_PAYLOAD_SIZE {
+        debug!("failed to decompress packet, oversized: {}", len);
+        bail!(ErrorKind::OversizedPacket);
+    }
+
+    let packet = Decoder::new().decompress_vec(compressed).map_err(|e| {
+        debug!("failed to decompress packet: {:?}", e);
+        Error::from(ErrorKind::BadProtocol)
+    })?;
+
+    DECOMPRESS_IN_METER.mark(compressed.len());
+    DECOMPRESS_OUT_METER.mark(packet.len());
+
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress};
+
+    #[test]
+    fn test_compress_decompress() {
+        let packet = vec![7u8; 4096];
+
+        // below threshold
+        assert_eq!(compress(&packet, packet.len() + 1), None);
+
+        let compressed = compress(&packet, packet.len()).unwrap();
+        assert!(compressed.len() < packet.len());
+        assert_eq!(decompress(&compressed).unwrap(), packet);
+
+        assert!(decompress(&compressed[..compressed.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_incompressible() {
+        let packet: Vec<u8> = (0..64u8).collect();
+        assert_eq!(compress(&packet, 0), None);
+    }
+}
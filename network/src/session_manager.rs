@@ -180,8 +180,17 @@ impl SessionManager {
             }
         }
 
-        // validate against node IP policy.
+        // validate against the allowed and denied IP addresses.
         let ip = address.ip();
+        if !host.is_ip_filter_allowed(&ip) {
+            debug!("SessionManager.create: leave on IP filtered");
+            return Err(format!(
+                "IP filtered, nodeId = {:?}, addr = {:?}",
+                id, address
+            ));
+        }
+
+        // validate against node IP policy.
         if !ip_limit.is_allowed(&ip) {
             debug!("SessionManager.create: leave on IP policy limited");
             return Err(format!(
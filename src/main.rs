@@ -16,6 +16,10 @@ use client::{
     configuration::Configuration,
     full::FullClient,
     light::LightClient,
+    snapshot_inspection::{
+        diff_snapshots, list_snapshots, snapshot_stats, verify_snapshot,
+    },
+    state_dump::dump_state_at_epoch,
 };
 use command::account::{AccountCmd, ImportAccounts, ListAccounts, NewAccount};
 use log::{info, LevelFilter};
@@ -26,7 +30,7 @@ use log4rs::{
 };
 use network::throttling::THROTTLING_SERVICE;
 use parking_lot::{Condvar, Mutex};
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 fn main() -> Result<(), String> {
     #[cfg(feature = "deadlock-detection")]
@@ -201,6 +205,61 @@ fn handle_sub_command(matches: &ArgMatches) -> Result<Option<String>, String> {
         return Ok(Some(execute_output));
     }
 
+    // state dump sub-command
+    if let ("dump", Some(dump_matches)) = matches.subcommand() {
+        let conf = Configuration::parse(matches)?;
+        let epoch = dump_matches
+            .value_of("epoch")
+            .expect("CLI argument is required; qed")
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid epoch: {}", e))?;
+        let output = dump_matches
+            .value_of("output")
+            .expect("CLI argument has default value; qed");
+        let execute_output =
+            dump_state_at_epoch(&conf, epoch, Path::new(output))?;
+        return Ok(Some(execute_output));
+    }
+
+    // snapshot sub-commands
+    if let ("snapshot", Some(snapshot_matches)) = matches.subcommand() {
+        let conf = Configuration::parse(matches)?;
+        let execute_output = match snapshot_matches.subcommand() {
+            ("list", Some(_)) => list_snapshots(&conf)?,
+            ("verify", Some(verify_matches)) => verify_snapshot(
+                &conf,
+                verify_matches
+                    .value_of("epoch-id")
+                    .expect("CLI argument is required; qed"),
+            )?,
+            ("stats", Some(stats_matches)) => snapshot_stats(
+                &conf,
+                stats_matches
+                    .value_of("epoch-id")
+                    .expect("CLI argument is required; qed"),
+            )?,
+            ("diff", Some(diff_matches)) => {
+                let limit = diff_matches
+                    .value_of("limit")
+                    .expect("CLI argument has default value; qed")
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid limit: {}", e))?;
+                diff_snapshots(
+                    &conf,
+                    diff_matches
+                        .value_of("from")
+                        .expect("CLI argument is required; qed"),
+                    diff_matches
+                        .value_of("to")
+                        .expect("CLI argument is required; qed"),
+                    limit,
+                )?
+            }
+            _ => unreachable!(),
+        };
+        return Ok(Some(execute_output));
+    }
+
     // general RPC commands
     let mut subcmd_matches = matches;
     while let Some(m) = subcmd_matches.subcommand().1 {
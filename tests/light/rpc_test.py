@@ -7,6 +7,8 @@ sys.path.insert(1, os.path.join(sys.path[0], '..'))
 
 from eth_utils import decode_hex
 from conflux.rpc import RpcClient
+from conflux.utils import sha3 as keccak
+from test_framework.blocktools import encode_hex_0x
 from test_framework.test_framework import ConfluxTestFramework
 from test_framework.util import assert_equal, assert_greater_than, assert_is_hex_string, assert_raises_rpc_error, connect_nodes, sync_blocks, get_contract_instance
 from web3 import Web3
@@ -102,6 +104,9 @@ class LightRPCTest(ConfluxTestFramework):
         self.sponsored_address = "0x1637feaab9faa11bf809f37967c3c8a43b8b874d"
         self.call_contract(contractAddr, "0a3b0a4f0000000000000000000000001637feaab9faa11bf809f37967c3c8a43b8b874d")
 
+    def _par_add_data(self, start, end):
+        return encode_hex_0x(keccak(b"par_add(uint256,uint256)"))[:10] + "%064x" % start + "%064x" % end
+
     def setup_network(self):
         self.add_nodes(self.num_nodes)
 
@@ -144,6 +149,9 @@ class LightRPCTest(ConfluxTestFramework):
         self.log.info(f"contract deployed: {contractAddr}")
         self._setup_sponsor(contractAddr)
 
+        # set ss[0] and ss[1] to 1
+        self.call_contract(contractAddr, self._par_add_data(0, 2))
+
         (self.stake_addr, self.stake_priv) = self.rpc[FULLNODE0].rand_account()
         tx = self.rpc[FULLNODE0].new_tx(receiver=self.stake_addr, value=10**19)
         self.rpc[FULLNODE0].send_tx(tx, wait_for_receipt=True)
@@ -552,15 +560,34 @@ class LightRPCTest(ConfluxTestFramework):
 
         # note: cfx_getLogs and cfx_sendRawTransaction have separate tests
 
+    def test_call_methods(self):
+        latest_state = self.nodes[LIGHTNODE].cfx_epochNumber("latest_state")
+
+        # --------------------------
+
+        self.log.info(f"Checking cfx_call...")
+        data = encode_hex_0x(keccak(b"ss(uint256)"))[:10] + "%064x" % 1
+        tx = self.rpc[FULLNODE0].new_tx_for_call(self.contract, data)
+        full = self.nodes[FULLNODE0].cfx_call(tx, latest_state)
+        light = self.nodes[LIGHTNODE].cfx_call(tx, latest_state)
+        assert_equal(light, full)
+        assert_equal(int(light, 16), 1)
+        self.log.info(f"Pass -- cfx_call")
+
+        # --------------------------
+
+        self.log.info(f"Checking cfx_estimateGasAndCollateral...")
+        tx = self.rpc[FULLNODE0].new_tx_for_call(self.contract, self._par_add_data(1, 4))
+        full = self.nodes[FULLNODE0].cfx_estimateGasAndCollateral(tx, latest_state)
+        light = self.nodes[LIGHTNODE].cfx_estimateGasAndCollateral(tx, latest_state)
+        assert_equal(light, full)
+        self.log.info(f"Pass -- cfx_estimateGasAndCollateral")
+
     def test_not_supported(self):
         self.log.info(f"Checking not supported APIs...")
 
-        assert_raises_rpc_error(-32000, None, self.nodes[LIGHTNODE].cfx_call, {}, "latest_checkpoint")
-        assert_raises_rpc_error(-32000, None, self.nodes[LIGHTNODE].cfx_estimateGasAndCollateral, {}, "latest_checkpoint")
         assert_raises_rpc_error(-32000, None, self.nodes[LIGHTNODE].cfx_getBlockByBlockNumber, "0x1", False)
-        assert_raises_rpc_error(-32000, None, self.nodes[LIGHTNODE].cfx_getBlockRewardInfo, "latest_checkpoint")
         assert_raises_rpc_error(-32000, None, self.nodes[LIGHTNODE].cfx_getEpochReceipts, "latest_checkpoint")
-        assert_raises_rpc_error(-32000, None, self.nodes[LIGHTNODE].cfx_getSupplyInfo, "latest_checkpoint")
 
         self.log.info(f"Pass -- not supported APIs")
 
@@ -569,6 +596,7 @@ class LightRPCTest(ConfluxTestFramework):
         self.test_state_methods()
         self.test_block_methods()
         self.test_tx_methods()
+        self.test_call_methods()
         self.test_not_supported()
 
 if __name__ == "__main__":
@@ -0,0 +1,72 @@
+#!/usr/bin/env python3
+
+# allow imports from parent directory
+# source: https://stackoverflow.com/a/11158224
+import os, sys
+sys.path.insert(1, os.path.join(sys.path[0], '..'))
+
+import asyncio
+
+from conflux.rpc import RpcClient
+from conflux.pubsub import PubSubClient
+from test_framework.test_framework import ConfluxTestFramework
+from test_framework.util import assert_equal
+
+FULLNODE = 0
+
+NUM_TXS = 10
+
+class PubSubTest(ConfluxTestFramework):
+    def set_test_params(self):
+        self.num_nodes = 1
+
+    def setup_network(self):
+        self.add_nodes(self.num_nodes)
+        self.start_node(FULLNODE, ["--archive"])
+
+        self.rpc = RpcClient(self.nodes[FULLNODE])
+        self.pubsub = PubSubClient(self.nodes[FULLNODE])
+
+    async def run_async(self):
+        # subscribe
+        sub = await self.pubsub.subscribe("newPendingTransactions")
+
+        # -------- 1. receive hashes one-by-one --------
+        nonce = self.rpc.get_nonce(self.rpc.GENESIS_ADDR)
+
+        for ii in range(NUM_TXS):
+            tx = self.rpc.new_tx(nonce=nonce + ii)
+            tx_hash = self.rpc.send_tx(tx)
+
+            hash = await sub.next()
+            assert_equal(hash, tx_hash)
+
+        self.log.info("Pass -- 1")
+
+        # -------- 2. rejected transactions are not published --------
+        # resending a transaction already in the pool fails
+        tx = self.rpc.new_tx(nonce=nonce)
+        rejected = False
+
+        try:
+            self.rpc.send_tx(tx)
+        except Exception:
+            rejected = True
+
+        assert(rejected)
+
+        try:
+            await sub.next(timeout=2)
+            assert(False)
+        except TimeoutError:
+            pass
+
+        self.log.info("Pass -- 2")
+
+        await sub.unsubscribe()
+
+    def run_test(self):
+        asyncio.get_event_loop().run_until_complete(self.run_async())
+
+if __name__ == "__main__":
+    PubSubTest().main()
@@ -22,7 +22,7 @@ pub enum ThrottleResult {
     AlreadyThrottled,
 }
 
-#[derive(DeriveMallocSizeOf)]
+#[derive(Clone, DeriveMallocSizeOf)]
 pub struct ThrottleTokens {
     max_tokens: u64,    // maximum tokens allowed in bucket
     cur_tokens: u64,    // current tokens in bucket
@@ -43,7 +43,7 @@ impl ThrottleTokens {
     }
 }
 
-#[derive(DeriveMallocSizeOf)]
+#[derive(Clone, DeriveMallocSizeOf)]
 pub struct TokenBucket {
     cpu_tokens: ThrottleTokens,
     message_size_tokens: ThrottleTokens,
@@ -124,6 +124,16 @@ impl TokenBucket {
         )
     }
 
+    /// Returns a copy of an unused bucket, e.g. a template loaded from the
+    /// configuration, to throttle a new client from its initial state.
+    pub fn fresh_copy(&self) -> Self {
+        let mut bucket = self.clone();
+        bucket.last_update = Instant::now();
+        bucket.throttled_until = None;
+        bucket.throttled_counter = 0;
+        bucket
+    }
+
     pub fn set_max_throttled_counter(&mut self, max_throttled_counter: u64) {
         self.max_throttled_counter = max_throttled_counter;
     }
@@ -283,14 +293,28 @@ impl TokenBucketManager {
             .parse::<toml::Value>()
             .map_err(|e| format!("failed to parse toml file: {:?}", e))?;
 
+        Self::from_toml(&toml_val, section)
+    }
+
+    pub fn from_toml(
+        toml_val: &toml::Value, section: Option<&str>,
+    ) -> Result<Self, String> {
         let val = match section {
             Some(section) => match toml_val.get(section) {
                 Some(val) => val,
                 None => return Err(format!("section [{}] not found", section)),
             },
-            None => &toml_val,
+            None => toml_val,
+        };
+        let table = match val.as_table() {
+            Some(table) => table,
+            None => {
+                return Err(format!(
+                    "invalid value type {:?}, table type required",
+                    val.type_str()
+                ))
+            }
         };
-        let table = val.as_table().expect("not table value");
 
         let mut manager = TokenBucketManager::default();
 